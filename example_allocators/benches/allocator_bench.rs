@@ -1,9 +1,133 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
 use example_allocators::{
+    bit_vec::BitVec,
+    copy_arena::CopyArena,
+    dense_arena::DenseArena,
+    dense_map::DenseMap,
+    ghost_arena::{GhostArena, GhostToken},
+    hot_cold_arena::{HotCold, HotColdArena},
+    recycling_arena::{Recycle, RecyclingArena},
+    generational_allocator::GenerationalAllocator,
+    kyren_generational_indices::{GIAUninitCell, GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell},
     memory_allocators::{GIABoxUninit, InPlaceAllocator},
     *,
 };
 
+const N_ENTITIES: usize = 10_000;
+const CHURN_BATCH: usize = 1_000;
+const CHURN_ROUNDS: usize = 10;
+
+/// Global allocator that counts every byte requested from the system, so benches can
+/// report how much heap traffic each allocator causes and not only how long it takes
+struct TrackingAllocator {
+    allocated: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocated.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.allocated.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static TRACKING: TrackingAllocator = TrackingAllocator { allocated: AtomicUsize::new(0) };
+
+fn bytes_allocated() -> usize {
+    TRACKING.allocated.load(Ordering::Relaxed)
+}
+
+/// Criterion measurement that reports bytes allocated instead of wall time
+struct BytesAllocated;
+
+impl Measurement for BytesAllocated {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> usize {
+        bytes_allocated()
+    }
+
+    fn end(&self, start: usize) -> usize {
+        bytes_allocated() - start
+    }
+
+    fn add(&self, v1: &usize, v2: &usize) -> usize {
+        v1 + v2
+    }
+
+    fn zero(&self) -> usize {
+        0
+    }
+
+    fn to_f64(&self, value: &usize) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = match typical_value {
+            v if v >= 1024.0 * 1024.0 => (1024.0 * 1024.0, "MiB"),
+            v if v >= 1024.0 => (1024.0, "KiB"),
+            _ => (1.0, "B"),
+        };
+        for value in values.iter_mut() {
+            *value /= factor;
+        }
+        unit
+    }
+
+    fn scale_throughputs(&self, _typical_value: f64, throughput: &Throughput, values: &mut [f64]) -> &'static str {
+        // Values are bytes per iteration, turn them into bytes per element
+        let elements = match throughput {
+            Throughput::Elements(n) | Throughput::Bytes(n) | Throughput::BytesDecimal(n) => *n as f64,
+        };
+        for value in values.iter_mut() {
+            *value /= elements;
+        }
+        "B/elem"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+/// Small xorshift generator so benches pick the same "random" entities every run
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
 #[derive(Default)]
 struct Entity {
     id: usize,
@@ -11,88 +135,673 @@ struct Entity {
     name: String,
 }
 
-fn generational_array_allocation_bench(c: &mut Criterion) {
-    const N_ENTITIES: u64 = 100;
-    c.bench_function("Generational Pointers: Entity Allocation 100", |b| {
+impl Recycle for Entity {
+    fn reset(&mut self) {
+        self.name.clear();
+    }
+}
+
+fn new_entity(id: usize) -> Entity {
+    Entity {
+        id,
+        is_active: true,
+        name: "Testing".to_string(),
+    }
+}
+
+/// Fisher-Yates shuffle, so accesses jump around memory instead of following allocation order
+fn shuffled<H>(mut items: Vec<H>) -> Vec<H> {
+    let mut rng = Rng(0xacce55);
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+    items
+}
+
+fn filled<A: GenerationalAllocator<Entity> + Default>(n: usize) -> (A, Vec<GenerationalIndex>) {
+    let mut alloc = A::default();
+    let handles = (0..n).map(|i| alloc.insert(new_entity(i))).collect();
+    (alloc, handles)
+}
+
+fn allocate_all<A: GenerationalAllocator<Entity> + Default>() -> A {
+    let mut alloc = A::default();
+    for i in 0..N_ENTITIES {
+        let _ = alloc.insert(new_entity(i));
+    }
+    alloc
+}
+
+/// Keep 10k entities live while freeing 1k random ones and allocating 1k new ones per
+/// round. This is where free lists and generation bumping actually get exercised
+fn interleaved_churn<A: GenerationalAllocator<Entity>>(
+    (mut alloc, mut handles): (A, Vec<GenerationalIndex>),
+) -> (A, Vec<GenerationalIndex>) {
+    let mut rng = Rng(0x5eed);
+    for round in 0..CHURN_ROUNDS {
+        for _ in 0..CHURN_BATCH {
+            let handle = handles.swap_remove(rng.below(handles.len()));
+            alloc.free(&handle);
+        }
+        for i in 0..CHURN_BATCH {
+            handles.push(alloc.insert(new_entity(round * CHURN_BATCH + i)));
+        }
+    }
+    (alloc, handles)
+}
+
+fn read_all<A: GenerationalAllocator<Entity>>(alloc: &A, handles: &[GenerationalIndex]) {
+    for handle in handles.iter() {
+        alloc.with(handle, |entity| {
+            black_box(entity.id);
+            black_box(&entity.name);
+            black_box(entity.is_active);
+        });
+    }
+}
+
+/// Allocation, access and churn benches for any allocator implementing the common trait.
+/// New allocators only need a line in `generational_allocator_benches`
+fn bench_allocator<A: GenerationalAllocator<Entity> + Default>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+
+    group.bench_function("Allocation 10k", |b| b.iter(allocate_all::<A>));
+
+    // Same handles visited in allocation order and in random order, the gap between both
+    // shows how much each design benefits from cache locality
+    let (alloc, handles) = filled::<A>(N_ENTITIES);
+    let random_handles = shuffled(handles.clone());
+    for (label, handles) in [("Sequential access 10k", &handles), ("Shuffled access 10k", &random_handles)] {
+        group.bench_function(label, |b| b.iter(|| read_all(&alloc, handles)));
+    }
+
+    // Free every other entity and allocate them again, so half the allocations reuse slots
+    group.bench_function("Churn 10k", |b| {
+        b.iter_batched(
+            || filled::<A>(N_ENTITIES),
+            |(mut alloc, handles)| {
+                for handle in handles.iter().step_by(2) {
+                    alloc.free(handle);
+                }
+                for i in 0..N_ENTITIES / 2 {
+                    let _ = alloc.insert(new_entity(i));
+                }
+                alloc
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.throughput(Throughput::Elements((CHURN_BATCH * CHURN_ROUNDS) as u64));
+    group.bench_function("Interleaved churn 10k", |b| {
+        b.iter_batched(|| filled::<A>(N_ENTITIES), interleaved_churn::<A>, BatchSize::LargeInput)
+    });
+
+    group.finish();
+}
+
+/// Same allocation and churn workloads as `bench_allocator`, measured in bytes requested
+/// from the global allocator per iteration
+fn bench_allocator_bytes<A: GenerationalAllocator<Entity> + Default>(c: &mut Criterion<BytesAllocated>, name: &str) {
+    let mut group = c.benchmark_group(format!("Bytes allocated/{}", name));
+
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+    group.bench_function("Allocation 10k", |b| b.iter(allocate_all::<A>));
+
+    group.throughput(Throughput::Elements((CHURN_BATCH * CHURN_ROUNDS) as u64));
+    group.bench_function("Interleaved churn 10k", |b| {
+        b.iter_batched(|| filled::<A>(N_ENTITIES), interleaved_churn::<A>, BatchSize::LargeInput)
+    });
+
+    group.finish();
+}
+
+/// Entity padded to exactly `BYTES` bytes, for the payload size sweep
+struct Payload<const BYTES: usize>([u8; BYTES]);
+
+// The allocators derive `Default`, which requires it from the payload too
+impl<const BYTES: usize> Default for Payload<BYTES> {
+    fn default() -> Self {
+        Payload([0; BYTES])
+    }
+}
+
+const SWEEP_ENTITIES: usize = 1_000;
+
+fn bench_payload_size<const BYTES: usize, A: GenerationalAllocator<Payload<BYTES>> + Default>(
+    c: &mut Criterion,
+    name: &str,
+) {
+    let mut group = c.benchmark_group(format!("Payload sweep/{}", name));
+
+    group.bench_with_input(BenchmarkId::new("Allocation 1k", BYTES), &BYTES, |b, _| {
         b.iter(|| {
-            let mut gpa = memory_allocators::GIABoxUninit::<Entity>::default();
-            for i in 0..N_ENTITIES {
-                let entity = gpa.new(Entity::default());
-                let mut entity_ref = gpa.get(&entity).unwrap().borrow_mut();
-                entity_ref.id = i as usize;
-                entity_ref.name = "Testing".to_string();
-                entity_ref.is_active = true;
+            let mut alloc = A::default();
+            for i in 0..SWEEP_ENTITIES {
+                let _ = alloc.insert(Payload([i as u8; BYTES]));
+            }
+            alloc
+        })
+    });
+
+    let mut alloc = A::default();
+    let handles: Vec<_> = (0..SWEEP_ENTITIES).map(|i| alloc.insert(Payload([i as u8; BYTES]))).collect();
+    group.bench_with_input(BenchmarkId::new("Access 1k", BYTES), &BYTES, |b, _| {
+        b.iter(|| {
+            for handle in handles.iter() {
+                alloc.with(handle, |payload| black_box(payload.0[0]));
             }
         })
     });
+
+    group.finish();
 }
 
-fn pointers_array_access_bench(c: &mut Criterion) {
-    const N_ENTITIES: u64 = 100;
+/// Run the payload sweep (8 B, 64 B, 256 B and 1 KiB) for an allocator type constructor
+macro_rules! payload_sweep {
+    ($c:expr, $allocator:ident) => {
+        bench_payload_size::<8, $allocator<Payload<8>>>($c, stringify!($allocator));
+        bench_payload_size::<64, $allocator<Payload<64>>>($c, stringify!($allocator));
+        bench_payload_size::<256, $allocator<Payload<256>>>($c, stringify!($allocator));
+        bench_payload_size::<1024, $allocator<Payload<1024>>>($c, stringify!($allocator));
+    };
+}
+
+fn payload_size_benches(c: &mut Criterion) {
+    payload_sweep!(c, GenerationalIndexArray);
+    payload_sweep!(c, GenerationalIndexArrayCell);
+    payload_sweep!(c, GIAUninitCell);
+    payload_sweep!(c, GIABoxUninit);
+    payload_sweep!(c, InPlaceAllocator);
+    payload_sweep!(c, DenseArena);
+}
+
+fn generational_allocator_benches(c: &mut Criterion) {
+    bench_allocator::<GenerationalIndexArray<Entity>>(c, "GenerationalIndexArray");
+    bench_allocator::<GenerationalIndexArrayCell<Entity>>(c, "GenerationalIndexArrayCell");
+    bench_allocator::<GIAUninitCell<Entity>>(c, "GIAUninitCell");
+    bench_allocator::<GIABoxUninit<Entity>>(c, "GIABoxUninit");
+    bench_allocator::<InPlaceAllocator<Entity>>(c, "InPlaceAllocator");
+    bench_allocator::<DenseArena<Entity>>(c, "DenseArena");
+}
+
+fn bytes_allocated_benches(c: &mut Criterion<BytesAllocated>) {
+    bench_allocator_bytes::<GenerationalIndexArray<Entity>>(c, "GenerationalIndexArray");
+    bench_allocator_bytes::<GenerationalIndexArrayCell<Entity>>(c, "GenerationalIndexArrayCell");
+    bench_allocator_bytes::<GIAUninitCell<Entity>>(c, "GIAUninitCell");
+    bench_allocator_bytes::<GIABoxUninit<Entity>>(c, "GIABoxUninit");
+    bench_allocator_bytes::<InPlaceAllocator<Entity>>(c, "InPlaceAllocator");
+    bench_allocator_bytes::<DenseArena<Entity>>(c, "DenseArena");
+    bench_recycling(c, "Bytes allocated/Recycling churn 10k");
+}
+
+/// Interleaved churn where allocations fill in recycled entities instead of building new
+/// ones, compared with the same churn dropping and rebuilding every entity
+fn bench_recycling<M: Measurement>(c: &mut Criterion<M>, name: &str) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements((CHURN_BATCH * CHURN_ROUNDS) as u64));
+
+    group.bench_function("Drop and rebuild (RecyclingArena::new)", |b| {
+        b.iter_batched(|| filled::<RecyclingArena<Entity>>(N_ENTITIES), interleaved_churn, BatchSize::LargeInput)
+    });
+    group.bench_function("Recycle (RecyclingArena::new_with)", |b| {
+        b.iter_batched(
+            || filled::<RecyclingArena<Entity>>(N_ENTITIES),
+            |(mut alloc, mut handles)| {
+                let mut rng = Rng(0x5eed);
+                for round in 0..CHURN_ROUNDS {
+                    for _ in 0..CHURN_BATCH {
+                        let handle = handles.swap_remove(rng.below(handles.len()));
+                        alloc.free(&handle);
+                    }
+                    for i in 0..CHURN_BATCH {
+                        handles.push(alloc.insert_with(|entity| {
+                            entity.id = round * CHURN_BATCH + i;
+                            entity.is_active = true;
+                            entity.name.push_str("Testing");
+                        }));
+                    }
+                }
+                (alloc, handles)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn recycling_benches(c: &mut Criterion) {
+    bench_recycling(c, "Recycling churn 10k");
+}
 
-    fn benched_fn(
-        (pointers, _alloc): (
-            Vec<memory_allocators::GenerationalIndex>,
-            GIABoxUninit<Entity>,
-        ),
-    ) {
-        let mut _alloc = _alloc;
-        for ptr in pointers {
-            let entity_ref = _alloc.get(&ptr).unwrap().borrow_mut();
-            let _id = black_box(entity_ref.id);
-            let _name = black_box(&entity_ref.name);
-            let _is_active = black_box(entity_ref.is_active);
+/// One row of the summary: a workload run against one allocator
+struct SummaryRow {
+    allocator: &'static str,
+    workload: &'static str,
+    elements: usize,
+    median: Duration,
+    bytes: usize,
+}
+
+impl SummaryRow {
+    fn elements_per_second(&self) -> f64 {
+        self.elements as f64 / self.median.as_secs_f64()
+    }
+}
+
+const SUMMARY_SAMPLES: usize = 21;
+
+/// Run `routine` on fresh inputs and return its median time and the bytes it allocated
+fn measure<I, O>(mut setup: impl FnMut() -> I, mut routine: impl FnMut(I) -> O) -> (Duration, usize) {
+    let mut times = Vec::with_capacity(SUMMARY_SAMPLES);
+    let mut bytes = 0;
+    for _ in 0..SUMMARY_SAMPLES {
+        let input = setup();
+        let (start, allocated) = (Instant::now(), bytes_allocated());
+        let output = routine(input);
+        times.push(start.elapsed());
+        bytes = bytes_allocated() - allocated;
+        drop(black_box(output));
+    }
+    times.sort();
+    (times[SUMMARY_SAMPLES / 2], bytes)
+}
+
+fn summarize<A: GenerationalAllocator<Entity> + Default>(allocator: &'static str, rows: &mut Vec<SummaryRow>) {
+    let (median, bytes) = measure(|| (), |_| allocate_all::<A>());
+    rows.push(SummaryRow { allocator, workload: "Allocation 10k", elements: N_ENTITIES, median, bytes });
+
+    let (alloc, handles) = filled::<A>(N_ENTITIES);
+    let handles = shuffled(handles);
+    let (median, bytes) = measure(|| (), |_| read_all(&alloc, &handles));
+    rows.push(SummaryRow { allocator, workload: "Shuffled access 10k", elements: N_ENTITIES, median, bytes });
+
+    let (median, bytes) = measure(|| filled::<A>(N_ENTITIES), interleaved_churn::<A>);
+    rows.push(SummaryRow {
+        allocator,
+        workload: "Interleaved churn 10k",
+        elements: CHURN_BATCH * CHURN_ROUNDS,
+        median,
+        bytes,
+    });
+}
+
+/// Write `allocator-summary.csv` and `allocator-summary.json` next to criterion's own
+/// reports, with every allocator side by side per workload. Criterion keeps its estimates
+/// per bench, this gives one file to diff between branches or plot
+fn write_summary(_: &mut Criterion) {
+    let mut rows = Vec::new();
+    summarize::<GenerationalIndexArray<Entity>>("GenerationalIndexArray", &mut rows);
+    summarize::<GenerationalIndexArrayCell<Entity>>("GenerationalIndexArrayCell", &mut rows);
+    summarize::<GIAUninitCell<Entity>>("GIAUninitCell", &mut rows);
+    summarize::<GIABoxUninit<Entity>>("GIABoxUninit", &mut rows);
+    summarize::<InPlaceAllocator<Entity>>("InPlaceAllocator", &mut rows);
+    summarize::<DenseArena<Entity>>("DenseArena", &mut rows);
+    rows.sort_by_key(|row| row.workload);
+
+    let mut csv = String::from("workload,allocator,elements,median_ns,elements_per_second,bytes_allocated\n");
+    let mut json = String::from("[\n");
+    for (i, row) in rows.iter().enumerate() {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{:.0},{}",
+            row.workload,
+            row.allocator,
+            row.elements,
+            row.median.as_nanos(),
+            row.elements_per_second(),
+            row.bytes
+        );
+        let _ = writeln!(
+            json,
+            "  {{\"workload\": \"{}\", \"allocator\": \"{}\", \"elements\": {}, \"median_ns\": {}, \"elements_per_second\": {:.0}, \"bytes_allocated\": {}}}{}",
+            row.workload,
+            row.allocator,
+            row.elements,
+            row.median.as_nanos(),
+            row.elements_per_second(),
+            row.bytes,
+            if i + 1 < rows.len() { "," } else { "" }
+        );
+    }
+    json.push_str("]\n");
+
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("criterion");
+    let written = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(dir.join("allocator-summary.csv"), csv))
+        .and_then(|_| std::fs::write(dir.join("allocator-summary.json"), json));
+    match written {
+        Ok(()) => println!("Allocator summary written to {}", dir.join("allocator-summary.{csv,json}").display()),
+        Err(error) => eprintln!("Could not write the allocator summary: {}", error),
+    }
+}
+
+/// Fill an allocator with 10k entities and free all but `live_percent` of them,
+/// spreading the survivors over the whole allocator
+fn with_occupancy<A: GenerationalAllocator<Entity> + Default>(live_percent: usize) -> A {
+    let (mut alloc, handles) = filled::<A>(N_ENTITIES);
+    for (i, handle) in handles.iter().enumerate() {
+        if i % 100 >= live_percent {
+            alloc.free(handle);
         }
     }
+    alloc
+}
+
+/// Full iteration over 10k slots at different occupancies, for the three ways of finding
+/// live entries: scanning every entry, scanning an occupancy bitset and walking dense storage
+fn iteration_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Iteration 10k");
+
+    for live_percent in [100, 50, 10] {
+        let entry_scan = with_occupancy::<GenerationalIndexArray<Entity>>(live_percent);
+        group.bench_with_input(BenchmarkId::new("Entry scan (GenerationalIndexArray)", live_percent), &live_percent, |b, _| {
+            b.iter(|| entry_scan.iter().map(|(_, entity)| entity.id).sum::<usize>())
+        });
+
+        group.bench_with_input(BenchmarkId::new("Values only (GenerationalIndexArray)", live_percent), &live_percent, |b, _| {
+            b.iter(|| entry_scan.values().map(|entity| entity.id).sum::<usize>())
+        });
+
+        let bitset_scan = with_occupancy::<InPlaceAllocator<Entity>>(live_percent);
+        group.bench_with_input(BenchmarkId::new("Bitset scan (InPlaceAllocator)", live_percent), &live_percent, |b, _| {
+            b.iter(|| bitset_scan.iter().map(|(_, entity)| entity.id).sum::<usize>())
+        });
 
-    c.bench_function("Pointers Array: Entity Access 100", |b| {
-        b.iter(move || {
-            benched_fn({
-                let mut alloc = memory_allocators::GIABoxUninit::<Entity>::default();
-                let mut pointers = Vec::with_capacity(N_ENTITIES as usize);
+        group.bench_with_input(BenchmarkId::new("Values only (InPlaceAllocator)", live_percent), &live_percent, |b, _| {
+            b.iter(|| bitset_scan.values().map(|entity| entity.id).sum::<usize>())
+        });
 
-                for i in 0..N_ENTITIES {
-                    let handle = alloc.new(Entity::default());
-                    let mut entity_ref = alloc.get(&handle).unwrap().borrow_mut();
-                    entity_ref.name = "Testing".to_string();
-                    entity_ref.id = i as usize;
-                    entity_ref.is_active = true;
+        let dense = with_occupancy::<DenseArena<Entity>>(live_percent);
+        group.bench_with_input(BenchmarkId::new("Dense storage (DenseArena)", live_percent), &live_percent, |b, _| {
+            b.iter(|| dense.iter().map(|(_, entity)| entity.id).sum::<usize>())
+        });
+    }
+
+    group.finish();
+}
+
+const LIVENESS_HANDLES: usize = 1_000_000;
+
+/// Liveness checks over 1M handles where about half are stale. `GenerationalIndexArray`
+/// bounds checks each index with a branch and `is_live_unchecked` skips it, while
+/// `InPlaceAllocator` clamps out of range indices to an always dead sentinel slot.
+/// `validate_many` does the same checks into a bitset, 64 handles per word
+fn is_live_benches(c: &mut Criterion) {
+    let mut alloc = GenerationalIndexArray::<u8>::default();
+    let mut inplace_alloc = InPlaceAllocator::<u8>::default();
+    let handles: Vec<_> = (0..LIVENESS_HANDLES).map(|i| alloc.insert(i as u8)).collect();
+    for i in 0..LIVENESS_HANDLES {
+        let _ = inplace_alloc.insert(i as u8);
+    }
+    let mut rng = Rng(0x11fe);
+    for handle in handles.iter() {
+        if rng.below(2) == 0 {
+            alloc.free(handle);
+            inplace_alloc.free(handle);
+        }
+    }
+    let handles = shuffled(handles);
+
+    let mut group = c.benchmark_group("Liveness 1M");
+    group.throughput(Throughput::Elements(LIVENESS_HANDLES as u64));
+
+    group.bench_function("is_live (bounds check branch)", |b| {
+        b.iter(|| handles.iter().filter(|handle| alloc.is_live(handle)).count())
+    });
+    group.bench_function("is_live_unchecked", |b| {
+        // Every handle came from `alloc`, so all indices are in bounds
+        b.iter(|| handles.iter().filter(|handle| unsafe { alloc.is_live_unchecked(handle) }).count())
+    });
+    group.bench_function("is_live (sentinel, InPlaceAllocator)", |b| {
+        b.iter(|| handles.iter().filter(|handle| inplace_alloc.is_live(handle)).count())
+    });
+    let mut live = BitVec::default();
+    group.bench_function("validate_many (InPlaceAllocator)", |b| {
+        b.iter(|| {
+            inplace_alloc.validate_many(&handles, &mut live);
+            live.count_ones()
+        })
+    });
+    group.finish();
+
+    // Handles from a larger allocator, past the end of both, so the bounds check branch
+    // can't be predicted
+    let mut larger = GenerationalIndexArray::<u8>::default();
+    let foreign: Vec<_> = (0..LIVENESS_HANDLES * 5 / 4).map(|i| larger.insert(i as u8)).skip(LIVENESS_HANDLES).collect();
+    let mixed = shuffled(handles.iter().cloned().chain(foreign).collect());
+
+    let mut group = c.benchmark_group("Liveness 1M + 250k out of range");
+    group.throughput(Throughput::Elements(mixed.len() as u64));
+    group.bench_function("is_live (bounds check branch)", |b| {
+        b.iter(|| mixed.iter().filter(|handle| alloc.is_live(handle)).count())
+    });
+    group.bench_function("is_live (sentinel, InPlaceAllocator)", |b| {
+        b.iter(|| mixed.iter().filter(|handle| inplace_alloc.is_live(handle)).count())
+    });
+    group.bench_function("validate_many (InPlaceAllocator)", |b| {
+        b.iter(|| {
+            inplace_alloc.validate_many(&mixed, &mut live);
+            live.count_ones()
+        })
+    });
+    group.finish();
+}
 
-                    pointers.push(handle);
+/// Looking up side data for every live entity, from a handle keyed `HashMap` and from a
+/// `DenseMap` indexed by slot
+fn side_table_benches(c: &mut Criterion) {
+    let mut arena = InPlaceAllocator::<u32>::default();
+    let handles: Vec<_> = (0..N_ENTITIES).map(|i| arena.insert(i as u32)).collect();
+    let mut hashed = HashMap::new();
+    let mut dense = DenseMap::default();
+    for handle in handles.iter() {
+        hashed.insert(handle.clone(), handle.get_index());
+        dense.insert(handle, handle.get_index());
+    }
+    let handles = shuffled(handles);
+
+    let mut group = c.benchmark_group("Side table 10k");
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+    group.bench_function("HashMap<GenerationalIndex, V>", |b| {
+        b.iter(|| handles.iter().map(|handle| hashed[handle]).sum::<usize>())
+    });
+    group.bench_function("DenseMap", |b| b.iter(|| handles.iter().map(|handle| *dense.get(handle).unwrap()).sum::<usize>()));
+    group.bench_function("DenseMap::iter_live", |b| b.iter(|| dense.iter_live(&arena).map(|(_, value)| *value).sum::<usize>()));
+    group.finish();
+}
+
+/// Handle validation when every slot also holds a 256 byte payload. Only the generations
+/// are read, so this shows whether they're packed together or spread between payloads
+fn validation_benches(c: &mut Criterion) {
+    let mut alloc = InPlaceAllocator::<Payload<256>>::default();
+    let handles: Vec<_> = (0..N_ENTITIES * 10).map(|_| alloc.insert(Payload::default())).collect();
+    let mut rng = Rng(0x7a11);
+    for handle in handles.iter() {
+        if rng.below(2) == 0 {
+            alloc.free(handle);
+        }
+    }
+    let handles = shuffled(handles);
+
+    let mut group = c.benchmark_group("Validation 100k");
+    group.throughput(Throughput::Elements(handles.len() as u64));
+    group.bench_function("is_live (InPlaceAllocator, 256 byte payload)", |b| {
+        b.iter(|| handles.iter().filter(|handle| alloc.is_live(handle)).count())
+    });
+    group.finish();
+}
+
+/// Mutating every entity through a shared reference to the arena: `RefCell` flags checked
+/// on each access against a `GhostToken` that is checked once, at compile time
+fn interior_mutability_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Interior mutability 10k");
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+
+    let (cells, handles) = filled::<GenerationalIndexArrayCell<Entity>>(N_ENTITIES);
+    group.bench_function("RefCell (GenerationalIndexArrayCell)", |b| {
+        b.iter(|| {
+            for handle in handles.iter() {
+                cells.get(handle).unwrap().borrow_mut().id += 1;
+            }
+        })
+    });
+
+    let (uninit_cells, handles) = filled::<GIAUninitCell<Entity>>(N_ENTITIES);
+    group.bench_function("RefCell (GIAUninitCell)", |b| {
+        b.iter(|| {
+            for handle in handles.iter() {
+                uninit_cells.borrow_mut(handle).unwrap().id += 1;
+            }
+        })
+    });
+
+    GhostToken::new(|mut token| {
+        let mut ghosts = GhostArena::default();
+        let handles: Vec<_> = (0..N_ENTITIES).map(|i| ghosts.insert(new_entity(i))).collect();
+        let ghosts = &ghosts;
+        group.bench_function("GhostToken (GhostArena)", |b| {
+            b.iter(|| {
+                for handle in handles.iter() {
+                    ghosts.get_mut(handle, &mut token).unwrap().id += 1;
                 }
-                (pointers, alloc)
             })
+        });
+    });
+
+    group.finish();
+}
+
+#[derive(Clone, Copy, Default)]
+struct Particle {
+    position: [f32; 3],
+    velocity: [f32; 3],
+}
+
+fn new_particle(id: usize) -> Particle {
+    let speed = id as f32;
+    Particle { position: [0.0; 3], velocity: [speed, -speed, 1.0] }
+}
+
+fn step(mut particle: Particle) -> Particle {
+    for axis in 0..3 {
+        particle.position[axis] += particle.velocity[axis];
+    }
+    particle
+}
+
+/// One simulation step over 10k small `Copy` particles through a shared arena: `Cell`
+/// slots copy values in and out, the others hand out references
+fn copy_payload_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Copy payload 10k");
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+
+    let mut copies = CopyArena::default();
+    let handles: Vec<_> = (0..N_ENTITIES).map(|i| copies.insert(new_particle(i))).collect();
+    group.bench_function("Cell (CopyArena)", |b| {
+        b.iter(|| {
+            for handle in handles.iter() {
+                copies.update(handle, step);
+            }
+        })
+    });
+
+    let mut in_place = InPlaceAllocator::default();
+    let handles: Vec<_> = (0..N_ENTITIES).map(|i| in_place.insert(new_particle(i))).collect();
+    group.bench_function("InPlaceAllocator", |b| {
+        b.iter(|| {
+            for handle in handles.iter() {
+                let mut particle = in_place.get(handle);
+                *particle = step(*particle);
+            }
+        })
+    });
+
+    let mut cells = GenerationalIndexArrayCell::default();
+    let handles: Vec<_> = (0..N_ENTITIES).map(|i| cells.insert(new_particle(i))).collect();
+    group.bench_function("RefCell (GenerationalIndexArrayCell)", |b| {
+        b.iter(|| {
+            for handle in handles.iter() {
+                let mut particle = cells.get(handle).unwrap().borrow_mut();
+                *particle = step(*particle);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Particle with a large payload that the simulation step never reads
+struct Body {
+    motion: Particle,
+    details: Payload<256>,
+}
+
+impl HotCold for Body {
+    type Hot = Particle;
+    type Cold = Payload<256>;
+
+    fn split(self) -> (Particle, Payload<256>) {
+        (self.motion, self.details)
+    }
+
+    fn join(motion: Particle, details: Payload<256>) -> Self {
+        Body { motion, details }
+    }
+}
+
+/// One simulation step over 10k bodies that only touches their motion, with the cold
+/// payload stored inline next to it or split off into its own boxes
+fn hot_cold_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Hot fields 10k");
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+
+    let mut whole = DenseArena::default();
+    let mut split = HotColdArena::default();
+    for i in 0..N_ENTITIES {
+        let _ = whole.insert(Body { motion: new_particle(i), details: Payload::default() });
+        let _ = split.insert(Body { motion: new_particle(i), details: Payload::default() });
+    }
+
+    group.bench_function("Whole values (DenseArena)", |b| {
+        b.iter(|| {
+            for body in whole.values_mut() {
+                body.motion = step(body.motion);
+            }
+        })
+    });
+    group.bench_function("Hot part only (HotColdArena)", |b| {
+        b.iter(|| {
+            for motion in split.hot_values_mut() {
+                *motion = step(*motion);
+            }
         })
     });
+
+    group.finish();
 }
 
+// BoxAllocator hands out pointers instead of indices, so it doesn't fit the common trait
+
 fn box_alloc_allocation_bench(c: &mut Criterion) {
-    const N_ENTITIES: u64 = 10_000;
     c.bench_function("Box Allocator: Entity Allocation 10k", |b| {
         b.iter(|| {
             let mut box_alloc = memory_allocators::BoxAllocator::<Entity>::default();
             for i in 0..N_ENTITIES {
-                box_alloc.new(Entity {
-                    name: "Testing".to_string(),
-                    id: i as usize,
-                    is_active: true,
-                });
+                let _ = box_alloc.insert(new_entity(i));
             }
+            box_alloc
         })
     });
 }
 
 fn box_alloc_access_bench(c: &mut Criterion) {
-    const N_ENTITIES: u64 = 10_000;
-
     let mut en_alloc = memory_allocators::BoxAllocator::<Entity>::default();
-    let mut pointers = Vec::with_capacity(N_ENTITIES as usize);
+    let mut pointers = Vec::with_capacity(N_ENTITIES);
     for i in 0..N_ENTITIES {
-        pointers.push(en_alloc.new(Entity {
-            name: "Testing".to_string(),
-            id: i as usize,
-            is_active: true,
-        }));
+        pointers.push(en_alloc.insert(new_entity(i)));
     }
     let input = (pointers, en_alloc);
 
@@ -106,61 +815,91 @@ fn box_alloc_access_bench(c: &mut Criterion) {
             }
         })
     });
-}
 
-fn inplace_alloc_allocation_bench(c: &mut Criterion) {
-    const N_ENTITIES: u64 = 10_000;
-    c.bench_function("InPlace allocator: Allocation 10k", |b| {
-        b.iter(|| {
-            let mut alloc = memory_allocators::InPlaceAllocator::<Entity>::default();
-            for _ in 0..N_ENTITIES {
-                let entity_handle = alloc.new(Entity::default());
-                let entity_ref = alloc.get(&entity_handle);
-                entity_ref.id = 42;
-                entity_ref.name = "test".to_owned();
-                entity_ref.is_active = true;
+    let (pointers, en_alloc) = input;
+    let input = (shuffled(pointers), en_alloc);
+    c.bench_with_input(BenchmarkId::new("Box Allocator: Shuffled access 10k", "10k Pointers parameter"),&input, |b, input| {
+        let (pointers, _alloc) = input;
+        b.iter(move||{
+            for ptr in pointers {
+                let _id = black_box(ptr.id);
+                let _name = black_box(&ptr.name);
+                let _is_active = black_box(ptr.is_active);
             }
         })
     });
 }
 
-fn inplace_alloc_access_bench(c: &mut Criterion) {
-    const N_ENTITIES: u64 = 10_000;
-    let mut alloc = InPlaceAllocator::<Entity>::default();
-    let mut handles = vec![];
-
-    for _ in 0..N_ENTITIES {
-        let new_handle = alloc.new(Entity::default());
-        handles.push(new_handle);
-    }
+/// Shuffled reads through 32 byte `EntityPtr`s and the experimental `TaggedEntityPtr`,
+/// which keeps only a 16 bit generation tag to fit in 8 bytes. Handle sizes are in the
+/// bench names, debug builds add the full generation back to the tagged pointer
+fn tagged_pointer_benches(c: &mut Criterion) {
+    let mut alloc = memory_allocators::BoxAllocator::<Entity>::default();
+    let pointers: Vec<_> = shuffled((0..N_ENTITIES).map(|i| alloc.insert(new_entity(i))).collect());
+    let tagged: Vec<_> = shuffled((0..N_ENTITIES).map(|i| alloc.insert(new_entity(i)).into_tagged().ok().unwrap()).collect());
 
-    let input = (alloc, handles);
+    let mut group = c.benchmark_group("Pointer handles 10k");
+    group.throughput(Throughput::Elements(N_ENTITIES as u64));
+    let name = format!("EntityPtr ({} bytes)", std::mem::size_of::<memory_allocators::EntityPtr<Entity>>());
+    group.bench_function(name, |b| b.iter(|| pointers.iter().map(|ptr| black_box(ptr.id)).sum::<usize>()));
+    let name = format!("TaggedEntityPtr ({} bytes)", std::mem::size_of::<memory_allocators::TaggedEntityPtr<Entity>>());
+    group.bench_function(name, |b| {
+        // `alloc` outlives the pointers and is never trimmed
+        b.iter(|| tagged.iter().map(|ptr| black_box(unsafe { ptr.get() }.unwrap().id)).sum::<usize>())
+    });
+    group.finish();
+}
 
-    c.bench_with_input(BenchmarkId::new("InPlace allocator: Access 10k", "10k Allocated entities"), {
-        &input
-       },
-       |b, input| {
-        b.iter(move || {
-            let (alloc, handles) = &input;
-            for handle in handles.iter() {
-                let _id = black_box(alloc.get(handle));
-                let _name = black_box(alloc.get(handle));
-                let _is_active = black_box(alloc.get(handle));
-            }
-        })
-       }
-    );
+fn box_alloc_churn_bench(c: &mut Criterion) {
+    c.bench_function("Box Allocator: Interleaved churn 10k", |b| {
+        b.iter_batched(
+            || {
+                let mut alloc = memory_allocators::BoxAllocator::<Entity>::default();
+                let pointers: Vec<_> = (0..N_ENTITIES).map(|i| alloc.insert(new_entity(i))).collect();
+                (alloc, pointers)
+            },
+            |(mut alloc, mut pointers)| {
+                let mut rng = Rng(0x5eed);
+                for round in 0..CHURN_ROUNDS {
+                    for _ in 0..CHURN_BATCH {
+                        let ptr = pointers.swap_remove(rng.below(pointers.len()));
+                        alloc.free(&ptr);
+                    }
+                    for i in 0..CHURN_BATCH {
+                        pointers.push(alloc.insert(new_entity(round * CHURN_BATCH + i)));
+                    }
+                }
+                (alloc, pointers)
+            },
+            BatchSize::LargeInput,
+        )
+    });
 }
 
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(50);
-    targets =   generational_array_allocation_bench,
-                pointers_array_access_bench,
+    targets =   generational_allocator_benches,
+                payload_size_benches,
+                iteration_benches,
+                is_live_benches,
+                validation_benches,
+                side_table_benches,
+                interior_mutability_benches,
+                copy_payload_benches,
+                hot_cold_benches,
+                recycling_benches,
 
                 box_alloc_allocation_bench,
                 box_alloc_access_bench,
-                inplace_alloc_allocation_bench,
-                inplace_alloc_access_bench
+                box_alloc_churn_bench,
+                tagged_pointer_benches
+);
+criterion_group!(
+    name = byte_benches;
+    // Byte counts barely vary between samples, which the plot density estimation can't handle
+    config = Criterion::default().with_measurement(BytesAllocated).sample_size(10).without_plots();
+    targets = bytes_allocated_benches
 );
-criterion_main!(benches);
+criterion_group!(summary, write_summary);
+criterion_main!(benches, byte_benches, summary);
@@ -0,0 +1,56 @@
+//! Writers hammering neighbouring slots of a `RwLockArena`, with and without cache line
+//! padding. Needs the `rwlock-slots` feature:
+//!
+//!     cargo bench --features rwlock-slots --bench false_sharing
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use example_allocators::{
+    cache_padded::CachePadded,
+    kyren_generational_indices::GenerationalIndex,
+    rwlock_arena::{PaddedRwLockArena, RwLockArena},
+};
+
+const WRITERS: usize = 4;
+const WRITES_PER_THREAD: usize = 100_000;
+
+/// Every writer bumps its own slot, so there's no logical contention at all, only the
+/// cache lines the slots happen to share
+fn contended_writes<T: Send + Sync>(arena: &RwLockArena<T>, handles: &[GenerationalIndex], bump: impl Fn(&mut T) + Sync) {
+    thread::scope(|scope| {
+        for handle in handles {
+            let bump = &bump;
+            scope.spawn(move || {
+                for _ in 0..WRITES_PER_THREAD {
+                    bump(&mut arena.write(handle).unwrap());
+                }
+            });
+        }
+    });
+}
+
+fn false_sharing_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("False sharing");
+    group.throughput(Throughput::Elements((WRITERS * WRITES_PER_THREAD) as u64));
+
+    let mut packed = RwLockArena::default();
+    let handles: Vec<_> = (0..WRITERS).map(|_| packed.insert(0u64)).collect();
+    group.bench_function("Neighbouring slots (RwLockArena)", |b| {
+        b.iter(|| contended_writes(&packed, &handles, |value| *value += 1))
+    });
+
+    let mut padded = PaddedRwLockArena::default();
+    let handles: Vec<_> = (0..WRITERS).map(|_| padded.insert(CachePadded::new(0u64))).collect();
+    group.bench_function("Padded slots (PaddedRwLockArena)", |b| {
+        b.iter(|| contended_writes(&padded, &handles, |value| **value += 1))
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = false_sharing_benches
+);
+criterion_main!(benches);
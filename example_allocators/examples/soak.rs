@@ -0,0 +1,203 @@
+//! Soak test for every allocator in the crate.
+//!
+//! Runs a long random sequence of allocations, frees and accesses against each allocator,
+//! checking the stored values on every access and the allocator invariants every few
+//! thousand operations, then prints a report. Doubles as an example of each API.
+//!
+//! Usage: `cargo run --release --example soak [cycles] [seed]`
+use std::time::{Duration, Instant};
+
+use example_allocators::dense_arena::DenseArena;
+use example_allocators::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use example_allocators::kyren_generational_indices::{
+    GIAUninitCell, GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell, GenerationalIndices,
+};
+use example_allocators::memory_allocators::{BoxAllocator, EntityPtr, GIABoxUninit, InPlaceAllocator};
+
+const CHECK_EVERY: usize = 10_000;
+
+/// Small xorshift generator, good enough to shuffle operations around
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+enum Op {
+    Allocate,
+    Free,
+    Access,
+}
+
+impl Op {
+    /// Grow while small, then keep the live count hovering around a few thousand
+    fn pick(rng: &mut Rng, live: usize) -> Op {
+        let roll = rng.below(100);
+        if live == 0 || (live < 4096 && roll < 50) || roll < 30 {
+            return Op::Allocate;
+        }
+        if roll < 60 {
+            return Op::Free;
+        }
+        Op::Access
+    }
+}
+
+struct Report {
+    name: &'static str,
+    elapsed: Duration,
+    stats: AllocatorStats,
+    stale_checks: usize,
+}
+
+fn soak<A: GenerationalAllocator<u64> + Default>(name: &'static str, cycles: usize, seed: u64) -> Report {
+    let mut rng = Rng(seed);
+    let mut allocator = A::default();
+    let mut live: Vec<(GenerationalIndex, u64)> = Vec::new();
+    let mut dead: Vec<GenerationalIndex> = Vec::new();
+    let mut stale_checks = 0;
+    let start = Instant::now();
+
+    for cycle in 0..cycles {
+        match Op::pick(&mut rng, live.len()) {
+            Op::Allocate => {
+                let value = rng.next();
+                live.push((allocator.insert(value), value));
+            }
+            Op::Free => {
+                let (index, _) = live.swap_remove(rng.below(live.len()));
+                allocator.free(&index);
+                dead.push(index);
+            }
+            Op::Access => {
+                let (index, value) = &live[rng.below(live.len())];
+                assert_eq!(allocator.with(index, |stored| *stored), Some(*value), "{}: wrong value", name);
+                allocator.with_mut(index, |stored| *stored = *value);
+
+                if !dead.is_empty() {
+                    let stale = &dead[rng.below(dead.len())];
+                    assert!(!allocator.is_live(stale), "{}: stale handle is live", name);
+                    stale_checks += 1;
+                }
+            }
+        }
+
+        if cycle % CHECK_EVERY == 0 {
+            allocator.check_invariants();
+            assert_eq!(allocator.stats().live, live.len(), "{}: wrong live count", name);
+        }
+    }
+
+    allocator.check_invariants();
+    Report { name, elapsed: start.elapsed(), stats: allocator.stats(), stale_checks }
+}
+
+/// `GenerationalIndices` only hands out indices, so it gets its own loop
+fn soak_indices(cycles: usize, seed: u64) -> Report {
+    let mut rng = Rng(seed);
+    let mut indices = GenerationalIndices::default();
+    let mut live: Vec<GenerationalIndex> = Vec::new();
+    let mut dead: Vec<GenerationalIndex> = Vec::new();
+    let mut stale_checks = 0;
+    let start = Instant::now();
+
+    for cycle in 0..cycles {
+        match Op::pick(&mut rng, live.len()) {
+            Op::Allocate => live.push(indices.new()),
+            Op::Free => {
+                let index = live.swap_remove(rng.below(live.len()));
+                indices.free(&index);
+                dead.push(index);
+            }
+            Op::Access => {
+                assert!(indices.is_live(&live[rng.below(live.len())]));
+                if !dead.is_empty() {
+                    assert!(!indices.is_live(&dead[rng.below(dead.len())]));
+                    stale_checks += 1;
+                }
+            }
+        }
+
+        if cycle % CHECK_EVERY == 0 {
+            indices.check_invariants();
+        }
+    }
+
+    indices.check_invariants();
+    Report { name: "GenerationalIndices", elapsed: start.elapsed(), stats: indices.stats(), stale_checks }
+}
+
+/// `BoxAllocator` hands out pointers instead of indices, so it gets its own loop too
+fn soak_box_allocator(cycles: usize, seed: u64) -> Report {
+    let mut rng = Rng(seed);
+    let mut allocator = BoxAllocator::<u64>::default();
+    let mut live: Vec<(EntityPtr<u64>, u64)> = Vec::new();
+    let mut dead: Vec<EntityPtr<u64>> = Vec::new();
+    let mut stale_checks = 0;
+    let start = Instant::now();
+
+    for cycle in 0..cycles {
+        match Op::pick(&mut rng, live.len()) {
+            Op::Allocate => {
+                let value = rng.next();
+                live.push((allocator.insert(value), value));
+            }
+            Op::Free => {
+                let (ptr, _) = live.swap_remove(rng.below(live.len()));
+                allocator.free(&ptr);
+                dead.push(ptr);
+            }
+            Op::Access => {
+                let (ptr, value) = &live[rng.below(live.len())];
+                assert_eq!(**ptr, *value, "BoxAllocator: wrong value");
+                if !dead.is_empty() {
+                    assert!(!dead[rng.below(dead.len())].is_live(), "BoxAllocator: stale pointer is live");
+                    stale_checks += 1;
+                }
+            }
+        }
+
+        if cycle % CHECK_EVERY == 0 {
+            allocator.check_invariants();
+            assert_eq!(allocator.stats().live, live.len(), "BoxAllocator: wrong live count");
+        }
+    }
+
+    allocator.check_invariants();
+    Report { name: "BoxAllocator", elapsed: start.elapsed(), stats: allocator.stats(), stale_checks }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let cycles: usize = args.next().map(|arg| arg.parse().expect("cycles must be a number")).unwrap_or(1_000_000);
+    let seed: u64 = args.next().map(|arg| arg.parse().expect("seed must be a number")).unwrap_or(0x5eed);
+    assert!(seed != 0, "xorshift needs a non-zero seed");
+
+    println!("Soaking every allocator with {} cycles (seed {})", cycles, seed);
+    let reports = vec![
+        soak_indices(cycles, seed),
+        soak::<GenerationalIndexArray<u64>>("GenerationalIndexArray", cycles, seed),
+        soak::<GenerationalIndexArrayCell<u64>>("GenerationalIndexArrayCell", cycles, seed),
+        soak::<GIAUninitCell<u64>>("GIAUninitCell", cycles, seed),
+        soak::<GIABoxUninit<u64>>("GIABoxUninit", cycles, seed),
+        soak::<InPlaceAllocator<u64>>("InPlaceAllocator", cycles, seed),
+        soak::<DenseArena<u64>>("DenseArena", cycles, seed),
+        soak_box_allocator(cycles, seed),
+    ];
+
+    for report in reports {
+        println!(
+            "{:<28} {:>10.2?}  {}  stale handles checked: {}",
+            report.name, report.elapsed, report.stats, report.stale_checks
+        );
+    }
+}
@@ -0,0 +1,270 @@
+/// Entity pointer slot that can be shared between threads.
+///
+/// An `EntityPtr` is a pointer plus a generation, too wide for a single portable atomic.
+/// `AtomicEntityPtr` keeps the parts in separate atomics behind a sequence lock: readers
+/// never block, they retry if a writer was halfway through, and writers serialize on the
+/// sequence number for the few stores they need. It's meant for lock-free structures that
+/// reference entities, e.g. on top of a thread safe arena. `load_handle` gives an index
+/// handle to look up through the allocator; `load` gives a pointer, which is unsafe since
+/// every load is another copy that derefs mutably. `read_optimistic` copies the entity
+/// out instead with atomic loads, and uses its generation as a second sequence number to
+/// notice when it was freed halfway through the copy.
+use std::hint::spin_loop;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of, MaybeUninit};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{fence, AtomicPtr, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+use crate::errors::AccessError;
+use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+use crate::memory_allocators::{Entry, EntityPtr};
+use crate::slot_headers::SlotHeader;
+
+pub struct AtomicEntityPtr<T> {
+    // Even while idle, odd while a writer is updating the parts
+    sequence: AtomicU32,
+    // Null when empty
+    ptr: AtomicPtr<Entry<T>>,
+    header: AtomicPtr<SlotHeader>,
+    index: AtomicUsize,
+    generation: AtomicU32,
+}
+
+// The parts are only touched through atomics, and the pointers they hold are checked
+// against their slot header before anyone uses them
+unsafe impl<T: Send + Sync> Send for AtomicEntityPtr<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicEntityPtr<T> {}
+
+impl<T> Default for AtomicEntityPtr<T> {
+    fn default() -> Self {
+        return AtomicEntityPtr {
+            sequence: AtomicU32::new(0),
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            header: AtomicPtr::new(ptr::null_mut()),
+            index: AtomicUsize::new(0),
+            generation: AtomicU32::new(0),
+        };
+    }
+}
+
+impl<T> AtomicEntityPtr<T> {
+    /// An empty slot
+    pub fn empty() -> Self {
+        return Self::default();
+    }
+
+    pub fn new(value: &EntityPtr<T>) -> Self {
+        let atomic = Self::default();
+        atomic.store(Some(value));
+        return atomic;
+    }
+
+    /// Current pointer, None if the slot is empty. The pointer may be dead, check it
+    /// with `is_live` or `try_get`
+    ///
+    /// # Safety
+    /// Every load hands out another copy of the stored pointer, and the copies deref
+    /// mutably. While one copy is dereferenced mutably no other may be dereferenced at
+    /// all, on any thread. A copy may only be dereferenced while the allocator that owns
+    /// the entity is alive and can't free it meanwhile, which across threads takes
+    /// synchronizing with the allocator's thread. `load_handle` and `is_live` need none
+    /// of this
+    pub unsafe fn load(&self) -> Option<EntityPtr<T>> {
+        let (ptr, header, index, generation) = self.snapshot();
+        return Self::assemble(ptr, header, index, generation);
+    }
+
+    /// Index handle to the current entity, None if the slot is empty. Turn it into a
+    /// reference through the allocator
+    pub fn load_handle(&self) -> Option<GenerationalIndex> {
+        let (ptr, _, index, generation) = self.snapshot();
+        return (!ptr.is_null()).then_some(GenerationalIndex { index, generation });
+    }
+
+    /// Whether the slot holds a pointer to a live entity. Safe from any thread, even
+    /// after the allocator was dropped
+    pub fn is_live(&self) -> bool {
+        let (ptr, header, _, generation) = self.snapshot();
+        // Slot headers are leaked, never freed
+        return !ptr.is_null() && unsafe { (*header).generation() } == generation;
+    }
+
+    /// Read the entity without locking anything, for read-mostly data shared across
+    /// threads. The value is copied out, then the slot's sequence and the entity's
+    /// generation are checked again: if the slot was stored to meanwhile the read starts
+    /// over, if the entity was freed meanwhile (or before) it's `NotLive`. `f` only ever
+    /// sees a copy that passed both checks. None if the slot is empty
+    ///
+    /// # Safety
+    /// The allocator behind the pointer must not be dropped or trimmed during the call.
+    /// Only frees are detected, so nothing may write to the live entity through another
+    /// pointer while it's read. The entry may be freed and reused from another thread
+    /// meanwhile. `T` must not have padding bytes, the copy reads every byte as an integer
+    pub unsafe fn read_optimistic<R>(&self, f: impl FnOnce(&T) -> R) -> Option<Result<R, AccessError>>
+    where
+        T: Copy,
+    {
+        loop {
+            let before = self.sequence.load(Ordering::Acquire);
+            if before & 1 == 1 {
+                spin_loop();
+                continue;
+            }
+
+            let ptr = self.ptr.load(Ordering::Relaxed);
+            let header = self.header.load(Ordering::Relaxed);
+            let index = self.index.load(Ordering::Relaxed);
+            let generation = self.generation.load(Ordering::Relaxed);
+            if ptr.is_null() {
+                fence(Ordering::Acquire);
+                if self.sequence.load(Ordering::Relaxed) == before {
+                    return None;
+                }
+                continue;
+            }
+
+            // May race with the entry being freed and reused, which the checks below
+            // catch. The copy is made with atomic loads so the race isn't a data race,
+            // and kept as `MaybeUninit` so a dead entry is never taken for a T
+            let copy = atomic_copy(Entry::value_ptr(ptr));
+
+            // Pairs with the fence in `SlotHeader::bump`: a copy holding anything written
+            // after a free comes with the new generation
+            fence(Ordering::Acquire);
+            if self.sequence.load(Ordering::Relaxed) != before {
+                continue;
+            }
+
+            let entity = Self::assemble(ptr, header, index, generation).unwrap();
+            if let Err(mismatch) = entity.validate() {
+                return Some(Err(mismatch.into()));
+            }
+
+            return Some(Ok(f(copy.assume_init_ref())));
+        }
+    }
+
+    pub fn store(&self, value: Option<&EntityPtr<T>>) {
+        let sequence = self.lock();
+        self.write(value);
+        self.unlock(sequence);
+    }
+
+    /// Store `new` only if the slot still holds `current` (same entity and generation).
+    /// On failure returns a handle to what the slot holds instead
+    pub fn compare_exchange(
+        &self,
+        current: Option<&EntityPtr<T>>,
+        new: Option<&EntityPtr<T>>,
+    ) -> Result<(), Option<GenerationalIndex>> {
+        let sequence = self.lock();
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        let matches = match current {
+            Some(current) => ptr == current.ptr.as_ptr() && generation == current.generation,
+            None => ptr.is_null(),
+        };
+        if !matches {
+            let index = self.index.load(Ordering::Relaxed);
+            self.unlock(sequence);
+            return Err((!ptr.is_null()).then_some(GenerationalIndex { index, generation }));
+        }
+
+        self.write(new);
+        self.unlock(sequence);
+        return Ok(());
+    }
+
+    /// One consistent read of the parts, waiting out writers
+    fn snapshot(&self) -> (*mut Entry<T>, *mut SlotHeader, usize, Generation) {
+        loop {
+            let before = self.sequence.load(Ordering::Acquire);
+            if before & 1 == 1 {
+                spin_loop();
+                continue;
+            }
+
+            let ptr = self.ptr.load(Ordering::Relaxed);
+            let header = self.header.load(Ordering::Relaxed);
+            let index = self.index.load(Ordering::Relaxed);
+            let generation = self.generation.load(Ordering::Relaxed);
+
+            fence(Ordering::Acquire);
+            if self.sequence.load(Ordering::Relaxed) == before {
+                return (ptr, header, index, generation);
+            }
+        }
+    }
+
+    /// Take the write side of the sequence lock, returns the odd sequence number to unlock with
+    fn lock(&self) -> u32 {
+        loop {
+            let sequence = self.sequence.load(Ordering::Relaxed);
+            if sequence & 1 == 0
+                && self
+                    .sequence
+                    .compare_exchange_weak(sequence, sequence + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                // Readers that see any of the new parts must also see the odd sequence
+                fence(Ordering::Release);
+                return sequence + 1;
+            }
+            spin_loop();
+        }
+    }
+
+    fn unlock(&self, sequence: u32) {
+        self.sequence.store(sequence.wrapping_add(1), Ordering::Release);
+    }
+
+    fn write(&self, value: Option<&EntityPtr<T>>) {
+        match value {
+            Some(value) => {
+                self.ptr.store(value.ptr.as_ptr(), Ordering::Relaxed);
+                self.header.store(value.header as *const SlotHeader as *mut SlotHeader, Ordering::Relaxed);
+                self.index.store(value.index, Ordering::Relaxed);
+                self.generation.store(value.generation, Ordering::Relaxed);
+            }
+            None => {
+                self.ptr.store(ptr::null_mut(), Ordering::Relaxed);
+                self.header.store(ptr::null_mut(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// # Safety
+    /// The parts must come from one consistent read, `header` is a leaked slot header
+    unsafe fn assemble(
+        ptr: *mut Entry<T>,
+        header: *mut SlotHeader,
+        index: usize,
+        generation: Generation,
+    ) -> Option<EntityPtr<T>> {
+        let ptr = NonNull::new(ptr)?;
+        return Some(EntityPtr { ptr, header: &*header, index, generation, marker: PhantomData });
+    }
+}
+
+/// Copy the value at `src` with relaxed atomic loads, a word at a time when `T` is word
+/// aligned and sized, otherwise a byte at a time. Writers to `src` may race with the copy,
+/// which then holds a mix of old and new bytes
+///
+/// # Safety
+/// `src` must be valid for reads for the whole copy, and every byte of it initialized
+unsafe fn atomic_copy<T>(src: *const MaybeUninit<T>) -> MaybeUninit<T> {
+    let mut copy = MaybeUninit::<T>::uninit();
+    if align_of::<T>() >= align_of::<usize>() && size_of::<T>().is_multiple_of(size_of::<usize>()) {
+        let (src, dst) = (src as *mut usize, copy.as_mut_ptr() as *mut usize);
+        for word in 0..size_of::<T>() / size_of::<usize>() {
+            dst.add(word).write(AtomicUsize::from_ptr(src.add(word)).load(Ordering::Relaxed));
+        }
+    } else {
+        let (src, dst) = (src as *mut u8, copy.as_mut_ptr() as *mut u8);
+        for byte in 0..size_of::<T>() {
+            dst.add(byte).write(AtomicU8::from_ptr(src.add(byte)).load(Ordering::Relaxed));
+        }
+    }
+    return copy;
+}
@@ -0,0 +1,258 @@
+//! Run an allocation workload against the allocators and print timings and stats.
+//!
+//! Quicker to tweak than the criterion benches when experimenting. The workload comes
+//! from flags, from a config file, or both (flags win):
+//!
+//! ```text
+//! arena-bench --entities 100000 --payload 64 --churn 0.25 --rounds 10 --access shuffled --allocator all
+//! arena-bench --config workload.toml
+//! ```
+//!
+//! The config file uses a small TOML subset, one `key = value` per line with the same
+//! names as the flags, `#` comments allowed:
+//!
+//! ```text
+//! entities = 100000
+//! payload = 256        # 8, 64, 256 or 1024 bytes
+//! churn = 0.1          # fraction of the entities freed and reallocated every round
+//! rounds = 20
+//! access = "shuffled"  # or "sequential"
+//! allocator = "InPlaceAllocator"
+//! ```
+use std::time::{Duration, Instant};
+
+use example_allocators::dense_arena::DenseArena;
+use example_allocators::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use example_allocators::kyren_generational_indices::{
+    GIAUninitCell, GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell,
+};
+use example_allocators::memory_allocators::{GIABoxUninit, InPlaceAllocator};
+
+const ALLOCATORS: [&str; 6] = [
+    "GenerationalIndexArray",
+    "GenerationalIndexArrayCell",
+    "GIAUninitCell",
+    "GIABoxUninit",
+    "InPlaceAllocator",
+    "DenseArena",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Access {
+    Sequential,
+    Shuffled,
+}
+
+struct Workload {
+    entities: usize,
+    payload: usize,
+    churn: f64,
+    rounds: usize,
+    access: Access,
+    allocator: String,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Workload {
+            entities: 10_000,
+            payload: 64,
+            churn: 0.1,
+            rounds: 10,
+            access: Access::Sequential,
+            allocator: "all".to_string(),
+        }
+    }
+}
+
+impl Workload {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim().trim_matches('"');
+        let invalid = |_| format!("Invalid value for {}: {}", key, value);
+        match key {
+            "entities" => self.entities = value.parse().map_err(invalid)?,
+            "payload" => self.payload = value.parse().map_err(invalid)?,
+            "churn" => self.churn = value.parse().map_err(|_| format!("Invalid value for churn: {}", value))?,
+            "rounds" => self.rounds = value.parse().map_err(invalid)?,
+            "access" => {
+                self.access = match value {
+                    "sequential" => Access::Sequential,
+                    "shuffled" => Access::Shuffled,
+                    _ => return Err(format!("Unknown access pattern: {}", value)),
+                }
+            }
+            "allocator" => self.allocator = value.to_string(),
+            _ => return Err(format!("Unknown setting: {}", key)),
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|error| format!("Can't read {}: {}", path, error))?;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| format!("Expected `key = value`: {}", line))?;
+            self.set(key.trim(), value)?;
+        }
+        Ok(())
+    }
+
+    fn from_args() -> Result<Workload, String> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let mut workload = Workload::default();
+
+        // Load the config file first so flags can override it
+        if let Some(position) = args.iter().position(|arg| arg == "--config") {
+            workload.load(args.get(position + 1).ok_or("--config needs a path")?)?;
+        }
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let key = flag.strip_prefix("--").ok_or_else(|| format!("Unexpected argument: {}", flag))?;
+            let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+            if key != "config" {
+                workload.set(key, value)?;
+            }
+        }
+
+        if !(0.0..=1.0).contains(&workload.churn) {
+            return Err("churn must be between 0 and 1".to_string());
+        }
+        Ok(workload)
+    }
+}
+
+/// Entity padded to exactly `BYTES` bytes
+struct Payload<const BYTES: usize>([u8; BYTES]);
+
+impl<const BYTES: usize> Default for Payload<BYTES> {
+    fn default() -> Self {
+        Payload([0; BYTES])
+    }
+}
+
+/// Small xorshift generator so runs are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+struct Report {
+    allocation: Duration,
+    access: Duration,
+    churn: Duration,
+    stats: AllocatorStats,
+}
+
+fn run<const BYTES: usize, A: GenerationalAllocator<Payload<BYTES>> + Default>(workload: &Workload) -> Report {
+    let mut rng = Rng(0x5eed);
+    let mut alloc = A::default();
+
+    let start = Instant::now();
+    let mut handles: Vec<GenerationalIndex> =
+        (0..workload.entities).map(|i| alloc.insert(Payload([i as u8; BYTES]))).collect();
+    let allocation = start.elapsed();
+
+    let mut order: Vec<usize> = (0..handles.len()).collect();
+    if workload.access == Access::Shuffled {
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i + 1));
+        }
+    }
+
+    let start = Instant::now();
+    let mut checksum = 0u64;
+    for i in order.iter() {
+        checksum += alloc.with(&handles[*i], |payload| payload.0[0] as u64).unwrap_or(0);
+    }
+    let access = start.elapsed();
+    std::hint::black_box(checksum);
+
+    let batch = (workload.entities as f64 * workload.churn) as usize;
+    let start = Instant::now();
+    for _ in 0..workload.rounds {
+        for _ in 0..batch.min(handles.len()) {
+            let handle = handles.swap_remove(rng.below(handles.len()));
+            alloc.free(&handle);
+        }
+        for i in 0..batch {
+            handles.push(alloc.insert(Payload([i as u8; BYTES])));
+        }
+    }
+    let churn = start.elapsed();
+
+    alloc.check_invariants();
+    Report { allocation, access, churn, stats: alloc.stats() }
+}
+
+fn run_allocator<const BYTES: usize>(name: &str, workload: &Workload) -> Option<Report> {
+    Some(match name {
+        "GenerationalIndexArray" => run::<BYTES, GenerationalIndexArray<Payload<BYTES>>>(workload),
+        "GenerationalIndexArrayCell" => run::<BYTES, GenerationalIndexArrayCell<Payload<BYTES>>>(workload),
+        "GIAUninitCell" => run::<BYTES, GIAUninitCell<Payload<BYTES>>>(workload),
+        "GIABoxUninit" => run::<BYTES, GIABoxUninit<Payload<BYTES>>>(workload),
+        "InPlaceAllocator" => run::<BYTES, InPlaceAllocator<Payload<BYTES>>>(workload),
+        "DenseArena" => run::<BYTES, DenseArena<Payload<BYTES>>>(workload),
+        _ => return None,
+    })
+}
+
+fn main() {
+    let workload = match Workload::from_args() {
+        Ok(workload) => workload,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("Usage: arena-bench [--config FILE] [--entities N] [--payload 8|64|256|1024] [--churn RATIO] [--rounds N] [--access sequential|shuffled] [--allocator NAME|all]");
+            std::process::exit(2);
+        }
+    };
+
+    let names: Vec<&str> = if workload.allocator == "all" {
+        ALLOCATORS.to_vec()
+    } else {
+        vec![workload.allocator.as_str()]
+    };
+
+    println!(
+        "{} entities of {} bytes, {} rounds of {:.0}% churn, {} access",
+        workload.entities,
+        workload.payload,
+        workload.rounds,
+        workload.churn * 100.0,
+        if workload.access == Access::Sequential { "sequential" } else { "shuffled" }
+    );
+
+    for name in names {
+        let report = match workload.payload {
+            8 => run_allocator::<8>(name, &workload),
+            64 => run_allocator::<64>(name, &workload),
+            256 => run_allocator::<256>(name, &workload),
+            1024 => run_allocator::<1024>(name, &workload),
+            other => {
+                eprintln!("Unsupported payload size {}, use 8, 64, 256 or 1024", other);
+                std::process::exit(2);
+            }
+        };
+
+        match report {
+            Some(report) => println!(
+                "{:<28} alloc {:>10.2?}  access {:>10.2?}  churn {:>10.2?}\n{:<28} {}",
+                name, report.allocation, report.access, report.churn, "", report.stats
+            ),
+            None => {
+                eprintln!("Unknown allocator {}, expected one of {:?} or all", name, ALLOCATORS);
+                std::process::exit(2);
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+/// Bits written by batch queries like `InPlaceAllocator::validate_many`, one per input.
+///
+/// Bit `i` is bit `i % 64` of word `i / 64`, the layout `InPlaceAllocator::occupancy`
+/// uses, so results can be combined with it a word at a time. Bits past `len` in the
+/// last word are always clear.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct BitVec {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVec {
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// Bit `index`, None past the end
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len {
+            return None;
+        }
+
+        return Some(self.words[index / 64] & (1 << (index % 64)) != 0);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        return self.words.iter().map(|word| word.count_ones() as usize).sum();
+    }
+
+    /// Indices of the set bits in increasing order
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.words.iter().enumerate().flat_map(|(word_index, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(word_index * 64 + bit)
+            })
+        });
+    }
+
+    pub fn words(&self) -> &[u64] {
+        return &self.words;
+    }
+
+    /// Drop every bit, keeping the capacity
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.len = 0;
+    }
+
+    pub(crate) fn reserve(&mut self, bits: usize) {
+        self.words.reserve(bits.div_ceil(64));
+    }
+
+    /// Append the low `bits` bits of `word`. `len` must be a multiple of 64, so appends
+    /// always start a new word
+    pub(crate) fn push_word(&mut self, word: u64, bits: usize) {
+        debug_assert!(self.len.is_multiple_of(64) && bits <= 64);
+        let mask = if bits == 64 { u64::MAX } else { (1 << bits) - 1 };
+        self.words.push(word & mask);
+        self.len += bits;
+    }
+}
@@ -0,0 +1,138 @@
+/// Growable bitset used to track which slots of an allocator are occupied.
+///
+/// Scanning the bitset skips 64 dead slots per word, so iterating a sparse allocator
+/// doesn't have to touch the dead entries at all.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    #[inline(always)]
+    pub(crate) fn insert(&mut self, index: usize) {
+        let word = index / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (index % 64);
+    }
+
+    #[inline(always)]
+    pub(crate) fn remove(&mut self, index: usize) {
+        if let Some(word) = self.words.get_mut(index / 64) {
+            *word &= !(1 << (index % 64));
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn contains(&self, index: usize) -> bool {
+        return match self.words.get(index / 64) {
+            Some(word) => word & (1 << (index % 64)) != 0,
+            None => false,
+        };
+    }
+
+    /// The bits, 64 slots per word, lowest slot in the lowest bit
+    pub(crate) fn words(&self) -> &[u64] {
+        return &self.words;
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        return self.words.iter().map(|word| word.count_ones() as usize).sum();
+    }
+
+    /// Indices of the set bits with the given ranks (0 being the lowest set bit). `ranks`
+    /// must be sorted and below `count()`. Skips whole words by their popcount, so only
+    /// the words holding a selected bit are scanned bit by bit
+    pub(crate) fn select(&self, ranks: &[usize]) -> Vec<usize> {
+        let mut selected = Vec::with_capacity(ranks.len());
+        let mut ranks = ranks.iter().copied().peekable();
+        let mut seen = 0;
+        for (word_index, word) in self.words.iter().enumerate() {
+            let ones = word.count_ones() as usize;
+            let mut word = *word;
+            let mut rank_in_word = 0;
+            while let Some(rank) = ranks.next_if(|rank| *rank < seen + ones) {
+                // Drop the set bits below the one we're after
+                while rank_in_word < rank - seen {
+                    word &= word - 1;
+                    rank_in_word += 1;
+                }
+                selected.push(word_index * 64 + word.trailing_zeros() as usize);
+            }
+            seen += ones;
+        }
+
+        return selected;
+    }
+
+    /// Indices of the set bits in increasing order
+    pub(crate) fn iter(&self) -> Ones<'_> {
+        return Ones {
+            words: &self.words,
+            front: self.words.first().copied().unwrap_or(0),
+            front_word: 0,
+            back: self.words.last().copied().unwrap_or(0),
+            back_word: self.words.len().saturating_sub(1),
+            remaining: self.count(),
+        };
+    }
+}
+
+/// Iterator over the set bits of a `Bitset`, from either end.
+///
+/// The two ends keep their own copy of the word they're on. When they reach the same
+/// word they'd both see its bits, but `remaining` stops the iteration as soon as every
+/// set bit was yielded once, so they never hand out the same bit twice.
+pub(crate) struct Ones<'a> {
+    words: &'a [u64],
+    // Bits of `words[front_word]` not yielded from the front yet
+    front: u64,
+    front_word: usize,
+    // Bits of `words[back_word]` not yielded from the back yet
+    back: u64,
+    back_word: usize,
+    remaining: usize,
+}
+
+impl Iterator for Ones<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        while self.front == 0 {
+            self.front_word += 1;
+            self.front = self.words[self.front_word];
+        }
+        let bit = self.front.trailing_zeros() as usize;
+        self.front &= self.front - 1;
+        self.remaining -= 1;
+        return Some(self.front_word * 64 + bit);
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        return (self.remaining, Some(self.remaining));
+    }
+}
+
+impl DoubleEndedIterator for Ones<'_> {
+    fn next_back(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        while self.back == 0 {
+            self.back_word -= 1;
+            self.back = self.words[self.back_word];
+        }
+        let bit = 63 - self.back.leading_zeros() as usize;
+        self.back &= !(1 << bit);
+        self.remaining -= 1;
+        return Some(self.back_word * 64 + bit);
+    }
+}
+
+impl ExactSizeIterator for Ones<'_> {}
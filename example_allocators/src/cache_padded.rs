@@ -0,0 +1,42 @@
+/// Wrapper that gives a value a cache line to itself.
+///
+/// When two threads write to values that share a 64 byte cache line, every write
+/// invalidates the line in the other core's cache even though the values are
+/// unrelated (false sharing). Storing `CachePadded<T>` in an arena aligns every slot to
+/// 64 bytes, so neighbouring slots never share a line, at the cost of rounding each slot
+/// up to a multiple of 64 bytes.
+use std::ops::{Deref, DerefMut};
+
+/// Size of the cache lines the padding is meant for
+pub const CACHE_LINE: usize = 64;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(align(64))]
+pub struct CachePadded<T>(pub T);
+
+// `repr(align)` needs a literal, keep it in sync with the constant
+const _: () = assert!(std::mem::align_of::<CachePadded<u8>>() == CACHE_LINE);
+
+impl<T> CachePadded<T> {
+    pub fn new(value: T) -> Self {
+        return CachePadded(value);
+    }
+
+    pub fn into_inner(self) -> T {
+        return self.0;
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.0;
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return &mut self.0;
+    }
+}
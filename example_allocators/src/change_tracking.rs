@@ -0,0 +1,294 @@
+/// Arena that remembers when each slot last changed, to send deltas over the network.
+///
+/// Sending the whole arena every tick (`to_bytes`) wastes bandwidth when only a few
+/// entities moved. `ChangeTrackedArena` wraps a `GenerationalIndexArray` and stamps a
+/// slot with the current tick whenever it's allocated, freed or borrowed mutably.
+/// `serialize_delta(since)` then writes only the slots stamped after `since`, and
+/// `apply_delta` replays them on a copy, which ends up equal to the original, handles
+/// and free list included.
+///
+/// A typical server loop mutates the arena, sends `serialize_delta(last_sent)`, sets
+/// `last_sent` to `current_tick()` and calls `advance_tick()`. Clients start from a
+/// full `to_bytes` snapshot and apply every delta in order.
+use crate::compact_encoding::{read_len, read_varint, write_varint, CompactDecode, CompactEncode};
+use crate::errors::DecodeError;
+use crate::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{Generation, GenerationalIndex, GenerationalIndexArray};
+
+pub type Tick = u64;
+
+// Bumped whenever the layout written by `serialize_delta` changes
+const DELTA_VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub struct ChangeTrackedArena<T> {
+    slots: GenerationalIndexArray<T>,
+    // Tick each slot last changed at, parallel to the slots
+    changed: Vec<Tick>,
+    tick: Tick,
+}
+
+impl<T> Default for ChangeTrackedArena<T> {
+    fn default() -> Self {
+        return Self::from_array(GenerationalIndexArray::default());
+    }
+}
+
+impl<T> ChangeTrackedArena<T> {
+    /// Start tracking an existing array, e.g. one rebuilt with `from_bytes` on a client.
+    /// Every slot counts as changed at tick 1, the first tick
+    pub fn from_array(slots: GenerationalIndexArray<T>) -> Self {
+        return ChangeTrackedArena { changed: vec![1; slots.slot_count()], slots, tick: 1 };
+    }
+
+    /// The tracked array, e.g. to take a full snapshot with `to_bytes`
+    pub fn array(&self) -> &GenerationalIndexArray<T> {
+        return &self.slots;
+    }
+
+    pub fn current_tick(&self) -> Tick {
+        return self.tick;
+    }
+
+    /// Move on to the next tick, changes from here on are stamped with it
+    pub fn advance_tick(&mut self) -> Tick {
+        self.tick += 1;
+        return self.tick;
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        let handle = self.slots.insert(element);
+        self.stamp(handle.get_index());
+        return handle;
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        self.slots.free(index);
+        self.stamp(index.get_index());
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        let value = self.slots.remove(index)?;
+        self.stamp(index.get_index());
+        return Some(value);
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.slots.contains(index);
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return self.slots.get(index);
+    }
+
+    /// Mutable access, marks the entry as changed whether or not it's written to
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        if !self.slots.contains(index) {
+            return None;
+        }
+
+        self.stamp(index.get_index());
+        return self.slots.get_mut(index);
+    }
+
+    /// Tick the slot of `index` last changed at, whether or not the handle is live
+    pub fn changed_at(&self, index: &GenerationalIndex) -> Option<Tick> {
+        return self.changed.get(index.get_index()).copied();
+    }
+
+    /// Live handles whose entry changed after `since`
+    pub fn changed_since(&self, since: Tick) -> Vec<GenerationalIndex> {
+        return self.slots.iter().filter(|(handle, _)| self.changed[handle.get_index()] > since).map(|(handle, _)| handle).collect();
+    }
+
+    pub fn handles(&self) -> Vec<GenerationalIndex> {
+        return self.slots.handles();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.slots.stats();
+    }
+
+    /// Panic if the array is inconsistent or a slot has no tick
+    pub fn check_invariants(&self) {
+        self.slots.check_invariants();
+        assert_eq!(self.changed.len(), self.slots.slot_count(), "Every slot needs exactly one change tick");
+        assert!(self.changed.iter().all(|tick| *tick <= self.tick), "A slot changed in the future");
+    }
+
+    fn stamp(&mut self, index: usize) {
+        if index >= self.changed.len() {
+            self.changed.resize(index + 1, self.tick);
+        }
+        self.changed[index] = self.tick;
+    }
+}
+
+impl<T: CompactEncode> ChangeTrackedArena<T> {
+    /// Encode the slots that changed after `since`, plus the slot count and free list, so
+    /// `apply_delta` can bring a copy that's up to date as of `since` in line with this
+    /// arena. Freed slots are sent as dead, without a value
+    pub fn serialize_delta(&self, since: Tick) -> Vec<u8> {
+        let mut out = vec![DELTA_VERSION];
+        write_varint(&mut out, self.tick);
+        write_varint(&mut out, self.slots.slot_count() as u64);
+
+        let changed: Vec<usize> = (0..self.changed.len()).filter(|index| self.changed[*index] > since).collect();
+        write_varint(&mut out, changed.len() as u64);
+        let mut previous = 0;
+        for index in changed {
+            // Indices only go up, so the gaps are small and never negative
+            write_varint(&mut out, (index - previous) as u64);
+            previous = index;
+
+            let (generation, item) = self.slots.slot(index);
+            write_varint(&mut out, generation as u64);
+            item.is_some().encode(&mut out);
+            if let Some(item) = item {
+                item.encode(&mut out);
+            }
+        }
+
+        let free: Vec<usize> = self.slots.iter_free().map(|(index, _)| index).collect();
+        free.encode(&mut out);
+
+        return out;
+    }
+}
+
+impl<T: CompactDecode> ChangeTrackedArena<T> {
+    /// Replay a delta from `serialize_delta` and return the tick it was taken at. The
+    /// whole delta is checked before anything is applied, so on error the arena is left
+    /// as it was. Applied slots are stamped with this arena's current tick, so deltas
+    /// can be relayed further
+    pub fn apply_delta(&mut self, bytes: &[u8]) -> Result<Tick, DecodeError> {
+        let input = &mut &bytes[..];
+        let version = u8::decode(input)?;
+        if version != DELTA_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let sender_tick = read_varint(input)?;
+        let len = read_len(input)?;
+        if len < self.slots.slot_count() {
+            return Err(DecodeError::Invalid("delta has fewer slots than the arena"));
+        }
+
+        let count = read_len(input)?;
+        let mut changes: Vec<(usize, Generation, Option<T>)> = Vec::with_capacity(count.min(input.len()));
+        let mut index: usize = 0;
+        for position in 0..count {
+            let gap = read_len(input)?;
+            if position > 0 && gap == 0 {
+                return Err(DecodeError::Invalid("slot changed twice in the same delta"));
+            }
+            index = index.checked_add(gap).filter(|index| *index < len).ok_or(DecodeError::Invalid("changed slot out of range"))?;
+            let generation = Generation::try_from(read_varint(input)?).map_err(|_| DecodeError::Invalid("generation out of range"))?;
+            let item = Option::<T>::decode(input)?;
+            if item.is_some() && generation == Generation::MAX {
+                return Err(DecodeError::Invalid("retired slot holds a value"));
+            }
+            changes.push((index, generation, item));
+        }
+        let free = Vec::<usize>::decode(input)?;
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        self.check_delta(len, &changes, &free)?;
+        for (index, generation, item) in changes {
+            self.slots.overwrite_slot(index, generation, item);
+            self.stamp(index);
+        }
+        self.slots.replace_free_list(free);
+
+        return Ok(sender_tick);
+    }
+
+    // Check the arena that `changes` and `free` would produce without building it
+    fn check_delta(&self, len: usize, changes: &[(usize, Generation, Option<T>)], free: &[usize]) -> Result<(), DecodeError> {
+        let old_len = self.slots.slot_count();
+        let grown = changes.iter().filter(|(index, _, _)| *index >= old_len).count();
+        if grown != len - old_len {
+            return Err(DecodeError::Invalid("new slots are missing from the delta"));
+        }
+
+        // (generation, live) of every slot once the delta is applied
+        let mut after: Vec<(Generation, bool)> = (0..old_len)
+            .map(|index| {
+                let (generation, item) = self.slots.slot(index);
+                (generation, item.is_some())
+            })
+            .collect();
+        after.resize(len, (0, false));
+        for (index, generation, item) in changes {
+            after[*index] = (*generation, item.is_some());
+        }
+
+        let mut listed = vec![false; len];
+        for index in free {
+            match after.get(*index) {
+                Some((generation, false)) if *generation != Generation::MAX && !listed[*index] => listed[*index] = true,
+                _ => return Err(DecodeError::Invalid("free list holds a live, retired, repeated or missing slot")),
+            }
+        }
+        let unaccounted = after.iter().zip(listed.iter()).filter(|((generation, live), listed)| !live && !**listed && *generation != Generation::MAX);
+        if unaccounted.count() > 0 {
+            return Err(DecodeError::Invalid("some dead slots are missing from the free list"));
+        }
+
+        return Ok(());
+    }
+}
+
+impl<T> GenerationalAllocator<T> for ChangeTrackedArena<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return ChangeTrackedArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        ChangeTrackedArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return ChangeTrackedArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return ChangeTrackedArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return ChangeTrackedArena::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return ChangeTrackedArena::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return ChangeTrackedArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        ChangeTrackedArena::check_invariants(self);
+    }
+}
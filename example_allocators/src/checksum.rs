@@ -0,0 +1,54 @@
+/// CRC-32 trailers for saved arenas.
+///
+/// `from_bytes` rejects input that doesn't decode, but a flipped bit in a generation or a
+/// free slot index still decodes fine, and gives back an arena whose handles point at the
+/// wrong values. `seal` appends the CRC-32 (the zlib/PNG one) of a payload to it, and
+/// `unseal` checks it before anything is decoded. They work on the output of any of the
+/// crate's encodings: `to_bytes`, `export_relocatable`, `VersionedFormat::save`...
+use crate::errors::DecodeError;
+
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc = TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    return !crc;
+}
+
+/// Append the checksum of `payload` to it
+pub fn seal(mut payload: Vec<u8>) -> Vec<u8> {
+    let checksum = crc32(&payload);
+    payload.extend_from_slice(&checksum.to_le_bytes());
+    return payload;
+}
+
+/// The payload of a buffer written by `seal`, if its checksum matches
+pub fn unseal(sealed: &[u8]) -> Result<&[u8], DecodeError> {
+    let split = sealed.len().checked_sub(4).ok_or(DecodeError::UnexpectedEnd)?;
+    let (payload, trailer) = sealed.split_at(split);
+    let expected = u32::from_le_bytes(trailer.try_into().unwrap());
+    let actual = crc32(payload);
+    if expected != actual {
+        return Err(DecodeError::ChecksumMismatch { expected, actual });
+    }
+
+    return Ok(payload);
+}
@@ -0,0 +1,197 @@
+/// Compact binary encoding for save files and network snapshots.
+///
+/// Arenas are mostly small integers (indices, generations, lengths), so everything
+/// integer is written as a LEB128 varint: 7 bits per byte, high bit set while more bytes
+/// follow. Signed values are zigzag encoded first, so small negative numbers stay small.
+///
+/// Values stored in an arena implement `CompactEncode`/`CompactDecode`. The crate has no
+/// dependencies, so these are its own traits rather than serde's; implementing them for
+/// a struct means encoding its fields one after the other.
+use crate::errors::DecodeError;
+
+pub trait CompactEncode {
+    /// Append the encoding of `self` to `out`
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+pub trait CompactDecode: Sized {
+    /// Decode a value from the front of `input`, advancing it past the bytes read
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn read_varint(input: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = input.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        *input = rest;
+        // The tenth byte can only hold the top bit of a u64
+        if shift == 63 && *byte > 1 {
+            return Err(DecodeError::VarintOverflow);
+        }
+
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    return Err(DecodeError::VarintOverflow);
+}
+
+pub fn write_signed_varint(out: &mut Vec<u8>, value: i64) {
+    write_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+pub fn read_signed_varint(input: &mut &[u8]) -> Result<i64, DecodeError> {
+    let zigzag = read_varint(input)?;
+    return Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+}
+
+/// Varint that must fit in a `usize`, e.g. a length or an index
+pub(crate) fn read_len(input: &mut &[u8]) -> Result<usize, DecodeError> {
+    return usize::try_from(read_varint(input)?).map_err(|_| DecodeError::Invalid("length doesn't fit in usize"));
+}
+
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    return Ok(bytes);
+}
+
+macro_rules! unsigned {
+    ($($ty:ty),*) => {$(
+        impl CompactEncode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                write_varint(out, *self as u64);
+            }
+        }
+
+        impl CompactDecode for $ty {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                return <$ty>::try_from(read_varint(input)?).map_err(|_| DecodeError::Invalid("integer out of range"));
+            }
+        }
+    )*};
+}
+
+macro_rules! signed {
+    ($($ty:ty),*) => {$(
+        impl CompactEncode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                write_signed_varint(out, *self as i64);
+            }
+        }
+
+        impl CompactDecode for $ty {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                return <$ty>::try_from(read_signed_varint(input)?).map_err(|_| DecodeError::Invalid("integer out of range"));
+            }
+        }
+    )*};
+}
+
+// Floats don't shrink as varints, they're stored as their little endian bytes
+macro_rules! float {
+    ($($ty:ty),*) => {$(
+        impl CompactEncode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl CompactDecode for $ty {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                let bytes = take(input, std::mem::size_of::<$ty>())?;
+                return Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()));
+            }
+        }
+    )*};
+}
+
+unsigned!(u8, u16, u32, u64, usize);
+signed!(i8, i16, i32, i64, isize);
+float!(f32, f64);
+
+impl CompactEncode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl CompactDecode for bool {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        return match take(input, 1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid("bool that isn't 0 or 1")),
+        };
+    }
+}
+
+impl CompactEncode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.len() as u64);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl CompactDecode for String {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = read_len(input)?;
+        let bytes = take(input, len)?;
+        return String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Invalid("string isn't UTF-8"));
+    }
+}
+
+impl<T: CompactEncode> CompactEncode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.len() as u64);
+        for item in self.iter() {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T: CompactDecode> CompactDecode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = read_len(input)?;
+        // Every item takes at least a byte, don't let a corrupt length reserve more
+        let mut items = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            items.push(T::decode(input)?);
+        }
+
+        return Ok(items);
+    }
+}
+
+impl<T: CompactEncode> CompactEncode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_some().encode(out);
+        if let Some(value) = self {
+            value.encode(out);
+        }
+    }
+}
+
+impl<T: CompactDecode> CompactDecode for Option<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        if !bool::decode(input)? {
+            return Ok(None);
+        }
+
+        return Ok(Some(T::decode(input)?));
+    }
+}
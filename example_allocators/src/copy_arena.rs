@@ -0,0 +1,156 @@
+/// Arena for small `Copy` values, mutable through `&self` without borrow flags.
+///
+/// Transforms, particles and similar payloads are read and written whole, never
+/// borrowed for long, so the `RefCell` arenas spend a flag check per access on
+/// something that can't go wrong. Slots here are `Cell<T>`: values are copied in and
+/// out with `get`/`set`/`update`, and no reference into a slot ever escapes.
+use std::cell::Cell;
+use std::ops::Deref;
+
+use crate::errors::AllocError;
+use crate::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+#[derive(Debug)]
+pub struct CopyArena<T: Copy> {
+    slots: GenerationalIndexArray<Cell<T>>,
+}
+
+/// Copy of a value, what `GenerationalAllocator::get` hands out since no reference into
+/// a slot may escape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Copied<T>(pub T);
+
+impl<T> Deref for Copied<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.0;
+    }
+}
+
+impl<T: Copy> Default for CopyArena<T> {
+    fn default() -> Self {
+        return CopyArena { slots: GenerationalIndexArray::default() };
+    }
+}
+
+impl<T: Copy> CopyArena<T> {
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        return self.slots.insert(Cell::new(element));
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.slots.try_insert(Cell::new(element)).map_err(|error| error.map(Cell::into_inner));
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        self.slots.free(index);
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return self.slots.remove(index).map(Cell::into_inner);
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.slots.contains(index);
+    }
+
+    /// Copy of the value, None if the handle is dead
+    pub fn get(&self, index: &GenerationalIndex) -> Option<T> {
+        return self.slots.get(index).map(Cell::get);
+    }
+
+    /// Overwrite the value. Returns false, leaving the arena untouched, if the handle is dead
+    pub fn set(&self, index: &GenerationalIndex, value: T) -> bool {
+        return match self.slots.get(index) {
+            Some(cell) => {
+                cell.set(value);
+                true
+            }
+            None => false,
+        };
+    }
+
+    /// Replace the value with `f(value)` and return the new value, None if the handle is dead
+    pub fn update(&self, index: &GenerationalIndex, f: impl FnOnce(T) -> T) -> Option<T> {
+        let cell = self.slots.get(index)?;
+        let value = f(cell.get());
+        cell.set(value);
+        return Some(value);
+    }
+
+    /// Snapshot of the live handles, so callers can free or allocate while walking it
+    pub fn handles(&self) -> Vec<GenerationalIndex> {
+        return self.slots.handles();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.slots.stats();
+    }
+
+    pub fn check_invariants(&self) {
+        self.slots.check_invariants();
+    }
+}
+
+impl<T: Copy> GenerationalAllocator<T> for CopyArena<T> {
+    type Ref<'a> = Copied<T> where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return CopyArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        CopyArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return CopyArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return CopyArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<Copied<T>> {
+        return CopyArena::get(self, index).map(Copied);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return self.slots.get_mut(index).map(Cell::get_mut);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(|value| f(&value));
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let cell = self.slots.get_mut(index)?;
+        return Some(f(cell.get_mut()));
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return CopyArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        CopyArena::check_invariants(self);
+    }
+}
@@ -0,0 +1,302 @@
+/// Handle based allocator that keeps every live value packed in one dense array.
+///
+/// Handles point to a slot, and the slot points to the value's position in the dense
+/// array. Freeing swaps the last value into the hole, so values are always contiguous:
+/// iterating is a plain walk over an array with no dead entries in between. The price
+/// is an extra indirection on every lookup and values moving around on free.
+use std::panic::Location;
+
+use crate::errors::AllocError;
+use crate::fault_injection::try_reserve_one;
+use crate::free_sites::FreeSites;
+use crate::generational_allocator::{check_free_list, AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{bump_generation, Generation, GenerationalIndex};
+use crate::sampling::{distinct_ranks, RandomSource};
+
+#[derive(Debug)]
+pub struct DenseArena<T> {
+    values: Vec<T>,
+    // Slot owning each value, parallel to `values`
+    owners: Vec<usize>,
+    slots: Vec<DenseSlot>,
+    free: Vec<usize>,
+    free_sites: FreeSites,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DenseSlot {
+    generation: Generation,
+    // Position of the value in `values`, only meaningful while the slot is live
+    dense: usize,
+}
+
+// Written by hand so an empty arena doesn't require `T: Default`
+impl<T> Default for DenseArena<T> {
+    fn default() -> Self {
+        return DenseArena {
+            values: Vec::new(),
+            owners: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            free_sites: FreeSites::default(),
+        };
+    }
+}
+
+impl<T> DenseArena<T> {
+    /// Test hook: an arena whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        return DenseArena {
+            values: Vec::new(),
+            owners: Vec::new(),
+            slots: vec![DenseSlot { generation, dense: 0 }; count],
+            free: (0..count).rev().collect(),
+            free_sites: FreeSites::default(),
+        };
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert_no_grow(element);
+    }
+
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        // The dense arrays only shrink on free, so a free slot normally means they have
+        // room too, but check rather than assume
+        let dense_has_room = self.values.len() < self.values.capacity() && self.owners.len() < self.owners.capacity();
+        if self.free.is_empty() || !dense_has_room {
+            return Err(AllocError::NoFreeSlot(element));
+        }
+
+        return self.try_insert(element);
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        if self.free.is_empty() && !try_reserve_one(&mut self.slots, 0) {
+            return Err(AllocError::OutOfMemory(element));
+        }
+        if !try_reserve_one(&mut self.values, std::mem::size_of::<usize>()) || self.owners.try_reserve(1).is_err() {
+            return Err(AllocError::OutOfMemory(element));
+        }
+
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(DenseSlot { generation: 0, dense: 0 });
+                self.slots.len() - 1
+            }
+        };
+
+        let slot = &mut self.slots[index];
+        slot.dense = self.values.len();
+        self.values.push(element);
+        self.owners.push(index);
+
+        return Ok(GenerationalIndex { index, generation: slot.generation });
+    }
+
+    #[inline(always)]
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.slots.get(index.index) {
+            Some(slot) => slot.generation == index.generation && self.owners.get(slot.dense) == Some(&index.index),
+            None => false,
+        };
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return Some(&self.values[self.slots[index.index].dense]);
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return Some(&mut self.values[self.slots[index.index].dense]);
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        if self.remove(index).is_none() {
+            panic!("Trying to free already unused index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        let dense = self.slots[index.index].dense;
+
+        // Move the last value into the hole and tell its slot where it went
+        let value = self.values.swap_remove(dense);
+        self.owners.swap_remove(dense);
+        // The last position was vacated, whichever value was there has moved or left
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize(&mut self.values.spare_capacity_mut()[0]);
+        if let Some(moved) = self.owners.get(dense) {
+            self.slots[*moved].dense = dense;
+        }
+
+        if bump_generation(&mut self.slots[index.index].generation) {
+            self.free.push(index.index);
+        }
+        return Some(value);
+    }
+
+    /// Number of live values
+    pub fn len(&self) -> usize {
+        return self.values.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.values.is_empty();
+    }
+
+    /// Iterate over the live entries. Walks the dense array, so the order follows
+    /// the dense layout rather than the slot order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &T)> + DoubleEndedIterator {
+        return self.owners.iter().zip(self.values.iter()).map(move |(index, value)| {
+            (GenerationalIndex { index: *index, generation: self.slots[*index].generation }, value)
+        });
+    }
+
+    /// Handles of `k` live entries picked uniformly at random without repeats, in dense
+    /// order. Every live entry if there are at most `k`
+    pub fn sample(&self, rng: &mut impl RandomSource, k: usize) -> Vec<GenerationalIndex> {
+        return distinct_ranks(rng, k, self.owners.len())
+            .into_iter()
+            .map(|rank| {
+                let index = self.owners[rank];
+                GenerationalIndex { index, generation: self.slots[index].generation }
+            })
+            .collect();
+    }
+
+    /// First live entry, in `iter` order, for which `predicate` returns true
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<(GenerationalIndex, &T)> {
+        return self.iter().find(|(_, value)| predicate(value));
+    }
+
+    /// First non-None result of `f` over the live entries, in `iter` order, with the
+    /// handle it came from
+    pub fn find_map<R>(&self, mut f: impl FnMut(&T) -> Option<R>) -> Option<(GenerationalIndex, R)> {
+        return self.iter().find_map(|(handle, value)| f(value).map(|result| (handle, result)));
+    }
+
+    /// Snapshot of the live handles in dense order, so callers can free or allocate while
+    /// walking it
+    pub fn handles(&self) -> Vec<GenerationalIndex> {
+        return self.iter().map(|(handle, _)| handle).collect();
+    }
+
+    /// The live values as a contiguous slice, in dense order
+    pub fn values(&self) -> &[T] {
+        return &self.values;
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        return &mut self.values;
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_ {
+        return self.free.iter().rev().map(|index| (*index, self.slots[*index].generation));
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.slots.iter().filter(|slot| slot.generation == Generation::MAX).count();
+        return AllocatorStats { capacity: self.slots.len(), live: self.values.len(), free: self.free.len(), retired };
+    }
+
+    /// Panic if the free list is inconsistent or slots and dense values don't point to each other
+    pub fn check_invariants(&self) {
+        check_free_list(self.free.iter().copied(), self.slots.len(), |index| self.slots[index].generation);
+        assert_eq!(self.values.len(), self.owners.len(), "Every value needs exactly one owner");
+        for (dense, owner) in self.owners.iter().enumerate() {
+            assert_eq!(self.slots[*owner].dense, dense, "Slot {} doesn't point back to its value", owner);
+            assert!(!self.free.contains(owner), "Free slot {} still owns a value", owner);
+        }
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+}
+
+impl<T> GenerationalAllocator<T> for DenseArena<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return DenseArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        DenseArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return DenseArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return DenseArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return DenseArena::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return DenseArena::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return DenseArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        DenseArena::check_invariants(self);
+    }
+}
@@ -0,0 +1,130 @@
+/// Side data for an arena's entries, stored by slot index instead of hashed by handle.
+///
+/// Systems often attach extra data to some entities (a physics body, an AI state) without
+/// putting it in the arena itself. A `HashMap<GenerationalIndex, V>` works, but hashes
+/// every lookup. Handles already carry a dense slot index, so `DenseMap` keeps one slot
+/// per arena slot and a lookup is an index plus a generation compare. A value stored for
+/// a handle that has since died is never returned for the handle that reuses its slot.
+///
+/// The slots holding a value are tracked in a bitset laid out like `InPlaceAllocator`'s
+/// occupancy, so `iter_live` walks both a word at a time.
+use crate::bitset::Bitset;
+use crate::generational_allocator::GenerationalAllocator;
+use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+use crate::memory_allocators::InPlaceAllocator;
+
+#[derive(Debug, Clone)]
+pub struct DenseMap<V> {
+    // By slot index, with the generation of the handle the value was inserted for
+    slots: Vec<Option<(Generation, V)>>,
+    // Which slots hold a value
+    occupied: Bitset,
+    len: usize,
+}
+
+impl<V> Default for DenseMap<V> {
+    fn default() -> Self {
+        return DenseMap { slots: Vec::new(), occupied: Bitset::default(), len: 0 };
+    }
+}
+
+impl<V> DenseMap<V> {
+    /// Set the value for `handle`, returning the one it had. A value left by an older
+    /// handle to the same slot is dropped
+    pub fn insert(&mut self, handle: &GenerationalIndex, value: V) -> Option<V> {
+        if handle.index >= self.slots.len() {
+            self.slots.resize_with(handle.index + 1, || None);
+        }
+
+        let previous = self.slots[handle.index].replace((handle.generation, value));
+        self.occupied.insert(handle.index);
+        return match previous {
+            Some((generation, previous)) if generation == handle.generation => Some(previous),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            }
+        };
+    }
+
+    pub fn get(&self, handle: &GenerationalIndex) -> Option<&V> {
+        return match self.slots.get(handle.index) {
+            Some(Some((generation, value))) if *generation == handle.generation => Some(value),
+            _ => None,
+        };
+    }
+
+    pub fn get_mut(&mut self, handle: &GenerationalIndex) -> Option<&mut V> {
+        return match self.slots.get_mut(handle.index) {
+            Some(Some((generation, value))) if *generation == handle.generation => Some(value),
+            _ => None,
+        };
+    }
+
+    pub fn contains(&self, handle: &GenerationalIndex) -> bool {
+        return self.get(handle).is_some();
+    }
+
+    /// Take the value for `handle` out, None if it has none
+    pub fn remove(&mut self, handle: &GenerationalIndex) -> Option<V> {
+        self.get(handle)?;
+        self.occupied.remove(handle.index);
+        self.len -= 1;
+        return self.slots[handle.index].take().map(|(_, value)| value);
+    }
+
+    /// Handles with a value, in slot order, dead ones that weren't pruned yet included
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &V)> + DoubleEndedIterator + '_ {
+        return self.occupied.iter().map(move |index| {
+            let (generation, value) = self.slots[index].as_ref().expect("Occupied slot without a value");
+            (GenerationalIndex { index, generation: *generation }, value)
+        });
+    }
+
+    /// Handles live in `arena` that have a value, in slot order. The two occupancy
+    /// bitsets are intersected a word at a time, so slots empty in either are skipped
+    /// 64 at a time
+    pub fn iter_live<'a, T>(&'a self, arena: &'a InPlaceAllocator<T>) -> impl Iterator<Item = (GenerationalIndex, &'a V)> + 'a {
+        let (generations, _, _) = arena.as_raw_parts();
+        let words = self.occupied.words().iter().zip(arena.occupancy()).map(|(ours, theirs)| ours & theirs);
+        return words.enumerate().flat_map(move |(word_index, mut word)| {
+            std::iter::from_fn(move || {
+                while word != 0 {
+                    let index = word_index * 64 + word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    let (generation, value) = self.slots[index].as_ref().expect("Occupied slot without a value");
+                    if *generation == generations[index] {
+                        return Some((GenerationalIndex { index, generation: *generation }, value));
+                    }
+                }
+                None
+            })
+        });
+    }
+
+    /// Drop the values of every handle that is dead in `allocator`, returning how many
+    /// there were
+    pub fn prune<T>(&mut self, allocator: &impl GenerationalAllocator<T>) -> usize {
+        let dead: Vec<GenerationalIndex> = self.iter().map(|(handle, _)| handle).filter(|handle| !allocator.is_live(handle)).collect();
+        for handle in dead.iter() {
+            self.remove(handle);
+        }
+        return dead.len();
+    }
+
+    /// Number of values, those of dead handles that weren't pruned yet included
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.occupied = Bitset::default();
+        self.len = 0;
+    }
+}
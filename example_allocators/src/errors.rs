@@ -0,0 +1,215 @@
+/// Errors returned by the fallible operations of the allocators
+use std::error::Error;
+use std::fmt;
+
+use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+
+/// Why an allocation failed. Carries the element that couldn't be stored, so the
+/// caller gets it back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocError<T> {
+    /// The backing storage couldn't grow
+    OutOfMemory(T),
+    /// A no-grow allocation found no free slot to reuse
+    NoFreeSlot(T),
+}
+
+impl<T> AllocError<T> {
+    /// The element that wasn't stored
+    pub fn into_inner(self) -> T {
+        return match self {
+            AllocError::OutOfMemory(element) | AllocError::NoFreeSlot(element) => element,
+        };
+    }
+
+    /// Same error, with `f` applied to the element. For wrappers that store something
+    /// other than what they were given
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> AllocError<U> {
+        return match self {
+            AllocError::OutOfMemory(element) => AllocError::OutOfMemory(f(element)),
+            AllocError::NoFreeSlot(element) => AllocError::NoFreeSlot(f(element)),
+        };
+    }
+}
+
+impl<T> fmt::Display for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::OutOfMemory(_) => write!(f, "out of memory while allocating a new entry"),
+            AllocError::NoFreeSlot(_) => write!(f, "no free slot to allocate without growing"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for AllocError<T> {}
+
+/// What a dead handle found at its index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotState {
+    /// The slot holds a newer value than the one the handle was made for
+    Live,
+    /// The slot is waiting in the free list
+    Free,
+    /// The slot ran out of generations and is never reused
+    Retired,
+    /// The index is past the end of the allocator, e.g. a handle from another allocator
+    OutOfRange,
+    /// Only the generation is known. Pointer handles can't tell a reused slot from a free
+    /// one, or from one whose allocator was dropped
+    Unknown,
+}
+
+impl fmt::Display for SlotState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotState::Live => write!(f, "live"),
+            SlotState::Free => write!(f, "free"),
+            SlotState::Retired => write!(f, "retired"),
+            SlotState::OutOfRange => write!(f, "out of range"),
+            SlotState::Unknown => write!(f, "in an unknown state"),
+        }
+    }
+}
+
+/// Details of a handle that doesn't point to a value: where it points, the generation it
+/// carries and what's in the slot now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleMismatch {
+    pub index: usize,
+    /// Generation of the handle
+    pub expected_gen: Generation,
+    /// Generation of the slot, None if the index is out of range
+    pub found_gen: Option<Generation>,
+    pub slot_state: SlotState,
+}
+
+impl fmt::Display for HandleMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "handle to slot {} at generation {}, slot is {}", self.index, self.expected_gen, self.slot_state)?;
+        if let Some(found_gen) = self.found_gen {
+            write!(f, " at generation {}", found_gen)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Error for HandleMismatch {}
+
+/// Check `handle` against the slot it points to, the one validation every allocator
+/// goes through. `slot` is the generation of the slot and whether it holds a value,
+/// None if the index is out of range
+pub(crate) fn validate_handle(handle: &GenerationalIndex, slot: Option<(Generation, bool)>) -> Result<(), HandleMismatch> {
+    let (found_gen, slot_state) = match slot {
+        Some((generation, true)) if generation == handle.generation => return Ok(()),
+        Some((generation, true)) => (Some(generation), SlotState::Live),
+        Some((Generation::MAX, false)) => (Some(Generation::MAX), SlotState::Retired),
+        Some((generation, false)) => (Some(generation), SlotState::Free),
+        None => (None, SlotState::OutOfRange),
+    };
+
+    return Err(HandleMismatch { index: handle.index, expected_gen: handle.generation, found_gen, slot_state });
+}
+
+/// Why a handle couldn't be turned into a reference.
+///
+/// `NotLive` usually means the entity went away, which callers can expect and resync
+/// from. `OutOfBounds` means the handle never belonged to this allocator, which is a bug.
+/// Handles don't record which allocator made them, so a handle from another allocator
+/// that happens to be in range reports `NotLive`, or even resolves to a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessError {
+    /// The slot exists but holds no value, or a newer one than the handle was made for
+    NotLive(HandleMismatch),
+    /// The index is past the end of the allocator
+    OutOfBounds(HandleMismatch),
+    /// The value is live but its `RefCell` is already borrowed in a conflicting way
+    AlreadyBorrowed,
+    /// A panic unwound while the value was being written through a `SlotGuard`, so it may
+    /// be half updated. Stays until `clear_poison` is called on the slot
+    Poisoned,
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessError::NotLive(mismatch) => write!(f, "handle points to a dead slot: {}", mismatch),
+            AccessError::OutOfBounds(mismatch) => write!(f, "handle points past the end of the allocator: {}", mismatch),
+            AccessError::AlreadyBorrowed => write!(f, "value is already borrowed"),
+            AccessError::Poisoned => write!(f, "value was poisoned by a panic while it was borrowed"),
+        }
+    }
+}
+
+impl Error for AccessError {}
+
+impl From<HandleMismatch> for AccessError {
+    fn from(mismatch: HandleMismatch) -> Self {
+        return match mismatch.slot_state {
+            SlotState::OutOfRange => AccessError::OutOfBounds(mismatch),
+            _ => AccessError::NotLive(mismatch),
+        };
+    }
+}
+
+/// Why a handle couldn't be freed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreeError {
+    /// The handle was already freed, or never belonged to this allocator
+    DeadHandle,
+}
+
+impl fmt::Display for FreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreeError::DeadHandle => write!(f, "handle was already freed or is unknown"),
+        }
+    }
+}
+
+impl Error for FreeError {}
+
+/// Report misuse of a handle from a non-panicking method. With the `strict` feature misuse
+/// is a bug and panics with `message`, otherwise `error` goes back to the caller
+#[track_caller]
+pub(crate) fn misuse<E>(error: E, message: impl FnOnce() -> String) -> E {
+    if cfg!(feature = "strict") {
+        panic!("{}", message());
+    }
+
+    return error;
+}
+
+/// Why `from_bytes` couldn't rebuild an allocator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a value
+    UnexpectedEnd,
+    /// A varint was longer than any 64 bit value
+    VarintOverflow,
+    /// The input was written by an incompatible version of the encoding
+    UnsupportedVersion(u8),
+    /// The input decodes, but doesn't describe a valid allocator or value
+    Invalid(&'static str),
+    /// There were bytes left after the allocator
+    TrailingBytes,
+    /// The checksum stored with the input doesn't match its contents, so it was corrupted
+    /// after being written
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "input ended in the middle of a value"),
+            DecodeError::VarintOverflow => write!(f, "varint doesn't fit in 64 bits"),
+            DecodeError::UnsupportedVersion(version) => write!(f, "unsupported encoding version {}", version),
+            DecodeError::Invalid(reason) => write!(f, "invalid input: {}", reason),
+            DecodeError::TrailingBytes => write!(f, "unexpected bytes after the end of the input"),
+            DecodeError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum mismatch: stored {:08x}, computed {:08x}", expected, actual)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
@@ -0,0 +1,87 @@
+/// Simulated allocation failures.
+///
+/// Allocators only touch the heap when they grow, so every growth goes through
+/// `try_reserve_one`. With the `fault-injection` feature (or in this crate's own tests)
+/// that function can be told to fail on purpose, which lets you exercise `try_insert` error
+/// paths without actually running out of memory. Reusing a free slot never allocates,
+/// so it never fails.
+///
+/// The plan is thread local, so tests running in parallel don't affect each other.
+#[cfg(any(test, feature = "fault-injection"))]
+use std::cell::Cell;
+#[cfg(any(test, feature = "fault-injection"))]
+use std::mem::size_of;
+
+#[cfg(any(test, feature = "fault-injection"))]
+#[derive(Clone, Copy, Default)]
+struct FaultPlan {
+    // Growths left until the one that fails, 1 means the next one
+    fail_in: Option<usize>,
+    // Bytes that can still be allocated before every growth starts failing
+    budget: Option<usize>,
+}
+
+#[cfg(any(test, feature = "fault-injection"))]
+thread_local! {
+    static PLAN: Cell<FaultPlan> = Cell::new(FaultPlan::default());
+}
+
+/// Make the `n`-th allocator growth from now on fail, counting from 1. Only that
+/// growth fails, the following ones succeed again
+#[cfg(any(test, feature = "fault-injection"))]
+pub fn fail_nth_allocation(n: usize) {
+    assert!(n > 0, "Allocations are counted from 1");
+    PLAN.with(|plan| plan.set(FaultPlan { fail_in: Some(n), ..plan.get() }));
+}
+
+/// Let allocators grow by at most `budget` bytes from now on, every growth after that fails
+#[cfg(any(test, feature = "fault-injection"))]
+pub fn fail_after_bytes(budget: usize) {
+    PLAN.with(|plan| plan.set(FaultPlan { budget: Some(budget), ..plan.get() }));
+}
+
+/// Go back to never failing on purpose
+#[cfg(any(test, feature = "fault-injection"))]
+pub fn reset() {
+    PLAN.with(|plan| plan.set(FaultPlan::default()));
+}
+
+#[cfg(any(test, feature = "fault-injection"))]
+fn should_fail(bytes: usize) -> bool {
+    return PLAN.with(|plan| {
+        let mut current = plan.get();
+        let mut fail = false;
+
+        if let Some(fail_in) = current.fail_in {
+            fail = fail_in == 1;
+            current.fail_in = if fail { None } else { Some(fail_in - 1) };
+        }
+
+        if let Some(budget) = current.budget {
+            if !fail && bytes > budget {
+                fail = true;
+            } else if !fail {
+                current.budget = Some(budget - bytes);
+            }
+        }
+
+        plan.set(current);
+        return fail;
+    });
+}
+
+/// Make room for one more entry in `entries`. `extra_bytes` accounts for memory the
+/// entry owns besides its own slot, like a `Box`. Returns false when the memory can't
+/// be obtained, in which case `entries` is left untouched
+#[inline]
+pub(crate) fn try_reserve_one<E>(entries: &mut Vec<E>, extra_bytes: usize) -> bool {
+    #[cfg(any(test, feature = "fault-injection"))]
+    if should_fail(size_of::<E>() + extra_bytes) {
+        return false;
+    }
+
+    #[cfg(not(any(test, feature = "fault-injection")))]
+    let _ = extra_bytes;
+
+    return entries.try_reserve(1).is_ok();
+}
@@ -0,0 +1,65 @@
+/// Bookkeeping for the `track-free-sites` feature.
+///
+/// With the feature on, allocators remember the source location of the last `free` of
+/// every slot and mention it when a stale handle is misused, so a double free points
+/// at both frees. With the feature off these types are empty and every call is a no-op.
+use std::panic::Location;
+
+#[cfg(feature = "track-free-sites")]
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct FreeSite(Option<&'static Location<'static>>);
+
+#[cfg(not(feature = "track-free-sites"))]
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct FreeSite {}
+
+impl FreeSite {
+    #[inline(always)]
+    pub(crate) fn record(&mut self, _site: &'static Location<'static>) {
+        #[cfg(feature = "track-free-sites")]
+        {
+            self.0 = Some(_site);
+        }
+    }
+
+    /// Text to append to a misuse panic message, empty if nothing is known
+    pub(crate) fn describe(&self) -> String {
+        #[cfg(feature = "track-free-sites")]
+        if let Some(site) = self.0 {
+            return format!(" (last freed at {})", site);
+        }
+
+        return String::new();
+    }
+}
+
+/// Free sites for allocators that identify slots by index
+#[cfg(feature = "track-free-sites")]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FreeSites(Vec<FreeSite>);
+
+#[cfg(not(feature = "track-free-sites"))]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FreeSites {}
+
+impl FreeSites {
+    #[inline(always)]
+    pub(crate) fn record(&mut self, _index: usize, _site: &'static Location<'static>) {
+        #[cfg(feature = "track-free-sites")]
+        {
+            if self.0.len() <= _index {
+                self.0.resize(_index + 1, FreeSite::default());
+            }
+            self.0[_index].record(_site);
+        }
+    }
+
+    pub(crate) fn describe(&self, _index: usize) -> String {
+        #[cfg(feature = "track-free-sites")]
+        if let Some(site) = self.0.get(_index) {
+            return site.describe();
+        }
+
+        return String::new();
+    }
+}
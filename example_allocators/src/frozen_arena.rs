@@ -0,0 +1,71 @@
+/// Read-only form of a `GenerationalIndexArrayCell` that threads can share.
+///
+/// Between simulation steps an arena is often only read, by several systems at once.
+/// `freeze` takes the arena by value, so no `RefCell` borrow can be outstanding and no
+/// slot can be allocated or freed until `thaw` gives it back. Liveness can't change while
+/// frozen, so a handle is validated once, by `resolve`, into a `FrozenIndex` that can't
+/// outlive the frozen arena, and `get` through it skips both the generation compare and
+/// the borrow flag. Nothing reachable through `&FrozenArena` is ever mutated, so it's
+/// `Sync`.
+///
+/// Poison flags are kept for `thaw`, frozen reads don't look at them.
+use std::marker::PhantomData;
+
+use crate::errors::HandleMismatch;
+use crate::generational_allocator::AllocatorStats;
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArrayCell};
+
+pub struct FrozenArena<T> {
+    array: GenerationalIndexArrayCell<T>,
+}
+
+// Shared references only read values, and no `RefCell` is borrowed while frozen, so the
+// flags are never touched from two threads
+unsafe impl<T: Sync> Sync for FrozenArena<T> {}
+
+/// Handle checked against a frozen arena, usable until the arena is thawed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrozenIndex<'a> {
+    index: usize,
+    marker: PhantomData<&'a ()>,
+}
+
+impl<T> GenerationalIndexArrayCell<T> {
+    /// Stop allocating and freeing to share the arena between threads, see `FrozenArena`
+    pub fn freeze(self) -> FrozenArena<T> {
+        return FrozenArena { array: self };
+    }
+}
+
+impl<T> FrozenArena<T> {
+    /// Give the mutable arena back, owned by the calling thread
+    pub fn thaw(self) -> GenerationalIndexArrayCell<T> {
+        let mut array = self.array;
+        array.rebind_owner_thread();
+        return array;
+    }
+
+    /// Check a handle once, to read through it without further checks
+    pub fn resolve(&self, handle: &GenerationalIndex) -> Result<FrozenIndex<'_>, HandleMismatch> {
+        self.array.validate(handle)?;
+        return Ok(FrozenIndex { index: handle.get_index(), marker: PhantomData });
+    }
+
+    /// The value behind a resolved handle. Indices are still bounds checked, so one
+    /// resolved against another frozen arena reads the wrong value (or panics) but never
+    /// out of bounds
+    #[inline(always)]
+    pub fn get(&self, index: FrozenIndex<'_>) -> &T {
+        let cell = self.array.slot_cell(index.index).expect("Index resolved against another frozen arena");
+        // Frozen arenas never hand out `RefMut`s, and none was alive when frozen
+        return unsafe { &*cell.as_ptr() };
+    }
+
+    pub fn is_live(&self, handle: &GenerationalIndex) -> bool {
+        return self.array.is_live(handle);
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.array.stats();
+    }
+}
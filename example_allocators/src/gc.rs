@@ -0,0 +1,177 @@
+/// Mark-and-sweep collection for entities that reference each other by handle.
+///
+/// Freeing by hand works while ownership is a tree, but once entities point at each
+/// other (a squad and its members, a node and its neighbours) it's easy to free the one
+/// entity the rest was reachable from and leak the island behind it. Values implement
+/// `Trace` by passing every handle they hold to the visitor, and
+/// `GenerationalIndexArray::collect_garbage` frees every live entry that can't be reached
+/// from the given roots.
+///
+/// Pinned entries count as roots, since something outside Rust still points at them.
+/// Handles that are dead or belong to another arena are skipped while tracing.
+///
+/// Code that frees through reference counts instead never frees a group of entries that
+/// point at each other. `find_cycles` lists those groups, to find the leak.
+use crate::bitset::Bitset;
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+pub trait Trace {
+    /// Call `visitor` with every handle this value references
+    fn trace(&self, visitor: &mut impl FnMut(&GenerationalIndex));
+}
+
+impl Trace for GenerationalIndex {
+    fn trace(&self, visitor: &mut impl FnMut(&GenerationalIndex)) {
+        visitor(self);
+    }
+}
+
+impl<T: Trace> Trace for Option<T> {
+    fn trace(&self, visitor: &mut impl FnMut(&GenerationalIndex)) {
+        if let Some(value) = self {
+            value.trace(visitor);
+        }
+    }
+}
+
+impl<T: Trace> Trace for Vec<T> {
+    fn trace(&self, visitor: &mut impl FnMut(&GenerationalIndex)) {
+        for value in self.iter() {
+            value.trace(visitor);
+        }
+    }
+}
+
+impl<T: Trace> GenerationalIndexArray<T> {
+    /// Live entries reachable from `roots` or a pinned entry, as a set of slot indices
+    fn mark_reachable(&self, roots: &[GenerationalIndex]) -> Bitset {
+        let mut marked = Bitset::default();
+        let mut pending: Vec<GenerationalIndex> = self.iter().filter(|(handle, _)| self.is_pinned(handle)).map(|(handle, _)| handle).collect();
+        pending.extend(roots.iter().cloned());
+
+        while let Some(handle) = pending.pop() {
+            if !self.contains(&handle) || marked.contains(handle.get_index()) {
+                continue;
+            }
+
+            marked.insert(handle.get_index());
+            self.get(&handle).unwrap().trace(&mut |reference| pending.push(reference.clone()));
+        }
+
+        return marked;
+    }
+
+    /// Free every live entry that can't be reached from `roots` by following the handles
+    /// values hold, and return the handles that were freed, e.g. to clean up side tables
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn collect_garbage(&mut self, roots: &[GenerationalIndex]) -> Vec<GenerationalIndex> {
+        let marked = self.mark_reachable(roots);
+        let garbage: Vec<GenerationalIndex> = self.iter().filter(|(handle, _)| !marked.contains(handle.get_index())).map(|(handle, _)| handle).collect();
+        for handle in garbage.iter() {
+            self.free(handle);
+        }
+
+        return garbage;
+    }
+}
+
+impl<T: Trace> GenerationalIndexArray<T> {
+    /// Groups of live entries that reference each other, directly or through other
+    /// entries of the group: the strongly connected components of the handle graph with
+    /// more than one entry, or with an entry that references itself. Each group is sorted
+    /// by slot, groups come out in no particular order
+    pub fn find_cycles(&self) -> Vec<Vec<GenerationalIndex>> {
+        let handles: Vec<GenerationalIndex> = self.handles();
+        // Position of each live slot in `handles`, to turn traced handles into graph nodes
+        let mut node_of = vec![usize::MAX; self.slot_count()];
+        for (node, handle) in handles.iter().enumerate() {
+            node_of[handle.get_index()] = node;
+        }
+        let edges: Vec<Vec<usize>> = handles
+            .iter()
+            .map(|handle| {
+                let mut targets = Vec::new();
+                self.get(handle).unwrap().trace(&mut |reference| {
+                    if self.contains(reference) {
+                        targets.push(node_of[reference.get_index()]);
+                    }
+                });
+                targets
+            })
+            .collect();
+
+        let mut cycles = Vec::new();
+        for component in strongly_connected_components(&edges) {
+            if component.len() > 1 || edges[component[0]].contains(&component[0]) {
+                let mut cycle: Vec<GenerationalIndex> = component.into_iter().map(|node| handles[node].clone()).collect();
+                cycle.sort_by_key(|handle| handle.get_index());
+                cycles.push(cycle);
+            }
+        }
+
+        return cycles;
+    }
+}
+
+/// Tarjan's algorithm, with an explicit stack so long reference chains can't overflow
+/// the call stack
+fn strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+    let mut order = vec![UNVISITED; edges.len()];
+    let mut low = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut visited = 0;
+
+    for start in 0..edges.len() {
+        if order[start] != UNVISITED {
+            continue;
+        }
+
+        // Nodes being explored, with the next edge to look at
+        let mut path = vec![(start, 0)];
+        order[start] = visited;
+        low[start] = visited;
+        visited += 1;
+        stack.push(start);
+        on_stack[start] = true;
+
+        while let Some((node, edge)) = path.last_mut() {
+            let node = *node;
+            if let Some(&target) = edges[node].get(*edge) {
+                *edge += 1;
+                if order[target] == UNVISITED {
+                    order[target] = visited;
+                    low[target] = visited;
+                    visited += 1;
+                    stack.push(target);
+                    on_stack[target] = true;
+                    path.push((target, 0));
+                } else if on_stack[target] {
+                    low[node] = low[node].min(order[target]);
+                }
+                continue;
+            }
+
+            path.pop();
+            if let Some((parent, _)) = path.last() {
+                low[*parent] = low[*parent].min(low[node]);
+            }
+            if low[node] == order[node] {
+                let mut component = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    return components;
+}
@@ -0,0 +1,70 @@
+/// Generations of an allocator's slots, followed by a sentinel slot that is always dead.
+///
+/// A liveness check on an arbitrary handle has to bounds check its index before reading
+/// the generation, which is a branch the predictor gets wrong whenever stale and foreign
+/// handles are mixed in. Here an out of range index is clamped to the sentinel at index
+/// `len` instead, so the check is a `min` and a compare. The sentinel holds
+/// `Generation::MAX`, which no handle is ever given.
+///
+/// Derefs to the real slots only, the sentinel is never visible from outside.
+use std::ops::{Deref, DerefMut};
+
+use crate::kyren_generational_indices::Generation;
+
+const SENTINEL: Generation = Generation::MAX;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GenerationTable {
+    // Never empty, the last entry is the sentinel
+    generations: Vec<Generation>,
+}
+
+impl Default for GenerationTable {
+    fn default() -> Self {
+        return GenerationTable { generations: vec![SENTINEL] };
+    }
+}
+
+impl GenerationTable {
+    pub(crate) fn from_vec(mut generations: Vec<Generation>) -> Self {
+        generations.push(SENTINEL);
+        return GenerationTable { generations };
+    }
+
+    /// Like `Vec::try_reserve`, for `additional` more slots
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> bool {
+        return self.generations.try_reserve(additional).is_ok();
+    }
+
+    pub(crate) fn push(&mut self, generation: Generation) {
+        // The sentinel's entry becomes the new slot, and a new sentinel goes after it
+        let sentinel = self.generations.len() - 1;
+        self.generations[sentinel] = generation;
+        self.generations.push(SENTINEL);
+    }
+
+    /// Whether slot `index` is at `generation`, without a bounds check branch. Always
+    /// false for indices out of range, unless `generation` is `Generation::MAX`, which
+    /// callers rule out by checking that the slot holds a value
+    #[inline(always)]
+    pub(crate) fn matches(&self, index: usize, generation: Generation) -> bool {
+        let slot = index.min(self.generations.len() - 1);
+        // Clamped to the sentinel, which is always there
+        return unsafe { *self.generations.get_unchecked(slot) } == generation;
+    }
+}
+
+impl Deref for GenerationTable {
+    type Target = [Generation];
+
+    fn deref(&self) -> &[Generation] {
+        return &self.generations[..self.generations.len() - 1];
+    }
+}
+
+impl DerefMut for GenerationTable {
+    fn deref_mut(&mut self) -> &mut [Generation] {
+        let len = self.generations.len() - 1;
+        return &mut self.generations[..len];
+    }
+}
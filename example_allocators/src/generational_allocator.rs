@@ -0,0 +1,361 @@
+/// Common interface over the handle based allocators of this crate.
+///
+/// Every allocator here has the same shape (allocate, free, check liveness, access),
+/// but each one exposes access in its own way: plain references, `RefCell`s or
+/// `MaybeUninit` cells. This trait hides those differences behind closures so generic
+/// code like tests, benchmarks and wrappers can be written once for all of them.
+///
+/// Methods are named the same everywhere: `insert` stores a value, `remove` frees a slot
+/// and hands its value back, `free` drops it, and `get`/`get_mut` access it. Fallible
+/// versions take a `try_` prefix. The old `new`, `try_new` and `try_new_no_grow` names,
+/// and `allocate` here, are kept as deprecated shims.
+use std::cell::Ref;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::kyren_generational_indices::{
+    GIAUninitCell, Generation, GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell,
+};
+use crate::memory_allocators::{GIABoxUninit, InPlaceAllocator};
+
+/// Snapshot of how an allocator is using its slots
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Slots the allocator has created so far
+    pub capacity: usize,
+    /// Slots holding a value
+    pub live: usize,
+    /// Slots waiting in the free list to be reused
+    pub free: usize,
+    /// Slots that ran out of generations and will never be used again
+    pub retired: usize,
+}
+
+impl AllocatorStats {
+    /// Fraction of the created slots that don't hold a value: 0 when every slot is in
+    /// use, close to 1 when the allocator is mostly holes left by freed entries
+    pub fn fragmentation(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+
+        return (self.capacity - self.live) as f64 / self.capacity as f64;
+    }
+}
+
+impl fmt::Display for AllocatorStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "capacity: {}, live: {}, free: {}, retired: {}, fragmentation: {:.1}%",
+            self.capacity,
+            self.live,
+            self.free,
+            self.retired,
+            self.fragmentation() * 100.0
+        )
+    }
+}
+
+/// Shared part of `check_invariants`: every free slot must exist, appear only once and
+/// not be retired. Returns the number of free slots
+pub(crate) fn check_free_list(
+    free: impl IntoIterator<Item = usize>,
+    capacity: usize,
+    generation_of: impl Fn(usize) -> Generation,
+) -> usize {
+    let mut seen = HashSet::new();
+    for index in free {
+        assert!(index < capacity, "Free slot {} is out of bounds, capacity is {}", index, capacity);
+        assert!(seen.insert(index), "Slot {} is in the free list more than once", index);
+        assert!(generation_of(index) != Generation::MAX, "Retired slot {} is in the free list", index);
+    }
+
+    return seen.len();
+}
+
+pub trait GenerationalAllocator<T> {
+    /// What `get` hands out: a plain reference, or a guard for the allocators that check
+    /// borrows at runtime
+    type Ref<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+
+    /// What `get_mut` hands out: a plain reference, or a guard for the allocators that
+    /// need to see the value after it was written
+    type RefMut<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    /// Store `element` in the allocator and return a handle to it
+    fn insert(&mut self, element: T) -> GenerationalIndex;
+
+    #[deprecated(note = "renamed to `insert`")]
+    fn allocate(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    /// Release the slot behind `index`. Freeing a dead index is a bug and panics
+    fn free(&mut self, index: &GenerationalIndex);
+
+    /// Free the slot behind `index` and hand its value back, or return `None` if the
+    /// index is dead
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T>;
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool;
+
+    /// The element behind `index`, or `None` if the index is dead
+    fn get(&self, index: &GenerationalIndex) -> Option<Self::Ref<'_>>;
+
+    /// The element behind `index` mutably, or `None` if the index is dead
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<Self::RefMut<'_>>;
+
+    /// Run `f` over the element behind `index`, or return `None` if the index is dead
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R>;
+
+    /// Run `f` over the element behind `index` mutably, or return `None` if the index is dead
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R>;
+
+    fn stats(&self) -> AllocatorStats;
+
+    /// Walk the internal structures and panic if any of them is inconsistent
+    fn check_invariants(&self);
+}
+
+impl<T> GenerationalAllocator<T> for GenerationalIndexArray<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return GenerationalIndexArray::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        GenerationalIndexArray::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return GenerationalIndexArray::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return GenerationalIndexArray::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return GenerationalIndexArray::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return GenerationalIndexArray::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return GenerationalIndexArray::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        GenerationalIndexArray::check_invariants(self);
+    }
+}
+
+impl<T> GenerationalAllocator<T> for GenerationalIndexArrayCell<T> {
+    type Ref<'a> = Ref<'a, T> where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return GenerationalIndexArrayCell::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        GenerationalIndexArrayCell::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return GenerationalIndexArrayCell::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return GenerationalIndexArrayCell::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<Ref<'_, T>> {
+        return GenerationalIndexArrayCell::get(self, index).map(|cell| cell.borrow());
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return GenerationalIndexArrayCell::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(|cell| f(&cell.borrow()));
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return GenerationalIndexArrayCell::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        GenerationalIndexArrayCell::check_invariants(self);
+    }
+}
+
+impl<T> GenerationalAllocator<T> for GIAUninitCell<T> {
+    type Ref<'a> = Ref<'a, T> where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return GIAUninitCell::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        GIAUninitCell::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return GIAUninitCell::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return GIAUninitCell::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<Ref<'_, T>> {
+        return GIAUninitCell::borrow(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return GIAUninitCell::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.borrow(index).map(|value| f(&value));
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return GIAUninitCell::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        GIAUninitCell::check_invariants(self);
+    }
+}
+
+impl<T> GenerationalAllocator<T> for GIABoxUninit<T> {
+    type Ref<'a> = Ref<'a, T> where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return GIABoxUninit::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        GIABoxUninit::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return GIABoxUninit::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return GIABoxUninit::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<Ref<'_, T>> {
+        return GIABoxUninit::get(self, index).map(|cell| cell.borrow());
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return GIABoxUninit::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(|cell| f(&cell.borrow()));
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return GIABoxUninit::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        GIABoxUninit::check_invariants(self);
+    }
+}
+
+impl<T> GenerationalAllocator<T> for InPlaceAllocator<T> {
+    type Ref<'a> = Ref<'a, T> where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return InPlaceAllocator::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        InPlaceAllocator::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return InPlaceAllocator::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return InPlaceAllocator::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<Ref<'_, T>> {
+        if !InPlaceAllocator::is_live(self, index) {
+            return None;
+        }
+
+        return Some(InPlaceAllocator::try_get(self, index).expect("Value is mutably borrowed"));
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return InPlaceAllocator::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        if !InPlaceAllocator::is_live(self, index) {
+            return None;
+        }
+
+        return Some(f(&InPlaceAllocator::try_get(self, index).expect("Value is mutably borrowed")));
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        if !InPlaceAllocator::is_live(self, index) {
+            return None;
+        }
+
+        return InPlaceAllocator::get_mut(self, index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return InPlaceAllocator::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        InPlaceAllocator::check_invariants(self);
+    }
+}
@@ -0,0 +1,134 @@
+/// Arena with compile time borrow checking of its slots, using branded lifetimes.
+///
+/// `GenerationalIndexArrayCell` lets callers mutate values through `&self`, but pays
+/// for it with a borrow flag per slot that is checked on every access. Here the
+/// permission to touch the values lives in a separate `GhostToken` instead: reading
+/// needs `&token`, writing needs `&mut token`, so the usual borrow rules on the token
+/// rule out aliasing at compile time and slot accesses are plain pointer reads.
+///
+/// The token and the arena share a `'brand` lifetime that is unique to one call of
+/// `GhostToken::new`. A token can't unlock an arena created under a different call,
+/// since the brands don't unify. This is the GhostCell technique (Yanovski et al.).
+///
+/// The catch is granularity: a `&mut token` locks every slot of the arena at once, so
+/// two values can't be borrowed mutably at the same time.
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+
+use crate::errors::AllocError;
+use crate::generational_allocator::AllocatorStats;
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+// Invariant in 'brand, so one brand can't be coerced into another
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// Permission to access every `GhostCell` branded with `'brand`
+pub struct GhostToken<'brand> {
+    _brand: Brand<'brand>,
+}
+
+impl<'brand> GhostToken<'brand> {
+    /// Run `f` with a token whose brand exists nowhere else
+    pub fn new<R>(f: impl for<'new> FnOnce(GhostToken<'new>) -> R) -> R {
+        return f(GhostToken { _brand: PhantomData });
+    }
+}
+
+/// Value that can only be accessed through the `GhostToken` of the same brand
+pub struct GhostCell<'brand, T> {
+    _brand: Brand<'brand>,
+    value: UnsafeCell<T>,
+}
+
+// Accessing the value needs the token, which follows the normal borrow rules, so sharing
+// the cell is as safe as sharing a `RwLock<T>`
+unsafe impl<'brand, T: Send> Send for GhostCell<'brand, T> {}
+unsafe impl<'brand, T: Send + Sync> Sync for GhostCell<'brand, T> {}
+
+impl<'brand, T> GhostCell<'brand, T> {
+    pub fn new(value: T) -> Self {
+        return GhostCell { _brand: PhantomData, value: UnsafeCell::new(value) };
+    }
+
+    pub fn borrow<'a>(&'a self, _token: &'a GhostToken<'brand>) -> &'a T {
+        // Holding `&token` rules out a live `&mut token`, so there's no mutable borrow
+        return unsafe { &*self.value.get() };
+    }
+
+    pub fn borrow_mut<'a>(&'a self, _token: &'a mut GhostToken<'brand>) -> &'a mut T {
+        // Holding `&mut token` rules out any other borrow of a cell with this brand
+        return unsafe { &mut *self.value.get() };
+    }
+
+    pub fn into_inner(self) -> T {
+        return self.value.into_inner();
+    }
+}
+
+pub struct GhostArena<'brand, T> {
+    slots: GenerationalIndexArray<GhostCell<'brand, T>>,
+}
+
+impl<'brand, T> Default for GhostArena<'brand, T> {
+    fn default() -> Self {
+        return GhostArena { slots: GenerationalIndexArray::default() };
+    }
+}
+
+impl<'brand, T> GhostArena<'brand, T> {
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        return self.slots.insert(GhostCell::new(element));
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.slots.try_insert(GhostCell::new(element)).map_err(|error| error.map(GhostCell::into_inner));
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        self.slots.free(index);
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return self.slots.remove(index).map(GhostCell::into_inner);
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.slots.contains(index);
+    }
+
+    pub fn get<'a>(&'a self, index: &GenerationalIndex, token: &'a GhostToken<'brand>) -> Option<&'a T> {
+        return self.slots.get(index).map(|cell| cell.borrow(token));
+    }
+
+    /// Mutable access through a shared arena, the `&mut token` is what keeps it exclusive
+    pub fn get_mut<'a>(&'a self, index: &GenerationalIndex, token: &'a mut GhostToken<'brand>) -> Option<&'a mut T> {
+        return self.slots.get(index).map(|cell| cell.borrow_mut(token));
+    }
+
+    /// Snapshot of the live handles, so callers can free or allocate while walking it
+    pub fn handles(&self) -> Vec<GenerationalIndex> {
+        return self.slots.handles();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.slots.stats();
+    }
+
+    pub fn check_invariants(&self) {
+        self.slots.check_invariants();
+    }
+}
@@ -0,0 +1,152 @@
+/// Directed graph whose nodes and edges live in two arenas.
+///
+/// Cyclic structures are where references and `Rc` get painful and where generational
+/// indices shine: nodes refer to their edges and edges to their nodes by handle, so
+/// cycles are just numbers, and a handle to something that was removed fails the
+/// generation check instead of dangling. Removing a node removes its edges too, so no
+/// edge is ever left pointing at a dead node.
+///
+/// `NodeId` and `EdgeId` wrap the handles so one can't be passed where the other is
+/// expected.
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[must_use = "dropping the only id of a node leaks it until the graph is dropped"]
+pub struct NodeId(GenerationalIndex);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EdgeId(GenerationalIndex);
+
+struct Node<N> {
+    value: N,
+    outgoing: Vec<EdgeId>,
+    incoming: Vec<EdgeId>,
+}
+
+struct Edge<E> {
+    value: E,
+    from: NodeId,
+    to: NodeId,
+}
+
+pub struct Graph<N, E> {
+    nodes: GenerationalIndexArray<Node<N>>,
+    edges: GenerationalIndexArray<Edge<E>>,
+}
+
+impl<N, E> Default for Graph<N, E> {
+    fn default() -> Self {
+        return Graph { nodes: GenerationalIndexArray::default(), edges: GenerationalIndexArray::default() };
+    }
+}
+
+impl<N, E> Graph<N, E> {
+    pub fn add_node(&mut self, value: N) -> NodeId {
+        return NodeId(self.nodes.insert(Node { value, outgoing: Vec::new(), incoming: Vec::new() }));
+    }
+
+    /// Edge from `from` to `to`, None if either node is dead. Edges from a node to itself
+    /// and several edges between the same nodes are allowed
+    pub fn add_edge(&mut self, from: &NodeId, to: &NodeId, value: E) -> Option<EdgeId> {
+        if !self.contains_node(from) || !self.contains_node(to) {
+            return None;
+        }
+
+        let edge = EdgeId(self.edges.insert(Edge { value, from: from.clone(), to: to.clone() }));
+        self.nodes.get_mut(&from.0).unwrap().outgoing.push(edge.clone());
+        self.nodes.get_mut(&to.0).unwrap().incoming.push(edge.clone());
+        return Some(edge);
+    }
+
+    /// Remove a node and every edge going in or out of it. None if the node is dead
+    pub fn remove_node(&mut self, node: &NodeId) -> Option<N> {
+        let removed = self.nodes.remove(&node.0)?;
+        for edge in removed.outgoing.iter().chain(removed.incoming.iter()) {
+            // Self loops are in both lists, the second time around the edge is gone
+            if self.contains_edge(edge) {
+                self.remove_edge(edge);
+            }
+        }
+
+        return Some(removed.value);
+    }
+
+    /// None if the edge is dead
+    pub fn remove_edge(&mut self, edge: &EdgeId) -> Option<E> {
+        let removed = self.edges.remove(&edge.0)?;
+        // One of the nodes may be the one `remove_node` is taking out
+        if self.contains_node(&removed.from) {
+            self.nodes.get_mut(&removed.from.0).unwrap().outgoing.retain(|outgoing| outgoing != edge);
+        }
+        if self.contains_node(&removed.to) {
+            self.nodes.get_mut(&removed.to.0).unwrap().incoming.retain(|incoming| incoming != edge);
+        }
+
+        return Some(removed.value);
+    }
+
+    pub fn contains_node(&self, node: &NodeId) -> bool {
+        return self.nodes.contains(&node.0);
+    }
+
+    pub fn contains_edge(&self, edge: &EdgeId) -> bool {
+        return self.edges.contains(&edge.0);
+    }
+
+    pub fn node(&self, node: &NodeId) -> Option<&N> {
+        return self.nodes.get(&node.0).map(|node| &node.value);
+    }
+
+    pub fn node_mut(&mut self, node: &NodeId) -> Option<&mut N> {
+        return self.nodes.get_mut(&node.0).map(|node| &mut node.value);
+    }
+
+    pub fn edge(&self, edge: &EdgeId) -> Option<&E> {
+        return self.edges.get(&edge.0).map(|edge| &edge.value);
+    }
+
+    pub fn edge_mut(&mut self, edge: &EdgeId) -> Option<&mut E> {
+        return self.edges.get_mut(&edge.0).map(|edge| &mut edge.value);
+    }
+
+    /// Source and target of an edge, None if the edge is dead
+    pub fn endpoints(&self, edge: &EdgeId) -> Option<(&NodeId, &NodeId)> {
+        return self.edges.get(&edge.0).map(|edge| (&edge.from, &edge.to));
+    }
+
+    /// Edges leaving `node` with the node each one goes to, empty if the node is dead
+    pub fn outgoing(&self, node: &NodeId) -> impl Iterator<Item = (&EdgeId, &NodeId)> {
+        let edges = self.nodes.get(&node.0).map(|node| node.outgoing.as_slice()).unwrap_or_default();
+        return edges.iter().map(|edge| (edge, &self.edges.get(&edge.0).unwrap().to));
+    }
+
+    /// Edges arriving at `node` with the node each one comes from, empty if the node is dead
+    pub fn incoming(&self, node: &NodeId) -> impl Iterator<Item = (&EdgeId, &NodeId)> {
+        let edges = self.nodes.get(&node.0).map(|node| node.incoming.as_slice()).unwrap_or_default();
+        return edges.iter().map(|edge| (edge, &self.edges.get(&edge.0).unwrap().from));
+    }
+
+    pub fn node_count(&self) -> usize {
+        return self.nodes.stats().live;
+    }
+
+    pub fn edge_count(&self) -> usize {
+        return self.edges.stats().live;
+    }
+
+    /// Panic if an arena is inconsistent, or an edge and its nodes disagree about each other
+    pub fn check_invariants(&self) {
+        self.nodes.check_invariants();
+        self.edges.check_invariants();
+        for (handle, edge) in self.edges.iter() {
+            let id = EdgeId(handle);
+            let from = self.nodes.get(&edge.from.0).expect("Edge leaves a dead node");
+            let to = self.nodes.get(&edge.to.0).expect("Edge arrives at a dead node");
+            assert!(from.outgoing.contains(&id), "Edge {:?} missing from its source", id);
+            assert!(to.incoming.contains(&id), "Edge {:?} missing from its target", id);
+        }
+        for (_, node) in self.nodes.iter() {
+            assert!(node.outgoing.iter().chain(node.incoming.iter()).all(|edge| self.contains_edge(edge)), "Node lists a dead edge");
+        }
+    }
+}
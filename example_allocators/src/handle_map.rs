@@ -0,0 +1,96 @@
+/// Old -> new handle table returned by operations that move entries around
+///
+/// Compacting, splitting or merging arenas changes where entries live, so every handle
+/// the program stored becomes stale. The operation returns a `HandleMap` and the caller
+/// runs its stored handles through `remap` to follow the entries to their new slots.
+///
+/// Handles the map doesn't mention are taken to be unmoved. When several operations run
+/// one after the other, `a.then(&b)` gives a single map from the handles before `a` to
+/// the handles after `b`, so stored handles only need to be walked once.
+use std::collections::HashMap;
+
+use crate::kyren_generational_indices::GenerationalIndex;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HandleMap {
+    moves: HashMap<GenerationalIndex, GenerationalIndex>,
+}
+
+impl HandleMap {
+    pub(crate) fn insert(&mut self, old: GenerationalIndex, new: GenerationalIndex) {
+        self.moves.insert(old, new);
+    }
+
+    /// New handle for an entry that was moved, None if `old` wasn't moved
+    pub fn get(&self, old: &GenerationalIndex) -> Option<&GenerationalIndex> {
+        return self.moves.get(old);
+    }
+
+    /// Owned version of `get`
+    pub fn lookup(&self, old: &GenerationalIndex) -> Option<GenerationalIndex> {
+        return self.moves.get(old).cloned();
+    }
+
+    /// Map that does what `self` does and then what `next` does. Entries `self` moved
+    /// follow `next` from their new handle; entries `self` left alone are moved by `next`
+    /// alone. Handles `next` gives to new entries aren't in it, so it only makes sense for
+    /// operations run back to back on the same entries
+    pub fn then(&self, next: &HandleMap) -> HandleMap {
+        let mut moves: HashMap<_, _> = next
+            .moves
+            .iter()
+            .filter(|(old, _)| !self.moves.contains_key(*old))
+            .map(|(old, new)| (old.clone(), new.clone()))
+            .collect();
+        for (old, new) in self.moves.iter() {
+            moves.insert(old.clone(), next.lookup(new).unwrap_or_else(|| new.clone()));
+        }
+
+        return HandleMap { moves };
+    }
+
+    /// `remap` every handle in `handles`. Returns how many changed
+    pub fn apply_to(&self, handles: &mut [GenerationalIndex]) -> usize {
+        let mut changed = 0;
+        for handle in handles.iter_mut() {
+            if self.remap(handle) {
+                changed += 1;
+            }
+        }
+
+        return changed;
+    }
+
+    /// Update `handle` in place if its entry was moved. Returns whether it changed
+    pub fn remap(&self, handle: &mut GenerationalIndex) -> bool {
+        match self.moves.get(handle) {
+            Some(new) => {
+                *handle = new.clone();
+                return true;
+            }
+            None => return false,
+        }
+    }
+
+    /// Every (old, new) pair, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&GenerationalIndex, &GenerationalIndex)> {
+        return self.moves.iter();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.moves.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.moves.is_empty();
+    }
+}
+
+/// Outcome of a full compaction
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Compaction {
+    /// Entries that moved, from their old handle to the new one
+    pub moved: HandleMap,
+    /// Pinned entries that were skipped even though there was a free slot before them
+    pub pinned: Vec<GenerationalIndex>,
+}
@@ -0,0 +1,159 @@
+/// Arena that splits every value into frequently used "hot" fields and rarely used
+/// "cold" ones.
+///
+/// Systems that run every frame usually touch a handful of fields (position, velocity,
+/// flags) and ignore the rest (names, descriptions, inventories). Storing whole values
+/// drags the cold bytes through the cache on every pass. Here the hot part of each value
+/// is kept packed in a `DenseArena`, so iterating over it is a walk over a contiguous
+/// array of small structs, while the cold part is boxed in a side table indexed by slot.
+///
+/// The split is chosen by implementing `HotCold` for the stored type.
+use std::collections::HashSet;
+
+use crate::dense_arena::DenseArena;
+use crate::errors::AllocError;
+use crate::generational_allocator::AllocatorStats;
+use crate::kyren_generational_indices::GenerationalIndex;
+
+/// How a value is split between hot and cold storage
+pub trait HotCold: Sized {
+    /// Fields read on hot paths, stored inline and packed
+    type Hot;
+    /// Everything else, stored behind a `Box` per slot
+    type Cold;
+
+    fn split(self) -> (Self::Hot, Self::Cold);
+
+    fn join(hot: Self::Hot, cold: Self::Cold) -> Self;
+}
+
+pub struct HotColdArena<T: HotCold> {
+    hot: DenseArena<T::Hot>,
+    // Cold part of each slot, indexed like the slots of `hot`. None while the slot is free
+    cold: Vec<Option<Box<T::Cold>>>,
+}
+
+impl<T: HotCold> Default for HotColdArena<T> {
+    fn default() -> Self {
+        return HotColdArena { hot: DenseArena::default(), cold: Vec::new() };
+    }
+}
+
+impl<T: HotCold> HotColdArena<T> {
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        let (hot, cold) = element.split();
+        // Reserve first so a failed growth doesn't leave a hot value without its cold part
+        if self.cold.try_reserve(1).is_err() {
+            return Err(AllocError::OutOfMemory(T::join(hot, cold)));
+        }
+
+        let handle = match self.hot.try_insert(hot) {
+            Ok(handle) => handle,
+            Err(error) => return Err(error.map(|hot| T::join(hot, cold))),
+        };
+        if handle.index >= self.cold.len() {
+            self.cold.resize_with(handle.index + 1, || None);
+        }
+        self.cold[handle.index] = Some(Box::new(cold));
+
+        return Ok(handle);
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        self.hot.free(index);
+        self.cold[index.index] = None;
+    }
+
+    /// Free the slot and join its two parts back into a value, None if the handle is dead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        let hot = self.hot.remove(index)?;
+        let cold = self.cold[index.index].take().expect("Live slot without a cold part");
+        return Some(T::join(hot, *cold));
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.hot.is_live(index);
+    }
+
+    pub fn hot(&self, index: &GenerationalIndex) -> Option<&T::Hot> {
+        return self.hot.get(index);
+    }
+
+    pub fn hot_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T::Hot> {
+        return self.hot.get_mut(index);
+    }
+
+    pub fn cold(&self, index: &GenerationalIndex) -> Option<&T::Cold> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.cold[index.index].as_deref();
+    }
+
+    pub fn cold_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T::Cold> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.cold[index.index].as_deref_mut();
+    }
+
+    /// The hot parts of every live value as a contiguous slice, in dense order
+    pub fn hot_values(&self) -> &[T::Hot] {
+        return self.hot.values();
+    }
+
+    pub fn hot_values_mut(&mut self) -> &mut [T::Hot] {
+        return self.hot.values_mut();
+    }
+
+    /// Iterate over the hot parts of the live entries, in dense order
+    pub fn iter_hot(&self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &T::Hot)> + DoubleEndedIterator {
+        return self.hot.iter();
+    }
+
+    /// Number of live values
+    pub fn len(&self) -> usize {
+        return self.hot.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.hot.is_empty();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.hot.stats();
+    }
+
+    /// Panic if the hot storage is inconsistent or a slot has a cold part without a hot
+    /// one, or the other way around
+    pub fn check_invariants(&self) {
+        self.hot.check_invariants();
+        let live: HashSet<_> = self.hot.handles().iter().map(|handle| handle.index).collect();
+        for (index, cold) in self.cold.iter().enumerate() {
+            assert_eq!(cold.is_some(), live.contains(&index), "Slot {} has mismatched hot and cold parts", index);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// String interner, as an example of putting the arenas to use.
+///
+/// Names of assets, tags and components get compared and hashed all the time. Interning
+/// stores each distinct string once in a `GenerationalIndexArray<Box<str>>` and hands out
+/// a `Symbol`, two `u32`s that are `Copy` and compare in one instruction. The lookup
+/// table only keeps string hashes and symbols, so every string is stored once.
+///
+/// Strings are never removed, so a symbol stays valid for as long as its interner lives.
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use crate::kyren_generational_indices::{Generation, GenerationalIndex, GenerationalIndexArray};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol {
+    index: u32,
+    generation: Generation,
+}
+
+impl Symbol {
+    fn handle(&self) -> GenerationalIndex {
+        return GenerationalIndex { index: self.index as usize, generation: self.generation };
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: GenerationalIndexArray<Box<str>>,
+    // Symbols by the hash of their string, several when hashes collide
+    lookup: HashMap<u64, Vec<Symbol>>,
+    hasher: RandomState,
+}
+
+impl Interner {
+    /// Symbol for `string`, storing it if it's the first time it's seen
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.get(string) {
+            return symbol;
+        }
+
+        let handle = self.strings.insert(string.into());
+        let index = u32::try_from(handle.get_index()).expect("More than u32::MAX interned strings");
+        let symbol = Symbol { index, generation: handle.get_generation() };
+        self.lookup.entry(self.hasher.hash_one(string)).or_default().push(symbol);
+        return symbol;
+    }
+
+    /// Symbol for `string` if it was interned, without storing it otherwise
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        let candidates = self.lookup.get(&self.hasher.hash_one(string))?;
+        return candidates.iter().copied().find(|symbol| self.resolve(*symbol) == string);
+    }
+
+    /// The string behind a symbol. Panics if the symbol comes from another interner
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        match self.strings.get(&symbol.handle()) {
+            Some(string) => string,
+            None => panic!("Symbol {:?} doesn't belong to this interner", symbol),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.strings.stats().live;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
@@ -0,0 +1,101 @@
+/// Arena addressed both by handle and by an external key.
+///
+/// Asset caches, session tables and the like need to find an entry from a name or id
+/// they got from outside, while the rest of the program holds on to cheap handles.
+/// `KeyedArena` keeps the key -> handle map and the arena in sync, so a handle is never
+/// left pointing at the value of a key that was overwritten or removed.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::generational_allocator::AllocatorStats;
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+pub struct KeyedArena<K, T> {
+    arena: GenerationalIndexArray<T>,
+    handles: HashMap<K, GenerationalIndex>,
+}
+
+impl<K, T> Default for KeyedArena<K, T> {
+    fn default() -> Self {
+        return KeyedArena {
+            arena: GenerationalIndexArray::default(),
+            handles: HashMap::new(),
+        };
+    }
+}
+
+impl<K: Hash + Eq, T> KeyedArena<K, T> {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Store `value` under `key` and return its handle. If the key was already present its
+    /// old value is freed and returned, and handles to it go stale instead of silently
+    /// pointing at the new value
+    pub fn insert(&mut self, key: K, value: T) -> (GenerationalIndex, Option<T>) {
+        let handle = self.arena.insert(value);
+        let previous = match self.handles.insert(key, handle.clone()) {
+            Some(old) => self.arena.remove(&old),
+            None => None,
+        };
+
+        return (handle, previous);
+    }
+
+    /// Current handle for `key`
+    pub fn handle_of(&self, key: &K) -> Option<GenerationalIndex> {
+        return self.handles.get(key).cloned();
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        return self.handles.contains_key(key);
+    }
+
+    pub fn get_by_key(&self, key: &K) -> Option<&T> {
+        return self.arena.get(self.handles.get(key)?);
+    }
+
+    pub fn get_mut_by_key(&mut self, key: &K) -> Option<&mut T> {
+        return self.arena.get_mut(self.handles.get(key)?);
+    }
+
+    /// Remove `key` and free its slot, every handle to it goes stale
+    pub fn remove_by_key(&mut self, key: &K) -> Option<T> {
+        let handle = self.handles.remove(key)?;
+        return self.arena.remove(&handle);
+    }
+
+    /// Look an entry up by handle, None if its key was removed or overwritten since
+    pub fn get(&self, handle: &GenerationalIndex) -> Option<&T> {
+        return self.arena.get(handle);
+    }
+
+    pub fn get_mut(&mut self, handle: &GenerationalIndex) -> Option<&mut T> {
+        return self.arena.get_mut(handle);
+    }
+
+    pub fn is_live(&self, handle: &GenerationalIndex) -> bool {
+        return self.arena.contains(handle);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.handles.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.handles.is_empty();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.arena.stats();
+    }
+
+    /// Panic if the arena is inconsistent or the key map and the arena disagree
+    pub fn check_invariants(&self) {
+        self.arena.check_invariants();
+        for handle in self.handles.values() {
+            assert!(self.arena.contains(handle), "Key maps to dead handle {:?}", handle);
+        }
+        assert_eq!(self.arena.stats().live, self.handles.len(), "Arena holds values that no key maps to");
+    }
+}
@@ -3,27 +3,112 @@
 /// 
 /// This is the base implementation I will be testing my allocators with.
 use std::collections::VecDeque;
-use std::cell::RefCell;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::hash::{Hash, Hasher};
 use std::mem::MaybeUninit;
 
+use std::panic::Location;
+use std::sync::{LockResult, PoisonError};
+
+use crate::bitset::Bitset;
+use crate::checksum;
+use crate::compact_encoding::{read_len, read_signed_varint, read_varint, write_signed_varint, write_varint, CompactDecode, CompactEncode};
+use crate::errors::{misuse, validate_handle, AccessError, AllocError, DecodeError, FreeError, HandleMismatch};
+use crate::fault_injection::try_reserve_one;
+use crate::generational_allocator::{check_free_list, AllocatorStats};
+use crate::free_sites::FreeSites;
+use crate::handle_map::{Compaction, HandleMap};
+use crate::live_slots::LiveSlots;
+use crate::owner_thread::OwnerThread;
+use crate::relocatable;
+use crate::slot_guard::SlotGuard;
+
+/// Which free slot `GenerationalIndices` hands out next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReusePolicy
+{
+    /// The slot freed longest ago. Spreads generation bumps over every slot, so slots
+    /// retire as late as possible
+    #[default]
+    Fifo,
+    /// The slot freed last, whose memory is most likely still in cache
+    Lifo
+}
+
 #[derive(Debug, PartialEq, Default)]
 /// This is the simplest implementation, this struct will tell you which index
 /// to use next, but the actual objects should be managed by yourself. 
 pub struct GenerationalIndices
 {
     indices : Vec<u32>, // Generations. Indices are specified by the array position
-    pub free : VecDeque<usize>
+    // Oldest free slot at the front, only changed by `new` and `free`
+    free : VecDeque<usize>,
+    policy : ReusePolicy
+}
+
+pub type Generation = u32;
+
+/// Move a slot to its next generation. Returns false once the slot reaches
+/// `Generation::MAX`: no handle is ever given that generation, so instead of wrapping
+/// around (and resurrecting stale handles) the slot is retired and never reused
+#[inline(always)]
+pub(crate) fn bump_generation(generation : &mut Generation) -> bool
+{
+    debug_assert!(*generation != Generation::MAX, "Retired slots can't be freed again");
+    *generation += 1;
+    *generation != Generation::MAX
+}
+
+/// Copy the bytes of each value in `values` back to back into `out`, returning how many
+/// bytes were written. Panics if `out` is too short for all of them
+#[cfg(feature = "bytemuck")]
+pub(crate) fn write_values_as_bytes<'a, T: bytemuck::Pod>(values : impl ExactSizeIterator<Item = &'a T>, out : &mut [u8]) -> usize
+{
+    let size = std::mem::size_of::<T>();
+    let written = values.len() * size;
+    assert!(out.len() >= written, "Buffer of {} bytes can't hold {} bytes of live values", out.len(), written);
+    if size == 0
+    {
+        return 0;
+    }
+
+    for (chunk, value) in out[..written].chunks_exact_mut(size).zip(values)
+    {
+        chunk.copy_from_slice(bytemuck::bytes_of(value));
+    }
+    written
 }
 
-#[derive(Debug, PartialEq, Default, Clone)]
+/// Handle shared by every index based allocator in this crate
+#[derive(Debug, PartialEq, Eq, Hash, Default, Clone)]
+#[must_use = "dropping the only handle to a live entry leaks the entry until the arena is dropped"]
 pub struct GenerationalIndex
 {
-    index : usize,
-    generation : u32
+    pub(crate) index : usize,
+    pub(crate) generation : Generation
 }
 
 impl GenerationalIndices
 {
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count : usize, generation : Generation) -> Self
+    {
+        GenerationalIndices { indices: vec![generation; count], free: (0..count).collect(), policy: ReusePolicy::default() }
+    }
+
+    /// Empty allocator that reuses free slots in the order `policy` picks
+    pub fn with_policy(policy : ReusePolicy) -> Self
+    {
+        GenerationalIndices { indices: Vec::new(), free: VecDeque::new(), policy }
+    }
+
+    pub fn policy(&self) -> ReusePolicy
+    {
+        self.policy
+    }
+
     pub fn new(&mut self) -> GenerationalIndex
     {
         if self.free.is_empty()
@@ -34,27 +119,69 @@ impl GenerationalIndices
             return GenerationalIndex{index: next_index, generation: 0};
         }
 
-        let index = self.free.pop_front().unwrap();
+        let index = match self.policy
+        {
+            ReusePolicy::Fifo => self.free.pop_front().unwrap(),
+            ReusePolicy::Lifo => self.free.pop_back().unwrap()
+        };
         let generation = self.indices[index];
 
         GenerationalIndex { index, generation}
     }
 
+    /// False for handles past the end too, e.g. ones that came from another allocator
     #[inline(always)]
     pub fn is_live(&self, index:  &GenerationalIndex) -> bool
     {
-        index.generation == self.indices[index.index]
+        self.indices.get(index.index) == Some(&index.generation)
     }
 
     pub fn free(&mut self, index:&GenerationalIndex)
     {
-        if ! self.is_live(&index)
+        if ! self.is_live(index)
         {
             return; // Report an error or something
         }
 
-        self.free.push_back(index.index);
-        self.indices[index.index] += 1;
+        if bump_generation(&mut self.indices[index.index])
+        {
+            self.free.push_back(index.index);
+        }
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_
+    {
+        let len = self.free.len();
+        (0..len).map(move |position|
+        {
+            let index = match self.policy
+            {
+                ReusePolicy::Fifo => self.free[position],
+                ReusePolicy::Lifo => self.free[len - 1 - position]
+            };
+            (index, self.indices[index])
+        })
+    }
+
+    /// Number of slots waiting to be reused, retired ones not included
+    pub fn free_count(&self) -> usize
+    {
+        self.free.len()
+    }
+
+    pub fn stats(&self) -> AllocatorStats
+    {
+        let retired = self.indices.iter().filter(|generation| **generation == Generation::MAX).count();
+        let free = self.free.len();
+        AllocatorStats { capacity: self.indices.len(), live: self.indices.len() - free - retired, free, retired }
+    }
+
+    /// Panic if the free list is inconsistent
+    pub fn check_invariants(&self)
+    {
+        check_free_list(self.free.iter().copied(), self.indices.len(), |index| self.indices[index]);
     }
 }
 
@@ -71,98 +198,196 @@ impl GenerationalIndex
     {
         self.index
     }
+
+    /// The handle as a `PackedHandle`, None if the index doesn't fit in 32 bits
+    pub fn pack(&self) -> Option<PackedHandle>
+    {
+        let index = u32::try_from(self.index).ok()?;
+        Some(PackedHandle((self.generation as u64) << 32 | index as u64))
+    }
 }
 
-// -- < Versions with the actual storage > ------------------------
-#[derive(Debug, Default)]
-pub struct GenerationalArrayEntry<T>
+/// Handle packed in a `u64`, for storing handles where only plain bytes fit, like GPU
+/// vertex or storage buffers. The layout is stable: the index is in the low 32 bits and
+/// the generation in the high 32 bits, in native byte order, so a shader reads it as a
+/// `vec2<u32>` of index then generation on little endian hosts. With the `bytemuck`
+/// feature it's `Pod`, so slices of handles cast to bytes and back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct PackedHandle(u64);
+
+impl PackedHandle
 {
-    item : Option<T>,
-    generation : u32
+    pub fn to_bits(self) -> u64
+    {
+        self.0
+    }
+
+    /// Any bits make a handle, at worst one that isn't live in any allocator
+    pub fn from_bits(bits : u64) -> Self
+    {
+        PackedHandle(bits)
+    }
 }
 
-/// This version also implements the storage for the thing being identified. 
-/// 
-/// It has some drawbacks: 
-/// 
-///  * You might have to resize an array with too many elements with possibly large storage
-///  * You have to construct objects in the stack and then copy the entire content into the internal array
-///  * You might end up with a lot of unused unrecoverable space after a lot of allocations
-#[derive(Debug, Default)]
-pub struct GenerationalIndexArray<T>
+impl From<PackedHandle> for GenerationalIndex
 {
-    elements : Vec<GenerationalArrayEntry<T>>,
-    free: VecDeque<usize>
+    fn from(packed : PackedHandle) -> Self
+    {
+        GenerationalIndex { index: (packed.0 & u32::MAX as u64) as usize, generation: (packed.0 >> 32) as Generation }
+    }
 }
 
-impl<T> GenerationalIndexArray<T>
+// A `u64` under `repr(transparent)`, so every bit pattern is valid and zero is a handle
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for PackedHandle {}
+
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for PackedHandle {}
+
+// -- < Versions with the actual storage > ------------------------
+/// A slot of `GenerationalIndexArray`. Liveness is kept in a flag next to the generation
+/// instead of in an `Option`, so a slot is the size of `T` plus that header, with no
+/// discriminant padded out to `T`'s alignment
+pub struct GenerationalArrayEntry<T>
 {
-    pub fn new(&mut self, element : T) -> GenerationalIndex
-    {
-        if self.free.is_empty()
-        {
-            let next_index = self.elements.len();
-            let entry = GenerationalArrayEntry{generation: 0, item: Some(element)};
-            self.elements.push(entry);
+    // Only initialized while `live` is set
+    item : MaybeUninit<T>,
+    generation : u32,
+    live : bool,
+    // Neighbours in insertion order while live, NIL at either end
+    #[cfg(feature = "insertion-order")]
+    older : usize,
+    #[cfg(feature = "insertion-order")]
+    newer : usize
+}
 
-            return GenerationalIndex{index: next_index, generation: 0};
-        }
+// End of the insertion order list
+#[cfg(feature = "insertion-order")]
+const NIL : usize = usize::MAX;
 
-        let index = self.free.pop_front().unwrap();
-        let entry = &mut self.elements[index];
-        entry.item = Some(element);
+impl<T> GenerationalArrayEntry<T>
+{
+    fn vacant(generation : Generation) -> Self
+    {
+        GenerationalArrayEntry {
+            item: MaybeUninit::uninit(),
+            generation,
+            live: false,
+            #[cfg(feature = "insertion-order")]
+            older: NIL,
+            #[cfg(feature = "insertion-order")]
+            newer: NIL
+        }
+    }
 
-        GenerationalIndex {index, generation: entry.generation}
+    fn occupied(element : T, generation : Generation) -> Self
+    {
+        GenerationalArrayEntry {
+            item: MaybeUninit::new(element),
+            generation,
+            live: true,
+            #[cfg(feature = "insertion-order")]
+            older: NIL,
+            #[cfg(feature = "insertion-order")]
+            newer: NIL
+        }
     }
 
     #[inline(always)]
-    pub fn is_live(&self, index:  &GenerationalIndex) -> bool
+    fn get(&self) -> Option<&T>
     {
-        index.get_generation() == self.elements[index.index].generation
+        // Live slots are initialized
+        if self.live { Some(unsafe { self.item.assume_init_ref() }) } else { None }
     }
 
-    pub fn free(&mut self, index:&GenerationalIndex)
+    #[inline(always)]
+    fn get_mut(&mut self) -> Option<&mut T>
     {
-        if !self.is_live(&index)
-        {
-            panic!("Trying to free an already dead index");
-        }
+        if self.live { Some(unsafe { self.item.assume_init_mut() }) } else { None }
+    }
 
-        self.free.push_back(index.index);
-        self.elements[index.index].generation += 1;
-        self.elements[index.index].item = None;
+    /// Store `element` in a vacant slot
+    fn put(&mut self, element : T)
+    {
+        debug_assert!(!self.live, "Overwriting a live slot");
+        self.item.write(element);
+        self.live = true;
     }
 
-    pub fn get(&self, index: &GenerationalIndex) -> Option<&T>
+    /// Move the value out, leaving the slot vacant. With `zero-on-free` the vacated bytes are
+    /// cleared too, and with `poison` filled with the poison byte, whether the value is
+    /// being freed or moved to another slot
+    fn take(&mut self) -> Option<T>
     {
-        if !self.is_live(index)
+        if !self.live
         {
             return None;
         }
 
-        return self.elements[index.get_index()].item.as_ref();
+        self.live = false;
+        // The flag is already cleared, so the value is read exactly once
+        let item = unsafe { self.item.assume_init_read() };
+
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize(&mut self.item);
+
+        #[cfg(feature = "poison")]
+        crate::poison::poison(&mut self.item);
+
+        Some(item)
     }
+}
 
-    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T>
+impl<T> Drop for GenerationalArrayEntry<T>
+{
+    fn drop(&mut self)
     {
-        if !self.is_live(index)
+        if self.live
         {
-            return None;
+            unsafe { self.item.assume_init_drop() };
         }
+    }
+}
 
-        return self.elements[index.get_index()].item.as_mut();
+impl<T> Default for GenerationalArrayEntry<T>
+{
+    fn default() -> Self
+    {
+        Self::vacant(0)
     }
 }
 
-// Te previous implementation has some problems about references and pointers. So instead 
-// we will store pointers instead of the entire thing we are allocating.
+impl<T: Clone> Clone for GenerationalArrayEntry<T>
+{
+    fn clone(&self) -> Self
+    {
+        GenerationalArrayEntry {
+            item: self.get().map_or(MaybeUninit::uninit(), |item| MaybeUninit::new(item.clone())),
+            generation: self.generation,
+            live: self.live,
+            #[cfg(feature = "insertion-order")]
+            older: self.older,
+            #[cfg(feature = "insertion-order")]
+            newer: self.newer
+        }
+    }
+}
 
-// We will try to implement a version with smart pointers instead
-#[derive(Default)]
-pub struct GenerationalArrayEntryCell<T>
+impl<T: PartialEq> PartialEq for GenerationalArrayEntry<T>
+{
+    fn eq(&self, other : &Self) -> bool
+    {
+        self.generation == other.generation && self.get() == other.get()
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for GenerationalArrayEntry<T>
 {
-    item : Box<Option<RefCell<T>>>,
-    generation : u32
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.debug_struct("GenerationalArrayEntry").field("item", &self.get()).field("generation", &self.generation).finish()
+    }
 }
 
 /// This version also implements the storage for the thing being identified. 
@@ -172,130 +397,1775 @@ pub struct GenerationalArrayEntryCell<T>
 ///  * You might have to resize an array with too many elements with possibly large storage
 ///  * You have to construct objects in the stack and then copy the entire content into the internal array
 ///  * You might end up with a lot of unused unrecoverable space after a lot of allocations
-#[derive(Default)]
-pub struct GenerationalIndexArrayCell<T>
+///
+/// Cloning keeps the slot layout, generations and free list, so every handle resolves to
+/// the same entry in the copy
+#[derive(Debug, Clone)]
+pub struct GenerationalIndexArray<T>
 {
-    elements : Vec<GenerationalArrayEntryCell<T>>,
-    free: VecDeque<usize>
+    elements : Vec<GenerationalArrayEntry<T>>,
+    free: VecDeque<usize>,
+    // Live slots that compaction must not move, e.g. because FFI holds a pointer into them
+    pinned: Bitset,
+    free_sites: FreeSites,
+    // Number of live entries, so iterators know their length up front
+    live: usize,
+    // Entries waiting for `tick` to free them, with the ticks left
+    deferred: Vec<(GenerationalIndex, u32)>,
+    // Ends of the list threading the live entries from oldest to newest
+    #[cfg(feature = "insertion-order")]
+    oldest : usize,
+    #[cfg(feature = "insertion-order")]
+    newest : usize
 }
 
-impl<T> GenerationalIndexArrayCell<T>
+// Written by hand so an empty array doesn't require `T: Default`
+impl<T> Default for GenerationalIndexArray<T>
 {
-    pub fn new(&mut self, element : T) -> GenerationalIndex
+    fn default() -> Self
     {
-        if self.free.is_empty()
-        {
-            let next_index = self.elements.len();
-            let entry = GenerationalArrayEntryCell{generation: 0, item:Box::new(Some(RefCell::new(element)))};
+        GenerationalIndexArray {
+            elements: Vec::new(),
+            free: VecDeque::new(),
+            pinned: Bitset::default(),
+            free_sites: FreeSites::default(),
+            live: 0,
+            deferred: Vec::new(),
+            #[cfg(feature = "insertion-order")]
+            oldest: NIL,
+            #[cfg(feature = "insertion-order")]
+            newest: NIL
+        }
+    }
+}
 
-            self.elements.push(entry);
+/// Two arrays are equal when every slot has the same generation and value and the free
+/// lists match, so both hand out the same handles from here on. Free sites, pins and
+/// deferred frees are ignored
+impl<T: PartialEq> PartialEq for GenerationalIndexArray<T>
+{
+    fn eq(&self, other : &Self) -> bool
+    {
+        self.elements == other.elements && self.free == other.free
+    }
+}
 
-            return GenerationalIndex{index: next_index, generation: 0};
+impl<T: Hash> GenerationalIndexArray<T>
+{
+    /// Hash of everything `==` compares, fed through `hasher`. Cheaper to exchange than
+    /// the whole array when checking two simulations for desyncs
+    pub fn content_hash<H: Hasher>(&self, mut hasher : H) -> u64
+    {
+        self.elements.len().hash(&mut hasher);
+        for entry in self.elements.iter()
+        {
+            entry.generation.hash(&mut hasher);
+            entry.get().hash(&mut hasher);
         }
+        self.free.hash(&mut hasher);
 
-        let index = self.free.pop_front().unwrap();
-        let entry = &mut self.elements[index];
-        *entry.item = Some(RefCell::new(element));
+        hasher.finish()
+    }
+}
 
-        GenerationalIndex {index, generation: entry.generation}
+impl<T: Clone> GenerationalIndexArray<T>
+{
+    /// Replace the contents of `out` with clones of the live values, in slot order, in one
+    /// pass. `out` keeps its capacity, so a buffer reused every frame stops reallocating
+    pub fn copy_live_to(&self, out : &mut Vec<T>)
+    {
+        out.clear();
+        out.extend(self.values().cloned());
     }
+}
 
-    #[inline(always)]
-    pub fn is_live(&self, index:  &GenerationalIndex) -> bool
+#[cfg(feature = "bytemuck")]
+impl<T: bytemuck::Pod> GenerationalIndexArray<T>
+{
+    /// Write the bytes of the live values back to back into `out`, in slot order, and
+    /// return how many bytes were written. Panics if `out` is shorter than
+    /// `size_of::<T>()` times the number of live values
+    pub fn write_live_bytes(&self, out : &mut [u8]) -> usize
     {
-        index.get_generation() == self.elements[index.index].generation
+        write_values_as_bytes(self.values(), out)
     }
+}
 
-    pub fn free(&mut self, index:&GenerationalIndex)
+#[cfg(feature = "json-debug")]
+impl<T: serde::Serialize> GenerationalIndexArray<T>
+{
+    /// Pretty printed JSON of the live slots (index, generation and value) and of the
+    /// array metadata, for inspecting arena state in external tools. Not meant to be
+    /// read back, use `to_bytes` for that
+    pub fn to_json_debug(&self) -> serde_json::Result<String>
     {
-        if !self.is_live(&index)
+        let mut slots = Vec::with_capacity(self.live);
+        for (handle, item) in self.iter()
         {
-            panic!("Trying to free an already dead index");
+            slots.push(serde_json::json!({
+                "index": handle.index,
+                "generation": handle.generation,
+                "value": serde_json::to_value(item)?
+            }));
         }
 
-        self.free.push_back(index.index);
-        self.elements[index.index].generation += 1;
-        *self.elements[index.index].item = None;
+        let stats = self.stats();
+        serde_json::to_string_pretty(&serde_json::json!({
+            "capacity": self.elements.capacity(),
+            "slots": stats.capacity,
+            "live": stats.live,
+            "retired": stats.retired,
+            "free_list": self.free,
+            "pinned": self.pinned.iter().collect::<Vec<_>>(),
+            "live_slots": slots
+        }))
     }
+}
 
-    pub fn get(&self, index: &GenerationalIndex) -> Option<&RefCell<T>>
+// Bumped whenever the layout written by `to_bytes` changes
+const ENCODING_VERSION : u8 = 1;
+
+impl<T: CompactEncode> GenerationalIndexArray<T>
+{
+    /// Compact encoding of everything `==` compares, for save files and network
+    /// snapshots. Slots are written as runs of live and dead ones, each slot's
+    /// generation as the difference from the previous slot's, and the free list in reuse
+    /// order, so handles stay valid across `from_bytes`. Pins and free sites aren't kept
+    pub fn to_bytes(&self) -> Vec<u8>
     {
-        if !self.is_live(index)
+        let mut out = vec![ENCODING_VERSION];
+        write_varint(&mut out, self.elements.len() as u64);
+
+        let mut previous = 0;
+        for run in self.elements.chunk_by(|a, b| a.live == b.live)
         {
-            return None;
+            write_varint(&mut out, ((run.len() as u64) << 1) | run[0].live as u64);
+            for entry in run
+            {
+                write_signed_varint(&mut out, entry.generation as i64 - previous as i64);
+                previous = entry.generation;
+                if let Some(item) = entry.get()
+                {
+                    item.encode(&mut out);
+                }
+            }
+        }
+
+        write_varint(&mut out, self.free.len() as u64);
+        let mut previous = 0;
+        for index in self.free.iter()
+        {
+            write_signed_varint(&mut out, *index as i64 - previous as i64);
+            previous = *index;
         }
 
-        return (*self.elements[index.get_index()].item).as_ref();
+        out
+    }
+
+    /// `to_bytes` followed by a CRC-32 of the encoding, for `from_bytes_checked`
+    pub fn to_bytes_checked(&self) -> Vec<u8>
+    {
+        checksum::seal(self.to_bytes())
     }
 }
 
+impl<T: CompactDecode> GenerationalIndexArray<T>
+{
+    /// Same as `from_bytes` for input written by `to_bytes_checked`, but corrupted input
+    /// fails with `ChecksumMismatch` before anything is decoded, instead of decoding to
+    /// an arena with the wrong generations
+    pub fn from_bytes_checked(input : &[u8]) -> Result<Self, DecodeError>
+    {
+        Self::from_bytes(checksum::unseal(input)?)
+    }
+
+    /// Rebuild an array written by `to_bytes`. Fails instead of panicking on truncated
+    /// or corrupt input, including input that decodes to an inconsistent free list.
+    /// With `insertion-order` the entries are ordered by slot, the order isn't saved
+    pub fn from_bytes(mut input : &[u8]) -> Result<Self, DecodeError>
+    {
+        let input = &mut input;
+        let (version, rest) = input.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        if *version != ENCODING_VERSION
+        {
+            return Err(DecodeError::UnsupportedVersion(*version));
+        }
+        *input = rest;
 
-// Maybeuninit implementation of previous allocator:
+        let len = read_len(input)?;
+        let mut elements = Vec::with_capacity(len.min(input.len()));
+        let mut generation : Generation = 0;
+        let mut live = 0;
+        while elements.len() < len
+        {
+            let header = read_varint(input)?;
+            let (run, is_live) = ((header >> 1) as usize, header & 1 == 1);
+            if run == 0 || run > len - elements.len()
+            {
+                return Err(DecodeError::Invalid("slot run doesn't fit in the array"));
+            }
 
+            for _ in 0..run
+            {
+                generation = Generation::try_from(generation as i64 + read_signed_varint(input)?)
+                    .map_err(|_| DecodeError::Invalid("generation out of range"))?;
+                if !is_live
+                {
+                    elements.push(GenerationalArrayEntry::vacant(generation));
+                    continue;
+                }
+                if generation == Generation::MAX
+                {
+                    return Err(DecodeError::Invalid("retired slot holds a value"));
+                }
 
-#[derive(Default)]
-pub struct GIAUninitCell<T> // I'm tired of writing "Generational index array" so now is GIA
-{
-    elements : Vec<GIAEntryUninitCell<T>>,
-    free: VecDeque<usize>
+                elements.push(GenerationalArrayEntry::occupied(T::decode(input)?, generation));
+                live += 1;
+            }
+        }
+
+        let free_len = read_len(input)?;
+        let mut free = VecDeque::with_capacity(free_len.min(len));
+        let mut index = 0;
+        for _ in 0..free_len
+        {
+            index = usize::try_from(index as i64 + read_signed_varint(input)?)
+                .map_err(|_| DecodeError::Invalid("free slot out of range"))?;
+            free.push_back(index);
+        }
+        if !input.is_empty()
+        {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Self::from_decoded(elements, free, live)
+    }
 }
-pub struct GIAEntryUninitCell<T>
+
+impl<T> GenerationalIndexArray<T>
 {
-    item : RefCell<MaybeUninit<T>>,
-    generation : u32
+    /// Same as `from_decoded`, from each slot's generation and value
+    pub(crate) fn from_decoded_slots(slots : Vec<(Generation, Option<T>)>, free : VecDeque<usize>, live : usize) -> Result<Self, DecodeError>
+    {
+        let elements = slots.into_iter()
+            .map(|(generation, item)| match item
+            {
+                Some(item) => GenerationalArrayEntry::occupied(item, generation),
+                None => GenerationalArrayEntry::vacant(generation)
+            })
+            .collect();
+        Self::from_decoded(elements, free, live)
+    }
+
+    /// Build an array from decoded slots and free list, after checking that the free
+    /// list names every dead slot that isn't retired, once
+    fn from_decoded(elements : Vec<GenerationalArrayEntry<T>>, free : VecDeque<usize>, live : usize) -> Result<Self, DecodeError>
+    {
+        let len = elements.len();
+        let mut listed = vec![false; len];
+        for index in free.iter()
+        {
+            let Some(entry) = elements.get(*index) else { return Err(DecodeError::Invalid("free slot out of range")) };
+            if entry.live || entry.generation == Generation::MAX || std::mem::replace(&mut listed[*index], true)
+            {
+                return Err(DecodeError::Invalid("free list holds a live, retired or repeated slot"));
+            }
+        }
+        let retired = elements.iter().filter(|entry| entry.generation == Generation::MAX).count();
+        if live + free.len() + retired != len
+        {
+            return Err(DecodeError::Invalid("some dead slots are missing from the free list"));
+        }
+
+        // Only the insertion order needs fixing up afterwards
+        #[cfg_attr(not(feature = "insertion-order"), allow(unused_mut))]
+        let mut array = GenerationalIndexArray { elements, free, live, ..Self::default() };
+        #[cfg(feature = "insertion-order")]
+        for index in 0..len
+        {
+            if array.elements[index].live
+            {
+                array.link_newest(index);
+            }
+        }
+
+        Ok(array)
+    }
 }
 
-impl<T> GIAUninitCell<T>
+impl<T: CompactEncode> GenerationalIndexArray<T>
 {
-    pub fn new(&mut self, element : T) -> GenerationalIndex
+    /// One contiguous buffer with the slots, generations and free list in fixed size
+    /// tables and the values behind relative offsets, see `relocatable` for the layout.
+    /// Meant for moving a world chunk to another process with `import_relocatable`.
+    /// Pins and free sites aren't kept. Panics if the buffer would reach 4 GiB
+    pub fn export_relocatable(&self) -> Vec<u8>
     {
-        if self.free.is_empty()
+        let tables = relocatable::HEADER_SIZE + self.elements.len() * relocatable::SLOT_SIZE + self.free.len() * 4;
+        let mut out = Vec::with_capacity(tables);
+        out.extend_from_slice(&[relocatable::RELOCATABLE_VERSION, 0, 0, 0]);
+        relocatable::push_u32(&mut out, relocatable::to_u32(self.elements.len()));
+        relocatable::push_u32(&mut out, relocatable::to_u32(self.free.len()));
+        relocatable::push_u32(&mut out, relocatable::to_u32(self.live));
+        // Slot records are filled in as the values get written behind the tables
+        out.resize(relocatable::HEADER_SIZE + self.elements.len() * relocatable::SLOT_SIZE, 0);
+        for index in self.free.iter()
         {
-            let next_index = self.elements.len();
-            let mut entry = GIAEntryUninitCell{generation: 0, item: RefCell::new(MaybeUninit::uninit())};
-            entry.item.get_mut().write(element);
-            self.elements.push(entry);
+            relocatable::push_u32(&mut out, relocatable::to_u32(*index));
+        }
 
-            return GenerationalIndex{index: next_index, generation: 0};
+        for (index, entry) in self.elements.iter().enumerate()
+        {
+            let record = relocatable::HEADER_SIZE + index * relocatable::SLOT_SIZE;
+            relocatable::set_u32(&mut out, record, entry.generation);
+            if let Some(item) = entry.get()
+            {
+                let offset = out.len();
+                item.encode(&mut out);
+                relocatable::set_u32(&mut out, record + 4, 1);
+                relocatable::set_u32(&mut out, record + 8, relocatable::to_u32(offset));
+                let value_len = relocatable::to_u32(out.len() - offset);
+                relocatable::set_u32(&mut out, record + 12, value_len);
+            }
         }
 
-        let index = self.free.pop_front().unwrap();
-        let entry = &mut self.elements[index];
-        entry.item.get_mut().write(element);
+        out
+    }
+}
 
-        GenerationalIndex {index, generation: entry.generation}
+impl<T: CompactDecode> GenerationalIndexArray<T>
+{
+    /// Rebuild an array from a buffer written by `export_relocatable`. Fails instead of
+    /// panicking on truncated or corrupt input: offsets pointing outside the buffer or
+    /// into the tables, values that don't decode to exactly their recorded length, and
+    /// inconsistent free lists
+    pub fn import_relocatable(buffer : &[u8]) -> Result<Self, DecodeError>
+    {
+        let tables = relocatable::read_tables(buffer)?;
+        let slots = tables.slots.into_iter()
+            .map(|slot| Ok((slot.generation, slot.value.map(|range| relocatable::decode_value(buffer, range)).transpose()?)))
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        Self::from_decoded_slots(slots, tables.free, tables.live)
     }
+}
 
-    #[inline(always)]
-    pub fn is_live(&self, index:  &GenerationalIndex) -> bool
+impl<T> GenerationalIndexArray<T>
+{
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count : usize, generation : Generation) -> Self
     {
-        index.get_generation() == self.elements[index.index].generation
+        GenerationalIndexArray {
+            elements: (0..count).map(|_| GenerationalArrayEntry::vacant(generation)).collect(),
+            free: (0..count).collect(),
+            pinned: Bitset::default(),
+            free_sites: FreeSites::default(),
+            live: 0,
+            deferred: Vec::new(),
+            #[cfg(feature = "insertion-order")]
+            oldest: NIL,
+            #[cfg(feature = "insertion-order")]
+            newest: NIL
+        }
     }
 
-    pub fn free(&mut self, index:&GenerationalIndex)
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element : T) -> GenerationalIndex
+    {
+        self.insert(element)
+    }
+
+    pub fn insert(&mut self, element : T) -> GenerationalIndex
     {
-        if !self.is_live(&index)
+        match self.try_insert(element)
         {
-            panic!("Trying to free an already dead index");
+            Ok(index) => index,
+            Err(_) => panic!("Out of memory while allocating a new entry")
         }
+    }
 
-        self.free.push_back(index.index);
-        self.elements[index.index].generation += 1;
-        unsafe {
-           self.elements[index.index].item.borrow_mut().assume_init_drop();
-        }
-        
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        self.try_insert_no_grow(element)
     }
 
-    pub fn get(&self, index: &GenerationalIndex) -> Option<&RefCell<MaybeUninit<T>>>
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
     {
-        if !self.is_live(index)
+        if self.free.is_empty()
         {
-            return None;
+            return Err(AllocError::NoFreeSlot(element));
         }
 
-        return Some(
-                &self.elements[index.get_index()].item
-        );
+        self.try_insert(element)
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        self.try_insert(element)
     }
-}
\ No newline at end of file
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// array can't grow
+    pub fn try_insert(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        if self.free.is_empty()
+        {
+            if !try_reserve_one(&mut self.elements, 0)
+            {
+                return Err(AllocError::OutOfMemory(element));
+            }
+
+            let next_index = self.elements.len();
+            let entry = GenerationalArrayEntry::occupied(element, 0);
+            self.elements.push(entry);
+            self.live += 1;
+            #[cfg(feature = "insertion-order")]
+            self.link_newest(next_index);
+
+            return Ok(GenerationalIndex{index: next_index, generation: 0});
+        }
+
+        let index = self.free.pop_front().unwrap();
+        let entry = &mut self.elements[index];
+        entry.put(element);
+        let generation = entry.generation;
+        self.live += 1;
+        #[cfg(feature = "insertion-order")]
+        self.link_newest(index);
+
+        Ok(GenerationalIndex {index, generation})
+    }
+
+    /// Whether the handle points to a value in this array. Any handle can be asked about:
+    /// one past the end, from another allocator or to a free slot is simply not live
+    #[inline(always)]
+    pub fn is_live(&self, index:  &GenerationalIndex) -> bool
+    {
+        match self.elements.get(index.index)
+        {
+            Some(entry) => entry.live && entry.generation == index.get_generation(),
+            None => false
+        }
+    }
+
+    /// Same as `is_live`
+    #[inline(always)]
+    pub fn contains(&self, index:  &GenerationalIndex) -> bool
+    {
+        self.is_live(index)
+    }
+
+    /// Ok if the handle is live, otherwise what it found at its index instead
+    pub fn validate(&self, index:  &GenerationalIndex) -> Result<(), HandleMismatch>
+    {
+        validate_handle(index, self.elements.get(index.index).map(|entry| (entry.generation, entry.live)))
+    }
+
+    /// Like `is_live`, without the bounds check
+    ///
+    /// # Safety
+    /// The handle must have been returned by this array, so its index is in bounds
+    #[inline(always)]
+    pub unsafe fn is_live_unchecked(&self, index:  &GenerationalIndex) -> bool
+    {
+        let entry = self.elements.get_unchecked(index.index);
+        entry.live && index.get_generation() == entry.generation
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index:&GenerationalIndex)
+    {
+        if self.try_free(index).is_err()
+        {
+            panic!("Trying to free an already dead index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Same as `free`, but fails with `DeadHandle` instead of panicking when the handle
+    /// is dead or out of range, unless the `strict` feature is on
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn try_free(&mut self, index:&GenerationalIndex) -> Result<(), FreeError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(FreeError::DeadHandle), || format!("Trying to free an already dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.remove(index);
+        Ok(())
+    }
+
+    /// Free the slot and hand its value back, or return None if the handle is already dead.
+    /// The `strict` feature panics on a dead handle instead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index:&GenerationalIndex) -> Option<T>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to remove an already dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        self.pinned.remove(index.index);
+        self.live -= 1;
+        #[cfg(feature = "insertion-order")]
+        self.unlink(index.index);
+        if bump_generation(&mut self.elements[index.index].generation)
+        {
+            self.free.push_back(index.index);
+        }
+        return self.elements[index.index].take();
+    }
+
+    /// Free the entry once `tick` has been called `frames` times, e.g. for a buffer the GPU
+    /// may still read for a couple of frames. The handle stays live until then, and
+    /// compaction, `partition` and `absorb` carry the pending free along with the entry.
+    /// A delay of 0 frees right away
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free_deferred(&mut self, index : &GenerationalIndex, frames : u32)
+    {
+        if !self.is_live(index)
+        {
+            panic!("Trying to free an already dead index{}", self.free_sites.describe(index.index));
+        }
+        if self.is_free_pending(index)
+        {
+            panic!("Trying to free an index that is already waiting to be freed");
+        }
+
+        if frames == 0
+        {
+            self.remove(index);
+            return;
+        }
+        self.deferred.push((index.clone(), frames));
+    }
+
+    /// Whether the entry is waiting for `tick` to free it
+    pub fn is_free_pending(&self, index : &GenerationalIndex) -> bool
+    {
+        self.deferred.iter().any(|(handle, _)| handle == index)
+    }
+
+    /// Count down the deferred frees and free the entries whose delay elapsed. Returns
+    /// their handles. Entries already freed some other way are just forgotten
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn tick(&mut self) -> Vec<GenerationalIndex>
+    {
+        let mut released = Vec::new();
+        let mut deferred = std::mem::take(&mut self.deferred);
+        deferred.retain_mut(|(handle, ticks)|
+        {
+            *ticks -= 1;
+            if *ticks > 0
+            {
+                return true;
+            }
+            if self.contains(handle)
+            {
+                self.remove(handle);
+                released.push(handle.clone());
+            }
+            false
+        });
+        self.deferred = deferred;
+
+        released
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T>
+    {
+        self.try_get(index).ok()
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T>
+    {
+        self.try_get_mut(index).ok()
+    }
+
+    /// Same as `get`, but says why the value isn't there: `NotLive` if the handle was freed,
+    /// `OutOfBounds` if it's past the end. The `strict` feature panics on both instead, and
+    /// so does `get`
+    pub fn try_get(&self, index: &GenerationalIndex) -> Result<&T, AccessError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        Ok(self.elements[index.get_index()].get().expect("Live slot without a value"))
+    }
+
+    pub fn try_get_mut(&mut self, index: &GenerationalIndex) -> Result<&mut T, AccessError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        Ok(self.elements[index.get_index()].get_mut().expect("Live slot without a value"))
+    }
+
+    /// Iterate over the live entries in slot order. Scans the slots up to the last live
+    /// one, dead ones included
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &T)> + DoubleEndedIterator
+    {
+        LiveSlots::new(self.elements.iter().enumerate(), self.live, |(index, entry)| {
+            entry.get().map(|item| (GenerationalIndex{index, generation: entry.generation}, item))
+        })
+    }
+
+    /// Iterate over the live values in slot order, without building their handles
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &T> + DoubleEndedIterator
+    {
+        LiveSlots::new(self.elements.iter(), self.live, GenerationalArrayEntry::get)
+    }
+
+    /// First live entry, in slot order, for which `predicate` returns true
+    pub fn find(&self, mut predicate : impl FnMut(&T) -> bool) -> Option<(GenerationalIndex, &T)>
+    {
+        self.iter().find(|(_, item)| predicate(item))
+    }
+
+    /// First non-None result of `f` over the live entries, in slot order, with the handle
+    /// it came from
+    pub fn find_map<R>(&self, mut f : impl FnMut(&T) -> Option<R>) -> Option<(GenerationalIndex, R)>
+    {
+        self.iter().find_map(|(handle, item)| f(item).map(|result| (handle, result)))
+    }
+
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut T> + DoubleEndedIterator
+    {
+        LiveSlots::new(self.elements.iter_mut(), self.live, GenerationalArrayEntry::get_mut)
+    }
+
+    /// Cursor positioned before the first live entry, for passes that free some of the
+    /// entries they visit
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T>
+    {
+        CursorMut { array: self, current: None, next: 0 }
+    }
+
+    /// Free every live entry for which `keep` returns false. `keep` gets mutable access, so
+    /// the same pass can update the entries that stay, e.g. tick timers and drop the
+    /// expired ones
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn retain_mut(&mut self, mut keep : impl FnMut(GenerationalIndex, &mut T) -> bool)
+    {
+        let mut cursor = self.cursor_mut();
+        while let Some((handle, item)) = cursor.next()
+        {
+            if !keep(handle, item)
+            {
+                cursor.remove_current();
+            }
+        }
+    }
+
+    /// Lazily free the live entries matching `predicate`, yielding them with their old
+    /// handles, e.g. to move a subset of entities into another arena. Entries that don't
+    /// match, and the ones the iterator doesn't get to if it's dropped early, stay put
+    pub fn extract_if<F>(&mut self, predicate : F) -> ExtractIf<'_, T, F>
+    where
+        F: FnMut(GenerationalIndex, &mut T) -> bool
+    {
+        ExtractIf { cursor: self.cursor_mut(), predicate }
+    }
+
+    /// Keep compaction from moving this entry, for as long as something outside Rust holds
+    /// a pointer into its slot. Returns false if the handle is dead. Freeing unpins
+    pub fn pin_slot(&mut self, index : &GenerationalIndex) -> bool
+    {
+        if !self.contains(index)
+        {
+            return false;
+        }
+
+        self.pinned.insert(index.index);
+        true
+    }
+
+    /// Let compaction move this entry again. Returns false if the handle is dead
+    pub fn unpin_slot(&mut self, index : &GenerationalIndex) -> bool
+    {
+        if !self.contains(index)
+        {
+            return false;
+        }
+
+        self.pinned.remove(index.index);
+        true
+    }
+
+    pub fn is_pinned(&self, index : &GenerationalIndex) -> bool
+    {
+        self.contains(index) && self.pinned.contains(index.index)
+    }
+
+    /// Move at most `budget` live entries from the back of the array into free slots
+    /// closer to the front, and return the handles that changed. Meant to be called once
+    /// per frame with a small budget, so a fragmented array gets defragmented over many
+    /// frames instead of stalling one. Handles of moved entries go stale, look them up in
+    /// the returned map. With a non-zero budget, an empty map means there's nothing left
+    /// to move. Pinned entries stay where they are. The free list is only reordered when
+    /// something moves
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn compact_step(&mut self, budget : usize) -> HandleMap
+    {
+        let mut moved = HandleMap::default();
+        if budget == 0
+        {
+            return moved;
+        }
+        let Some(&lowest) = self.free.iter().min() else { return moved };
+        let mut last = self.movable_end(self.elements.len());
+        if last == 0 || lowest >= last - 1
+        {
+            return moved;
+        }
+
+        // Sorted once, so the lowest free slot is always at the front. Slots vacated below
+        // go to the back, they are all past `last` and never a target
+        self.free.make_contiguous().sort_unstable();
+        while moved.len() < budget
+        {
+            // Lowest free slot and highest live one that isn't pinned
+            let Some(&to) = self.free.front() else { break };
+            last = self.movable_end(last);
+            if last == 0 || to >= last - 1
+            {
+                break;
+            }
+
+            let from = last - 1;
+            self.free.pop_front();
+            let old = GenerationalIndex{index: from, generation: self.elements[from].generation};
+
+            let item = self.elements[from].take().unwrap();
+            self.elements[to].put(item);
+            #[cfg(feature = "insertion-order")]
+            self.relink(from, to);
+            self.free_sites.record(from, Location::caller());
+            if bump_generation(&mut self.elements[from].generation)
+            {
+                self.free.push_back(from);
+            }
+
+            moved.insert(old, GenerationalIndex{index: to, generation: self.elements[to].generation});
+        }
+
+        // Deferred frees follow their entries
+        for (handle, _) in self.deferred.iter_mut()
+        {
+            moved.remap(handle);
+        }
+
+        moved
+    }
+
+    // One past the highest live entry below `end` that isn't pinned, 0 if there is none
+    fn movable_end(&self, mut end : usize) -> usize
+    {
+        while end > 0 && (!self.elements[end - 1].live || self.pinned.contains(end - 1))
+        {
+            end -= 1;
+        }
+        end
+    }
+
+    /// Move every live entry that isn't pinned as close to the front as possible, in one
+    /// go. Also reports the pinned entries that were left behind free slots, which are
+    /// the ones keeping the array from being fully compact
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn compact(&mut self) -> Compaction
+    {
+        let moved = self.compact_step(usize::MAX);
+        let first_free = self.free.iter().copied().min().unwrap_or(self.elements.len());
+        let pinned = self.pinned.iter()
+            .filter(|index| *index > first_free)
+            .map(|index| GenerationalIndex{index, generation: self.elements[index].generation})
+            .collect();
+
+        Compaction { moved, pinned }
+    }
+
+    /// Split the live entries into two new arenas, the ones matching `predicate` go to the
+    /// first. Useful to reorganize storage after a level load, e.g. static vs dynamic
+    /// entities. Returns one remap table per arena, from the handles in `self` to the
+    /// handles in that arena
+    pub fn partition(self, mut predicate : impl FnMut(&T) -> bool) -> (Self, Self, HandleMap, HandleMap)
+    {
+        let (mut matching, mut rest) = (Self::default(), Self::default());
+        let (mut matching_moves, mut rest_moves) = (HandleMap::default(), HandleMap::default());
+        let deferred = self.deferred.clone();
+        for (old, item) in self.into_live()
+        {
+            if predicate(&item)
+            {
+                matching_moves.insert(old, matching.insert(item));
+            }
+            else
+            {
+                rest_moves.insert(old, rest.insert(item));
+            }
+        }
+        for (handle, ticks) in deferred
+        {
+            match (matching_moves.lookup(&handle), rest_moves.lookup(&handle))
+            {
+                (Some(new), _) => matching.deferred.push((new, ticks)),
+                (_, Some(new)) => rest.deferred.push((new, ticks)),
+                _ => {}
+            }
+        }
+
+        (matching, rest, matching_moves, rest_moves)
+    }
+
+    /// Move every live entry of `other` into this array, e.g. to merge a streamed-in world
+    /// chunk into the main arena. Returns the handles in `other` mapped to their new
+    /// handles in `self`
+    pub fn absorb(&mut self, other : Self) -> HandleMap
+    {
+        let mut moves = HandleMap::default();
+        let deferred = other.deferred.clone();
+        for (old, item) in other.into_live()
+        {
+            moves.insert(old, self.insert(item));
+        }
+        self.deferred.extend(deferred.into_iter().filter_map(|(handle, ticks)| Some((moves.lookup(&handle)?, ticks))));
+
+        moves
+    }
+
+    /// Consume the array, yielding the live entries with their handles in slot order, or
+    /// oldest first with `insertion-order`, so `partition` and `absorb` keep that order
+    fn into_live(self) -> impl Iterator<Item = (GenerationalIndex, T)>
+    {
+        #[cfg(feature = "insertion-order")]
+        let order : Vec<usize> = self.insertion_order().collect();
+        #[cfg(not(feature = "insertion-order"))]
+        let order = 0..self.elements.len();
+
+        let mut elements = self.elements;
+        order.into_iter().filter_map(move |index| {
+            let entry = &mut elements[index];
+            entry.take().map(|item| (GenerationalIndex{index, generation: entry.generation}, item))
+        })
+    }
+
+    /// Snapshot of the live handles in slot order, so callers can free or allocate while
+    /// walking it without holding a borrow of the array
+    pub fn handles(&self) -> Vec<GenerationalIndex>
+    {
+        self.iter().map(|(handle, _)| handle).collect()
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_
+    {
+        self.free.iter().map(|index| (*index, self.elements[*index].generation))
+    }
+
+    pub fn stats(&self) -> AllocatorStats
+    {
+        let retired = self.elements.iter().filter(|entry| entry.generation == Generation::MAX).count();
+        AllocatorStats { capacity: self.elements.len(), live: self.live, free: self.free.len(), retired }
+    }
+
+    /// Panic if the free list is inconsistent or a dead slot still holds a value
+    pub fn check_invariants(&self)
+    {
+        check_free_list(self.free.iter().copied(), self.elements.len(), |index| self.elements[index].generation);
+        for index in self.free.iter()
+        {
+            assert!(!self.elements[*index].live, "Free slot {} still holds a value", index);
+        }
+        assert_eq!(self.elements.iter().filter(|entry| entry.live).count(), self.live, "Live count is off");
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+
+        #[cfg(feature = "insertion-order")]
+        {
+            let mut older = NIL;
+            for index in self.insertion_order()
+            {
+                assert!(self.elements[index].live, "Dead slot {} is in the insertion order", index);
+                assert_eq!(self.elements[index].older, older, "Slot {} is linked to the wrong older entry", index);
+                older = index;
+            }
+            assert_eq!(older, self.newest, "Insertion order doesn't end at the newest entry");
+            assert_eq!(self.insertion_order().count(), stats.live, "Some live entries are missing from the insertion order");
+        }
+    }
+}
+
+impl<T> GenerationalIndexArray<T>
+{
+    /// Turn every live value into a `U` with `f`, keeping slots, generations, pins and the
+    /// free list, so handles into this array resolve to the converted entries. Handy to
+    /// migrate an arena loaded from an old save to a new entity type
+    pub fn map<U>(mut self, mut f : impl FnMut(&mut T) -> U) -> GenerationalIndexArray<U>
+    {
+        let elements = self.elements.iter_mut().map(|entry| {
+            let mut mapped = GenerationalArrayEntry::vacant(entry.generation);
+            if let Some(mut item) = entry.take()
+            {
+                mapped.put(f(&mut item));
+            }
+            #[cfg(feature = "insertion-order")]
+            {
+                mapped.older = entry.older;
+                mapped.newer = entry.newer;
+            }
+            mapped
+        }).collect();
+
+        GenerationalIndexArray {
+            elements,
+            free: std::mem::take(&mut self.free),
+            pinned: std::mem::take(&mut self.pinned),
+            free_sites: std::mem::take(&mut self.free_sites),
+            live: self.live,
+            deferred: std::mem::take(&mut self.deferred),
+            #[cfg(feature = "insertion-order")]
+            oldest: self.oldest,
+            #[cfg(feature = "insertion-order")]
+            newest: self.newest
+        }
+    }
+}
+
+// Raw slot access for `ChangeTrackedArena`, which mirrors arrays slot by slot
+impl<T> GenerationalIndexArray<T>
+{
+    pub(crate) fn slot_count(&self) -> usize
+    {
+        self.elements.len()
+    }
+
+    /// Generation and value of any slot, dead ones included
+    pub(crate) fn slot(&self, index : usize) -> (Generation, Option<&T>)
+    {
+        let entry = &self.elements[index];
+        (entry.generation, entry.get())
+    }
+
+    /// Set slot `index` to `generation` and `item` as is, growing the array with vacant
+    /// slots if needed. The free list isn't touched, set it with `replace_free_list` once
+    /// every slot is in place
+    pub(crate) fn overwrite_slot(&mut self, index : usize, generation : Generation, item : Option<T>)
+    {
+        if index >= self.elements.len()
+        {
+            self.elements.resize_with(index + 1, || GenerationalArrayEntry::vacant(0));
+        }
+
+        let entry = &mut self.elements[index];
+        let was_live = entry.live;
+        // Same entry with a new value, it keeps its place in the insertion order
+        let same_entry = was_live && item.is_some() && entry.generation == generation;
+        entry.take();
+        entry.generation = generation;
+        match item
+        {
+            Some(item) =>
+            {
+                entry.put(item);
+                if !same_entry
+                {
+                    if was_live
+                    {
+                        self.live -= 1;
+                        #[cfg(feature = "insertion-order")]
+                        self.unlink(index);
+                    }
+                    self.live += 1;
+                    #[cfg(feature = "insertion-order")]
+                    self.link_newest(index);
+                }
+            }
+            None =>
+            {
+                self.pinned.remove(index);
+                if was_live
+                {
+                    self.live -= 1;
+                    #[cfg(feature = "insertion-order")]
+                    self.unlink(index);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn replace_free_list(&mut self, free : impl IntoIterator<Item = usize>)
+    {
+        self.free = free.into_iter().collect();
+    }
+}
+
+/// Live entries threaded oldest to newest through the slots, so iteration follows spawn
+/// order no matter which slots got reused
+#[cfg(feature = "insertion-order")]
+impl<T> GenerationalIndexArray<T>
+{
+    /// Iterate over the live entries from the oldest to the newest allocation
+    pub fn iter_in_insertion_order(&self) -> impl Iterator<Item = (GenerationalIndex, &T)>
+    {
+        self.insertion_order().map(move |index| {
+            let entry = &self.elements[index];
+            (GenerationalIndex{index, generation: entry.generation}, entry.get().unwrap())
+        })
+    }
+
+    fn insertion_order(&self) -> impl Iterator<Item = usize> + '_
+    {
+        let first = (self.oldest != NIL).then_some(self.oldest);
+        std::iter::successors(first, move |index| {
+            let newer = self.elements[*index].newer;
+            (newer != NIL).then_some(newer)
+        })
+    }
+
+    fn link_newest(&mut self, index : usize)
+    {
+        let newest = self.newest;
+        self.elements[index].older = newest;
+        self.elements[index].newer = NIL;
+        if newest == NIL { self.oldest = index } else { self.elements[newest].newer = index }
+        self.newest = index;
+    }
+
+    fn unlink(&mut self, index : usize)
+    {
+        let (older, newer) = (self.elements[index].older, self.elements[index].newer);
+        if older == NIL { self.oldest = newer } else { self.elements[older].newer = newer }
+        if newer == NIL { self.newest = older } else { self.elements[newer].older = older }
+    }
+
+    /// Give the entry moved from `from` to `to` the same place in the order
+    fn relink(&mut self, from : usize, to : usize)
+    {
+        let (older, newer) = (self.elements[from].older, self.elements[from].newer);
+        self.elements[to].older = older;
+        self.elements[to].newer = newer;
+        if older == NIL { self.oldest = to } else { self.elements[older].newer = to }
+        if newer == NIL { self.newest = to } else { self.elements[newer].older = to }
+    }
+}
+
+/// Walks the live entries of a `GenerationalIndexArray` in slot order and can free the
+/// one it's on, so a single pass can both inspect entries and despawn some of them
+pub struct CursorMut<'a, T>
+{
+    array : &'a mut GenerationalIndexArray<T>,
+    // Slot the cursor is on, None before the first `next` and after a removal
+    current : Option<usize>,
+    // First slot `next` looks at
+    next : usize
+}
+
+impl<'a, T> CursorMut<'a, T>
+{
+    /// Move to the next live entry, None once every entry was visited
+    // Not an `Iterator`: the value is borrowed from the cursor, so it has to be dropped
+    // before the cursor can move or remove it
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(GenerationalIndex, &mut T)>
+    {
+        let elements = &self.array.elements;
+        let index = (self.next..elements.len()).find(|index| elements[*index].live);
+        self.next = index.map_or(elements.len(), |index| index + 1);
+        self.current = index;
+
+        self.current()
+    }
+
+    /// The entry the cursor is on, None if there's none or it was just removed
+    pub fn current(&mut self) -> Option<(GenerationalIndex, &mut T)>
+    {
+        let index = self.current?;
+        let entry = &mut self.array.elements[index];
+        let handle = GenerationalIndex{index, generation: entry.generation};
+        entry.get_mut().map(|item| (handle, item))
+    }
+
+    /// Free the entry the cursor is on and hand its value back. The cursor stays in place,
+    /// `next` continues with the entry after it. Panics if there's no current entry
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove_current(&mut self) -> T
+    {
+        match self.current.take()
+        {
+            Some(index) =>
+            {
+                let handle = GenerationalIndex{index, generation: self.array.elements[index].generation};
+                self.array.remove(&handle).unwrap()
+            }
+            None => panic!("Cursor is not on an entry")
+        }
+    }
+}
+
+/// Iterator returned by `GenerationalIndexArray::extract_if`
+pub struct ExtractIf<'a, T, F>
+{
+    cursor : CursorMut<'a, T>,
+    predicate : F
+}
+
+impl<'a, T, F> Iterator for ExtractIf<'a, T, F>
+where
+    F: FnMut(GenerationalIndex, &mut T) -> bool
+{
+    type Item = (GenerationalIndex, T);
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        while let Some((handle, item)) = self.cursor.next()
+        {
+            if (self.predicate)(handle.clone(), item)
+            {
+                return Some((handle, self.cursor.remove_current()));
+            }
+        }
+
+        None
+    }
+}
+
+// Te previous implementation has some problems about references and pointers. So instead 
+// we will store pointers instead of the entire thing we are allocating.
+
+// We will try to implement a version with smart pointers instead.
+// Values used to live in a `Box<Option<RefCell<T>>>`, paying a heap allocation per slot and
+// a pointer chase per lookup. `get` hands out references tied to `&self`, so the array
+// can't grow while they're alive, and the cell can sit inline
+#[derive(Default)]
+pub struct GenerationalArrayEntryCell<T>
+{
+    item : Option<RefCell<T>>,
+    generation : u32,
+    // Set when a `SlotGuard` on this slot is dropped during a panic
+    poisoned : Cell<bool>
+}
+
+/// This version also implements the storage for the thing being identified. 
+/// 
+/// It has some drawbacks: 
+/// 
+///  * You might have to resize an array with too many elements with possibly large storage
+///  * You have to construct objects in the stack and then copy the entire content into the internal array
+///  * You might end up with a lot of unused unrecoverable space after a lot of allocations
+#[derive(Default)]
+pub struct GenerationalIndexArrayCell<T>
+{
+    elements : Vec<GenerationalArrayEntryCell<T>>,
+    free: VecDeque<usize>,
+    free_sites: FreeSites,
+    owner: OwnerThread
+}
+
+impl<T> GenerationalIndexArrayCell<T>
+{
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count : usize, generation : Generation) -> Self
+    {
+        GenerationalIndexArrayCell {
+            elements: (0..count).map(|_| GenerationalArrayEntryCell{item: None, generation, poisoned: Cell::new(false)}).collect(),
+            free: (0..count).collect(),
+            free_sites: FreeSites::default(),
+            owner: OwnerThread::default()
+        }
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element : T) -> GenerationalIndex
+    {
+        self.insert(element)
+    }
+
+    pub fn insert(&mut self, element : T) -> GenerationalIndex
+    {
+        match self.try_insert(element)
+        {
+            Ok(index) => index,
+            Err(_) => panic!("Out of memory while allocating a new entry")
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        self.try_insert_no_grow(element)
+    }
+
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        if self.free.is_empty()
+        {
+            return Err(AllocError::NoFreeSlot(element));
+        }
+
+        self.try_insert(element)
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        self.try_insert(element)
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// array can't grow
+    pub fn try_insert(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        if self.free.is_empty()
+        {
+            if !try_reserve_one(&mut self.elements, 0)
+            {
+                return Err(AllocError::OutOfMemory(element));
+            }
+
+            let next_index = self.elements.len();
+            let entry = GenerationalArrayEntryCell{generation: 0, item: Some(RefCell::new(element)), poisoned: Cell::new(false)};
+
+            self.elements.push(entry);
+
+            return Ok(GenerationalIndex{index: next_index, generation: 0});
+        }
+
+        let index = self.free.pop_front().unwrap();
+        let entry = &mut self.elements[index];
+        entry.item = Some(RefCell::new(element));
+
+        Ok(GenerationalIndex {index, generation: entry.generation})
+    }
+
+    /// False for any handle that doesn't point to a value, out of range ones included
+    #[inline(always)]
+    pub fn is_live(&self, index:  &GenerationalIndex) -> bool
+    {
+        match self.elements.get(index.index)
+        {
+            Some(entry) => entry.item.is_some() && entry.generation == index.get_generation(),
+            None => false
+        }
+    }
+
+    /// Ok if the handle is live, otherwise what it found at its index instead
+    pub fn validate(&self, index:  &GenerationalIndex) -> Result<(), HandleMismatch>
+    {
+        validate_handle(index, self.elements.get(index.index).map(|entry| (entry.generation, entry.item.is_some())))
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index:&GenerationalIndex)
+    {
+        if self.try_free(index).is_err()
+        {
+            panic!("Trying to free an already dead index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Same as `free`, but fails with `DeadHandle` instead of panicking when the handle
+    /// is dead or out of range, unless the `strict` feature is on
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn try_free(&mut self, index:&GenerationalIndex) -> Result<(), FreeError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(FreeError::DeadHandle), || format!("Trying to free an already dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.remove(index);
+        Ok(())
+    }
+
+    /// Free the slot and hand its value back, or return None if the handle is already dead.
+    /// The `strict` feature panics on a dead handle instead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index:&GenerationalIndex) -> Option<T>
+    {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to remove an already dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        if bump_generation(&mut self.elements[index.index].generation)
+        {
+            self.free.push_back(index.index);
+        }
+        let value = self.elements[index.index].item.take().map(RefCell::into_inner);
+        self.elements[index.index].poisoned.set(false);
+
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize_option(&mut self.elements[index.index].item);
+
+        value
+    }
+
+    /// In debug builds the arena panics when borrowed from a thread other than the one
+    /// that owns it. `&mut self` methods move ownership to the calling thread on their
+    /// own, call this after moving the arena if the new thread starts with `&self` ones
+    pub fn rebind_owner_thread(&mut self)
+    {
+        self.owner.claim();
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&RefCell<T>>
+    {
+        self.owner.check();
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        return self.elements[index.get_index()].item.as_ref();
+    }
+
+    /// Direct access when the arena is borrowed mutably: nobody else can hold a borrow,
+    /// so the RefCell flag is skipped
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T>
+    {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.elements[index.get_index()].item.as_mut().map(RefCell::get_mut)
+    }
+
+    /// Borrow the value without panicking: fails with `NotLive` or `OutOfBounds` if the
+    /// handle doesn't point to a value, and with `AlreadyBorrowed` if the value is mutably borrowed
+    pub fn try_get(&self, index: &GenerationalIndex) -> Result<Ref<'_, T>, AccessError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+        let cell = self.get(index).expect("Live slot without a value");
+        if self.is_poisoned(index)
+        {
+            return Err(AccessError::Poisoned);
+        }
+
+        cell.try_borrow().map_err(|_| AccessError::AlreadyBorrowed)
+    }
+
+    /// Mutable version of `try_get`, also fails if the value is borrowed at all
+    pub fn try_get_mut(&self, index: &GenerationalIndex) -> Result<RefMut<'_, T>, AccessError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+        let cell = self.get(index).expect("Live slot without a value");
+        if self.is_poisoned(index)
+        {
+            return Err(AccessError::Poisoned);
+        }
+
+        cell.try_borrow_mut().map_err(|_| AccessError::AlreadyBorrowed)
+    }
+
+    /// Mutable borrow that poisons the slot if a panic unwinds while the guard is alive,
+    /// like `Mutex::lock`. None if the handle is dead; `Err` carries the guard anyway if
+    /// the slot was already poisoned. Panics like `RefCell::borrow_mut` if the value is
+    /// already borrowed
+    pub fn lock(&self, index: &GenerationalIndex) -> Option<LockResult<SlotGuard<'_, T>>>
+    {
+        let cell = self.get(index)?;
+        let poisoned = &self.elements[index.get_index()].poisoned;
+        let guard = SlotGuard::new(cell.borrow_mut(), poisoned);
+
+        Some(if poisoned.get() { Err(PoisonError::new(guard)) } else { Ok(guard) })
+    }
+
+    /// Whether a panic unwound through a `lock` guard of this slot. Dead handles are
+    /// never poisoned
+    pub fn is_poisoned(&self, index: &GenerationalIndex) -> bool
+    {
+        self.is_live(index) && self.elements[index.get_index()].poisoned.get()
+    }
+
+    /// Mark the value as consistent again, after checking or repairing it
+    pub fn clear_poison(&self, index: &GenerationalIndex)
+    {
+        if self.is_live(index)
+        {
+            self.elements[index.get_index()].poisoned.set(false);
+        }
+    }
+
+    /// Borrow only the part of the value picked by `f`, e.g. `borrow_map(&handle, |e| &e.name)`.
+    /// The RefCell flag still covers the whole value, so this panics like `RefCell::borrow`
+    /// if the value is mutably borrowed, and blocks `borrow_mut` while the guard lives
+    pub fn borrow_map<U: ?Sized>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> &U) -> Option<Ref<'_, U>>
+    {
+        self.get(index).map(|cell| Ref::map(cell.borrow(), f))
+    }
+
+    /// Mutable version of `borrow_map`, panics if the value is borrowed at all
+    pub fn borrow_map_mut<U: ?Sized>(&self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> &mut U) -> Option<RefMut<'_, U>>
+    {
+        self.get(index).map(|cell| RefMut::map(cell.borrow_mut(), f))
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_
+    {
+        self.free.iter().map(|index| (*index, self.elements[*index].generation))
+    }
+
+    pub fn stats(&self) -> AllocatorStats
+    {
+        let retired = self.elements.iter().filter(|entry| entry.generation == Generation::MAX).count();
+        let live = self.elements.iter().filter(|entry| entry.item.is_some()).count();
+        AllocatorStats { capacity: self.elements.len(), live, free: self.free.len(), retired }
+    }
+
+    /// Panic if the free list is inconsistent or a dead slot still holds a value
+    pub fn check_invariants(&self)
+    {
+        check_free_list(self.free.iter().copied(), self.elements.len(), |index| self.elements[index].generation);
+        for index in self.free.iter()
+        {
+            assert!(self.elements[*index].item.is_none(), "Free slot {} still holds a value", index);
+        }
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+}
+
+// Raw slot access for `FrozenArena`, which reads slots it validated earlier
+impl<T> GenerationalIndexArrayCell<T>
+{
+    /// Value cell of any slot, whatever its generation. None if the slot is free
+    pub(crate) fn slot_cell(&self, index : usize) -> Option<&RefCell<T>>
+    {
+        self.elements.get(index)?.item.as_ref()
+    }
+}
+
+
+// Maybeuninit implementation of previous allocator:
+
+
+#[derive(Default)]
+pub struct GIAUninitCell<T> // I'm tired of writing "Generational index array" so now is GIA
+{
+    elements : Vec<GIAEntryUninitCell<T>>,
+    free: VecDeque<usize>,
+    free_sites: FreeSites,
+    owner: OwnerThread
+}
+pub struct GIAEntryUninitCell<T>
+{
+    // Only initialized while `live` is set
+    item : RefCell<MaybeUninit<T>>,
+    generation : u32,
+    live : bool
+}
+
+impl<T> GIAUninitCell<T>
+{
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count : usize, generation : Generation) -> Self
+    {
+        GIAUninitCell {
+            elements: (0..count).map(|_| GIAEntryUninitCell{item: RefCell::new(MaybeUninit::uninit()), generation, live: false}).collect(),
+            free: (0..count).collect(),
+            free_sites: FreeSites::default(),
+            owner: OwnerThread::default()
+        }
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element : T) -> GenerationalIndex
+    {
+        self.insert(element)
+    }
+
+    pub fn insert(&mut self, element : T) -> GenerationalIndex
+    {
+        match self.try_insert(element)
+        {
+            Ok(index) => index,
+            Err(_) => panic!("Out of memory while allocating a new entry")
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        self.try_insert_no_grow(element)
+    }
+
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        if self.free.is_empty()
+        {
+            return Err(AllocError::NoFreeSlot(element));
+        }
+
+        self.try_insert(element)
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        self.try_insert(element)
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// array can't grow
+    pub fn try_insert(&mut self, element : T) -> Result<GenerationalIndex, AllocError<T>>
+    {
+        if self.free.is_empty()
+        {
+            if !try_reserve_one(&mut self.elements, 0)
+            {
+                return Err(AllocError::OutOfMemory(element));
+            }
+
+            let next_index = self.elements.len();
+            let mut entry = GIAEntryUninitCell{generation: 0, item: RefCell::new(MaybeUninit::uninit()), live: true};
+            entry.item.get_mut().write(element);
+            self.elements.push(entry);
+
+            return Ok(GenerationalIndex{index: next_index, generation: 0});
+        }
+
+        let index = self.free.pop_front().unwrap();
+        let entry = &mut self.elements[index];
+        entry.item.get_mut().write(element);
+        entry.live = true;
+
+        Ok(GenerationalIndex {index, generation: entry.generation})
+    }
+
+    /// False for any handle that doesn't point to a value, out of range ones included
+    #[inline(always)]
+    pub fn is_live(&self, index:  &GenerationalIndex) -> bool
+    {
+        match self.elements.get(index.index)
+        {
+            Some(entry) => entry.live && entry.generation == index.get_generation(),
+            None => false
+        }
+    }
+
+    /// Ok if the handle is live, otherwise what it found at its index instead
+    pub fn validate(&self, index:  &GenerationalIndex) -> Result<(), HandleMismatch>
+    {
+        validate_handle(index, self.elements.get(index.index).map(|entry| (entry.generation, entry.live)))
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index:&GenerationalIndex)
+    {
+        if self.try_free(index).is_err()
+        {
+            panic!("Trying to free an already dead index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Same as `free`, but fails with `DeadHandle` instead of panicking when the handle
+    /// is dead or out of range, unless the `strict` feature is on
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn try_free(&mut self, index:&GenerationalIndex) -> Result<(), FreeError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(FreeError::DeadHandle), || format!("Trying to free an already dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.remove(index);
+        Ok(())
+    }
+
+    /// Free the slot and hand its value back, or return None if the handle is already dead.
+    /// The `strict` feature panics on a dead handle instead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index:&GenerationalIndex) -> Option<T>
+    {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to remove an already dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        if bump_generation(&mut self.elements[index.index].generation)
+        {
+            self.free.push_back(index.index);
+        }
+        // The slot counts as uninitialized from here on, so the value is moved out only once
+        self.elements[index.index].live = false;
+        let value = unsafe { self.elements[index.index].item.get_mut().assume_init_read() };
+
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize(self.elements[index.index].item.get_mut());
+
+        #[cfg(feature = "poison")]
+        crate::poison::poison(self.elements[index.index].item.get_mut());
+
+        Some(value)
+    }
+
+    /// In debug builds the arena panics when borrowed from a thread other than the one
+    /// that owns it. `&mut self` methods move ownership to the calling thread on their
+    /// own, call this after moving the arena if the new thread starts with `&self` ones
+    pub fn rebind_owner_thread(&mut self)
+    {
+        self.owner.claim();
+    }
+
+    /// The raw cell behind a live handle. `borrow` and `borrow_mut` cover most uses
+    ///
+    /// # Safety
+    /// The arena reads the slot as initialized for as long as the handle is live, so the
+    /// cell must hold an initialized value whenever it is not mutably borrowed. Don't
+    /// write `MaybeUninit::uninit()` into it or move the value out. Safe code can't get
+    /// at the cell:
+    ///
+    /// ```compile_fail,E0133
+    /// # use example_allocators::kyren_generational_indices::GIAUninitCell;
+    /// # use std::mem::MaybeUninit;
+    /// let mut arena = GIAUninitCell::<String>::default();
+    /// let handle = arena.insert("value".to_string());
+    /// *arena.get(&handle).unwrap().borrow_mut() = MaybeUninit::uninit();
+    /// ```
+    pub unsafe fn get(&self, index: &GenerationalIndex) -> Option<&RefCell<MaybeUninit<T>>>
+    {
+        self.owner.check();
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        return Some(
+                &self.elements[index.get_index()].item
+        );
+    }
+
+    /// Direct access when the arena is borrowed mutably, skipping the RefCell flag
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T>
+    {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        // Live slots are always initialized
+        Some(unsafe { self.elements[index.get_index()].item.get_mut().assume_init_mut() })
+    }
+
+    /// Borrow the value behind a live handle, without the `MaybeUninit` dance `get`
+    /// needs. Panics like `RefCell::borrow` if the value is mutably borrowed
+    pub fn borrow(&self, index: &GenerationalIndex) -> Option<Ref<'_, T>>
+    {
+        // Live slots are always initialized, and the cell never leaves the arena
+        unsafe { self.get(index) }.map(|cell| Ref::map(cell.borrow(), |value| unsafe { value.assume_init_ref() }))
+    }
+
+    /// Mutable version of `borrow`. Panics like `RefCell::borrow_mut` if the value is
+    /// already borrowed
+    pub fn borrow_mut(&self, index: &GenerationalIndex) -> Option<RefMut<'_, T>>
+    {
+        unsafe { self.get(index) }.map(|cell| RefMut::map(cell.borrow_mut(), |value| unsafe { value.assume_init_mut() }))
+    }
+
+    /// Like `borrow`, but fails with `NotLive` or `OutOfBounds` if the handle doesn't point
+    /// to a value and with `AlreadyBorrowed` instead of panicking
+    pub fn try_get(&self, index: &GenerationalIndex) -> Result<Ref<'_, T>, AccessError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+        let cell = unsafe { self.get(index) }.expect("Live slot without a value");
+        let value = cell.try_borrow().map_err(|_| AccessError::AlreadyBorrowed)?;
+        Ok(Ref::map(value, |value| unsafe { value.assume_init_ref() }))
+    }
+
+    /// Like `borrow_mut`, but returns an error instead of panicking
+    pub fn try_get_mut(&self, index: &GenerationalIndex) -> Result<RefMut<'_, T>, AccessError>
+    {
+        if let Err(mismatch) = self.validate(index)
+        {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+        let cell = unsafe { self.get(index) }.expect("Live slot without a value");
+        let value = cell.try_borrow_mut().map_err(|_| AccessError::AlreadyBorrowed)?;
+        Ok(RefMut::map(value, |value| unsafe { value.assume_init_mut() }))
+    }
+
+    /// Borrow only the part of the value picked by `f`, e.g. `borrow_map(&handle, |e| &e.name)`.
+    /// The RefCell flag still covers the whole value, so this panics like `borrow` if the
+    /// value is mutably borrowed
+    pub fn borrow_map<U: ?Sized>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> &U) -> Option<Ref<'_, U>>
+    {
+        self.borrow(index).map(|value| Ref::map(value, f))
+    }
+
+    /// Mutable version of `borrow_map`, panics if the value is borrowed at all
+    pub fn borrow_map_mut<U: ?Sized>(&self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> &mut U) -> Option<RefMut<'_, U>>
+    {
+        self.borrow_mut(index).map(|value| RefMut::map(value, f))
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_
+    {
+        self.free.iter().map(|index| (*index, self.elements[*index].generation))
+    }
+
+    pub fn stats(&self) -> AllocatorStats
+    {
+        let retired = self.elements.iter().filter(|entry| entry.generation == Generation::MAX).count();
+        let free = self.free.len();
+        AllocatorStats { capacity: self.elements.len(), live: self.elements.len() - free - retired, free, retired }
+    }
+
+    /// Panic if the free list is inconsistent, a free slot still holds a value or a slot is
+    /// still borrowed
+    pub fn check_invariants(&self)
+    {
+        check_free_list(self.free.iter().copied(), self.elements.len(), |index| self.elements[index].generation);
+        for index in self.free.iter()
+        {
+            assert!(!self.elements[*index].live, "Free slot {} still holds a value", index);
+        }
+        for (index, entry) in self.elements.iter().enumerate()
+        {
+            assert!(entry.item.try_borrow_mut().is_ok(), "Slot {} is still borrowed", index);
+        }
+    }
+}
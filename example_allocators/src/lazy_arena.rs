@@ -0,0 +1,108 @@
+/// Read-only view of a saved arena that decodes values on first access.
+///
+/// Loading a whole world with `import_relocatable` decodes every value up front, even
+/// when the player only ever visits one corner of it. `LazyArena::load` reads just the
+/// slot table and free list of an `export_relocatable` buffer, so handles can be checked
+/// straight away, and decodes a value the first time `get` asks for it (hydration). The
+/// buffer is kept around until every value that's needed has been read.
+///
+/// Values are only checked when they're hydrated, so a corrupted value shows up then
+/// rather than at load time. `try_get` reports it as an error, `get` panics. Seal the
+/// buffer with `checksum::seal` and `unseal` it before loading to catch corruption early.
+use std::cell::OnceCell;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::compact_encoding::CompactDecode;
+use crate::errors::DecodeError;
+use crate::kyren_generational_indices::{Generation, GenerationalIndex, GenerationalIndexArray};
+use crate::relocatable;
+
+struct LazySlot<T> {
+    generation: Generation,
+    // Where the encoded value is in the buffer, None for a dead slot
+    encoded: Option<Range<usize>>,
+    value: OnceCell<T>,
+}
+
+pub struct LazyArena<T> {
+    buffer: Vec<u8>,
+    slots: Vec<LazySlot<T>>,
+    free: VecDeque<usize>,
+    live: usize,
+}
+
+impl<T: CompactDecode> LazyArena<T> {
+    /// Read the tables of a buffer written by `export_relocatable`, without decoding any
+    /// value. Fails on the same truncated or corrupt tables `import_relocatable` rejects
+    pub fn load(buffer: Vec<u8>) -> Result<Self, DecodeError> {
+        let tables = relocatable::read_tables(&buffer)?;
+        let slots = tables
+            .slots
+            .into_iter()
+            .map(|slot| LazySlot { generation: slot.generation, encoded: slot.value, value: OnceCell::new() })
+            .collect();
+        return Ok(LazyArena { buffer, slots, free: tables.free, live: tables.live });
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.slots.get(index.index) {
+            Some(slot) => slot.generation == index.generation && slot.encoded.is_some(),
+            None => false,
+        };
+    }
+
+    /// The value, decoding it if this is the first time it's read. None if the handle
+    /// is dead, an error if the stored value doesn't decode
+    pub fn try_get(&self, index: &GenerationalIndex) -> Result<Option<&T>, DecodeError> {
+        if !self.is_live(index) {
+            return Ok(None);
+        }
+
+        let slot = &self.slots[index.index];
+        if let Some(value) = slot.value.get() {
+            return Ok(Some(value));
+        }
+
+        // Live slots always have an encoded value
+        let value = relocatable::decode_value(&self.buffer, slot.encoded.clone().unwrap())?;
+        return Ok(Some(slot.value.get_or_init(|| value)));
+    }
+
+    /// Same as `try_get`, panicking if the stored value doesn't decode
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        match self.try_get(index) {
+            Ok(value) => value,
+            Err(error) => panic!("Saved value for slot {} is corrupted: {}", index.index, error),
+        }
+    }
+
+    /// Number of live values that have been decoded so far
+    pub fn hydrated(&self) -> usize {
+        return self.slots.iter().filter(|slot| slot.value.get().is_some()).count();
+    }
+
+    /// Number of live values, decoded or not
+    pub fn live(&self) -> usize {
+        return self.live;
+    }
+
+    /// Decode the values that haven't been read yet and build a regular array, with the
+    /// same handles and free list
+    pub fn into_array(self) -> Result<GenerationalIndexArray<T>, DecodeError> {
+        let buffer = self.buffer;
+        let slots = self
+            .slots
+            .into_iter()
+            .map(|slot| {
+                let value = match (slot.value.into_inner(), slot.encoded) {
+                    (Some(value), _) => Some(value),
+                    (None, Some(encoded)) => Some(relocatable::decode_value(&buffer, encoded)?),
+                    (None, None) => None,
+                };
+                Ok((slot.generation, value))
+            })
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        return GenerationalIndexArray::from_decoded_slots(slots, self.free, self.live);
+    }
+}
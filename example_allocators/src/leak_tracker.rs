@@ -0,0 +1,72 @@
+/// Finds entries whose handles were all dropped without the entry being freed.
+///
+/// A generational arena never dangles, but it leaks quietly: drop the last handle to an
+/// entry and the slot stays live, unreachable, until the arena itself goes away.
+/// `#[must_use]` on the handle types catches a handle dropped right where it's returned.
+/// `LeakTracker` catches the rest: `track` wraps a handle in a `TrackedHandle` that counts
+/// its clones, and `leaked` lists the entries the arena still holds that no tracked handle
+/// points to anymore.
+///
+/// Every tracked handle costs an allocation, so this is meant for debug builds and tests.
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+
+use crate::generational_allocator::GenerationalAllocator;
+use crate::kyren_generational_indices::GenerationalIndex;
+
+/// Handle whose copies are counted by the `LeakTracker` that made it. Derefs to the
+/// plain handle to use it with the arena
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[must_use = "dropping the only handle to a live entry leaks the entry until the arena is dropped"]
+pub struct TrackedHandle {
+    handle: GenerationalIndex,
+    // Shared by every clone, the tracker only keeps a weak reference
+    alive: Arc<()>,
+}
+
+impl Deref for TrackedHandle {
+    type Target = GenerationalIndex;
+
+    fn deref(&self) -> &GenerationalIndex {
+        return &self.handle;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LeakTracker {
+    handles: HashMap<GenerationalIndex, Weak<()>>,
+}
+
+impl LeakTracker {
+    /// Start counting the copies of `handle`, usually right after allocating it. Tracking
+    /// a handle that still has tracked copies joins their count
+    pub fn track(&mut self, handle: GenerationalIndex) -> TrackedHandle {
+        if let Some(alive) = self.handles.get(&handle).and_then(Weak::upgrade) {
+            return TrackedHandle { handle, alive };
+        }
+
+        let alive = Arc::new(());
+        self.handles.insert(handle.clone(), Arc::downgrade(&alive));
+        return TrackedHandle { handle, alive };
+    }
+
+    /// Tracked entries `arena` still holds with every tracked copy of their handle dropped,
+    /// in slot order. Entries that were freed are forgotten
+    pub fn leaked<T>(&mut self, arena: &impl GenerationalAllocator<T>) -> Vec<GenerationalIndex> {
+        self.handles.retain(|handle, _| arena.is_live(handle));
+        let mut leaked: Vec<GenerationalIndex> =
+            self.handles.iter().filter(|(_, alive)| alive.strong_count() == 0).map(|(handle, _)| handle.clone()).collect();
+        leaked.sort_by_key(|handle| handle.get_index());
+        return leaked;
+    }
+
+    /// Handles being tracked, freed ones not yet forgotten by `leaked` included
+    pub fn len(&self) -> usize {
+        return self.handles.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.handles.is_empty();
+    }
+}
@@ -1,5 +1,58 @@
 pub mod tests;
 pub mod kyren_generational_indices;
 pub mod memory_allocators; 
+pub mod generational_allocator;
+pub mod validated_arena;
+pub mod dense_arena;
+pub mod keyed_arena;
+pub mod handle_map;
+pub mod atomic_entity_ptr;
+pub mod fault_injection;
+pub mod errors;
+pub mod ghost_arena;
+pub mod copy_arena;
+pub mod slot_guard;
+pub mod hot_cold_arena;
+pub mod cache_padded;
+pub mod size_class_allocator;
+pub mod repr_c_arena;
+pub mod recycling_arena;
+pub mod sorted_index;
+pub mod sampling;
+pub mod compact_encoding;
+pub mod change_tracking;
+pub mod stable_ids;
+pub mod migrations;
+pub mod relocatable;
+pub mod checksum;
+pub mod lazy_arena;
+pub mod op_log;
+pub mod gc;
+pub mod interner;
+pub mod graph;
+pub mod mailbox;
+pub mod spatial_grid;
+pub mod option_vec_adapter;
+pub mod leak_tracker;
+pub mod frozen_arena;
+pub mod dense_map;
+pub mod bit_vec;
 
-mod allocator_with_pointer;
+#[cfg(feature = "rwlock-slots")]
+pub mod rwlock_arena;
+
+#[cfg(feature = "mmap-arena")]
+pub mod mmap_arena;
+
+mod bitset;
+mod free_sites;
+mod generation_table;
+mod live_slots;
+mod owner_thread;
+mod slot_headers;
+
+#[cfg(feature = "poison")]
+mod poison;
+
+#[cfg(feature = "zero-on-free")]
+mod zeroize;
@@ -0,0 +1,57 @@
+/// Iterator adapter for arenas that walk every slot to find the live ones.
+///
+/// A plain `filter_map` over the slots can't tell how many items are left, so it's
+/// neither `ExactSizeIterator` nor able to stop before the last slot. The arenas know
+/// their live count, so `LiveSlots` carries it along: it reports an exact length, works
+/// from both ends, and stops as soon as every live entry was yielded, without scanning
+/// the dead slots after the last one.
+pub(crate) struct LiveSlots<I, F> {
+    slots: I,
+    // Maps a slot to its item, None for dead slots
+    live: F,
+    remaining: usize,
+}
+
+impl<I: Iterator, F> LiveSlots<I, F> {
+    /// `remaining` must be the number of slots of `slots` for which `live` returns Some
+    pub(crate) fn new<R>(slots: I, remaining: usize, live: F) -> Self
+    where
+        F: FnMut(I::Item) -> Option<R>,
+    {
+        return LiveSlots { slots, live, remaining };
+    }
+}
+
+impl<I: Iterator, R, F: FnMut(I::Item) -> Option<R>> Iterator for LiveSlots<I, F> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        while self.remaining > 0 {
+            if let Some(item) = (self.live)(self.slots.next()?) {
+                self.remaining -= 1;
+                return Some(item);
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        return (self.remaining, Some(self.remaining));
+    }
+}
+
+impl<I: DoubleEndedIterator, R, F: FnMut(I::Item) -> Option<R>> DoubleEndedIterator for LiveSlots<I, F> {
+    fn next_back(&mut self) -> Option<R> {
+        while self.remaining > 0 {
+            if let Some(item) = (self.live)(self.slots.next_back()?) {
+                self.remaining -= 1;
+                return Some(item);
+            }
+        }
+
+        return None;
+    }
+}
+
+impl<I: Iterator, R, F: FnMut(I::Item) -> Option<R>> ExactSizeIterator for LiveSlots<I, F> {}
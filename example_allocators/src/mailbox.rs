@@ -0,0 +1,209 @@
+/// Entities with a queue of messages each, as used by actor-style game logic.
+///
+/// Systems often want to tell an entity something (took damage, got picked up) without
+/// touching it right away, and the entity may be gone by the time the message is read.
+/// `Mailbox<T, M>` stores entities in a `GenerationalIndexArray` and gives each a FIFO
+/// queue. `send` through a dead handle drops the message and counts it, instead of
+/// delivering it to whatever entity reuses the slot.
+///
+/// Queues don't get a `Vec` each: every message is a node in one shared pool, linked to
+/// the next message of the same queue, and nodes of drained messages are reused. Freeing
+/// an entity drops its undelivered messages.
+use crate::generational_allocator::AllocatorStats;
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+// End of a queue, or no next free node
+const NIL: usize = usize::MAX;
+
+struct MessageNode<M> {
+    // None while the node is in the free list
+    message: Option<M>,
+    next: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Queue {
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+const EMPTY: Queue = Queue { head: NIL, tail: NIL, len: 0 };
+
+pub struct Mailbox<T, M> {
+    entities: GenerationalIndexArray<T>,
+    // Queue of each slot, by slot index
+    queues: Vec<Queue>,
+    pool: Vec<MessageNode<M>>,
+    // First free node in the pool, linked through `next`
+    free_node: usize,
+    dropped: u64,
+}
+
+impl<T, M> Default for Mailbox<T, M> {
+    fn default() -> Self {
+        return Mailbox { entities: GenerationalIndexArray::default(), queues: Vec::new(), pool: Vec::new(), free_node: NIL, dropped: 0 };
+    }
+}
+
+impl<T, M> Mailbox<T, M> {
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, entity: T) -> GenerationalIndex {
+        return self.insert(entity);
+    }
+
+    pub fn insert(&mut self, entity: T) -> GenerationalIndex {
+        let handle = self.entities.insert(entity);
+        if handle.get_index() >= self.queues.len() {
+            self.queues.resize(handle.get_index() + 1, EMPTY);
+        }
+        return handle;
+    }
+
+    /// Free the entity and drop the messages it didn't read
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        if self.remove(index).is_none() {
+            panic!("Trying to free an already dead index");
+        }
+    }
+
+    /// Free the entity and hand it back, dropping the messages it didn't read. None if
+    /// the handle is dead
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        let entity = self.entities.remove(index)?;
+        drop(self.take_queue(index.get_index()));
+        return Some(entity);
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.entities.contains(index);
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return self.entities.get(index);
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return self.entities.get_mut(index);
+    }
+
+    /// Queue `message` for the entity. Returns false, dropping the message and counting
+    /// it in `dropped`, if the handle is dead
+    pub fn send(&mut self, index: &GenerationalIndex, message: M) -> bool {
+        if !self.entities.contains(index) {
+            self.dropped += 1;
+            return false;
+        }
+
+        let node = match self.free_node {
+            NIL => {
+                self.pool.push(MessageNode { message: Some(message), next: NIL });
+                self.pool.len() - 1
+            }
+            node => {
+                self.free_node = self.pool[node].next;
+                self.pool[node] = MessageNode { message: Some(message), next: NIL };
+                node
+            }
+        };
+
+        let queue = &mut self.queues[index.get_index()];
+        match queue.tail {
+            NIL => queue.head = node,
+            tail => self.pool[tail].next = node,
+        }
+        queue.tail = node;
+        queue.len += 1;
+        return true;
+    }
+
+    /// Take the entity's messages, oldest first. Empty if the handle is dead. Messages
+    /// the iterator isn't advanced over are dropped with it
+    pub fn drain(&mut self, index: &GenerationalIndex) -> Drain<'_, T, M> {
+        if !self.entities.contains(index) {
+            return Drain { mailbox: self, node: NIL };
+        }
+
+        return self.take_queue(index.get_index());
+    }
+
+    /// Number of messages waiting for the entity, 0 if the handle is dead
+    pub fn pending(&self, index: &GenerationalIndex) -> usize {
+        if !self.entities.contains(index) {
+            return 0;
+        }
+
+        return self.queues[index.get_index()].len;
+    }
+
+    /// Messages sent to dead handles so far
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.entities.stats();
+    }
+
+    /// Panic if the entities are inconsistent, or queued and free nodes don't add up to the pool
+    pub fn check_invariants(&self) {
+        self.entities.check_invariants();
+        let mut queued = 0;
+        for queue in self.queues.iter() {
+            let mut node = queue.head;
+            let mut len = 0;
+            while node != NIL {
+                assert!(self.pool[node].message.is_some(), "Queue links a free node");
+                len += 1;
+                node = self.pool[node].next;
+            }
+            assert_eq!(len, queue.len, "Queue length doesn't match its nodes");
+            queued += len;
+        }
+
+        let mut free = 0;
+        let mut node = self.free_node;
+        while node != NIL {
+            assert!(self.pool[node].message.is_none(), "Free node holds a message");
+            free += 1;
+            node = self.pool[node].next;
+        }
+        assert_eq!(queued + free, self.pool.len(), "Some pool nodes are neither queued nor free");
+    }
+
+    // Detach the queue of `slot` and walk it
+    fn take_queue(&mut self, slot: usize) -> Drain<'_, T, M> {
+        let node = self.queues[slot].head;
+        self.queues[slot] = EMPTY;
+        return Drain { mailbox: self, node };
+    }
+}
+
+/// Messages of one entity, returning each node to the pool as it's read
+pub struct Drain<'a, T, M> {
+    mailbox: &'a mut Mailbox<T, M>,
+    node: usize,
+}
+
+impl<'a, T, M> Iterator for Drain<'a, T, M> {
+    type Item = M;
+
+    fn next(&mut self) -> Option<M> {
+        if self.node == NIL {
+            return None;
+        }
+
+        let node = &mut self.mailbox.pool[self.node];
+        let message = node.message.take();
+        let next = std::mem::replace(&mut node.next, self.mailbox.free_node);
+        self.mailbox.free_node = self.node;
+        self.node = next;
+        return message;
+    }
+}
+
+impl<'a, T, M> Drop for Drain<'a, T, M> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
@@ -3,15 +3,33 @@
 use std::mem::MaybeUninit;
 use std::ops::Deref;
 use std::ops::DerefMut;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::num::NonZeroU64;
+use std::ptr::NonNull;
+
+use std::panic::Location;
+
+use crate::bit_vec::BitVec;
+use crate::bitset::Bitset;
+use crate::errors::{misuse, validate_handle, AccessError, AllocError, FreeError, HandleMismatch, SlotState};
+use crate::fault_injection::try_reserve_one;
+use crate::free_sites::{FreeSite, FreeSites};
+use crate::generation_table::GenerationTable;
+use crate::generational_allocator::{check_free_list, AllocatorStats};
+use crate::live_slots::LiveSlots;
+use crate::owner_thread::OwnerThread;
+use crate::sampling::{distinct_ranks, RandomSource};
+use crate::slot_headers::SlotHeader;
 
 /// Default Index type for handle based implementations
-#[derive(Debug, PartialEq, Default, Clone)]
-pub struct GenerationalIndex {
-    index: usize,
-    generation: Generation,
-}
-pub type Generation = u32;
+pub use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+use crate::kyren_generational_indices::bump_generation;
+#[cfg(feature = "bytemuck")]
+use crate::kyren_generational_indices::write_values_as_bytes;
 
 /// This is a handle-based allocators.
 ///
@@ -21,20 +39,78 @@ pub type Generation = u32;
 pub struct GIABoxUninit<T> {
     entries: Vec<GIABoxUninitEntry<T>>,
     free: Vec<usize>,
+    free_sites: FreeSites,
+    owner: OwnerThread,
 }
 
 pub struct GIABoxUninitEntry<T> {
     generation: Generation,
+    // Only initialized while `live` is set
     ptr: Box<MaybeUninit<RefCell<T>>>,
+    live: bool,
 }
 
 impl<T> GIABoxUninit<T> {
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        return GIABoxUninit {
+            entries: (0..count)
+                .map(|_| GIABoxUninitEntry { generation, ptr: Box::new(MaybeUninit::uninit()), live: false })
+                .collect(),
+            free: (0..count).rev().collect(),
+            free_sites: FreeSites::default(),
+            owner: OwnerThread::default(),
+        };
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
     pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert_no_grow(element);
+    }
+
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        if self.free.is_empty() {
+            return Err(AllocError::NoFreeSlot(element));
+        }
+
+        return self.try_insert(element);
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// allocator can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
         if self.free.is_empty() {
+            if !try_reserve_one(&mut self.entries, std::mem::size_of::<RefCell<T>>()) {
+                return Err(AllocError::OutOfMemory(element));
+            }
+
             // Construct a new entry
             let mut new_entry = GIABoxUninitEntry {
                 generation: 0,
                 ptr: Box::new(MaybeUninit::<RefCell<T>>::uninit()),
+                live: true,
             };
             let new_entry_index = self.entries.len();
 
@@ -44,10 +120,10 @@ impl<T> GIABoxUninit<T> {
             // Add it to the current list of entries
             self.entries.push(new_entry);
 
-            return GenerationalIndex {
+            return Ok(GenerationalIndex {
                 index: new_entry_index,
                 generation: 0,
-            };
+            });
         }
 
         let next_free = self.free.pop().unwrap();
@@ -55,21 +131,40 @@ impl<T> GIABoxUninit<T> {
 
         // Initialize entry, don't return uninitialized memory
         entry.ptr.write(RefCell::new(element));
+        entry.live = true;
 
-        return GenerationalIndex {
+        return Ok(GenerationalIndex {
             index: next_free,
             generation: entry.generation,
-        };
+        });
     }
 
+    /// False for any handle that doesn't point to a value, out of range ones included
     #[inline(always)]
     pub fn is_live(&self, index: &GenerationalIndex) -> bool {
-        return index.generation == self.entries[index.index].generation;
+        return match self.entries.get(index.index) {
+            Some(entry) => entry.live && entry.generation == index.generation,
+            None => false,
+        };
+    }
+
+    /// Ok if the handle is live, otherwise what it found at its index instead
+    pub fn validate(&self, index: &GenerationalIndex) -> Result<(), HandleMismatch> {
+        return validate_handle(index, self.entries.get(index.index).map(|entry| (entry.generation, entry.live)));
+    }
+
+    /// In debug builds the arena panics when borrowed from a thread other than the one
+    /// that owns it. `&mut self` methods move ownership to the calling thread on their
+    /// own, call this after moving the arena if the new thread starts with `&self` ones
+    pub fn rebind_owner_thread(&mut self) {
+        self.owner.claim();
     }
 
+    /// None if the handle is dead, or a panic with the `strict` feature
     pub fn get(&self, index: &GenerationalIndex) -> Option<&RefCell<T>> {
-        if !self.is_live(index) {
-            return None;
+        self.owner.check();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
         }
 
         return unsafe {
@@ -77,17 +172,127 @@ impl<T> GIABoxUninit<T> {
         };
     }
 
+    /// Direct access when the arena is borrowed mutably: nobody else can hold a borrow,
+    /// so the RefCell flag is skipped
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        return unsafe {
+            Some(self.entries[index.index].ptr.assume_init_mut().get_mut())
+        };
+    }
+
+    /// Borrow the value without panicking: fails with `NotLive` or `OutOfBounds` if the
+    /// handle doesn't point to a value, and with `AlreadyBorrowed` if the value is mutably borrowed
+    pub fn try_get(&self, index: &GenerationalIndex) -> Result<Ref<'_, T>, AccessError> {
+        if let Err(mismatch) = self.validate(index) {
+            if mismatch.slot_state == SlotState::OutOfRange {
+                return misuse(Err(mismatch.into()), || format!("Trying to access index {} out of range", index.index));
+            }
+            return Err(mismatch.into());
+        }
+
+        let cell = self.get(index).expect("Live slot without a value");
+        return cell.try_borrow().map_err(|_| AccessError::AlreadyBorrowed);
+    }
+
+    /// Mutable version of `try_get`, also fails if the value is borrowed at all
+    pub fn try_get_mut(&self, index: &GenerationalIndex) -> Result<RefMut<'_, T>, AccessError> {
+        if let Err(mismatch) = self.validate(index) {
+            if mismatch.slot_state == SlotState::OutOfRange {
+                return misuse(Err(mismatch.into()), || format!("Trying to access index {} out of range", index.index));
+            }
+            return Err(mismatch.into());
+        }
+
+        let cell = self.get(index).expect("Live slot without a value");
+        return cell.try_borrow_mut().map_err(|_| AccessError::AlreadyBorrowed);
+    }
+
+    /// Borrow only the part of the value picked by `f`, e.g. `borrow_map(&handle, |e| &e.name)`.
+    /// The RefCell flag still covers the whole value, so this panics like `RefCell::borrow`
+    /// if the value is mutably borrowed
+    pub fn borrow_map<U: ?Sized>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> &U) -> Option<Ref<'_, U>> {
+        return self.get(index).map(|cell| Ref::map(cell.borrow(), f));
+    }
+
+    /// Mutable version of `borrow_map`, panics if the value is borrowed at all
+    pub fn borrow_map_mut<U: ?Sized>(&self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> &mut U) -> Option<RefMut<'_, U>> {
+        return self.get(index).map(|cell| RefMut::map(cell.borrow_mut(), f));
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
     pub fn free(&mut self, index: &GenerationalIndex) {
-        if !self.is_live(index) {
-            panic!("Trying to free already unused index");
+        if self.try_free(index).is_err() {
+            panic!("Trying to free already unused index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Same as `free`, but fails with `DeadHandle` instead of panicking when the handle
+    /// is dead or out of range, unless the `strict` feature is on
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn try_free(&mut self, index: &GenerationalIndex) -> Result<(), FreeError> {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(Err(FreeError::DeadHandle), || format!("Trying to free already unused index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        drop(self.take(index.index));
+        return Ok(());
+    }
+
+    /// Free the slot and hand its value back, or return None if the handle is dead or out
+    /// of range. The `strict` feature panics on a dead handle instead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(None, || format!("Trying to remove already unused index: {}{}", mismatch, self.free_sites.describe(index.index)));
         }
 
-        let index = index.index;
-        self.free.push(index);
+        return Some(self.take(index.index));
+    }
+
+    // Free a live slot and move its value out
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    fn take(&mut self, index: usize) -> T {
+        self.free_sites.record(index, Location::caller());
         let entry: &mut GIABoxUninitEntry<T> = &mut self.entries[index];
-        entry.generation += 1;
-        unsafe {
-            entry.ptr.assume_init_drop();
+        if bump_generation(&mut entry.generation) {
+            self.free.push(index);
+        }
+        entry.live = false;
+        let value = unsafe { entry.ptr.assume_init_read() }.into_inner();
+
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize(&mut *entry.ptr);
+
+        #[cfg(feature = "poison")]
+        crate::poison::poison(&mut *entry.ptr);
+
+        return value;
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_ {
+        return self.free.iter().rev().map(|index| (*index, self.entries[*index].generation));
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.entries.iter().filter(|entry| entry.generation == Generation::MAX).count();
+        let free = self.free.len();
+        return AllocatorStats { capacity: self.entries.len(), live: self.entries.len() - free - retired, free, retired };
+    }
+
+    /// Panic if the free list is inconsistent or a free slot still holds a value
+    pub fn check_invariants(&self) {
+        check_free_list(self.free.iter().copied(), self.entries.len(), |index| self.entries[index].generation);
+        for index in self.free.iter() {
+            assert!(!self.entries[*index].live, "Free slot {} still holds a value", index);
         }
     }
 }
@@ -101,29 +306,147 @@ impl<T> GIABoxUninit<T> {
 pub struct BoxAllocator<T> {
     entries: Vec<Box<Entry<T>>>,
     free: Vec<*mut Entry<T>>,
+    // Which entries hold a value, by position in `entries`
+    occupied: Bitset,
+    // Generation each trimmed position had, a header reusing that position must start
+    // past it so index handles from before the trim stay dead
+    trimmed: Vec<Generation>,
 }
 
 pub struct Entry<T> {
-    generation: Generation,
-    value: MaybeUninit<T>
+    // The generation lives outside the entry so pointers can check it after the
+    // allocator is dropped, see `slot_headers`
+    header: &'static SlotHeader,
+    // Position in `entries`, so pointers can be turned into index handles
+    index: usize,
+    value: MaybeUninit<T>,
+    free_site: FreeSite,
+}
+
+impl<T> Entry<T> {
+    /// Where the value of `entry` lives, without making a reference to the entry
+    pub(crate) fn value_ptr(entry: *mut Entry<T>) -> *const MaybeUninit<T> {
+        return unsafe { std::ptr::addr_of!((*entry).value) };
+    }
 }
 
 // To keep this implementation safe, you should not allow the user to construct 
 // an EntityPtr by themselves, always ask the allocator to give you a new one
+#[must_use = "dropping the only handle to a live entry leaks the entry until the arena is dropped"]
 pub struct EntityPtr<T> {
-    generation: Generation,
-    ptr: *mut Entry<T>, // super unsafe raw pointer!
+    pub(crate) generation: Generation,
+    pub(crate) header: &'static SlotHeader,
+    // Copied from the entry, so it can be read after the allocator is dropped
+    pub(crate) index: usize,
+    pub(crate) ptr: NonNull<Entry<T>>, // super unsafe raw pointer!
+    // Invariant in T: a covariant pointer could be turned into an `EntityPtr<&'short U>`
+    // and write a short-lived reference into an allocator of `&'long U` through DerefMut.
+    // The pointer doesn't own the value, so it doesn't claim to drop a T either
+    pub(crate) marker: PhantomData<fn(T) -> T>,
+}
+
+// Pointers are compared by the entry they point to and its generation, so a pointer to
+// a freed entity never equals one to whatever reuses the entry. There's no Clone or
+// Copy: DerefMut would let two copies hand out aliasing `&mut T`
+impl<T> PartialEq for EntityPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.ptr == other.ptr && self.generation == other.generation;
+    }
+}
+
+impl<T> Eq for EntityPtr<T> {}
+
+impl<T> Hash for EntityPtr<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ptr.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> PartialOrd for EntityPtr<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+/// Orders by address, then generation. Sorting a list of pointers groups them by memory
+/// location, which is a good order to visit them in
+impl<T> Ord for EntityPtr<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return (self.ptr, self.generation).cmp(&(other.ptr, other.generation));
+    }
 }
 
 impl<T> BoxAllocator<T> {
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        let mut entries: Vec<Box<Entry<T>>> = (0..count)
+            .map(|index| {
+                let header = SlotHeader::acquire();
+                header.set_generation(generation);
+                Box::new(Entry { header, index, value: MaybeUninit::uninit(), free_site: FreeSite::default() })
+            })
+            .collect();
+        let free = entries.iter_mut().rev().map(|entry| &mut **entry as *mut Entry<T>).collect();
+
+        return BoxAllocator { entries, free, occupied: Bitset::default(), trimmed: Vec::new() };
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
     pub fn new(&mut self, element: T) -> EntityPtr<T> {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> EntityPtr<T> {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element: T) -> Result<EntityPtr<T>, AllocError<T>> {
+        return self.try_insert_no_grow(element);
+    }
+
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element: T) -> Result<EntityPtr<T>, AllocError<T>> {
+        if self.free.is_empty() {
+            return Err(AllocError::NoFreeSlot(element));
+        }
+
+        return self.try_insert(element);
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<EntityPtr<T>, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// allocator can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<EntityPtr<T>, AllocError<T>> {
         if self.free.is_empty() {
+            if !try_reserve_one(&mut self.entries, std::mem::size_of::<Entry<T>>()) {
+                return Err(AllocError::OutOfMemory(element));
+            }
+
             // Construct a new entry
+            let new_entry_index = self.entries.len();
+            let header = SlotHeader::acquire();
+            if let Some(floor) = self.trimmed.get(new_entry_index) {
+                header.advance_to(*floor);
+            }
             let mut new_entry = Box::new(Entry {
-                generation: 0,
+                header,
+                index: new_entry_index,
                 value: MaybeUninit::<T>::uninit(),
+                free_site: FreeSite::default(),
             });
-            let new_entry_index = self.entries.len();
 
             // Initialize it since it will be retrieved from this function
             new_entry.value.write(element);
@@ -131,11 +454,16 @@ impl<T> BoxAllocator<T> {
 
             // Add it to the current list of entries
             self.entries.push(new_entry);
+            self.occupied.insert(new_entry_index);
 
-            return EntityPtr{
-                ptr: &mut *self.entries[new_entry_index] as  *mut Entry<T>,
-                generation: 0,
-            };
+            let header = self.entries[new_entry_index].header;
+            return Ok(EntityPtr{
+                ptr: NonNull::from(&mut *self.entries[new_entry_index]),
+                marker: PhantomData,
+                generation: header.generation(),
+                index: new_entry_index,
+                header,
+            });
         }
 
         let next_free = self.free.pop().unwrap();
@@ -143,131 +471,1013 @@ impl<T> BoxAllocator<T> {
         // Initialize entry, don't return uninitialized memory
         unsafe{(*next_free).value.write(element)};
 
-        let generation = unsafe {
-            (*next_free).generation
+        let (header, index) = unsafe {
+            ((*next_free).header, (*next_free).index)
         };
+        self.occupied.insert(index);
+
+        return Ok(EntityPtr{
+            // Free list entries point into `entries`, never null
+            ptr: unsafe { NonNull::new_unchecked(next_free) },
+            marker: PhantomData,
+            generation: header.generation(),
+            index,
+            header,
+        })
+    }
 
-        return EntityPtr{
-            ptr: next_free,
-            generation: generation
+    /// Drop every live value at once. All pointers and index handles go stale, the
+    /// entries stay allocated for reuse
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free_all(&mut self) {
+        for index in std::mem::take(&mut self.occupied).iter() {
+            let entry = &mut *self.entries[index];
+            entry.free_site.record(Location::caller());
+            // Occupied entries are initialized
+            unsafe { entry.value.assume_init_drop() };
+
+            #[cfg(feature = "zero-on-free")]
+            crate::zeroize::zeroize(&mut entry.value);
+
+            #[cfg(feature = "poison")]
+            crate::poison::poison(&mut entry.value);
+
+            if entry.header.bump() {
+                self.free.push(entry as *mut Entry<T>);
+            }
+        }
+    }
+
+    /// Deallocate free entries at the end of the allocator until only `keep` free ones are
+    /// left, or a live (or retired) entry is reached. Entries in the middle can't be
+    /// released without changing the positions index handles refer to. Returns how many
+    /// entries were deallocated
+    pub fn trim(&mut self, keep: usize) -> usize {
+        let mut removable = self.free.len().saturating_sub(keep);
+        let mut cut = self.entries.len();
+        while cut > 0 && removable > 0 {
+            let entry = &self.entries[cut - 1];
+            if self.occupied.contains(cut - 1) || entry.header.is_retired() {
+                break;
+            }
+
+            removable -= 1;
+            cut -= 1;
+        }
+
+        let removed = self.entries.len() - cut;
+        if removed == 0 {
+            return 0;
+        }
+
+        self.free.retain(|ptr| unsafe { (**ptr).index } < cut);
+        if self.trimmed.len() < self.entries.len() {
+            self.trimmed.resize(self.entries.len(), 0);
+        }
+        for entry in self.entries.drain(cut..) {
+            self.trimmed[entry.index] = entry.header.generation();
+            SlotHeader::release(entry.header);
         }
+        self.entries.shrink_to_fit();
+
+        return removed;
+    }
+
+    /// Iterate over the live entities in allocation slot order, with an index handle to
+    /// each. Not a pointer: it could deref mutably while the reference is alive
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &T)> + DoubleEndedIterator + '_ {
+        return self.occupied.iter().map(move |index| {
+            let entry = &self.entries[index];
+            // Occupied entries are initialized
+            (Self::handle_to(entry), unsafe { entry.value.assume_init_ref() })
+        });
+    }
+
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &mut T)> + DoubleEndedIterator + '_ {
+        let occupied = &self.occupied;
+        return LiveSlots::new(self.entries.iter_mut(), occupied.count(), move |entry| {
+            occupied
+                .contains(entry.index)
+                .then(|| (Self::handle_to(entry), unsafe { entry.value.assume_init_mut() }))
+        });
+    }
+
+    fn handle_to(entry: &Entry<T>) -> GenerationalIndex {
+        return GenerationalIndex { index: entry.index, generation: entry.header.generation() };
+    }
+
+    fn pointer_to(entry: &Entry<T>) -> EntityPtr<T> {
+        return EntityPtr {
+            ptr: NonNull::from(entry),
+            marker: PhantomData,
+            generation: entry.header.generation(),
+            index: entry.index,
+            header: entry.header,
+        };
     }
 
+    /// Whether an index handle points to a live entity. False for any handle that doesn't,
+    /// out of range ones included. Pointers answer this themselves with `EntityPtr::is_live`
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.entries.get(index.index) {
+            Some(entry) => self.occupied.contains(index.index) && entry.header.generation() == index.generation,
+            None => false,
+        };
+    }
+
+    /// Ok if the index handle is live, otherwise what it found at its index instead
+    pub fn validate(&self, index: &GenerationalIndex) -> Result<(), HandleMismatch> {
+        let slot = self.entries.get(index.index).map(|entry| (entry.header.generation(), self.occupied.contains(index.index)));
+        return validate_handle(index, slot);
+    }
+
+    /// Pointer to the entity behind an index handle, None if the handle is dead or out of
+    /// range. The other direction is `EntityPtr::downgrade`
+    ///
+    /// # Safety
+    /// The result is a second pointer to an entity that may already have one, and both
+    /// deref mutably. No other `EntityPtr` to the same entity may be dereferenced while
+    /// the returned one is, and the other way around. Safe code can't make the alias:
+    ///
+    /// ```compile_fail,E0133
+    /// # use example_allocators::memory_allocators::BoxAllocator;
+    /// let mut allocator = BoxAllocator::<u32>::default();
+    /// let mut ptr = allocator.insert(1);
+    /// let mut alias = allocator.upgrade(&ptr.downgrade()).unwrap();
+    /// let (a, b): (&mut u32, &mut u32) = (&mut ptr, &mut alias);
+    /// ```
+    pub unsafe fn upgrade(&self, index: &GenerationalIndex) -> Option<EntityPtr<T>> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return Some(Self::pointer_to(&self.entries[index.index]));
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
     pub fn free(&mut self, ptr: &EntityPtr<T>) {
+        if self.try_free(ptr).is_err() {
+            panic!("Trying to double-free a pointer{}", ptr.free_site());
+        }
+    }
 
-        debug_assert!(ptr.is_live(), "Trying to double-free a pointer");
-        self.free.push(ptr.ptr);
+    /// Same as `free`, but fails with `DeadHandle` instead of panicking when the pointer
+    /// was already freed, unless the `strict` feature is on
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn try_free(&mut self, ptr: &EntityPtr<T>) -> Result<(), FreeError> {
+        if !ptr.is_live() {
+            return misuse(Err(FreeError::DeadHandle), || format!("Trying to double-free a pointer{}", ptr.free_site()));
+        }
+
+        drop(self.take(ptr));
+        return Ok(());
+    }
+
+    /// Free the entity and hand its value back, or return None if the pointer was already
+    /// freed. The `strict` feature panics on a dead pointer instead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, ptr: &EntityPtr<T>) -> Option<T> {
+        if !ptr.is_live() {
+            return misuse(None, || format!("Trying to remove a freed pointer{}", ptr.free_site()));
+        }
+
+        return Some(self.take(ptr));
+    }
+
+    // Free a live entity and move its value out
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    fn take(&mut self, ptr: &EntityPtr<T>) -> T {
         unsafe {
-           (*ptr.ptr).generation += 1;
-           (*ptr.ptr).value.assume_init_drop();
+           (*ptr.ptr.as_ptr()).free_site.record(Location::caller());
+           self.occupied.remove(ptr.index);
+           let reusable = (*ptr.ptr.as_ptr()).header.bump();
+           let value = (*ptr.ptr.as_ptr()).value.assume_init_read();
+
+           #[cfg(feature = "zero-on-free")]
+           crate::zeroize::zeroize(&mut (*ptr.ptr.as_ptr()).value);
+
+           #[cfg(feature = "poison")]
+           crate::poison::poison(&mut (*ptr.ptr.as_ptr()).value);
+
+           // Slots that ran out of generations are never handed out again
+           if reusable {
+               self.free.push(ptr.ptr.as_ptr());
+           }
+
+           return value;
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.entries.iter().filter(|entry| entry.header.is_retired()).count();
+        let free = self.free.len();
+        return AllocatorStats { capacity: self.entries.len(), live: self.entries.len() - free - retired, free, retired };
+    }
+
+    /// Panic if the free list is inconsistent
+    pub fn check_invariants(&self) {
+        let entries: Vec<*const Entry<T>> = self.entries.iter().map(|entry| &**entry as *const Entry<T>).collect();
+        let position = |ptr: *mut Entry<T>| {
+            entries.iter().position(|entry| std::ptr::eq(*entry, ptr))
+        };
+
+        let mut free = Vec::with_capacity(self.free.len());
+        for ptr in self.free.iter() {
+            match position(*ptr) {
+                Some(index) => free.push(index),
+                None => panic!("Free pointer {:?} doesn't belong to this allocator", ptr),
+            }
+        }
+        check_free_list(free, self.entries.len(), |index| self.entries[index].header.generation());
+    }
+}
+
+impl<T> Drop for BoxAllocator<T> {
+    fn drop(&mut self) {
+        let free: HashSet<*mut Entry<T>> = self.free.drain(..).collect();
+
+        for entry in self.entries.iter_mut() {
+            let ptr = &mut **entry as *mut Entry<T>;
+            if !free.contains(&ptr) && !entry.header.is_retired() {
+                unsafe { entry.value.assume_init_drop() };
+            }
+
+            // Every pointer into this allocator dies here
+            SlotHeader::release(entry.header);
         }
     }
 }
 
 impl<T> EntityPtr<T> {
+    /// Check if the entity is still alive. This is always safe to call, even after
+    /// the allocator that created this pointer was dropped
     #[inline(always)]
     pub fn is_live(&self) -> bool {
-        return self.generation == unsafe {(*self.ptr).generation}
+        return self.generation == self.header.generation()
+    }
+
+    /// Index handle to the same entity, e.g. to serialize it. Turn it back into a pointer
+    /// with `BoxAllocator::upgrade`
+    pub fn downgrade(&self) -> GenerationalIndex {
+        return GenerationalIndex { index: self.index, generation: self.generation };
+    }
+
+    /// Raw entry pointer and generation, e.g. to hand the entity to C code as an opaque
+    /// pointer. Rebuild the `EntityPtr` with `from_raw`
+    pub fn as_raw(&self) -> (*mut Entry<T>, Generation) {
+        return (self.ptr.as_ptr(), self.generation);
+    }
+
+    /// Rebuild a pointer from the parts returned by `as_raw`, None if `entry` is null
+    ///
+    /// # Safety
+    /// `entry` must come from `as_raw` and its allocator must still be alive, the slot
+    /// header and index are read from the entry. The result may be dead, like any pointer
+    pub unsafe fn from_raw(entry: *mut Entry<T>, generation: Generation) -> Option<EntityPtr<T>> {
+        let ptr = NonNull::new(entry)?;
+        let (header, index) = ((*entry).header, (*entry).index);
+        return Some(EntityPtr { generation, header, index, ptr, marker: PhantomData });
+    }
+
+    /// Ok if the entity is live, otherwise the generation its slot moved on to. Whether
+    /// the slot was reused is unknown, the allocator may be gone
+    pub fn validate(&self) -> Result<(), HandleMismatch> {
+        let generation = self.header.generation();
+        if generation == self.generation {
+            return Ok(());
+        }
+
+        let slot_state = if self.header.is_retired() { SlotState::Retired } else { SlotState::Unknown };
+        return Err(HandleMismatch { index: self.index, expected_gen: self.generation, found_gen: Some(generation), slot_state });
+    }
+
+    /// The entity, or `NotLive` if it was freed or its allocator was dropped
+    #[inline(always)]
+    pub fn try_get(&self) -> Result<&T, AccessError> {
+        self.validate()?;
+
+        // Live means the allocator still owns the entry and the value is initialized
+        return Ok(unsafe { (*self.ptr.as_ptr()).value.assume_init_ref() });
+    }
+
+    #[inline(always)]
+    pub fn try_get_mut(&mut self) -> Result<&mut T, AccessError> {
+        self.validate()?;
+
+        return Ok(unsafe { (*self.ptr.as_ptr()).value.assume_init_mut() });
+    }
+
+    /// Only call while the allocator is alive, `ptr` dangles once it's dropped
+    fn free_site(&self) -> String {
+        return unsafe { (*self.ptr.as_ptr()).free_site.describe() };
     }
 }
 
+// Deref checks liveness in every build: a dead pointer may point into an allocator that
+// no longer exists, so nothing behind `ptr` is read (not even the free site) once the
+// check fails
 impl <T> Deref for EntityPtr<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        debug_assert!(self.is_live(), "Trying to deref free pointer");
-        return unsafe {(*self.ptr).value.assume_init_ref()}
+        match self.try_get() {
+            Ok(value) => value,
+            Err(_) => panic!("Trying to deref free pointer"),
+        }
     }
 }
 
 impl <T> DerefMut for EntityPtr<T> {
 
     fn deref_mut(&mut self) -> &mut Self::Target {
-        debug_assert!(self.is_live(), "Trying to deref free pointer");
-        return unsafe {(*self.ptr).value.assume_init_mut()}
+        match self.try_get_mut() {
+            Ok(value) => value,
+            Err(_) => panic!("Trying to deref free pointer"),
+        }
+    }
+}
+
+// Experimental 8 byte pointer handle. The entry address only needs the low 48 bits on
+// the platforms we care about, so the low 16 bits of the generation ride in the high
+// ones. Two things are given up for the size:
+//
+// - There's no room for the slot header, so liveness is read through the entry itself,
+//   which is only valid while the allocator is alive and hasn't trimmed the entry. Every
+//   access is unsafe for that reason.
+// - A pointer that goes stale and then sees its slot freed another multiple of 2^16
+//   times has a tag matching again. Debug builds keep the full generation next to the
+//   tag and panic when that happens, release builds can't tell.
+const TAG_SHIFT: u32 = 48;
+const ADDRESS_MASK: u64 = (1 << TAG_SHIFT) - 1;
+
+/// `EntityPtr` squeezed into 8 bytes by packing a 16 bit generation tag into the unused
+/// high bits of the entry address. Experimental, see `EntityPtr::into_tagged`
+///
+/// Like `EntityPtr` it's neither `Send` nor `Sync`:
+///
+/// ```compile_fail,E0277
+/// # use example_allocators::memory_allocators::BoxAllocator;
+/// let mut allocator = BoxAllocator::<std::rc::Rc<u32>>::default();
+/// let tagged = allocator.insert(std::rc::Rc::new(1)).into_tagged().ok().unwrap();
+/// std::thread::spawn(move || drop(tagged));
+/// ```
+#[must_use = "dropping the only handle to a live entry leaks the entry until the arena is dropped"]
+pub struct TaggedEntityPtr<T> {
+    bits: NonZeroU64,
+    #[cfg(debug_assertions)]
+    generation: Generation,
+    // The address is hidden in an integer, so this stands in for the pointer: invariant
+    // in T like `EntityPtr`, and not `Send` or `Sync`, for the same reasons
+    marker: PhantomData<*mut T>,
+}
+
+impl<T> EntityPtr<T> {
+    /// 8 byte version of this pointer. Gives the pointer back if the entry address
+    /// doesn't fit in 48 bits
+    pub fn into_tagged(self) -> Result<TaggedEntityPtr<T>, EntityPtr<T>> {
+        let address = self.ptr.as_ptr() as usize as u64;
+        if address & !ADDRESS_MASK != 0 {
+            return Err(self);
+        }
+
+        let tag = self.generation as u16 as u64;
+        return Ok(TaggedEntityPtr {
+            // The address isn't null, so neither are the bits
+            bits: unsafe { NonZeroU64::new_unchecked(address | (tag << TAG_SHIFT)) },
+            #[cfg(debug_assertions)]
+            generation: self.generation,
+            marker: PhantomData,
+        });
+    }
+}
+
+impl<T> TaggedEntityPtr<T> {
+    #[inline(always)]
+    fn entry(&self) -> *mut Entry<T> {
+        return (self.bits.get() & ADDRESS_MASK) as usize as *mut Entry<T>;
+    }
+
+    /// Whether the entity is still alive, judged by the low 16 bits of its generation
+    ///
+    /// # Safety
+    /// The allocator that made the pointer must still be alive, and must not have
+    /// trimmed the entry since. Call it on the allocator's thread only, while nothing
+    /// frees the entry or writes to it meanwhile
+    #[inline(always)]
+    pub unsafe fn is_live(&self) -> bool {
+        let generation = (*self.entry()).header.generation();
+        let tag_matches = generation as u16 as u64 == self.bits.get() >> TAG_SHIFT;
+
+        #[cfg(debug_assertions)]
+        assert!(
+            !tag_matches || generation == self.generation,
+            "Stale tagged pointer to generation {} matches the tag of generation {}",
+            self.generation,
+            generation
+        );
+
+        return tag_matches;
+    }
+
+    /// The entity, None if it was freed
+    ///
+    /// # Safety
+    /// Same as `is_live`, and the entity must not be written to through any other
+    /// pointer while the reference is alive
+    #[inline(always)]
+    pub unsafe fn get(&self) -> Option<&T> {
+        if !self.is_live() {
+            return None;
+        }
+
+        return Some((*self.entry()).value.assume_init_ref());
+    }
+
+    /// # Safety
+    /// Same as `is_live`, and no other pointer to the entity may be dereferenced while
+    /// the reference is alive
+    #[inline(always)]
+    pub unsafe fn get_mut(&mut self) -> Option<&mut T> {
+        if !self.is_live() {
+            return None;
+        }
+
+        return Some((*self.entry()).value.assume_init_mut());
+    }
+
+    /// Back to a full `EntityPtr`, dead if the tagged pointer was
+    ///
+    /// # Safety
+    /// Same as `is_live`, on the allocator's thread
+    pub unsafe fn untag(self) -> EntityPtr<T> {
+        let entry = &*self.entry();
+        // A live pointer has the slot's generation, a dead one just needs any other
+        let generation = if self.is_live() { entry.header.generation() } else { entry.header.generation().wrapping_sub(1) };
+        return EntityPtr { generation, header: entry.header, index: entry.index, ptr: NonNull::from(entry), marker: PhantomData };
     }
 }
 
+// Liveness of up to 64 handles as the low bits of a word, for `validate_many`. `occupied`
+// must not be empty
+#[inline(always)]
+fn live_bits(generations: &GenerationTable, occupied: &[u64], block: &[GenerationalIndex]) -> u64 {
+    let last = occupied.len() - 1;
+    let mut word = 0;
+    for (bit, handle) in block.iter().enumerate() {
+        let word_index = handle.index / 64;
+        // Slots past the last word are free, so the clamped read is masked off
+        let in_range = (word_index <= last) as u64;
+        let slot_occupied = (unsafe { *occupied.get_unchecked(word_index.min(last)) } >> (handle.index % 64)) & in_range;
+        let matches = generations.matches(handle.index, handle.generation) as u64;
+        word |= (slot_occupied & matches) << bit;
+    }
+
+    return word;
+}
+
 // The following example is a handle based implementation
 // with in-place memory segments, meaning that all entities will be contiguous in memory,
 // which should speed up access for multiple entities, but might be slower when allocating new entities
+//
+// Generations live in their own array next to the values, so liveness checks walk a
+// dense array of 4 byte integers instead of striding over the payloads. The array ends
+// with an always dead sentinel, so checking a handle doesn't branch on its index
 #[derive(Debug, Default)]
 pub struct InPlaceAllocator<T>
 {
-    entries: Vec<InPlaceAllocEntry<T>>,
+    // Note that since MaybeUninit has transparent layout, the values sit back to back with
+    // only the RefCell flag between them, making it in place
+    values: Vec<RefCell<MaybeUninit<T>>>,
+    // Generation of each slot, parallel to `values`
+    generations: GenerationTable,
     free: Vec<usize>,
+    // Which entries hold a value, so live entries can be found without touching dead ones
+    occupied: Bitset,
+    free_sites: FreeSites,
+    owner: OwnerThread,
+    // Set by `with_exact_capacity`: `values` never grows, so slots never move
+    fixed_capacity: bool,
 }
 
-#[derive(Debug)]
-struct InPlaceAllocEntry<T> {
-    // Note that since MaybeUninit has transparent layout, this is the same as having an actual T
-    // inside the struct. Having an array of these is the same as having an array of T,
-    // making it in place
-    value: RefCell<MaybeUninit<T>>,
-    generation: Generation,
+/// Copies the slot layout, generations and free list, so every handle resolves to the
+/// same entry in the copy. The copy is owned by the thread that cloned it
+impl<T: Clone> Clone for InPlaceAllocator<T> {
+    fn clone(&self) -> Self {
+        self.owner.check();
+        let values = self
+            .values
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                let mut value = MaybeUninit::uninit();
+                if self.occupied.contains(index) {
+                    // Occupied slots are initialized
+                    value.write(unsafe { slot.borrow().assume_init_ref().clone() });
+                } else {
+                    #[cfg(feature = "poison")]
+                    crate::poison::poison(&mut value);
+                }
+
+                RefCell::new(value)
+            })
+            .collect();
+
+        return InPlaceAllocator {
+            values,
+            generations: self.generations.clone(),
+            free: self.free.clone(),
+            occupied: self.occupied.clone(),
+            free_sites: self.free_sites.clone(),
+            owner: OwnerThread::default(),
+            fixed_capacity: self.fixed_capacity,
+        };
+    }
+}
+
+/// Two allocators are equal when they have the same slots, generations, live values and
+/// free list, so both hand out the same handles from here on. Free sites are ignored
+impl<T: PartialEq> PartialEq for InPlaceAllocator<T> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.generations != other.generations || self.free != other.free || self.occupied != other.occupied {
+            return false;
+        }
+
+        return self.occupied.iter().all(|index| {
+            // Occupied slots are initialized
+            unsafe { self.values[index].borrow().assume_init_ref() == other.values[index].borrow().assume_init_ref() }
+        });
+    }
+}
+
+impl<T: Hash> InPlaceAllocator<T> {
+    /// Hash of everything `==` compares, fed through `hasher`. Cheaper to exchange than
+    /// the whole allocator when checking two simulations for desyncs
+    pub fn content_hash<H: Hasher>(&self, mut hasher: H) -> u64 {
+        self.owner.check();
+        self.values.len().hash(&mut hasher);
+        for (index, generation) in self.generations.iter().enumerate() {
+            generation.hash(&mut hasher);
+            if self.occupied.contains(index) {
+                unsafe { self.values[index].borrow().assume_init_ref().hash(&mut hasher) };
+            }
+        }
+        self.free.hash(&mut hasher);
+
+        return hasher.finish();
+    }
+}
+
+impl<T: Clone> InPlaceAllocator<T> {
+    /// Replace the contents of `out` with clones of the live values, in slot order, in one
+    /// pass. `out` keeps its capacity, so a buffer reused every frame stops reallocating
+    pub fn copy_live_to(&self, out: &mut Vec<T>) {
+        out.clear();
+        out.extend(self.values().cloned());
+    }
+}
+
+#[cfg(feature = "bytemuck")]
+impl<T: bytemuck::Pod> InPlaceAllocator<T> {
+    /// Write the bytes of the live values back to back into `out`, in slot order, and
+    /// return how many bytes were written. Panics if `out` is shorter than
+    /// `size_of::<T>()` times the number of live values
+    pub fn write_live_bytes(&self, out: &mut [u8]) -> usize {
+        return write_values_as_bytes(self.values(), out);
+    }
 }
 
 impl<T> InPlaceAllocator<T>
 {
-    pub fn new(&mut self, element : T) -> GenerationalIndex {
+    /// Test hook: an allocator whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        return InPlaceAllocator {
+            values: (0..count).map(|_| RefCell::new(MaybeUninit::uninit())).collect(),
+            generations: GenerationTable::from_vec(vec![generation; count]),
+            free: (0..count).rev().collect(),
+            occupied: Bitset::default(),
+            free_sites: FreeSites::default(),
+            owner: OwnerThread::default(),
+            fixed_capacity: false,
+        };
+    }
+
+    /// Allocator with all `capacity` slots allocated up front that never grows: once
+    /// they're all taken `try_insert` fails with `NoFreeSlot` and `insert` panics. Since
+    /// the storage is never reallocated, slots stay at the same address for as long as
+    /// the allocator lives, see `value_ptr`
+    pub fn with_exact_capacity(capacity: usize) -> Self {
+        return InPlaceAllocator {
+            values: (0..capacity).map(|_| RefCell::new(MaybeUninit::uninit())).collect(),
+            generations: GenerationTable::from_vec(vec![0; capacity]),
+            // Popped from the back, lowest slot first
+            free: (0..capacity).rev().collect(),
+            occupied: Bitset::default(),
+            free_sites: FreeSites::default(),
+            owner: OwnerThread::default(),
+            fixed_capacity: true,
+        };
+    }
+
+    /// Whether the allocator was made by `with_exact_capacity`, so its slots never move
+    pub fn is_fixed_capacity(&self) -> bool {
+        return self.fixed_capacity;
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(AllocError::NoFreeSlot(_)) => panic!("All {} slots of a fixed capacity allocator are taken", self.values.len()),
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert_no_grow`")]
+    pub fn try_new_no_grow(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert_no_grow(element);
+    }
+
+    /// Like `try_insert`, but only reuses a free slot and never grows the backing storage,
+    /// so real-time code can allocate without risking a reallocation. Gives the element
+    /// back when there's no free slot
+    pub fn try_insert_no_grow(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
         if self.free.is_empty() {
-            // Construct a new entry
-           let mut new_entry = InPlaceAllocEntry {
-                value: RefCell::new(MaybeUninit::<T>::uninit()),
-                generation: 0,
-            };
-            let new_entry_index = self.entries.len();
+            return Err(AllocError::NoFreeSlot(element));
+        }
 
-            // Initialize it since it will be retrieved from this function
-            new_entry.value.borrow_mut().write(element);
+        return self.try_insert(element);
+    }
 
-            // Add it to the current list of entries
-            self.entries.push(new_entry);
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// allocator can't grow, or is full and made by `with_exact_capacity`
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        if self.free.is_empty() {
+            if self.fixed_capacity {
+                return Err(AllocError::NoFreeSlot(element));
+            }
 
-            return GenerationalIndex {
+            let grown = try_reserve_one(&mut self.values, std::mem::size_of::<Generation>())
+                && self.generations.try_reserve(1);
+            if !grown {
+                return Err(AllocError::OutOfMemory(element));
+            }
+
+            // Add a new slot, initialized since it will be retrieved from this function
+            let new_entry_index = self.values.len();
+            self.values.push(RefCell::new(MaybeUninit::new(element)));
+            self.generations.push(0);
+            self.occupied.insert(new_entry_index);
+
+            return Ok(GenerationalIndex {
                 index: new_entry_index,
                 generation: 0,
-            };
+            });
         }
 
         let next_free = self.free.pop().unwrap();
-        let entry = &mut self.entries[next_free];
 
         // Initialize entry, don't return uninitialized memory
-        entry.value.borrow_mut().write(element);
+        self.values[next_free].get_mut().write(element);
+        self.occupied.insert(next_free);
 
-        return GenerationalIndex {
+        return Ok(GenerationalIndex {
             index: next_free,
-            generation: entry.generation,
-        };
+            generation: self.generations[next_free],
+        });
     }
 
     #[inline(always)]
+    /// False for any handle that doesn't point to a value, out of range ones included
     pub fn is_live(&self, index: &GenerationalIndex) -> bool {
-        return index.generation == self.entries[index.index].generation;
+        return self.generations.matches(index.index, index.generation) && self.occupied.contains(index.index);
     }
 
-    pub fn get(&self, index: &GenerationalIndex) -> &mut T {
-        debug_assert!(
-            self.is_live(index),
-            "Trying to retrieve uninitialized memory"
-        );
+    /// Liveness of a batch of handles, bit `i` of `out` for handle `i`. `out` is
+    /// overwritten. Handles go 64 at a time into one output word, with no branch per
+    /// handle: an out of range index reads the generation sentinel and a clamped
+    /// occupancy word whose bit is masked off. Each handle still costs two scattered
+    /// loads, so over a large arena this runs at about the speed of `is_live` in a loop
+    /// (see the liveness benches); what it adds is the packed result
+    pub fn validate_many(&self, handles: &[GenerationalIndex], out: &mut BitVec) {
+        self.owner.check();
+        out.clear();
+        out.reserve(handles.len());
+        // Never empty, so the clamped read always lands on a word
+        let occupied = match self.occupied.words() {
+            [] => &[0][..],
+            words => words,
+        };
+        let mut blocks = handles.chunks_exact(64);
+        for block in blocks.by_ref() {
+            out.push_word(live_bits(&self.generations, occupied, block), 64);
+        }
+        let rest = blocks.remainder();
+        if !rest.is_empty() {
+            out.push_word(live_bits(&self.generations, occupied, rest), rest.len());
+        }
+    }
 
-        let entry = &self.entries[index.index];
-        return unsafe { entry.value.borrow_mut().as_mut_ptr().as_mut().unwrap() };
+    /// Ok if the handle is live, otherwise what it found at its index instead
+    pub fn validate(&self, index: &GenerationalIndex) -> Result<(), HandleMismatch> {
+        let slot = self.generations.get(index.index).map(|generation| (*generation, self.occupied.contains(index.index)));
+        return validate_handle(index, slot);
     }
 
+    /// In debug builds the arena panics when borrowed from a thread other than the one
+    /// that owns it. `&mut self` methods move ownership to the calling thread on their
+    /// own, call this after moving the arena if the new thread starts with `&self` ones
+    pub fn rebind_owner_thread(&mut self) {
+        self.owner.claim();
+    }
+
+    /// `try_get_mut` that panics instead of returning an error, in release builds too
+    pub fn get(&self, index: &GenerationalIndex) -> RefMut<'_, T> {
+        return self
+            .try_get_mut(index)
+            .unwrap_or_else(|error| panic!("Can't get index {}: {}{}", index.index, error, self.free_sites.describe(index.index)));
+    }
+
+    /// Checked version of `get`: borrows the value through its `RefCell`, and fails with
+    /// `NotLive` or `OutOfBounds` if the handle doesn't point to a value, or
+    /// `AlreadyBorrowed` if the value is mutably borrowed
+    pub fn try_get(&self, index: &GenerationalIndex) -> Result<Ref<'_, T>, AccessError> {
+        self.owner.check();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        let value = self.values[index.index].try_borrow().map_err(|_| AccessError::AlreadyBorrowed)?;
+        // Live slots are initialized
+        return Ok(Ref::map(value, |value| unsafe { value.assume_init_ref() }));
+    }
+
+    /// Mutable version of `try_get`, also fails if the value is borrowed at all
+    pub fn try_get_mut(&self, index: &GenerationalIndex) -> Result<RefMut<'_, T>, AccessError> {
+        self.owner.check();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(Err(mismatch.into()), || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        let value = self.values[index.index].try_borrow_mut().map_err(|_| AccessError::AlreadyBorrowed)?;
+        return Ok(RefMut::map(value, |value| unsafe { value.assume_init_mut() }));
+    }
+
+    /// Direct access when the allocator is borrowed mutably, skipping the RefCell flag.
+    /// None if the handle is dead, the `strict` feature panics instead
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(None, || format!("Trying to access a dead index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        // Live slots are initialized
+        return Some(unsafe { self.values[index.index].get_mut().assume_init_mut() });
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
     pub fn free(&mut self, index: &GenerationalIndex) {
+        if self.try_free(index).is_err() {
+            panic!("Trying to free already unused index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Same as `free`, but fails with `DeadHandle` instead of panicking when the handle
+    /// is dead or out of range, unless the `strict` feature is on
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn try_free(&mut self, index: &GenerationalIndex) -> Result<(), FreeError> {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(Err(FreeError::DeadHandle), || format!("Trying to free already unused index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        drop(self.take(index.index));
+        return Ok(());
+    }
+
+    /// Free the slot and hand its value back, or return None if the handle is dead or out
+    /// of range. The `strict` feature panics on a dead handle instead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        self.owner.claim();
+        if let Err(mismatch) = self.validate(index) {
+            return misuse(None, || format!("Trying to remove already unused index: {}{}", mismatch, self.free_sites.describe(index.index)));
+        }
+
+        return Some(self.take(index.index));
+    }
+
+    // Free a live slot and move its value out
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    fn take(&mut self, index: usize) -> T {
+        self.free_sites.record(index, Location::caller());
+        self.occupied.remove(index);
+        if bump_generation(&mut self.generations[index]) {
+            self.free.push(index);
+        }
+        let slot = self.values[index].get_mut();
+        let value = unsafe { slot.assume_init_read() };
+
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize(slot);
+
+        #[cfg(feature = "poison")]
+        crate::poison::poison(slot);
+
+        return value;
+    }
+
+    /// Iterate over the live entries in slot order. Scans the occupancy bitset, so dead
+    /// slots are skipped 64 at a time
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (GenerationalIndex, &T)> + DoubleEndedIterator + '_ {
+        self.owner.check();
+        return self.occupied.iter().map(move |index| {
+            // Occupied slots are initialized, and nothing keeps a borrow alive between calls
+            let value = unsafe {
+                self.values[index].try_borrow_unguarded().expect("Slot is mutably borrowed").assume_init_ref()
+            };
+
+            (GenerationalIndex { index, generation: self.generations[index] }, value)
+        });
+    }
+
+    /// Iterate over the live values in slot order, without building their handles
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &T> + DoubleEndedIterator + '_ {
+        return self.iter().map(|(_, value)| value);
+    }
+
+    /// Handles of `k` live entries picked uniformly at random without repeats, in slot
+    /// order. Every live entry if there are at most `k`
+    pub fn sample(&self, rng: &mut impl RandomSource, k: usize) -> Vec<GenerationalIndex> {
+        self.owner.check();
+        let ranks = distinct_ranks(rng, k, self.occupied.count());
+        return self
+            .occupied
+            .select(&ranks)
+            .into_iter()
+            .map(|index| GenerationalIndex { index, generation: self.generations[index] })
+            .collect();
+    }
+
+    /// First live entry, in slot order, for which `predicate` returns true
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<(GenerationalIndex, &T)> {
+        return self.iter().find(|(_, value)| predicate(value));
+    }
+
+    /// First non-None result of `f` over the live entries, in slot order, with the handle
+    /// it came from
+    pub fn find_map<R>(&self, mut f: impl FnMut(&T) -> Option<R>) -> Option<(GenerationalIndex, R)> {
+        return self.iter().find_map(|(handle, value)| f(value).map(|result| (handle, result)));
+    }
+
+    /// Mutable version of `values`. Walks every slot and checks the occupancy bitset,
+    /// since the slots can't be borrowed mutably one at a time through the bitset
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut T> + DoubleEndedIterator + '_ {
+        self.owner.claim();
+        let occupied = &self.occupied;
+        return LiveSlots::new(self.values.iter_mut().enumerate(), occupied.count(), move |(index, value)| {
+            // Occupied slots are initialized
+            occupied.contains(index).then(|| unsafe { value.get_mut().assume_init_mut() })
+        });
+    }
+
+    /// Split the slots into `n` contiguous chunks of about the same size, each borrowing
+    /// only its own values mutably. Chunks can be sent to different threads to mutate the
+    /// live entries in parallel. Fewer than `n` chunks come back when there are fewer slots
+    pub fn chunks_mut(&mut self, n: usize) -> Vec<InPlaceChunkMut<'_, T>> {
+        assert!(n > 0, "Can't split the allocator into 0 chunks");
+        self.owner.claim();
+        let chunk_len = self.values.len().div_ceil(n).max(1);
+        let (generations, occupied) = (&self.generations, &self.occupied);
+        return self
+            .values
+            .chunks_mut(chunk_len)
+            .enumerate()
+            .map(|(chunk, values)| InPlaceChunkMut { values, generations, occupied, start: chunk * chunk_len })
+            .collect();
+    }
+
+    /// Snapshot of the live handles in slot order, so callers can free or allocate while
+    /// walking it without invalidating an iterator
+    pub fn handles(&self) -> Vec<GenerationalIndex> {
+        self.owner.check();
+        return self
+            .occupied
+            .iter()
+            .map(|index| GenerationalIndex { index, generation: self.generations[index] })
+            .collect();
+    }
+
+    /// Free slots with their current generation, in the order they will be reused.
+    /// Retired slots are left out, `stats` counts them
+    pub fn iter_free(&self) -> impl Iterator<Item = (usize, Generation)> + '_ {
+        return self.free.iter().rev().map(|index| (*index, self.generations[*index]));
+    }
+
+    /// Raw view of the slots, for iteration the safe API can't express, like SIMD over the
+    /// generations or handing the values to C. Returns the generation of every slot, a
+    /// pointer to the value in slot 0 and the distance in bytes from one value to the next.
+    /// Each value sits behind its `RefCell` flag, so they aren't packed like a `[T]`: the
+    /// value in slot `i` is at `ptr.byte_add(i * stride)`.
+    ///
+    /// Only the slots set in `occupancy` hold a value, the others are uninitialized.
+    /// Reading through the pointer is unsafe: it's only valid until the allocator is
+    /// changed, and a value must not be read while a `try_get_mut` borrow of it is alive
+    pub fn as_raw_parts(&self) -> (&[Generation], *const T, usize) {
+        self.owner.check();
+        let values = match self.values.first() {
+            Some(value) => value.as_ptr() as *const T,
+            None => NonNull::dangling().as_ptr(),
+        };
+        return (&self.generations, values, std::mem::size_of::<RefCell<MaybeUninit<T>>>());
+    }
+
+    /// Pointer to the value behind a live handle, None if the handle is dead. With
+    /// `with_exact_capacity` the slot never moves, so the pointer can be kept for as long
+    /// as the allocator lives; otherwise it's only valid until the next `insert` grows the
+    /// storage. It says nothing about what the slot holds later on: check the handle with
+    /// `is_live` before each use, and don't use it while a `try_get_mut` borrow is alive
+    pub fn value_ptr(&self, index: &GenerationalIndex) -> Option<NonNull<T>> {
+        self.owner.check();
         if !self.is_live(index) {
-            panic!("Trying to free already unused index");
+            return None;
         }
 
-        let index = index.index;
-        self.free.push(index);
-        let entry = &mut self.entries[index];
-        entry.generation += 1;
-        unsafe {
-            entry.value.borrow_mut().assume_init_drop();
+        return NonNull::new(self.values[index.index].as_ptr() as *mut T);
+    }
+
+    /// Which slots hold a value, for use with `as_raw_parts`: slot `i` is occupied when
+    /// bit `i % 64` of word `i / 64` is set. Slots past the last word are free
+    pub fn occupancy(&self) -> &[u64] {
+        return self.occupied.words();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.generations.iter().filter(|generation| **generation == Generation::MAX).count();
+        return AllocatorStats { capacity: self.values.len(), live: self.occupied.count(), free: self.free.len(), retired };
+    }
+
+    /// Panic if the free list or the occupancy bitset are inconsistent, or a slot is
+    /// still borrowed
+    pub fn check_invariants(&self) {
+        assert_eq!(self.values.len(), self.generations.len(), "Every slot needs exactly one generation");
+        check_free_list(self.free.iter().copied(), self.values.len(), |index| self.generations[index]);
+        for (index, value) in self.values.iter().enumerate() {
+            assert!(value.try_borrow_mut().is_ok(), "Slot {} is still borrowed", index);
+        }
+        for index in self.free.iter() {
+            assert!(!self.occupied.contains(*index), "Free slot {} is marked as occupied", index);
         }
+        assert!(self.occupied.iter().all(|index| index < self.values.len()), "Occupied slot out of bounds");
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+}
+
+/// A range of `InPlaceAllocator` slots returned by `chunks_mut`, giving mutable access to
+/// the live entries in that range only
+pub struct InPlaceChunkMut<'a, T> {
+    values: &'a mut [RefCell<MaybeUninit<T>>],
+    generations: &'a [Generation],
+    occupied: &'a Bitset,
+    // Index of the first slot of the chunk in the allocator
+    start: usize,
+}
+
+impl<'a, T> InPlaceChunkMut<'a, T> {
+    /// Slot indices this chunk covers
+    pub fn slots(&self) -> std::ops::Range<usize> {
+        return self.start..self.start + self.values.len();
+    }
+
+    /// Iterate over the live entries of the chunk in slot order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (GenerationalIndex, &mut T)> + '_ {
+        let (start, generations, occupied) = (self.start, self.generations, self.occupied);
+        return self.values.iter_mut().enumerate().filter_map(move |(offset, value)| {
+            let index = start + offset;
+            if !occupied.contains(index) {
+                return None;
+            }
+
+            // Occupied slots are initialized
+            let value = unsafe { value.get_mut().assume_init_mut() };
+            Some((GenerationalIndex { index, generation: generations[index] }, value))
+        });
+    }
+
+    /// Iterate over the live values of the chunk, without building their handles
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        return self.iter_mut().map(|(_, value)| value);
     }
 }
@@ -0,0 +1,88 @@
+/// Saves tagged with a schema version, and the migrations that bring old saves up to date.
+///
+/// `GenerationalIndexArray::to_bytes` only versions its own layout: change the entity
+/// struct and every old save stops decoding. A `VersionedFormat<T>` writes the version of
+/// the entity schema in front of the array, and keeps one migration per older version.
+/// Formats are built from the oldest entity type up: `VersionedFormat::<EnemyV1>::new(1)`,
+/// then one `.migrate(2, |old: &mut EnemyV1| EnemyV2 { .. })` call per change.
+///
+/// Migrations go through `GenerationalIndexArray::map`, so slots, generations and the free
+/// list are kept and handles saved alongside the arena stay valid.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::compact_encoding::{read_varint, write_varint, CompactDecode, CompactEncode};
+use crate::errors::DecodeError;
+use crate::kyren_generational_indices::GenerationalIndexArray;
+
+pub type SchemaVersion = u32;
+
+// Decodes a save written under one older version and migrates it all the way to `T`
+type Loader<T> = Box<dyn Fn(&[u8]) -> Result<GenerationalIndexArray<T>, DecodeError>>;
+
+pub struct VersionedFormat<T> {
+    version: SchemaVersion,
+    loaders: HashMap<SchemaVersion, Loader<T>>,
+}
+
+impl<T> VersionedFormat<T> {
+    /// Format whose current schema is `version`, with no older versions to migrate from
+    pub fn new(version: SchemaVersion) -> Self {
+        return VersionedFormat { version, loaders: HashMap::new() };
+    }
+
+    /// Version new saves are written with
+    pub fn version(&self) -> SchemaVersion {
+        return self.version;
+    }
+
+    /// Versions that `load` accepts, oldest first
+    pub fn supported_versions(&self) -> Vec<SchemaVersion> {
+        let mut versions: Vec<_> = self.loaders.keys().copied().chain([self.version]).collect();
+        versions.sort_unstable();
+        return versions;
+    }
+}
+
+impl<T: CompactDecode + 'static> VersionedFormat<T> {
+    /// Move to schema `version`, whose entities are built from the current ones with
+    /// `migrate`. Saves from every version known so far go through it on load.
+    ///
+    /// Panics if `version` isn't newer than the current one
+    pub fn migrate<U: 'static>(self, version: SchemaVersion, migrate: impl Fn(&mut T) -> U + 'static) -> VersionedFormat<U> {
+        assert!(version > self.version, "Schema version {} isn't newer than {}", version, self.version);
+
+        let migrate = Rc::new(migrate);
+        let mut loaders: HashMap<SchemaVersion, Loader<U>> = HashMap::new();
+        for (old_version, load) in self.loaders {
+            let migrate = migrate.clone();
+            loaders.insert(old_version, Box::new(move |bytes| Ok(load(bytes)?.map(|item| migrate(item)))));
+        }
+        loaders.insert(self.version, Box::new(move |bytes| Ok(GenerationalIndexArray::<T>::from_bytes(bytes)?.map(|item| migrate(item)))));
+
+        return VersionedFormat { version, loaders };
+    }
+
+    /// Decode a save written by `save` under this version or any older one, migrating it
+    /// to the current schema
+    pub fn load(&self, bytes: &[u8]) -> Result<GenerationalIndexArray<T>, DecodeError> {
+        let input = &mut &bytes[..];
+        let version = SchemaVersion::try_from(read_varint(input)?).map_err(|_| DecodeError::Invalid("schema version out of range"))?;
+        if version == self.version {
+            return GenerationalIndexArray::from_bytes(input);
+        }
+
+        let load = self.loaders.get(&version).ok_or(DecodeError::Invalid("no migration from this schema version"))?;
+        return load(input);
+    }
+}
+
+impl<T: CompactEncode> VersionedFormat<T> {
+    /// Encode `array` tagged with the current schema version
+    pub fn save(&self, array: &GenerationalIndexArray<T>) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint(&mut out, self.version as u64);
+        out.extend(array.to_bytes());
+        return out;
+    }
+}
@@ -0,0 +1,332 @@
+/// Arena backed by a memory-mapped file, so its contents survive restarts.
+///
+/// Values are plain old data (`bytemuck::Pod`): any bit pattern is a valid value and
+/// there's nothing to drop, so the slots can live directly in the file and be read back
+/// as they are. Opening the same file again gives back the same slots with the same
+/// generations, so handles saved elsewhere (as two integers) stay valid across runs.
+///
+/// The file is a 64 byte header followed by the entries:
+///
+/// ```text
+/// header   [u8; 8] magic   "GIAMMAP1"
+///          u32     entry size, u32 entry alignment   checked on open
+///          u64     slot count                        slots in use, the rest is spare
+/// entries  u32 generation, u32 occupied, T value     `#[repr(C)]`, like `ReprCArena`
+/// ```
+///
+/// The free list isn't stored, `open` rebuilds it from the occupied flags.
+///
+/// # Crash consistency
+///
+/// Writes go straight to the shared mapping, so if the process crashes the OS still
+/// writes them back and nothing is lost. `flush()` only matters if the machine itself can
+/// go down: it blocks until every dirty page is on disk. Pages are written back in no
+/// particular order, so after a power loss without a `flush()` the file may mix old and
+/// new slots, and a value spanning two pages may be torn. The arena writes a value before
+/// marking its slot occupied and grows the file before bumping the slot count, so a file
+/// that was flushed and then only partially written back still opens, but it can hold
+/// values older than the last ones written. Call `flush()` at the points you want to be
+/// able to restart from.
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+
+use bytemuck::Pod;
+use memmap2::MmapMut;
+
+use crate::errors::AllocError;
+use crate::generational_allocator::{check_free_list, AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{bump_generation, Generation, GenerationalIndex};
+
+const MAGIC: &[u8; 8] = b"GIAMMAP1";
+// Header size, and so the largest alignment a value can have: the mapping is page
+// aligned and the entries start right after the header
+const HEADER_SIZE: usize = 64;
+// Slots added to the file the first time it grows
+const INITIAL_CAPACITY: usize = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Header {
+    magic: [u8; 8],
+    entry_size: u32,
+    entry_align: u32,
+    len: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MmapEntry<T> {
+    generation: Generation,
+    occupied: u32,
+    value: T,
+}
+
+pub struct MmapArena<T: Pod> {
+    file: File,
+    map: MmapMut,
+    // Slots the file has room for, `len` of them are in use
+    capacity: usize,
+    free: Vec<usize>,
+    _values: PhantomData<T>,
+}
+
+impl<T: Pod> MmapArena<T> {
+    const LAYOUT: () = assert!(align_of::<MmapEntry<T>>() <= HEADER_SIZE, "Values can't be aligned to more than 64 bytes");
+
+    /// Open the arena stored at `path`, creating an empty one if the file doesn't exist
+    /// or is empty. Fails with `InvalidData` if the file holds something else, an arena
+    /// of a value type with a different size or alignment, or slots no arena would write
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::LAYOUT;
+
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.set_len(HEADER_SIZE as u64)?;
+            let mut arena = Self::map(file)?;
+            *arena.header_mut() = Header {
+                magic: *MAGIC,
+                entry_size: size_of::<MmapEntry<T>>() as u32,
+                entry_align: align_of::<MmapEntry<T>>() as u32,
+                len: 0,
+            };
+            return Ok(arena);
+        }
+
+        let mut arena = Self::map(file)?;
+        if arena.map.len() < HEADER_SIZE || arena.header().magic != *MAGIC {
+            return Err(invalid_data("not a memory-mapped arena"));
+        }
+        let header = *arena.header();
+        if header.entry_size as usize != size_of::<MmapEntry<T>>() || header.entry_align as usize != align_of::<MmapEntry<T>>() {
+            return Err(invalid_data("arena was written for a value type with a different layout"));
+        }
+        if header.len > arena.capacity as u64 {
+            return Err(invalid_data("slot count is larger than the file"));
+        }
+        for entry in arena.entries() {
+            if entry.occupied > 1 {
+                return Err(invalid_data("slot is neither occupied nor free"));
+            }
+            // Freeing it would have to move past the last generation
+            if entry.occupied == 1 && entry.generation == Generation::MAX {
+                return Err(invalid_data("occupied slot is at the retired generation"));
+            }
+        }
+
+        // Retired slots stay out of the free list, same as when they were freed
+        arena.free = (0..header.len as usize)
+            .rev()
+            .filter(|index| {
+                let entry = &arena.entries()[*index];
+                entry.occupied == 0 && entry.generation != Generation::MAX
+            })
+            .collect();
+        return Ok(arena);
+    }
+
+    fn map(file: File) -> io::Result<Self> {
+        // Nothing else is expected to resize or write the file while it's mapped, same as
+        // with any other file the program owns
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        let capacity = map.len().saturating_sub(HEADER_SIZE) / size_of::<MmapEntry<T>>();
+        return Ok(MmapArena { file, map, capacity, free: Vec::new(), _values: PhantomData });
+    }
+
+    fn header(&self) -> &Header {
+        return unsafe { &*(self.map.as_ptr() as *const Header) };
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        return unsafe { &mut *(self.map.as_mut_ptr() as *mut Header) };
+    }
+
+    fn len(&self) -> usize {
+        return self.header().len as usize;
+    }
+
+    fn entries(&self) -> &[MmapEntry<T>] {
+        // The mapping is page aligned and the header is padded to a multiple of the entry
+        // alignment. Every byte of the file is initialized, and every bit pattern is a
+        // valid entry since the header words are integers and `T` is `Pod`
+        return unsafe { std::slice::from_raw_parts(self.map.as_ptr().add(HEADER_SIZE) as *const MmapEntry<T>, self.len()) };
+    }
+
+    fn entries_mut(&mut self) -> &mut [MmapEntry<T>] {
+        let len = self.len();
+        return unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr().add(HEADER_SIZE) as *mut MmapEntry<T>, len) };
+    }
+
+    /// Double the file and map it again
+    fn grow(&mut self) -> io::Result<()> {
+        let capacity = (self.capacity * 2).max(INITIAL_CAPACITY);
+        self.file.set_len((HEADER_SIZE + capacity * size_of::<MmapEntry<T>>()) as u64)?;
+        self.map = unsafe { MmapMut::map_mut(&self.file)? };
+        self.capacity = capacity;
+        return Ok(());
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Couldn't grow the arena file while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the file
+    /// can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                if self.len() == self.capacity && self.grow().is_err() {
+                    return Err(AllocError::OutOfMemory(element));
+                }
+                // The new slot is past the end of the old file, so it's all zeroes: free,
+                // at generation 0
+                self.header_mut().len += 1;
+                self.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries_mut()[index];
+        entry.value = element;
+        entry.occupied = 1;
+
+        return Ok(GenerationalIndex { index, generation: entry.generation });
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.entries().get(index.index) {
+            Some(entry) => entry.generation == index.generation && entry.occupied == 1,
+            None => false,
+        };
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return Some(&self.entries()[index.index].value);
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return Some(&mut self.entries_mut()[index.index].value);
+    }
+
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        if self.remove(index).is_none() {
+            panic!("Trying to free already unused index");
+        }
+    }
+
+    /// Free the slot and hand back a copy of its value, None if the handle is dead. The
+    /// value's bytes stay in the file unless `zero-on-free` is on
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        let entry = &mut self.entries_mut()[index.index];
+        entry.occupied = 0;
+        let value = entry.value;
+        // The file outlives the process, secrets would stay on disk
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize_bytes(bytemuck::bytes_of_mut(&mut entry.value));
+        if bump_generation(&mut entry.generation) {
+            self.free.push(index.index);
+        }
+        return Some(value);
+    }
+
+    /// Block until every change made so far is written to the file. See the module docs
+    /// for what can be lost without it
+    pub fn flush(&self) -> io::Result<()> {
+        return self.map.flush();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.entries().iter().filter(|entry| entry.generation == Generation::MAX).count();
+        let live = self.entries().iter().filter(|entry| entry.occupied == 1).count();
+        return AllocatorStats { capacity: self.len(), live, free: self.free.len(), retired };
+    }
+
+    /// Panic if the free list is inconsistent or a free slot is still marked as occupied
+    pub fn check_invariants(&self) {
+        assert!(self.len() <= self.capacity, "Slot count is larger than the file");
+        check_free_list(self.free.iter().copied(), self.len(), |index| self.entries()[index].generation);
+        for index in self.free.iter() {
+            assert_eq!(self.entries()[*index].occupied, 0, "Free slot {} is marked as occupied", index);
+        }
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+}
+
+fn invalid_data(reason: &'static str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, reason);
+}
+
+impl<T: Pod> GenerationalAllocator<T> for MmapArena<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return MmapArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        MmapArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return MmapArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return MmapArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return MmapArena::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return MmapArena::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return MmapArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        MmapArena::check_invariants(self);
+    }
+}
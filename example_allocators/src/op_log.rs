@@ -0,0 +1,330 @@
+/// Record every change made to an arena, and replay it into an identical one.
+///
+/// Allocator bugs reported from the field usually come down to a particular sequence of
+/// allocations and frees. `RecordingArena` wraps a `GenerationalIndexArray` and appends
+/// each `insert`, `free`, `remove` and `replace` to an `OpLog`, along with the handle it
+/// touched and an encoded snapshot of the new value. The log can be saved with
+/// `OpLog::to_bytes`, attached to a bug report, and turned back into the same arena,
+/// handles and free list included, with `replay`. Replays also make deterministic test
+/// fixtures: record a scenario once and check the arena it produces.
+///
+/// Values are snapshotted with `CompactEncode` by default, or with an encoder passed to
+/// `with_encoder` for payloads that don't implement it. There's no plain `get_mut`, since
+/// the log couldn't see what it changes: `update` snapshots the value after changing it,
+/// and `GenerationalAllocator::get_mut` hands out a guard that does it when dropped.
+use std::ops::{Deref, DerefMut};
+
+use crate::compact_encoding::{read_len, read_varint, take, write_varint, CompactDecode, CompactEncode};
+use crate::errors::DecodeError;
+use crate::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{Generation, GenerationalIndex, GenerationalIndexArray};
+
+// Bumped whenever the layout written by `OpLog::to_bytes` changes
+const LOG_VERSION: u8 = 1;
+
+type Encoder<T> = Box<dyn Fn(&T, &mut Vec<u8>)>;
+
+/// One recorded change, with the handle it was made through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// A value was allocated and got `handle`
+    Allocate { handle: GenerationalIndex, payload: Vec<u8> },
+    /// The value at `handle` was freed or removed
+    Free { handle: GenerationalIndex },
+    /// The value at `handle` was overwritten with `payload`
+    Replace { handle: GenerationalIndex, payload: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpLog {
+    ops: Vec<Op>,
+}
+
+impl OpLog {
+    pub fn ops(&self) -> &[Op] {
+        return &self.ops;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.ops.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.ops.is_empty();
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![LOG_VERSION];
+        write_varint(&mut out, self.ops.len() as u64);
+        for op in self.ops.iter() {
+            let (tag, handle, payload) = match op {
+                Op::Allocate { handle, payload } => (0, handle, Some(payload)),
+                Op::Free { handle } => (1, handle, None),
+                Op::Replace { handle, payload } => (2, handle, Some(payload)),
+            };
+            out.push(tag);
+            write_varint(&mut out, handle.get_index() as u64);
+            write_varint(&mut out, handle.get_generation() as u64);
+            if let Some(payload) = payload {
+                write_varint(&mut out, payload.len() as u64);
+                out.extend_from_slice(payload);
+            }
+        }
+
+        return out;
+    }
+
+    pub fn from_bytes(mut input: &[u8]) -> Result<Self, DecodeError> {
+        let input = &mut input;
+        let version = u8::decode(input)?;
+        if version != LOG_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let count = read_len(input)?;
+        let mut ops = Vec::with_capacity(count.min(input.len()));
+        for _ in 0..count {
+            let tag = u8::decode(input)?;
+            let index = read_len(input)?;
+            let generation = Generation::try_from(read_varint(input)?).map_err(|_| DecodeError::Invalid("generation out of range"))?;
+            let handle = GenerationalIndex { index, generation };
+            ops.push(match tag {
+                0 => Op::Allocate { handle, payload: read_payload(input)? },
+                1 => Op::Free { handle },
+                2 => Op::Replace { handle, payload: read_payload(input)? },
+                _ => return Err(DecodeError::Invalid("unknown operation")),
+            });
+        }
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        return Ok(OpLog { ops });
+    }
+}
+
+fn read_payload(input: &mut &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let len = read_len(input)?;
+    return Ok(take(input, len)?.to_vec());
+}
+
+/// Rebuild the arena a log was recorded from, decoding payloads with `CompactDecode`
+pub fn replay<T: CompactDecode>(log: &OpLog) -> Result<GenerationalIndexArray<T>, DecodeError> {
+    return replay_with(log, |mut payload| {
+        let value = T::decode(&mut payload)?;
+        if !payload.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(value)
+    });
+}
+
+/// Same as `replay`, decoding payloads with `decode`, e.g. the counterpart of an encoder
+/// given to `with_encoder`. Fails if the log doesn't match what the arena does: an
+/// allocation that gets a different handle than the recorded one, or a free or replace
+/// through a dead handle
+pub fn replay_with<T>(
+    log: &OpLog,
+    mut decode: impl FnMut(&[u8]) -> Result<T, DecodeError>,
+) -> Result<GenerationalIndexArray<T>, DecodeError> {
+    let mut slots = GenerationalIndexArray::default();
+    for op in log.ops.iter() {
+        match op {
+            Op::Allocate { handle, payload } => {
+                if slots.insert(decode(payload)?) != *handle {
+                    return Err(DecodeError::Invalid("replayed allocation got a different handle"));
+                }
+            }
+            // Checked up front, the `strict` feature would panic on a dead handle
+            Op::Free { handle } => {
+                if !slots.contains(handle) {
+                    return Err(DecodeError::Invalid("log frees a dead handle"));
+                }
+                slots.remove(handle);
+            }
+            Op::Replace { handle, payload } => {
+                let value = decode(payload)?;
+                if !slots.contains(handle) {
+                    return Err(DecodeError::Invalid("log replaces a dead handle"));
+                }
+                *slots.get_mut(handle).unwrap() = value;
+            }
+        }
+    }
+
+    return Ok(slots);
+}
+
+pub struct RecordingArena<T> {
+    slots: GenerationalIndexArray<T>,
+    log: OpLog,
+    encoder: Encoder<T>,
+}
+
+impl<T: CompactEncode> Default for RecordingArena<T> {
+    fn default() -> Self {
+        return Self::with_encoder(|value: &T, out: &mut Vec<u8>| value.encode(out));
+    }
+}
+
+impl<T> RecordingArena<T> {
+    /// Empty arena that snapshots values with `encoder` instead of `CompactEncode`
+    pub fn with_encoder(encoder: impl Fn(&T, &mut Vec<u8>) + 'static) -> Self {
+        return RecordingArena { slots: GenerationalIndexArray::default(), log: OpLog::default(), encoder: Box::new(encoder) };
+    }
+
+    /// The changes recorded so far
+    pub fn log(&self) -> &OpLog {
+        return &self.log;
+    }
+
+    /// The recorded array
+    pub fn array(&self) -> &GenerationalIndexArray<T> {
+        return &self.slots;
+    }
+
+    fn snapshot(&self, value: &T) -> Vec<u8> {
+        let mut payload = Vec::new();
+        (self.encoder)(value, &mut payload);
+        return payload;
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        let payload = self.snapshot(&element);
+        let handle = self.slots.insert(element);
+        self.log.ops.push(Op::Allocate { handle: handle.clone(), payload });
+        return handle;
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        self.slots.free(index);
+        self.log.ops.push(Op::Free { handle: index.clone() });
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        let value = self.slots.remove(index)?;
+        self.log.ops.push(Op::Free { handle: index.clone() });
+        return Some(value);
+    }
+
+    /// Overwrite the value and return the old one, None if the handle is dead
+    pub fn replace(&mut self, index: &GenerationalIndex, value: T) -> Option<T> {
+        let payload = self.snapshot(&value);
+        let old = std::mem::replace(self.slots.get_mut(index)?, value);
+        self.log.ops.push(Op::Replace { handle: index.clone(), payload });
+        return Some(old);
+    }
+
+    /// Change the value in place and record the result. Returns false if the handle is dead
+    pub fn update(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T)) -> bool {
+        let Some(value) = self.slots.get_mut(index) else { return false };
+        f(value);
+        let payload = self.snapshot(self.slots.get(index).unwrap());
+        self.log.ops.push(Op::Replace { handle: index.clone(), payload });
+        return true;
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.slots.contains(index);
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return self.slots.get(index);
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.slots.stats();
+    }
+
+    pub fn check_invariants(&self) {
+        self.slots.check_invariants();
+    }
+}
+
+/// Mutable access through `GenerationalAllocator::get_mut`. The value is recorded when
+/// the guard is dropped, like `update` does
+pub struct RecordedMut<'a, T> {
+    arena: &'a mut RecordingArena<T>,
+    index: GenerationalIndex,
+}
+
+impl<T> Deref for RecordedMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return self.arena.slots.get(&self.index).expect("Live slot without a value");
+    }
+}
+
+impl<T> DerefMut for RecordedMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return self.arena.slots.get_mut(&self.index).expect("Live slot without a value");
+    }
+}
+
+impl<T> Drop for RecordedMut<'_, T> {
+    fn drop(&mut self) {
+        let payload = self.arena.snapshot(&**self);
+        self.arena.log.ops.push(Op::Replace { handle: self.index.clone(), payload });
+    }
+}
+
+impl<T> GenerationalAllocator<T> for RecordingArena<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = RecordedMut<'a, T> where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return RecordingArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        RecordingArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return RecordingArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return RecordingArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return RecordingArena::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<RecordedMut<'_, T>> {
+        if !RecordingArena::is_live(self, index) {
+            return None;
+        }
+
+        return Some(RecordedMut { arena: self, index: index.clone() });
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    // Recorded like `update`, since the value may have changed
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut result = None;
+        self.update(index, |value| result = Some(f(value)));
+        return result;
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return RecordingArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        RecordingArena::check_invariants(self);
+    }
+}
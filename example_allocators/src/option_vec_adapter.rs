@@ -0,0 +1,200 @@
+/// Generations on top of an existing `Vec<Option<T>>`, to move old code over gradually.
+///
+/// Plenty of codebases already keep entities in a `Vec<Option<T>>` with a free list next
+/// to it and pass raw indices around. `OptionVecAdapter` takes that vector as it is, adds
+/// a generation per slot and implements `GenerationalAllocator`, so new code can use
+/// handles while old code keeps indexing the same storage through `slots`. Raw indices
+/// are turned into handles with `handle_at` as call sites get migrated, and `into_vec`
+/// gives the vector back untouched.
+use std::panic::Location;
+
+use crate::free_sites::FreeSites;
+use crate::generational_allocator::{check_free_list, AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{bump_generation, Generation, GenerationalIndex};
+
+pub struct OptionVecAdapter<T> {
+    slots: Vec<Option<T>>,
+    // Generation of each slot, parallel to `slots`
+    generations: Vec<Generation>,
+    free: Vec<usize>,
+    free_sites: FreeSites,
+}
+
+impl<T> Default for OptionVecAdapter<T> {
+    fn default() -> Self {
+        return Self::from_vec(Vec::new());
+    }
+}
+
+impl<T> OptionVecAdapter<T> {
+    /// Test hook: an adapter whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        let mut adapter = Self::from_vec((0..count).map(|_| None).collect());
+        adapter.generations.fill(generation);
+        return adapter;
+    }
+
+    /// Adopt `slots`, with every empty slot free, lowest index reused first. Every slot
+    /// starts at generation 0
+    pub fn from_vec(slots: Vec<Option<T>>) -> Self {
+        let free = (0..slots.len()).rev().filter(|index| slots[*index].is_none()).collect();
+        return Self::from_parts(slots, free);
+    }
+
+    /// Adopt `slots` and the free list that came with them, which is popped from the
+    /// back like a stack. Panics if the free list names a full slot, names one twice or
+    /// misses an empty one
+    pub fn from_parts(slots: Vec<Option<T>>, free: Vec<usize>) -> Self {
+        let generations = vec![0; slots.len()];
+        let adapter = OptionVecAdapter { slots, generations, free, free_sites: FreeSites::default() };
+        adapter.check_invariants();
+        return adapter;
+    }
+
+    /// Give the vector back, dropping the generations. Handles into it are meaningless after
+    pub fn into_vec(self) -> Vec<Option<T>> {
+        return self.slots;
+    }
+
+    /// The vector as old code expects it, to index it directly
+    pub fn slots(&self) -> &[Option<T>] {
+        return &self.slots;
+    }
+
+    /// Handle to the value at a raw index, for code still passing indices around. None
+    /// if the slot is empty
+    pub fn handle_at(&self, index: usize) -> Option<GenerationalIndex> {
+        self.slots.get(index)?.as_ref()?;
+        return Some(GenerationalIndex { index, generation: self.generations[index] });
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Some(element);
+                index
+            }
+            None => {
+                self.slots.push(Some(element));
+                self.generations.push(0);
+                self.slots.len() - 1
+            }
+        };
+
+        return GenerationalIndex { index, generation: self.generations[index] };
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.slots.get(index.index) {
+            Some(slot) => slot.is_some() && self.generations[index.index] == index.generation,
+            None => false,
+        };
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.slots[index.index].as_ref();
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.slots[index.index].as_mut();
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        if self.remove(index).is_none() {
+            panic!("Trying to free already unused index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        if bump_generation(&mut self.generations[index.index]) {
+            self.free.push(index.index);
+        }
+        return self.slots[index.index].take();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.generations.iter().filter(|generation| **generation == Generation::MAX).count();
+        let live = self.slots.iter().filter(|slot| slot.is_some()).count();
+        return AllocatorStats { capacity: self.slots.len(), live, free: self.free.len(), retired };
+    }
+
+    /// Panic if the free list is inconsistent with the slots
+    pub fn check_invariants(&self) {
+        assert_eq!(self.generations.len(), self.slots.len(), "Every slot needs exactly one generation");
+        check_free_list(self.free.iter().copied(), self.slots.len(), |index| self.generations[index]);
+        for index in self.free.iter() {
+            assert!(self.slots[*index].is_none(), "Free slot {} holds a value", index);
+        }
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+}
+
+impl<T> GenerationalAllocator<T> for OptionVecAdapter<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return OptionVecAdapter::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        OptionVecAdapter::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return OptionVecAdapter::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return OptionVecAdapter::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return OptionVecAdapter::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return OptionVecAdapter::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return OptionVecAdapter::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        OptionVecAdapter::check_invariants(self);
+    }
+}
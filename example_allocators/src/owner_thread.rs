@@ -0,0 +1,48 @@
+/// Debug-only check that an arena is used from the thread that created it.
+///
+/// The `RefCell` based arenas aren't `Sync`, but unsafe code can still smuggle a
+/// reference to another thread. In debug builds these arenas stamp the creating thread
+/// and panic when a `&self` method like `get` runs somewhere else. `&mut self` methods
+/// already have exclusive access, so they claim the arena for the calling thread instead
+/// of checking. In release builds the stamp is empty and the check compiles away.
+#[cfg(debug_assertions)]
+use std::thread::{self, ThreadId};
+
+#[cfg(debug_assertions)]
+#[derive(Debug, Clone)]
+pub(crate) struct OwnerThread(ThreadId);
+
+#[cfg(not(debug_assertions))]
+#[derive(Debug, Clone)]
+pub(crate) struct OwnerThread {}
+
+impl Default for OwnerThread {
+    fn default() -> Self {
+        #[cfg(debug_assertions)]
+        return OwnerThread(thread::current().id());
+
+        #[cfg(not(debug_assertions))]
+        return OwnerThread {};
+    }
+}
+
+impl OwnerThread {
+    /// Panic if the current thread isn't the one that owns the arena
+    #[inline(always)]
+    #[track_caller]
+    pub(crate) fn check(&self) {
+        #[cfg(debug_assertions)]
+        if self.0 != thread::current().id() {
+            panic!(
+                "Arena owned by thread {:?} was accessed from thread {:?}. These arenas are not thread safe",
+                self.0,
+                thread::current().id()
+            );
+        }
+    }
+
+    /// Make the current thread the owner
+    pub(crate) fn claim(&mut self) {
+        *self = OwnerThread::default();
+    }
+}
@@ -0,0 +1,18 @@
+/// Helpers for the `poison` feature.
+///
+/// When a slot is freed its bytes are overwritten with `POISON_BYTE`, so reading a
+/// freed slot through an unchecked path shows a recognizable pattern instead of
+/// whatever the dropped value left behind. Whether a slot was freed is checked with the
+/// allocator's own liveness bit, never by looking for the pattern: the bytes include
+/// padding, and a live value may well be all `0xDD`.
+use std::mem::{size_of, MaybeUninit};
+
+pub const POISON_BYTE: u8 = 0xDD;
+
+/// Overwrite every byte of `slot` with `POISON_BYTE`. The slot must not hold a live value.
+#[inline]
+pub(crate) fn poison<T>(slot: &mut MaybeUninit<T>) {
+    unsafe {
+        std::ptr::write_bytes(slot.as_mut_ptr() as *mut u8, POISON_BYTE, size_of::<T>());
+    }
+}
@@ -0,0 +1,271 @@
+/// Arena that keeps freed values around to reuse their heap allocations.
+///
+/// Freeing an `Entity { name: String, .. }` in the other arenas drops the `String`, and
+/// the next allocation builds a new one, so a churning arena keeps asking the global
+/// allocator for the same buffers over and over. Here `free` only calls
+/// `Recycle::reset` on the value and leaves it in its slot; `insert_with` then hands that
+/// value, with its capacity intact, to an initializer instead of constructing a new one.
+use std::panic::Location;
+
+use crate::errors::AllocError;
+use crate::fault_injection::try_reserve_one;
+use crate::free_sites::FreeSites;
+use crate::generational_allocator::{check_free_list, AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{bump_generation, Generation, GenerationalIndex};
+
+/// Values that can be cleared for reuse without giving their memory back
+pub trait Recycle {
+    /// Bring the value back to a blank state, keeping whatever capacity it owns
+    fn reset(&mut self);
+}
+
+impl Recycle for String {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Recycle for Vec<T> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+struct RecyclingSlot<T> {
+    // Live value, or the reset leftovers of the last one while the slot is free
+    value: Option<T>,
+    generation: Generation,
+    live: bool,
+}
+
+pub struct RecyclingArena<T: Recycle> {
+    slots: Vec<RecyclingSlot<T>>,
+    free: Vec<usize>,
+    free_sites: FreeSites,
+}
+
+impl<T: Recycle> Default for RecyclingArena<T> {
+    fn default() -> Self {
+        return RecyclingArena { slots: Vec::new(), free: Vec::new(), free_sites: FreeSites::default() };
+    }
+}
+
+impl<T: Recycle> RecyclingArena<T> {
+    /// Test hook: an arena whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        return RecyclingArena {
+            slots: (0..count).map(|_| RecyclingSlot { value: None, generation, live: false }).collect(),
+            free: (0..count).rev().collect(),
+            free_sites: FreeSites::default(),
+        };
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    /// Store `element`, dropping the recycled value of the slot if there was one
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        let index = match self.claim_slot() {
+            Some(index) => index,
+            None => return Err(AllocError::OutOfMemory(element)),
+        };
+
+        self.slots[index].value = Some(element);
+        return Ok(self.activate(index));
+    }
+
+    #[deprecated(note = "renamed to `insert_with`")]
+    pub fn new_with(&mut self, init: impl FnOnce(&mut T)) -> GenerationalIndex
+    where
+        T: Default,
+    {
+        return self.insert_with(init);
+    }
+
+    /// Allocate by filling in a recycled value with `init`. The value passed to `init`
+    /// was reset by `Recycle::reset`, or is `T::default()` if no freed value is available
+    pub fn insert_with(&mut self, init: impl FnOnce(&mut T)) -> GenerationalIndex
+    where
+        T: Default,
+    {
+        let index = match self.claim_slot() {
+            Some(index) => index,
+            None => panic!("Out of memory while allocating a new entry"),
+        };
+
+        init(self.slots[index].value.get_or_insert_with(T::default));
+        return self.activate(index);
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.slots.get(index.index) {
+            Some(slot) => slot.live && slot.generation == index.generation,
+            None => false,
+        };
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.slots[index.index].value.as_ref();
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.slots[index.index].value.as_mut();
+    }
+
+    /// Reset the value and keep it in the slot for the next `insert_with`
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        if !self.is_live(index) {
+            panic!("Trying to free already unused index{}", self.free_sites.describe(index.index));
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        let slot = &mut self.slots[index.index];
+        slot.live = false;
+        if let Some(value) = slot.value.as_mut() {
+            value.reset();
+        }
+        if bump_generation(&mut slot.generation) {
+            self.free.push(index.index);
+        } else {
+            // Retired slots are never reused, so there's nothing to keep the value for
+            slot.value = None;
+        }
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead. The value leaves
+    /// with its capacity, so the slot has nothing left to recycle
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        let slot = &mut self.slots[index.index];
+        slot.live = false;
+        let value = slot.value.take();
+        if bump_generation(&mut slot.generation) {
+            self.free.push(index.index);
+        }
+
+        return value;
+    }
+
+    /// Drop every recycled value held by free slots, giving their memory back
+    pub fn release_recycled(&mut self) {
+        for slot in self.slots.iter_mut().filter(|slot| !slot.live) {
+            slot.value = None;
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.slots.iter().filter(|slot| slot.generation == Generation::MAX).count();
+        let live = self.slots.iter().filter(|slot| slot.live).count();
+        return AllocatorStats { capacity: self.slots.len(), live, free: self.free.len(), retired };
+    }
+
+    /// Panic if the free list is inconsistent or a free slot is marked as live
+    pub fn check_invariants(&self) {
+        check_free_list(self.free.iter().copied(), self.slots.len(), |index| self.slots[index].generation);
+        for index in self.free.iter() {
+            assert!(!self.slots[*index].live, "Free slot {} is marked as live", index);
+        }
+        for (index, slot) in self.slots.iter().enumerate() {
+            assert!(!slot.live || slot.value.is_some(), "Live slot {} holds no value", index);
+        }
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+
+    // Free slot to fill, growing the arena if needed. None if it can't grow
+    fn claim_slot(&mut self) -> Option<usize> {
+        if let Some(index) = self.free.pop() {
+            return Some(index);
+        }
+        if !try_reserve_one(&mut self.slots, 0) {
+            return None;
+        }
+
+        self.slots.push(RecyclingSlot { value: None, generation: 0, live: false });
+        return Some(self.slots.len() - 1);
+    }
+
+    fn activate(&mut self, index: usize) -> GenerationalIndex {
+        let slot = &mut self.slots[index];
+        slot.live = true;
+        return GenerationalIndex { index, generation: slot.generation };
+    }
+}
+
+impl<T: Recycle> GenerationalAllocator<T> for RecyclingArena<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return RecyclingArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        RecyclingArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return RecyclingArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return RecyclingArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return RecyclingArena::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return RecyclingArena::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return RecyclingArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        RecyclingArena::check_invariants(self);
+    }
+}
@@ -0,0 +1,121 @@
+/// Self-contained buffers for moving an arena to another process.
+///
+/// `to_bytes` squeezes an arena as small as it goes, but has to be decoded front to back.
+/// `GenerationalIndexArray::export_relocatable` writes one contiguous buffer with fixed
+/// size tables instead, where every reference is an offset from the start of the buffer,
+/// never a pointer. The buffer can be sent over a socket, written to shared memory or
+/// copied anywhere else, and slot `i` is still found at the same place:
+///
+/// ```text
+/// header      u8 version, 3 zero bytes, u32 slot count, u32 free count, u32 live count
+/// slots       per slot: u32 generation, u32 live, u32 value offset, u32 value length
+/// free list   u32 per free slot, in reuse order
+/// values      `CompactEncode` bytes of each live value, pointed at by its slot
+/// ```
+///
+/// Every word is little endian, dead slots have offset and length 0. Handles stay valid
+/// across `import_relocatable`, like with `from_bytes`. `LazyArena` loads the same
+/// buffers but only decodes a value the first time it's read.
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::compact_encoding::CompactDecode;
+use crate::errors::DecodeError;
+use crate::kyren_generational_indices::Generation;
+
+// Bumped whenever the layout above changes
+pub(crate) const RELOCATABLE_VERSION: u8 = 1;
+pub(crate) const HEADER_SIZE: usize = 4 * 4;
+pub(crate) const SLOT_SIZE: usize = 4 * 4;
+
+/// Append `value` to `out` as a little endian u32
+pub(crate) fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Overwrite the u32 at `offset`, to fill in a table entry once its value is written
+pub(crate) fn set_u32(out: &mut [u8], offset: usize, value: u32) {
+    out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn u32_at(buffer: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    let bytes = buffer.get(offset..offset + 4).ok_or(DecodeError::UnexpectedEnd)?;
+    return Ok(u32::from_le_bytes(bytes.try_into().unwrap()));
+}
+
+/// Offset or count that has to fit in the u32 fields of the layout
+pub(crate) fn to_u32(value: usize) -> u32 {
+    return u32::try_from(value).expect("Relocatable buffers are limited to 4 GiB");
+}
+
+/// One slot of the slot table
+pub(crate) struct SlotRecord {
+    pub generation: Generation,
+    // Where the encoded value is in the buffer, None for a dead slot
+    pub value: Option<Range<usize>>,
+}
+
+/// Everything in a buffer except the values themselves
+pub(crate) struct Tables {
+    pub slots: Vec<SlotRecord>,
+    pub free: VecDeque<usize>,
+    pub live: usize,
+}
+
+/// Read the header, slot table and free list, checking that every value range lies in
+/// the value area. The free list is only checked against the slots once the array is built
+pub(crate) fn read_tables(buffer: &[u8]) -> Result<Tables, DecodeError> {
+    let version = *buffer.first().ok_or(DecodeError::UnexpectedEnd)?;
+    if version != RELOCATABLE_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let len = u32_at(buffer, 4)? as usize;
+    let free_len = u32_at(buffer, 8)? as usize;
+    let live = u32_at(buffer, 12)? as usize;
+    let free_start = HEADER_SIZE + len * SLOT_SIZE;
+    let values_start = free_start + free_len * 4;
+    if values_start > buffer.len() {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+
+    let mut slots = Vec::with_capacity(len);
+    for index in 0..len {
+        let record = HEADER_SIZE + index * SLOT_SIZE;
+        let generation = u32_at(buffer, record)?;
+        let value = match u32_at(buffer, record + 4)? {
+            0 => None,
+            1 if generation == Generation::MAX => return Err(DecodeError::Invalid("retired slot holds a value")),
+            1 => {
+                let offset = u32_at(buffer, record + 8)? as usize;
+                let value_len = u32_at(buffer, record + 12)? as usize;
+                if offset < values_start || offset + value_len > buffer.len() {
+                    return Err(DecodeError::Invalid("value offset outside the value area"));
+                }
+                Some(offset..offset + value_len)
+            }
+            _ => return Err(DecodeError::Invalid("slot live flag is neither 0 nor 1")),
+        };
+        slots.push(SlotRecord { generation, value });
+    }
+
+    let free = (0..free_len)
+        .map(|position| u32_at(buffer, free_start + position * 4).map(|index| index as usize))
+        .collect::<Result<VecDeque<_>, _>>()?;
+    if slots.iter().filter(|slot| slot.value.is_some()).count() != live {
+        return Err(DecodeError::Invalid("live count doesn't match the slots"));
+    }
+
+    return Ok(Tables { slots, free, live });
+}
+
+/// Decode the value stored at `range`, which has to be used up exactly
+pub(crate) fn decode_value<T: CompactDecode>(buffer: &[u8], range: Range<usize>) -> Result<T, DecodeError> {
+    let mut value = &buffer[range];
+    let item = T::decode(&mut value)?;
+    if !value.is_empty() {
+        return Err(DecodeError::Invalid("value is shorter than its recorded length"));
+    }
+
+    return Ok(item);
+}
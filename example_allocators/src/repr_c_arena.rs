@@ -0,0 +1,256 @@
+/// Arena with a fixed, documented entry layout, for sharing slots with C or GPU code.
+///
+/// Rust is free to reorder the fields of the other arenas' entries, so their memory
+/// can't be handed to code written against a struct definition. `ReprCArena` stores
+/// plain `Copy` values in `#[repr(C)]` entries laid out as:
+///
+/// ```text
+/// offset 0          u32  generation   same value the handles carry
+/// offset 4          u32  occupied     1 while the slot holds a value, 0 otherwise
+/// offset 8 or more  T    value        rounded up to T's alignment
+/// ```
+///
+/// which in C reads `struct { uint32_t generation; uint32_t occupied; T value; }`.
+/// `entries()` exposes the array as is, so it can be copied into a buffer or passed
+/// through FFI. Free slots keep whatever bytes their last value left behind, unless the
+/// `zero-on-free` feature is on.
+use std::mem::{align_of, offset_of, size_of, MaybeUninit};
+use std::panic::Location;
+
+use crate::errors::AllocError;
+use crate::fault_injection::try_reserve_one;
+use crate::free_sites::FreeSites;
+use crate::generational_allocator::{check_free_list, AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{bump_generation, Generation, GenerationalIndex};
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ReprCEntry<T: Copy> {
+    pub generation: u32,
+    pub occupied: u32,
+    // Only initialized while `occupied` is 1
+    value: MaybeUninit<T>,
+}
+
+/// Offset of the value in an entry: right after the two header words, rounded up to the
+/// value's alignment
+pub const fn value_offset<T>() -> usize {
+    let header = 2 * size_of::<u32>();
+    return header.div_ceil(align_of::<T>()) * align_of::<T>();
+}
+
+impl<T: Copy> ReprCEntry<T> {
+    // Evaluated for every `T` the arena is used with, so a layout that doesn't match the
+    // documented one fails to compile
+    const LAYOUT: () = {
+        assert!(offset_of!(Self, generation) == 0);
+        assert!(offset_of!(Self, occupied) == 4);
+        assert!(offset_of!(Self, value) == value_offset::<T>());
+        assert!(size_of::<Generation>() == size_of::<u32>());
+    };
+
+    /// The value, None for a free slot
+    pub fn value(&self) -> Option<&T> {
+        if self.occupied == 0 {
+            return None;
+        }
+
+        return Some(unsafe { self.value.assume_init_ref() });
+    }
+}
+
+// Spot checks of the documented layout on concrete payloads
+const _: () = assert!(size_of::<ReprCEntry<u32>>() == 12);
+const _: () = assert!(value_offset::<u64>() == 8 && size_of::<ReprCEntry<u64>>() == 16);
+const _: () = assert!(size_of::<ReprCEntry<[f32; 4]>>() == 24);
+const _: () = assert!(value_offset::<u16>() == 8 && align_of::<ReprCEntry<u8>>() == 4);
+
+pub struct ReprCArena<T: Copy> {
+    entries: Vec<ReprCEntry<T>>,
+    free: Vec<usize>,
+    free_sites: FreeSites,
+}
+
+impl<T: Copy> Default for ReprCArena<T> {
+    fn default() -> Self {
+        return ReprCArena { entries: Vec::new(), free: Vec::new(), free_sites: FreeSites::default() };
+    }
+}
+
+impl<T: Copy> ReprCArena<T> {
+    /// Test hook: an arena whose first `count` slots are free and already at
+    /// `generation`, to check what happens when generations run out
+    #[cfg(test)]
+    pub(crate) fn with_free_slots_at(count: usize, generation: Generation) -> Self {
+        return ReprCArena {
+            entries: vec![ReprCEntry { generation, occupied: 0, value: MaybeUninit::uninit() }; count],
+            free: (0..count).rev().collect(),
+            free_sites: FreeSites::default(),
+        };
+    }
+
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        match self.try_insert(element) {
+            Ok(handle) => handle,
+            Err(_) => panic!("Out of memory while allocating a new entry"),
+        }
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        #[allow(clippy::let_unit_value)]
+        let _ = ReprCEntry::<T>::LAYOUT;
+
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                if !try_reserve_one(&mut self.entries, 0) {
+                    return Err(AllocError::OutOfMemory(element));
+                }
+                self.entries.push(ReprCEntry { generation: 0, occupied: 0, value: MaybeUninit::uninit() });
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[index];
+        entry.value.write(element);
+        entry.occupied = 1;
+
+        return Ok(GenerationalIndex { index, generation: entry.generation });
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return match self.entries.get(index.index) {
+            Some(entry) => entry.generation == index.generation && entry.occupied == 1,
+            None => false,
+        };
+    }
+
+    pub fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        return self.entries[index.index].value();
+    }
+
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        // Live slots are initialized
+        return Some(unsafe { self.entries[index.index].value.assume_init_mut() });
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        if self.remove(index).is_none() {
+            panic!("Trying to free already unused index{}", self.free_sites.describe(index.index));
+        }
+    }
+
+    /// Free the slot and hand back a copy of its value, None if the handle is dead. The
+    /// bytes stay in the entry like with `free`, unless `zero-on-free` clears them
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        if !self.is_live(index) {
+            return None;
+        }
+
+        self.free_sites.record(index.index, Location::caller());
+        let entry = &mut self.entries[index.index];
+        // Live slots are initialized
+        let value = unsafe { entry.value.assume_init() };
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize(&mut entry.value);
+        entry.occupied = 0;
+        if bump_generation(&mut entry.generation) {
+            self.free.push(index.index);
+        }
+        return Some(value);
+    }
+
+    /// Every slot, free ones included, in the layout described in the module docs
+    pub fn entries(&self) -> &[ReprCEntry<T>] {
+        return &self.entries;
+    }
+
+    /// Pointer to the first entry and number of entries, for passing the array to C
+    pub fn as_raw_parts(&self) -> (*const ReprCEntry<T>, usize) {
+        return (self.entries.as_ptr(), self.entries.len());
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let retired = self.entries.iter().filter(|entry| entry.generation == Generation::MAX).count();
+        let live = self.entries.iter().filter(|entry| entry.occupied == 1).count();
+        return AllocatorStats { capacity: self.entries.len(), live, free: self.free.len(), retired };
+    }
+
+    /// Panic if the free list is inconsistent or a free slot is still marked as occupied
+    pub fn check_invariants(&self) {
+        check_free_list(self.free.iter().copied(), self.entries.len(), |index| self.entries[index].generation);
+        for index in self.free.iter() {
+            assert_eq!(self.entries[*index].occupied, 0, "Free slot {} is marked as occupied", index);
+        }
+
+        let stats = self.stats();
+        assert_eq!(stats.live + stats.free + stats.retired, stats.capacity, "Some slots are neither live, free nor retired");
+    }
+}
+
+impl<T: Copy> GenerationalAllocator<T> for ReprCArena<T> {
+    type Ref<'a> = &'a T where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return ReprCArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        ReprCArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return ReprCArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return ReprCArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<&T> {
+        return ReprCArena::get(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return ReprCArena::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.get(index).map(f);
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return ReprCArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        ReprCArena::check_invariants(self);
+    }
+}
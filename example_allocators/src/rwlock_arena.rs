@@ -0,0 +1,170 @@
+/// Arena whose slots are each behind their own `RwLock`.
+///
+/// The `RefCell` arenas let one thread read or write a value through `&self`. This one
+/// does the same across threads: share the arena by reference and any number of
+/// readers can look at an entity while writers to other entities go ahead in parallel,
+/// only readers and writers of the same slot wait for each other. Allocating and
+/// freeing still take `&mut self`, so the slot table itself needs no locking.
+///
+/// Locks are `std::sync::RwLock`. A panic while holding a write guard poisons that slot
+/// only; the arena ignores the poison flag, since its own bookkeeping lives outside the
+/// locks and can't be left half updated.
+///
+/// Slots sit next to each other, so writers on neighbouring slots fight over the same
+/// cache lines. `PaddedRwLockArena` puts every slot on its own line instead.
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use crate::cache_padded::CachePadded;
+use crate::errors::{AccessError, AllocError};
+use crate::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+#[derive(Debug)]
+pub struct RwLockArena<T> {
+    slots: GenerationalIndexArray<RwLock<T>>,
+}
+
+/// `RwLockArena` whose slots are padded to a cache line each, lock included, so
+/// concurrent writers on different slots don't false share
+pub type PaddedRwLockArena<T> = RwLockArena<CachePadded<T>>;
+
+impl<T> Default for RwLockArena<T> {
+    fn default() -> Self {
+        return RwLockArena { slots: GenerationalIndexArray::default() };
+    }
+}
+
+impl<T> RwLockArena<T> {
+    #[deprecated(note = "renamed to `insert`")]
+    pub fn new(&mut self, element: T) -> GenerationalIndex {
+        return self.insert(element);
+    }
+
+    pub fn insert(&mut self, element: T) -> GenerationalIndex {
+        return self.slots.insert(RwLock::new(element));
+    }
+
+    #[deprecated(note = "renamed to `try_insert`")]
+    pub fn try_new(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.try_insert(element);
+    }
+
+    /// Same as `insert`, but gives the element back instead of panicking when the
+    /// arena can't grow
+    pub fn try_insert(&mut self, element: T) -> Result<GenerationalIndex, AllocError<T>> {
+        return self.slots.try_insert(RwLock::new(element)).map_err(|error| error.map(into_value));
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, index: &GenerationalIndex) {
+        self.slots.free(index);
+    }
+
+    /// Free the slot and hand its value back, None if the handle is dead
+    pub fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return self.slots.remove(index).map(into_value);
+    }
+
+    pub fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return self.slots.contains(index);
+    }
+
+    /// Shared access to the value, blocking while another thread writes to it
+    pub fn read(&self, index: &GenerationalIndex) -> Option<RwLockReadGuard<'_, T>> {
+        return self.slots.get(index).map(|lock| lock.read().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Exclusive access to the value, blocking while any other thread reads or writes it
+    pub fn write(&self, index: &GenerationalIndex) -> Option<RwLockWriteGuard<'_, T>> {
+        return self.slots.get(index).map(|lock| lock.write().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Like `read`, but fails with `AlreadyBorrowed` instead of blocking
+    pub fn try_read(&self, index: &GenerationalIndex) -> Result<RwLockReadGuard<'_, T>, AccessError> {
+        self.slots.validate(index)?;
+        let lock = self.slots.get(index).expect("Live slot without a value");
+        return match lock.try_read() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => Err(AccessError::AlreadyBorrowed),
+        };
+    }
+
+    /// Like `write`, but fails with `AlreadyBorrowed` instead of blocking
+    pub fn try_write(&self, index: &GenerationalIndex) -> Result<RwLockWriteGuard<'_, T>, AccessError> {
+        self.slots.validate(index)?;
+        let lock = self.slots.get(index).expect("Live slot without a value");
+        return match lock.try_write() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => Err(AccessError::AlreadyBorrowed),
+        };
+    }
+
+    /// Direct access when the arena is borrowed mutably, no locking needed
+    pub fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return self.slots.get_mut(index).map(|lock| lock.get_mut().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Snapshot of the live handles, so callers can free or allocate while walking it
+    pub fn handles(&self) -> Vec<GenerationalIndex> {
+        return self.slots.handles();
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return self.slots.stats();
+    }
+
+    pub fn check_invariants(&self) {
+        self.slots.check_invariants();
+    }
+}
+
+fn into_value<T>(lock: RwLock<T>) -> T {
+    return lock.into_inner().unwrap_or_else(PoisonError::into_inner);
+}
+
+impl<T> GenerationalAllocator<T> for RwLockArena<T> {
+    type Ref<'a> = RwLockReadGuard<'a, T> where T: 'a;
+    type RefMut<'a> = &'a mut T where T: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        return RwLockArena::insert(self, element);
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        RwLockArena::free(self, index);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        return RwLockArena::remove(self, index);
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        return RwLockArena::is_live(self, index);
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<RwLockReadGuard<'_, T>> {
+        return RwLockArena::read(self, index);
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut T> {
+        return RwLockArena::get_mut(self, index);
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        return self.read(index).map(|guard| f(&guard));
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        return self.get_mut(index).map(f);
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        return RwLockArena::stats(self);
+    }
+
+    fn check_invariants(&self) {
+        RwLockArena::check_invariants(self);
+    }
+}
@@ -0,0 +1,39 @@
+/// Picking random live entries, e.g. "K random enemies" for AI or fuzzing code.
+///
+/// The arenas that can sample know how many entries are live and where they are (the
+/// occupancy bitset of `InPlaceAllocator`, the dense array of `DenseArena`), so `sample`
+/// draws ranks among the live entries and maps them to slots. Dead slots are never
+/// drawn, so there's no rejection loop no matter how sparse the arena is.
+use std::collections::BTreeSet;
+
+/// Source of random numbers for `sample`. Mirrors `next_u64` of `rand::RngCore`, so any
+/// `rand` generator can be passed as `|| rng.next_u64()`
+pub trait RandomSource {
+    fn next_u64(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> RandomSource for F {
+    fn next_u64(&mut self) -> u64 {
+        return self();
+    }
+}
+
+// Number in `0..bound`. Multiply-shift instead of a modulo: the bias is below
+// bound / 2^64, which is nothing for arena sized bounds
+fn below(rng: &mut impl RandomSource, bound: usize) -> usize {
+    return ((rng.next_u64() as u128 * bound as u128) >> 64) as usize;
+}
+
+/// `k` distinct numbers drawn uniformly from `0..n` (all of them if `k >= n`), sorted.
+/// Floyd's algorithm: one draw per number picked, however large `n` is
+pub(crate) fn distinct_ranks(rng: &mut impl RandomSource, k: usize, n: usize) -> Vec<usize> {
+    let mut picked = BTreeSet::new();
+    for upper in n - k.min(n)..n {
+        let rank = below(rng, upper + 1);
+        if !picked.insert(rank) {
+            picked.insert(upper);
+        }
+    }
+
+    return picked.into_iter().collect();
+}
@@ -0,0 +1,222 @@
+/// Untyped allocator handing out byte blocks from a few size classes.
+///
+/// The other allocators store one type `T`. This one shows the same generational
+/// scheme working for heterogeneous data: requests are rounded up to the smallest size
+/// class that fits (16, 32, 64 or 128 bytes), and each class is a pool with its own
+/// slots, generations and free list.
+///
+/// All classes share one handle namespace. The class of a block is stored in the low bits
+/// of the handle's index (`index = slot * CLASS_COUNT + class`), so a handle finds its
+/// pool without a lookup table, and a handle from one class can never be mistaken for a
+/// slot of another.
+use std::panic::Location;
+
+use crate::fault_injection::try_reserve_one;
+use crate::free_sites::FreeSites;
+use crate::generational_allocator::{check_free_list, AllocatorStats};
+use crate::kyren_generational_indices::{bump_generation, Generation, GenerationalIndex};
+
+/// Block sizes, in bytes, of every class
+pub const SIZE_CLASSES: [usize; 4] = [16, 32, 64, 128];
+
+const CLASS_COUNT: usize = SIZE_CLASSES.len();
+
+// Unit of storage, every block is 16 byte aligned so it can hold any primitive. The
+// bytes are only ever read through the slices built in `Pool::block`
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+#[repr(align(16))]
+struct Chunk([u8; 16]);
+
+const CHUNK: usize = std::mem::size_of::<Chunk>();
+
+struct Pool {
+    // Blocks back to back, `chunks_per_block` chunks each
+    memory: Vec<Chunk>,
+    chunks_per_block: usize,
+    generations: Vec<Generation>,
+    // Bytes asked for in each live block, at most the class size
+    lengths: Vec<usize>,
+    free: Vec<usize>,
+    free_sites: FreeSites,
+}
+
+impl Pool {
+    fn new(block_size: usize) -> Self {
+        return Pool {
+            memory: Vec::new(),
+            chunks_per_block: block_size / CHUNK,
+            generations: Vec::new(),
+            lengths: Vec::new(),
+            free: Vec::new(),
+            free_sites: FreeSites::default(),
+        };
+    }
+
+    fn is_live(&self, slot: usize, generation: Generation) -> bool {
+        return self.generations.get(slot) == Some(&generation);
+    }
+
+    /// Zeroed block of `len` bytes, None if the pool can't grow
+    fn allocate(&mut self, len: usize) -> Option<(usize, Generation)> {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                let block_bytes = self.chunks_per_block * CHUNK;
+                if !try_reserve_one(&mut self.generations, block_bytes) || self.lengths.try_reserve(1).is_err() {
+                    return None;
+                }
+                if self.memory.try_reserve(self.chunks_per_block).is_err() {
+                    return None;
+                }
+
+                self.memory.resize(self.memory.len() + self.chunks_per_block, Chunk([0; 16]));
+                self.generations.push(0);
+                self.lengths.push(0);
+                self.generations.len() - 1
+            }
+        };
+
+        self.lengths[slot] = len;
+        self.block_mut(slot).fill(0);
+        return Some((slot, self.generations[slot]));
+    }
+
+    fn block(&self, slot: usize) -> &[u8] {
+        let chunks = &self.memory[slot * self.chunks_per_block..(slot + 1) * self.chunks_per_block];
+        // Chunks are plain bytes with no padding
+        let bytes = unsafe { std::slice::from_raw_parts(chunks.as_ptr() as *const u8, chunks.len() * CHUNK) };
+        return &bytes[..self.lengths[slot]];
+    }
+
+    fn block_mut(&mut self, slot: usize) -> &mut [u8] {
+        let len = self.lengths[slot];
+        return &mut self.whole_block_mut(slot)[..len];
+    }
+
+    // All of the block, past the bytes asked for too
+    fn whole_block_mut(&mut self, slot: usize) -> &mut [u8] {
+        let chunks = &mut self.memory[slot * self.chunks_per_block..(slot + 1) * self.chunks_per_block];
+        return unsafe { std::slice::from_raw_parts_mut(chunks.as_mut_ptr() as *mut u8, chunks.len() * CHUNK) };
+    }
+}
+
+pub struct SizeClassAllocator {
+    pools: [Pool; CLASS_COUNT],
+}
+
+impl Default for SizeClassAllocator {
+    fn default() -> Self {
+        return SizeClassAllocator { pools: SIZE_CLASSES.map(Pool::new) };
+    }
+}
+
+impl SizeClassAllocator {
+    /// Smallest class that fits `size` bytes, None if it's bigger than every class
+    pub fn class_for(size: usize) -> Option<usize> {
+        return SIZE_CLASSES.iter().position(|class_size| size <= *class_size);
+    }
+
+    /// Block size of the class `handle` belongs to
+    pub fn class_size(handle: &GenerationalIndex) -> usize {
+        return SIZE_CLASSES[handle.index % CLASS_COUNT];
+    }
+
+    /// Allocate a zeroed block of `size` bytes. Panics if `size` is bigger than the
+    /// largest class or the allocator can't grow
+    pub fn allocate(&mut self, size: usize) -> GenerationalIndex {
+        match self.try_allocate(size) {
+            Some(handle) => handle,
+            None => panic!("Can't allocate a block of {} bytes, the largest class is {}", size, SIZE_CLASSES[CLASS_COUNT - 1]),
+        }
+    }
+
+    /// Same as `allocate`, but returns None instead of panicking
+    pub fn try_allocate(&mut self, size: usize) -> Option<GenerationalIndex> {
+        let class = Self::class_for(size)?;
+        let (slot, generation) = self.pools[class].allocate(size)?;
+        return Some(GenerationalIndex { index: slot * CLASS_COUNT + class, generation });
+    }
+
+    /// Allocate a block holding a copy of `bytes`
+    pub fn allocate_from(&mut self, bytes: &[u8]) -> GenerationalIndex {
+        let handle = self.allocate(bytes.len());
+        self.get_mut(&handle).unwrap().copy_from_slice(bytes);
+        return handle;
+    }
+
+    pub fn is_live(&self, handle: &GenerationalIndex) -> bool {
+        let (pool, slot) = self.locate(handle);
+        return pool.is_live(slot, handle.generation);
+    }
+
+    /// The bytes of the block, as many as were asked for when allocating it
+    pub fn get(&self, handle: &GenerationalIndex) -> Option<&[u8]> {
+        let (pool, slot) = self.locate(handle);
+        if !pool.is_live(slot, handle.generation) {
+            return None;
+        }
+
+        return Some(pool.block(slot));
+    }
+
+    pub fn get_mut(&mut self, handle: &GenerationalIndex) -> Option<&mut [u8]> {
+        let (slot, class) = (handle.index / CLASS_COUNT, handle.index % CLASS_COUNT);
+        let pool = &mut self.pools[class];
+        if !pool.is_live(slot, handle.generation) {
+            return None;
+        }
+
+        return Some(pool.block_mut(slot));
+    }
+
+    #[cfg_attr(feature = "track-free-sites", track_caller)]
+    pub fn free(&mut self, handle: &GenerationalIndex) {
+        let (slot, class) = (handle.index / CLASS_COUNT, handle.index % CLASS_COUNT);
+        let pool = &mut self.pools[class];
+        if !pool.is_live(slot, handle.generation) {
+            panic!("Trying to free already unused block{}", pool.free_sites.describe(slot));
+        }
+
+        pool.free_sites.record(slot, Location::caller());
+        #[cfg(feature = "zero-on-free")]
+        crate::zeroize::zeroize_bytes(pool.whole_block_mut(slot));
+        if bump_generation(&mut pool.generations[slot]) {
+            pool.free.push(slot);
+        }
+    }
+
+    /// Slot usage of one class, `class` being a position in `SIZE_CLASSES`
+    pub fn class_stats(&self, class: usize) -> AllocatorStats {
+        let pool = &self.pools[class];
+        let capacity = pool.generations.len();
+        let retired = pool.generations.iter().filter(|generation| **generation == Generation::MAX).count();
+        return AllocatorStats { capacity, live: capacity - pool.free.len() - retired, free: pool.free.len(), retired };
+    }
+
+    /// Slot usage summed over every class
+    pub fn stats(&self) -> AllocatorStats {
+        return (0..CLASS_COUNT).map(|class| self.class_stats(class)).fold(AllocatorStats::default(), |total, stats| {
+            AllocatorStats {
+                capacity: total.capacity + stats.capacity,
+                live: total.live + stats.live,
+                free: total.free + stats.free,
+                retired: total.retired + stats.retired,
+            }
+        });
+    }
+
+    /// Panic if a pool's free list is inconsistent or its storage doesn't match its slots
+    pub fn check_invariants(&self) {
+        for (class, pool) in self.pools.iter().enumerate() {
+            check_free_list(pool.free.iter().copied(), pool.generations.len(), |slot| pool.generations[slot]);
+            assert_eq!(pool.memory.len(), pool.generations.len() * pool.chunks_per_block, "Pool {} storage doesn't match its slots", class);
+            assert_eq!(pool.lengths.len(), pool.generations.len(), "Pool {} is missing block lengths", class);
+            assert!(pool.lengths.iter().all(|len| *len <= SIZE_CLASSES[class]), "Pool {} has a block longer than its class", class);
+        }
+    }
+
+    fn locate(&self, handle: &GenerationalIndex) -> (&Pool, usize) {
+        return (&self.pools[handle.index % CLASS_COUNT], handle.index / CLASS_COUNT);
+    }
+}
@@ -0,0 +1,44 @@
+/// Mutable borrow guard that marks its slot as poisoned if a panic unwinds through it.
+///
+/// A panic in the middle of an update can leave an entity half written. `RefCell`
+/// forgets about it as soon as the `RefMut` is dropped, so the next reader sees the
+/// broken value with no warning. `SlotGuard` works like `MutexGuard` instead: when it
+/// is dropped while the thread is panicking it sets the slot's poison flag, and the
+/// arena reports the slot as poisoned until someone calls `clear_poison`.
+use std::cell::{Cell, RefMut};
+use std::ops::{Deref, DerefMut};
+use std::thread;
+
+#[derive(Debug)]
+pub struct SlotGuard<'a, T> {
+    value: RefMut<'a, T>,
+    poisoned: &'a Cell<bool>,
+}
+
+impl<'a, T> SlotGuard<'a, T> {
+    pub(crate) fn new(value: RefMut<'a, T>, poisoned: &'a Cell<bool>) -> Self {
+        return SlotGuard { value, poisoned };
+    }
+}
+
+impl<T> Deref for SlotGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.value;
+    }
+}
+
+impl<T> DerefMut for SlotGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return &mut self.value;
+    }
+}
+
+impl<T> Drop for SlotGuard<'_, T> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.poisoned.set(true);
+        }
+    }
+}
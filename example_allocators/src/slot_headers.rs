@@ -0,0 +1,76 @@
+/// Generation headers that outlive the allocator they belong to.
+///
+/// Pointer handles check liveness by reading the generation of their slot. If that
+/// generation lived inside the allocator, calling `is_live` after the allocator is dropped
+/// would read freed memory. Instead, headers are leaked once and recycled through a global
+/// pool: an allocator takes headers when it grows and gives them back when dropped,
+/// bumping their generation first. Generations only ever go up, so a handle that was
+/// dead stays dead, no matter who uses the header next.
+use std::sync::atomic::{fence, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use crate::kyren_generational_indices::Generation;
+
+pub(crate) struct SlotHeader {
+    generation: AtomicU32,
+}
+
+static POOL: Mutex<Vec<&'static SlotHeader>> = Mutex::new(Vec::new());
+
+impl SlotHeader {
+    /// Take a header from the pool, or leak a new one if the pool is empty
+    pub(crate) fn acquire() -> &'static SlotHeader {
+        let recycled = POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).pop();
+
+        return match recycled {
+            Some(header) => header,
+            None => Box::leak(Box::new(SlotHeader { generation: AtomicU32::new(0) })),
+        };
+    }
+
+    /// Kill every handle to this header and give it back to the pool. Retired headers
+    /// are kept out of the pool for good
+    pub(crate) fn release(header: &'static SlotHeader) {
+        if header.bump() {
+            POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(header);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn generation(&self) -> Generation {
+        return self.generation.load(Ordering::Relaxed);
+    }
+
+    /// Move to the next generation. Returns false once the header reaches
+    /// `Generation::MAX`: no handle is ever given that generation, so the slot has to
+    /// be retired instead of reused
+    #[inline(always)]
+    pub(crate) fn bump(&self) -> bool {
+        let generation = self.generation();
+        if generation == Generation::MAX {
+            return false;
+        }
+
+        self.generation.store(generation + 1, Ordering::Relaxed);
+        // Optimistic readers copy the value before checking the generation, anything
+        // written to the entry after this must come with the new generation
+        fence(Ordering::Release);
+        return generation + 1 != Generation::MAX;
+    }
+
+    /// Move forward to `generation` if the header is behind it. Only ever kills handles
+    #[inline(always)]
+    pub(crate) fn advance_to(&self, generation: Generation) {
+        self.generation.fetch_max(generation, Ordering::Relaxed);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_generation(&self, generation: Generation) {
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_retired(&self) -> bool {
+        return self.generation() == Generation::MAX;
+    }
+}
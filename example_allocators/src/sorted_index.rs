@@ -0,0 +1,132 @@
+/// Handles of an arena kept ordered by a user key, e.g. z-order or priority.
+///
+/// Arenas only know slot order. A `SortedIndex` sits next to one and answers ordered
+/// queries over its contents: iterate back to front, find the highest priority entry,
+/// or walk every entry in a key range. Keys are set through `insert`/`update_key` and
+/// dropped with `remove`.
+///
+/// Freeing an entry doesn't have to touch the index. Generations are validated lazily:
+/// queries take the arena and skip handles that died since they were inserted, and
+/// `prune` drops them for good.
+use std::collections::{BTreeSet, HashMap};
+use std::ops::{Bound, RangeBounds};
+
+use crate::generational_allocator::GenerationalAllocator;
+use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+
+#[derive(Debug, Clone)]
+pub struct SortedIndex<K> {
+    // Ordered by key, ties broken by slot then generation. Handles aren't `Ord`, so they
+    // are stored as their two parts
+    ordered: BTreeSet<(K, usize, Generation)>,
+    keys: HashMap<GenerationalIndex, K>,
+}
+
+impl<K> Default for SortedIndex<K> {
+    fn default() -> Self {
+        return SortedIndex { ordered: BTreeSet::new(), keys: HashMap::new() };
+    }
+}
+
+impl<K: Ord + Clone> SortedIndex<K> {
+    /// Index `handle` under `key`, returning its previous key if it was already indexed
+    pub fn insert(&mut self, handle: GenerationalIndex, key: K) -> Option<K> {
+        let previous = self.remove(&handle);
+        self.ordered.insert((key.clone(), handle.index, handle.generation));
+        self.keys.insert(handle, key);
+        return previous;
+    }
+
+    /// Move an indexed handle to `key`, returning the old key. Does nothing and returns
+    /// None if the handle isn't indexed
+    pub fn update_key(&mut self, handle: &GenerationalIndex, key: K) -> Option<K> {
+        if !self.keys.contains_key(handle) {
+            return None;
+        }
+
+        return self.insert(handle.clone(), key);
+    }
+
+    /// Stop indexing `handle`, returning its key
+    pub fn remove(&mut self, handle: &GenerationalIndex) -> Option<K> {
+        let key = self.keys.remove(handle)?;
+        self.ordered.remove(&(key.clone(), handle.index, handle.generation));
+        return Some(key);
+    }
+
+    pub fn key(&self, handle: &GenerationalIndex) -> Option<&K> {
+        return self.keys.get(handle);
+    }
+
+    /// Live handles of `allocator` in key order
+    pub fn iter<'a, T>(&'a self, allocator: &'a impl GenerationalAllocator<T>) -> impl DoubleEndedIterator<Item = (GenerationalIndex, &'a K)> + 'a {
+        return live(self.ordered.iter(), allocator);
+    }
+
+    /// Live handles whose key falls in `range`, in key order
+    pub fn range<'a, T>(
+        &'a self,
+        range: impl RangeBounds<K>,
+        allocator: &'a impl GenerationalAllocator<T>,
+    ) -> impl DoubleEndedIterator<Item = (GenerationalIndex, &'a K)> + 'a {
+        // Widen the key bounds so they take in or leave out every handle under that key
+        let start = match range.start_bound() {
+            Bound::Included(key) => Bound::Included((key.clone(), 0, 0)),
+            Bound::Excluded(key) => Bound::Excluded((key.clone(), usize::MAX, Generation::MAX)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => Bound::Included((key.clone(), usize::MAX, Generation::MAX)),
+            Bound::Excluded(key) => Bound::Excluded((key.clone(), 0, 0)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        return live(self.ordered.range((start, end)), allocator);
+    }
+
+    /// Live handle with the smallest key
+    pub fn first<'a, T>(&'a self, allocator: &'a impl GenerationalAllocator<T>) -> Option<(GenerationalIndex, &'a K)> {
+        return self.iter(allocator).next();
+    }
+
+    /// Live handle with the largest key
+    pub fn last<'a, T>(&'a self, allocator: &'a impl GenerationalAllocator<T>) -> Option<(GenerationalIndex, &'a K)> {
+        return self.iter(allocator).next_back();
+    }
+
+    /// Drop every handle that is dead in `allocator`, returning how many there were
+    pub fn prune<T>(&mut self, allocator: &impl GenerationalAllocator<T>) -> usize {
+        let before = self.keys.len();
+        self.keys.retain(|handle, _| allocator.is_live(handle));
+        self.ordered.retain(|(_, index, generation)| allocator.is_live(&GenerationalIndex { index: *index, generation: *generation }));
+        return before - self.keys.len();
+    }
+
+    /// Number of indexed handles, dead ones that weren't pruned yet included
+    pub fn len(&self) -> usize {
+        return self.keys.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.keys.is_empty();
+    }
+
+    /// Panic if the ordered set and the key table disagree
+    pub fn check_invariants(&self) {
+        assert_eq!(self.ordered.len(), self.keys.len(), "Ordered set and key table have different sizes");
+        for (key, index, generation) in self.ordered.iter() {
+            let handle = GenerationalIndex { index: *index, generation: *generation };
+            assert!(self.keys.get(&handle) == Some(key), "Handle {:?} is ordered under a stale key", handle);
+        }
+    }
+}
+
+// Handles of `entries` that are still live in `allocator`
+fn live<'a, K: 'a, T>(
+    entries: impl DoubleEndedIterator<Item = &'a (K, usize, Generation)> + 'a,
+    allocator: &'a impl GenerationalAllocator<T>,
+) -> impl DoubleEndedIterator<Item = (GenerationalIndex, &'a K)> + 'a {
+    return entries
+        .map(|(key, index, generation)| (GenerationalIndex { index: *index, generation: *generation }, key))
+        .filter(move |(handle, _)| allocator.is_live(handle));
+}
@@ -0,0 +1,124 @@
+/// Uniform grid of handles, for finding the entities in an area.
+///
+/// A secondary index over an arena usually has to be told about every free, or it ends
+/// up returning entities that no longer exist. Handles make that unnecessary: the grid
+/// keeps the handles it was given, checks them against the arena when a query walks
+/// past them, and forgets the dead ones there and then. Freeing an entity needs no call
+/// to the grid at all.
+///
+/// Entries are 2D axis aligned boxes, stored in every cell they overlap.
+use std::collections::{HashMap, HashSet};
+
+use crate::generational_allocator::GenerationalAllocator;
+use crate::kyren_generational_indices::GenerationalIndex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Aabb {
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        return Aabb { min, max };
+    }
+
+    /// Whether the boxes share at least a point, edges included
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        return self.min[0] <= other.max[0] && other.min[0] <= self.max[0] && self.min[1] <= other.max[1] && other.min[1] <= self.max[1];
+    }
+}
+
+type Cell = (i32, i32);
+
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<GenerationalIndex>>,
+    // Current box of every handle the grid knows about, to find its old cells on update
+    bounds: HashMap<GenerationalIndex, Aabb>,
+}
+
+impl SpatialGrid {
+    /// Empty grid with square cells of side `cell_size`, about the size of a typical entity
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "Cells must have a positive size");
+        return SpatialGrid { cell_size, cells: HashMap::new(), bounds: HashMap::new() };
+    }
+
+    fn cells_of(&self, aabb: &Aabb) -> impl Iterator<Item = Cell> {
+        let cell = |coordinate: f32| (coordinate / self.cell_size).floor() as i32;
+        let (min_x, min_y, max_x, max_y) = (cell(aabb.min[0]), cell(aabb.min[1]), cell(aabb.max[0]), cell(aabb.max[1]));
+        return (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)));
+    }
+
+    /// Insert `handle` with box `aabb`, or move it there if it's already in the grid
+    pub fn update(&mut self, handle: &GenerationalIndex, aabb: Aabb) {
+        self.remove(handle);
+        for cell in self.cells_of(&aabb).collect::<Vec<_>>() {
+            self.cells.entry(cell).or_default().push(handle.clone());
+        }
+        self.bounds.insert(handle.clone(), aabb);
+    }
+
+    /// Take `handle` out of the grid. Only needed for entities that stay alive, dead
+    /// ones are dropped by the queries that come across them
+    pub fn remove(&mut self, handle: &GenerationalIndex) -> bool {
+        let Some(old) = self.bounds.remove(handle) else { return false };
+        for cell in self.cells_of(&old).collect::<Vec<_>>() {
+            if let Some(handles) = self.cells.get_mut(&cell) {
+                handles.retain(|other| other != handle);
+                if handles.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+
+        return true;
+    }
+
+    /// Live handles whose box overlaps `aabb`, each once. Handles that `arena` says are
+    /// dead are removed from the cells the query visits
+    pub fn query<T>(&mut self, aabb: &Aabb, arena: &impl GenerationalAllocator<T>) -> Vec<GenerationalIndex> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        for cell in self.cells_of(aabb).collect::<Vec<_>>() {
+            let Some(handles) = self.cells.get_mut(&cell) else { continue };
+            handles.retain(|handle| {
+                if arena.is_live(handle) {
+                    return true;
+                }
+                self.bounds.remove(handle);
+                false
+            });
+
+            for handle in handles.iter() {
+                if self.bounds[handle].overlaps(aabb) && seen.insert(handle.clone()) {
+                    found.push(handle.clone());
+                }
+            }
+            if handles.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+
+        return found;
+    }
+
+    /// Drop every dead handle at once, e.g. after a level unload freed most entities
+    pub fn prune<T>(&mut self, arena: &impl GenerationalAllocator<T>) {
+        self.cells.retain(|_, handles| {
+            handles.retain(|handle| arena.is_live(handle));
+            !handles.is_empty()
+        });
+        self.bounds.retain(|handle, _| arena.is_live(handle));
+    }
+
+    /// Handles in the grid, dead ones not yet pruned included
+    pub fn len(&self) -> usize {
+        return self.bounds.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.bounds.is_empty();
+    }
+}
@@ -0,0 +1,160 @@
+/// Persistent ids for handles, so saved data can refer to entities across runs.
+///
+/// A handle is only meaningful for the arena that issued it: once an arena is rebuilt on
+/// load its entries may land in other slots, and any handle written to a save file points
+/// at the wrong thing. A `StableIdMap` gives handles `u64` ids that are never reused.
+/// Saved data stores ids instead of handles, the map is saved next to it with
+/// `to_bytes`, and `from_bytes` reattaches every id to the handle its entity got in the
+/// rebuilt arena.
+use std::collections::HashMap;
+
+use crate::compact_encoding::{read_len, read_varint, write_varint, CompactDecode};
+use crate::errors::DecodeError;
+use crate::generational_allocator::GenerationalAllocator;
+use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+
+pub type StableId = u64;
+
+// Bumped whenever the layout written by `to_bytes` changes
+const ENCODING_VERSION: u8 = 1;
+
+#[derive(Debug, Clone)]
+pub struct StableIdMap {
+    handles: HashMap<StableId, GenerationalIndex>,
+    ids: HashMap<GenerationalIndex, StableId>,
+    // Ids are never handed out twice, even after their handle is removed
+    next_id: StableId,
+}
+
+impl Default for StableIdMap {
+    fn default() -> Self {
+        // 0 is left unused so saved data can use it as "no entity"
+        return StableIdMap { handles: HashMap::new(), ids: HashMap::new(), next_id: 1 };
+    }
+}
+
+impl StableIdMap {
+    /// Id of `handle`, giving it a new one if it has none yet
+    pub fn assign(&mut self, handle: &GenerationalIndex) -> StableId {
+        if let Some(id) = self.ids.get(handle) {
+            return *id;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.handles.insert(id, handle.clone());
+        self.ids.insert(handle.clone(), id);
+        return id;
+    }
+
+    pub fn id(&self, handle: &GenerationalIndex) -> Option<StableId> {
+        return self.ids.get(handle).copied();
+    }
+
+    /// Handle attached to `id`, whether or not it's still live
+    pub fn handle(&self, id: StableId) -> Option<&GenerationalIndex> {
+        return self.handles.get(&id);
+    }
+
+    /// Handle attached to `id` if it's live in `allocator`
+    pub fn resolve<T>(&self, id: StableId, allocator: &impl GenerationalAllocator<T>) -> Option<GenerationalIndex> {
+        return self.handles.get(&id).filter(|handle| allocator.is_live(handle)).cloned();
+    }
+
+    /// Forget the id of `handle`, returning it. The id is never handed out again
+    pub fn remove(&mut self, handle: &GenerationalIndex) -> Option<StableId> {
+        let id = self.ids.remove(handle)?;
+        self.handles.remove(&id);
+        return Some(id);
+    }
+
+    /// Drop every id whose handle is dead in `allocator`, returning how many there were
+    pub fn prune<T>(&mut self, allocator: &impl GenerationalAllocator<T>) -> usize {
+        let before = self.ids.len();
+        self.ids.retain(|handle, _| allocator.is_live(handle));
+        self.handles.retain(|_, handle| allocator.is_live(handle));
+        return before - self.ids.len();
+    }
+
+    /// Id/handle pairs in id order
+    pub fn iter(&self) -> impl Iterator<Item = (StableId, &GenerationalIndex)> {
+        let mut pairs: Vec<_> = self.handles.iter().map(|(id, handle)| (*id, handle)).collect();
+        pairs.sort_unstable_by_key(|(id, _)| *id);
+        return pairs.into_iter();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.ids.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.ids.is_empty();
+    }
+
+    /// Panic if the two directions of the map disagree or an id is ahead of the counter
+    pub fn check_invariants(&self) {
+        assert_eq!(self.handles.len(), self.ids.len(), "Id and handle tables have different sizes");
+        for (id, handle) in self.handles.iter() {
+            assert_eq!(self.ids.get(handle), Some(id), "Handle {:?} doesn't map back to id {}", handle, id);
+            assert!(*id < self.next_id, "Id {} wasn't handed out yet", id);
+        }
+    }
+
+    /// Encode the ids and the handles they point to as of now, for `from_bytes`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![ENCODING_VERSION];
+        write_varint(&mut out, self.next_id);
+        write_varint(&mut out, self.len() as u64);
+
+        let mut previous = 0;
+        for (id, handle) in self.iter() {
+            // Ids only go up, so store the gaps
+            write_varint(&mut out, id - previous);
+            previous = id;
+            write_varint(&mut out, handle.index as u64);
+            write_varint(&mut out, handle.generation as u64);
+        }
+
+        return out;
+    }
+
+    /// Rebuild a map written by `to_bytes`, reattaching ids through `remap`, which takes a
+    /// handle from the saved run and returns the handle the same entity has now. Ids it
+    /// returns None for are dropped. If the arena was restored as is (e.g. with
+    /// `GenerationalIndexArray::from_bytes`) handles didn't change and `remap` can be
+    /// `|handle| Some(handle.clone())`
+    pub fn from_bytes(bytes: &[u8], mut remap: impl FnMut(&GenerationalIndex) -> Option<GenerationalIndex>) -> Result<Self, DecodeError> {
+        let input = &mut &bytes[..];
+        let version = u8::decode(input)?;
+        if version != ENCODING_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let mut map = StableIdMap { next_id: read_varint(input)?, ..StableIdMap::default() };
+        let count = read_len(input)?;
+        let mut id: StableId = 0;
+        for _ in 0..count {
+            let gap = read_varint(input)?;
+            if gap == 0 {
+                return Err(DecodeError::Invalid("ids must be listed in increasing order"));
+            }
+            id = id.checked_add(gap).filter(|id| *id < map.next_id).ok_or(DecodeError::Invalid("id was never handed out"))?;
+            let index = read_len(input)?;
+            let generation = Generation::try_from(read_varint(input)?).map_err(|_| DecodeError::Invalid("generation out of range"))?;
+
+            let Some(handle) = remap(&GenerationalIndex { index, generation }) else {
+                continue;
+            };
+            if map.ids.contains_key(&handle) {
+                return Err(DecodeError::Invalid("two ids were reattached to the same handle"));
+            }
+            map.handles.insert(id, handle.clone());
+            map.ids.insert(handle, id);
+        }
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        return Ok(map);
+    }
+}
@@ -1,5 +1,6 @@
 
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::bool_assert_comparison)]
 mod tests
 {
     mod kyren_tests
@@ -43,6 +44,53 @@ mod tests
             generational_indices.free(&index);
             assert!( !generational_indices.is_live(&index) );
         }
+
+        #[test]
+        fn test_packed_handle_layout()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let stale = array.insert(1);
+            array.free(&stale);
+            let handle = array.insert(2);
+
+            let packed = handle.pack().unwrap();
+            assert_eq!(packed.to_bits(), 1 << 32, "Index in the low half, generation in the high half");
+            assert_eq!(GenerationalIndex::from(packed), handle);
+            assert_eq!(GenerationalIndex::from(kyren::PackedHandle::from_bits(packed.to_bits())), handle);
+            assert!(GenerationalIndex { index: u32::MAX as usize + 1, generation: 0 }.pack().is_none());
+
+            #[cfg(feature = "bytemuck")]
+            {
+                let packed = [handle.pack().unwrap(), stale.pack().unwrap()];
+                let bytes: &[u8] = bytemuck::cast_slice(&packed);
+                assert_eq!(bytes.len(), 16);
+                let read_back: &[kyren::PackedHandle] = bytemuck::cast_slice(bytes);
+                assert_eq!(GenerationalIndex::from(read_back[1]), stale);
+                assert_eq!(<kyren::PackedHandle as bytemuck::Zeroable>::zeroed(), kyren::PackedHandle::default());
+            }
+        }
+
+        #[test]
+        fn test_kyren_reuse_policy()
+        {
+            let mut fifo = kyren::GenerationalIndices::default();
+            let mut lifo = kyren::GenerationalIndices::with_policy(kyren::ReusePolicy::Lifo);
+            assert_eq!(fifo.policy(), kyren::ReusePolicy::Fifo);
+
+            let fifo_handles: Vec<_> = (0..3).map(|_| fifo.new()).collect();
+            let lifo_handles: Vec<_> = (0..3).map(|_| lifo.new()).collect();
+            for i in [0, 2]
+            {
+                fifo.free(&fifo_handles[i]);
+                lifo.free(&lifo_handles[i]);
+            }
+            assert_eq!(fifo.free_count(), 2);
+            assert_eq!(lifo.iter_free().map(|(index, _)| index).collect::<Vec<_>>(), vec![2, 0]);
+
+            assert_eq!(fifo.new().get_index(), 0, "Fifo reuses the slot freed first");
+            assert_eq!(lifo.new().get_index(), 2, "Lifo reuses the slot freed last");
+            assert_eq!(lifo.free_count(), 1);
+        }
     
         /// Dummy implementation of an entity for testing purposes
         #[derive(Debug, Default)]
@@ -64,7 +112,7 @@ mod tests
                 _id: GenerationalIndex::default()
             };
     
-            let index = generational_array.new(entity);
+            let index = generational_array.insert(entity);
     
             let entity_ref = generational_array.get(&index);
             assert!(entity_ref.is_some());
@@ -72,6 +120,20 @@ mod tests
             assert!(entity_ref.is_some());
         }
     
+        #[test]
+        fn test_kyren_contains_out_of_bounds()
+        {
+            let mut generational_array = GenerationalIndexArray::<u32>::default();
+            let index = generational_array.insert(1);
+            let foreign = GenerationalIndex{index: 10, generation: 0};
+
+            assert!(generational_array.contains(&index));
+            assert!(!generational_array.contains(&foreign));
+            generational_array.free(&index);
+            assert!(!generational_array.contains(&index));
+            assert!(!unsafe { generational_array.is_live_unchecked(&index) });
+        }
+
         #[test]
         fn test_kyren_free_array()
         {
@@ -82,12 +144,14 @@ mod tests
                 _id: GenerationalIndex::default()
             };
     
-            let index = generational_array.new(entity1);
+            let index = generational_array.insert(entity1);
             let entity_ref = generational_array.get(&index);
             assert!(entity_ref.is_some());
     
             generational_array.free(&index);
+            #[cfg(not(feature = "strict"))]
             let entity_ref = generational_array.get(&index);
+            #[cfg(not(feature = "strict"))]
             assert!(entity_ref.is_none());
         }
     
@@ -107,10 +171,10 @@ mod tests
                 _id: GenerationalIndex::default()
             };
     
-            let index = generational_array.new(entity1);
+            let index = generational_array.insert(entity1);
             generational_array.free(&index);
     
-            let index = generational_array.new(entity2);
+            let index = generational_array.insert(entity2);
             let entity_ref = generational_array.get(&index);
     
             assert!(entity_ref.is_some());
@@ -124,12 +188,12 @@ mod tests
         {
             let mut allocator : GenerationalIndexArrayCell<Entity>= GenerationalIndexArrayCell::default();
 
-            let genid1 = allocator.new(Entity{
+            let genid1 = allocator.insert(Entity{
                     name: "e1".to_string(),
                     _is_active: false,
                     _id: GenerationalIndex::default()}
                 );
-            let genid2 = allocator.new(Entity{
+            let genid2 = allocator.insert(Entity{
                     name: "e2".to_string(),
                     _is_active: false,
                     _id: GenerationalIndex::default()}
@@ -142,12 +206,36 @@ mod tests
             entity2.borrow_mut()._is_active = true;
         }
 
+        #[test]
+        fn test_array_entries_drop_values_once()
+        {
+            use std::rc::Rc;
+
+            // No discriminant next to the value: a u64 slot is the value plus the header
+            #[cfg(not(feature = "insertion-order"))]
+            assert_eq!(std::mem::size_of::<kyren::GenerationalArrayEntry<u64>>(), 16);
+
+            let counter = Rc::new(());
+            let mut array = GenerationalIndexArray::default();
+            let handles : Vec<_> = (0..4).map(|_| array.insert(counter.clone())).collect();
+
+            assert!(array.remove(&handles[0]).is_some());
+            array.free(&handles[1]);
+            let copy = array.clone();
+            assert_eq!(Rc::strong_count(&counter), 5, "Removed and freed values must be dropped");
+            assert!(copy == array);
+
+            drop(copy);
+            drop(array);
+            assert_eq!(Rc::strong_count(&counter), 1, "Dropping the array must drop the live values");
+        }
+
     }
     
     // Memory allocators:
     mod memallocs_test
     {
-        use crate::{kyren_generational_indices::GIAUninitCell, memory_allocators::*, allocator_with_pointer};
+        use crate::{kyren_generational_indices::{GIAUninitCell, GenerationalIndexArray}, memory_allocators::*};
 
         struct Entity
         {
@@ -167,7 +255,7 @@ mod tests
         fn test_generational_pointer_array_allocate()
         {
             let mut gpa = GIABoxUninit::<Entity>::default();
-            let entity_handle = gpa.new(Entity::default());
+            let entity_handle = gpa.insert(Entity::default());
 
             // Try initialize it 
             {
@@ -196,10 +284,11 @@ mod tests
         fn test_generational_pointer_array_free()
         {
             let mut gpa = GIABoxUninit::<Entity>::default();
-            let entity_handle = gpa.new(Entity::default());
+            let entity_handle = gpa.insert(Entity::default());
 
             assert!(gpa.get(&entity_handle).is_some());
             gpa.free(&entity_handle);
+            #[cfg(not(feature = "strict"))]
             assert!(gpa.get(&entity_handle).is_none());
         }
 
@@ -208,7 +297,7 @@ mod tests
         fn test_generational_pointer_array_double_free()
         {
             let mut gpa = GIABoxUninit::<Entity>::default();
-            let entity_handle = gpa.new(Entity::default());
+            let entity_handle = gpa.insert(Entity::default());
 
             gpa.free(&entity_handle);
             gpa.free(&entity_handle);
@@ -219,7 +308,7 @@ mod tests
         {
             let mut allocator = BoxAllocator::<Entity>::default();
     
-            let entity = allocator.new(
+            let entity = allocator.insert(
                 Entity {
                             name : "Example1".to_string(),
                             is_active : true,
@@ -238,13 +327,33 @@ mod tests
             assert!(!entity.is_live());
         }
 
+        #[test]
+        fn test_box_alloc_is_live_after_drop()
+        {
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let live = allocator.insert(Entity::default());
+            let freed = allocator.insert(Entity::default());
+            allocator.free(&freed);
+
+            drop(allocator);
+            assert!(!live.is_live(), "Pointers die with their allocator");
+            assert!(!freed.is_live());
+
+            // Headers get recycled by new allocators, old pointers must stay dead
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let new_entity = allocator.insert(Entity::default());
+            assert!(new_entity.is_live());
+            assert!(!live.is_live());
+            assert!(!freed.is_live());
+        }
+
         #[test]
         fn test_inplace_alloc_alloc()
         {
             let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
-            let entity_handle = inplace_alloc.new(Entity::default());
+            let entity_handle = inplace_alloc.insert(Entity::default());
             {
-                let entity_ref = inplace_alloc.get(&entity_handle);
+                let mut entity_ref = inplace_alloc.get(&entity_handle);
                 entity_ref.id = 42;
                 entity_ref.is_active = true;
                 entity_ref.name = "test".to_owned();
@@ -260,7 +369,7 @@ mod tests
         fn test_inplace_alloc_free()
         {
             let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
-            let entity_handle = inplace_alloc.new(Entity::default());
+            let entity_handle = inplace_alloc.insert(Entity::default());
             assert!(inplace_alloc.is_live(&entity_handle));
             inplace_alloc.free(&entity_handle);
             assert!(!inplace_alloc.is_live(&entity_handle));
@@ -271,13 +380,185 @@ mod tests
         fn test_inplace_alloc_get_free()
         {
             let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
-            let entity_handle = inplace_alloc.new(Entity::default());
+            let entity_handle = inplace_alloc.insert(Entity::default());
 
             assert!(inplace_alloc.is_live(&entity_handle));
             inplace_alloc.free(&entity_handle);
             inplace_alloc.get(&entity_handle); // boom
         }
 
+        #[test]
+        #[should_panic(expected = "already borrowed")]
+        fn test_inplace_alloc_get_twice_panics()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
+            let entity_handle = inplace_alloc.insert(Entity::default());
+
+            let _first = inplace_alloc.get(&entity_handle);
+            let _second = inplace_alloc.get(&entity_handle); // would alias the first
+        }
+
+
+        #[test]
+        fn test_try_get_reports_dead_and_borrowed()
+        {
+            use crate::errors::AccessError;
+            #[cfg(not(feature = "strict"))]
+            use crate::kyren_generational_indices::GenerationalIndex;
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let mut box_gia = GIABoxUninit::<u32>::default();
+            let mut uninit_gia = GIAUninitCell::<u32>::default();
+            let handles = [cell_gia.insert(1), box_gia.insert(1), uninit_gia.insert(1)];
+
+            {
+                let _cell_guard = cell_gia.try_get_mut(&handles[0]).unwrap();
+                let _box_guard = box_gia.try_get(&handles[1]).unwrap();
+                let _uninit_guard = uninit_gia.try_get_mut(&handles[2]).unwrap();
+                assert_eq!(cell_gia.try_get(&handles[0]).err(), Some(AccessError::AlreadyBorrowed));
+                assert_eq!(box_gia.try_get_mut(&handles[1]).err(), Some(AccessError::AlreadyBorrowed));
+                assert!(box_gia.try_get(&handles[1]).is_ok(), "Shared borrows can overlap");
+                assert_eq!(uninit_gia.try_get(&handles[2]).err(), Some(AccessError::AlreadyBorrowed));
+            }
+
+            cell_gia.free(&handles[0]);
+            box_gia.free(&handles[1]);
+            uninit_gia.free(&handles[2]);
+            #[cfg(not(feature = "strict"))]
+            assert!(matches!(cell_gia.try_get(&handles[0]), Err(AccessError::NotLive(_))));
+            #[cfg(not(feature = "strict"))]
+            assert!(matches!(box_gia.try_get_mut(&handles[1]), Err(AccessError::NotLive(_))));
+            #[cfg(not(feature = "strict"))]
+            assert!(matches!(uninit_gia.try_get(&handles[2]), Err(AccessError::NotLive(_))));
+
+            #[cfg(not(feature = "strict"))]
+            {
+                let foreign = GenerationalIndex::default();
+                let out_of_range = GIABoxUninit::<u32>::default();
+                assert!(matches!(out_of_range.try_get(&foreign), Err(AccessError::OutOfBounds(_))));
+            }
+        }
+
+        #[test]
+        fn test_borrow_map_projects_a_field()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            #[derive(Default)]
+            struct Named { name: String, hits: u32 }
+            let named = |name: &str| Named { name: name.to_string(), hits: 0 };
+
+            let mut cell_gia = GenerationalIndexArrayCell::default();
+            let mut box_gia = GIABoxUninit::default();
+            let mut uninit_gia = GIAUninitCell::default();
+            let handles = [cell_gia.insert(named("cell")), box_gia.insert(named("box")), uninit_gia.insert(named("uninit"))];
+
+            *cell_gia.borrow_map_mut(&handles[0], |e| &mut e.hits).unwrap() += 1;
+            *box_gia.borrow_map_mut(&handles[1], |e| &mut e.hits).unwrap() += 2;
+            *uninit_gia.borrow_map_mut(&handles[2], |e| &mut e.hits).unwrap() += 3;
+
+            let names = [
+                cell_gia.borrow_map(&handles[0], |e| e.name.as_str()).unwrap(),
+                box_gia.borrow_map(&handles[1], |e| e.name.as_str()).unwrap(),
+                uninit_gia.borrow_map(&handles[2], |e| e.name.as_str()).unwrap(),
+            ];
+            assert_eq!(names.iter().map(|name| &**name).collect::<Vec<_>>(), ["cell", "box", "uninit"]);
+            assert_eq!(*cell_gia.borrow_map(&handles[0], |e| &e.hits).unwrap(), 1);
+            assert_eq!(*uninit_gia.borrow_map(&handles[2], |e| &e.hits).unwrap(), 3);
+            // The projection still holds a shared borrow of the whole entity
+            assert!(box_gia.try_get_mut(&handles[1]).is_err());
+            drop(names);
+
+            box_gia.free(&handles[1]);
+            #[cfg(not(feature = "strict"))]
+            assert!(box_gia.borrow_map(&handles[1], |e| &e.hits).is_none());
+        }
+
+        #[test]
+        fn test_get_mut_with_exclusive_access()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut cell_gia = GenerationalIndexArrayCell::default();
+            let mut box_gia = GIABoxUninit::default();
+            let mut uninit_gia = GIAUninitCell::default();
+            let handles = [cell_gia.insert(1), box_gia.insert(2), uninit_gia.insert(3)];
+
+            *cell_gia.get_mut(&handles[0]).unwrap() += 10;
+            *box_gia.get_mut(&handles[1]).unwrap() += 10;
+            *uninit_gia.get_mut(&handles[2]).unwrap() += 10;
+            assert_eq!(*cell_gia.try_get(&handles[0]).unwrap(), 11);
+            assert_eq!(*box_gia.try_get(&handles[1]).unwrap(), 12);
+            assert_eq!(*uninit_gia.try_get(&handles[2]).unwrap(), 13);
+
+            cell_gia.free(&handles[0]);
+            box_gia.free(&handles[1]);
+            uninit_gia.free(&handles[2]);
+            #[cfg(not(feature = "strict"))]
+            assert!(cell_gia.get_mut(&handles[0]).is_none());
+            #[cfg(not(feature = "strict"))]
+            assert!(box_gia.get_mut(&handles[1]).is_none());
+            #[cfg(not(feature = "strict"))]
+            assert!(uninit_gia.get_mut(&handles[2]).is_none());
+        }
+
+        #[test]
+        fn test_lock_poisons_slot_on_panic()
+        {
+            use std::panic::{catch_unwind, AssertUnwindSafe};
+            use crate::errors::AccessError;
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut gia = GenerationalIndexArrayCell::default();
+            let handle = gia.insert(vec![1, 2]);
+            let other = gia.insert(vec![3]);
+
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                let mut guard = gia.lock(&handle).unwrap().unwrap();
+                guard.push(3);
+                panic!("Interrupted halfway through the update");
+            }));
+            assert!(result.is_err());
+
+            assert!(gia.is_poisoned(&handle));
+            assert!(!gia.is_poisoned(&other), "Only the slot being written is poisoned");
+            assert_eq!(gia.try_get(&handle).err(), Some(AccessError::Poisoned));
+            assert_eq!(gia.try_get_mut(&handle).err(), Some(AccessError::Poisoned));
+            // Like a Mutex, the value is still reachable through the error
+            assert_eq!(*gia.lock(&handle).unwrap().unwrap_err().into_inner(), vec![1, 2, 3]);
+
+            gia.clear_poison(&handle);
+            assert_eq!(*gia.try_get(&handle).unwrap(), vec![1, 2, 3]);
+            assert!(gia.lock(&handle).unwrap().is_ok());
+
+            // A panic after the guard is gone doesn't poison anything
+            let _ = catch_unwind(AssertUnwindSafe(|| {
+                gia.lock(&other).unwrap().unwrap().push(4);
+                panic!("Unrelated");
+            }));
+            assert!(!gia.is_poisoned(&other));
+
+            gia.free(&handle);
+            #[cfg(not(feature = "strict"))]
+            assert!(gia.lock(&handle).is_none());
+        }
+
+        #[test]
+        fn test_uninit_cell_gia_safe_borrow()
+        {
+            let mut uninit_cell_gia = GIAUninitCell::<Entity>::default();
+            let index = uninit_cell_gia.insert(Entity{name: "entity".to_string(), id: 0, is_active: false});
+
+            uninit_cell_gia.borrow_mut(&index).unwrap().id = 42;
+            assert_eq!(uninit_cell_gia.borrow(&index).unwrap().id, 42);
+
+            uninit_cell_gia.free(&index);
+            #[cfg(not(feature = "strict"))]
+            assert!(uninit_cell_gia.borrow(&index).is_none());
+            #[cfg(not(feature = "strict"))]
+            assert!(uninit_cell_gia.borrow_mut(&index).is_none());
+        }
 
         // Uninit Cell Generational array 
         #[test]
@@ -286,7 +567,7 @@ mod tests
             let mut uninit_cell_gia = GIAUninitCell::<Entity>::default();
             let entity1 = Entity{name: "entity1".to_string(), id: 0, is_active: true};
 
-            let idx1 = uninit_cell_gia.new(entity1);
+            let idx1 = uninit_cell_gia.insert(entity1);
 
             assert!(uninit_cell_gia.is_live(&idx1));
             
@@ -309,9 +590,9 @@ mod tests
         fn test_access_basic_allocator_with_pointers() {
             // Test that you can easily access entities and alter its values without crashing 
             // and without much boilerplate
-            let mut allocator = allocator_with_pointer::BoxAllocator::<Entity>::default();
-            let mut entity1 = allocator.new(Entity::default());
-            let mut entity2 = allocator.new(Entity::default());
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let mut entity1 = allocator.insert(Entity::default());
+            let mut entity2 = allocator.insert(Entity::default());
 
             // we can initialize both entities, no problem!
             entity1.id = 42;
@@ -325,14 +606,3561 @@ mod tests
         fn test_free_basic_allocator_with_pointers() {
             // Test that you can easily access entities and alter its values without crashing 
             // and without much boilerplate
-            let mut allocator = allocator_with_pointer::BoxAllocator::<Entity>::default();
-            let entity1 = allocator.new(Entity::default());
-            let entity2 = allocator.new(Entity::default());
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let entity1 = allocator.insert(Entity::default());
+            let entity2 = allocator.insert(Entity::default());
 
 
             allocator.free(&entity1);
             assert!(!entity1.is_live());
             assert!(entity2.is_live());
         }
+
+        #[cfg(debug_assertions)]
+        #[test]
+        fn test_owner_thread_check()
+        {
+            let mut gpa = GIABoxUninit::<Entity>::default();
+            let entity_handle = gpa.insert(Entity::default());
+
+            let result = std::thread::spawn(move || {
+                gpa.get(&entity_handle).is_some()
+            }).join();
+            assert!(result.is_err(), "Using the arena from another thread should panic");
+        }
+
+        #[test]
+        fn test_owner_thread_rebind()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
+            let entity_handle = inplace_alloc.insert(Entity::default());
+
+            let result = std::thread::spawn(move || {
+                inplace_alloc.rebind_owner_thread();
+                inplace_alloc.get(&entity_handle).id = 42;
+                inplace_alloc.free(&entity_handle);
+            }).join();
+            assert!(result.is_ok());
+        }
+
+        #[test]
+        fn test_owner_thread_claimed_by_mut_methods()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut box_gia = GIABoxUninit::<u32>::default();
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let mut uninit_gia = GIAUninitCell::<u32>::default();
+            let handles = [inplace_alloc.insert(1), box_gia.insert(2), cell_gia.insert(3), uninit_gia.insert(4)];
+
+            // Moving an arena is safe, and `&mut self` proves nobody else can reach it
+            let result = std::thread::spawn(move || {
+                *inplace_alloc.get_mut(&handles[0]).unwrap() += 10;
+                inplace_alloc.values_mut().for_each(|value| *value += 1);
+                assert_eq!(*inplace_alloc.try_get(&handles[0]).unwrap(), 12);
+                inplace_alloc.free(&handles[0]);
+                assert_eq!(box_gia.remove(&handles[1]), Some(2));
+                *cell_gia.get_mut(&handles[2]).unwrap() += 10;
+                assert_eq!(cell_gia.remove(&handles[2]), Some(13));
+                assert_eq!(uninit_gia.remove(&handles[3]), Some(4));
+            }).join();
+            assert!(result.is_ok());
+        }
+
+        #[cfg(feature = "track-free-sites")]
+        #[test]
+        #[should_panic(expected = "last freed at src/tests.rs")]
+        fn test_double_free_reports_first_free_site()
+        {
+            let mut gpa = GIABoxUninit::<Entity>::default();
+            let entity_handle = gpa.insert(Entity::default());
+
+            gpa.free(&entity_handle);
+            gpa.free(&entity_handle);
+        }
+
+        #[cfg(all(feature = "track-free-sites", debug_assertions))]
+        #[test]
+        #[should_panic(expected = "last freed at src/tests.rs")]
+        fn test_stale_get_reports_free_site()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
+            let entity_handle = inplace_alloc.insert(Entity::default());
+
+            inplace_alloc.free(&entity_handle);
+            inplace_alloc.get(&entity_handle);
+        }
+
+        #[cfg(feature = "poison")]
+        #[test]
+        #[should_panic(expected = "slot is free")]
+        fn test_inplace_alloc_get_poisoned()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<Entity>::default();
+            let entity_handle = inplace_alloc.insert(Entity::default());
+
+            inplace_alloc.free(&entity_handle);
+            inplace_alloc.get(&entity_handle); // freed slot is full of 0xDD, but the handle check stops the read
+        }
+
+        #[cfg(feature = "poison")]
+        #[test]
+        fn test_inplace_alloc_live_value_matching_poison()
+        {
+            use crate::poison::POISON_BYTE;
+
+            let mut inplace_alloc = InPlaceAllocator::<[u8; 8]>::default();
+            let handle = inplace_alloc.insert([POISON_BYTE; 8]);
+
+            // Only freeing poisons a slot, whatever its bytes look like
+            assert_eq!(*inplace_alloc.get(&handle), [POISON_BYTE; 8]);
+        }
+
+        #[cfg(feature = "poison")]
+        #[test]
+        fn test_array_free_poisons_slot()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArray;
+            use crate::poison::POISON_BYTE;
+
+            let mut array = GenerationalIndexArray::default();
+            let first = array.insert([0x11u8; 16]);
+            let second = array.insert([0x22u8; 16]);
+            let slots = [first.clone(), second.clone()].map(|handle| array.get(&handle).unwrap() as *const [u8; 16]);
+
+            // The entry stays in the array after free, so its bytes can still be inspected
+            array.free(&first);
+            assert_eq!(array.remove(&second), Some([0x22; 16]));
+            for slot in slots
+            {
+                assert_eq!(unsafe { *slot }, [POISON_BYTE; 16]);
+            }
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        #[test]
+        fn test_free_zeroes_secret_bytes()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArray;
+
+            const SECRET: [u8; 32] = [0xAB; 32];
+            let holds_secret = |bytes: &[u8]| bytes.windows(SECRET.len()).any(|window| window == SECRET);
+
+            let mut allocator = BoxAllocator::<[u8; 32]>::default();
+            let key = allocator.insert(SECRET);
+            // The entry stays allocated after free, so its bytes can still be inspected
+            let (entry, _) = key.as_raw();
+            let entry_bytes = || unsafe {
+                std::slice::from_raw_parts(entry as *const u8, std::mem::size_of::<Entry<[u8; 32]>>())
+            };
+            assert!(holds_secret(entry_bytes()));
+            allocator.free(&key);
+            assert!(!holds_secret(entry_bytes()), "Freed entry still holds the secret");
+
+            let mut array = GenerationalIndexArray::default();
+            let key = array.insert(SECRET);
+            let slot = array.get(&key).unwrap() as *const [u8; 32];
+            assert_eq!(array.remove(&key), Some(SECRET));
+            assert!(!holds_secret(unsafe { &*slot }), "Removed slot still holds the secret");
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        #[test]
+        fn test_compact_zeroes_vacated_slots()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArray;
+
+            let secret = |i: u8| [0xA0 | i; 32];
+            let mut array = GenerationalIndexArray::default();
+            let handles: Vec<_> = (0..6).map(|i| array.insert(secret(i))).collect();
+            array.free(&handles[0]);
+            array.free(&handles[1]);
+
+            // Compaction moves the two entries at the back into the holes up front
+            let vacated: Vec<_> = handles[4..].iter().map(|handle| array.get(handle).unwrap() as *const [u8; 32]).collect();
+            let compaction = array.compact();
+            assert_eq!(compaction.moved.len(), 2);
+            // Poisoning runs after zeroing when both features are on
+            #[cfg(feature = "poison")]
+            let cleared = [crate::poison::POISON_BYTE; 32];
+            #[cfg(not(feature = "poison"))]
+            let cleared = [0; 32];
+            for (i, slot) in vacated.iter().enumerate()
+            {
+                assert_eq!(unsafe { **slot }, cleared, "Vacated slot {} still holds its old bytes", i + 4);
+            }
+            assert_eq!(array.get(compaction.moved.get(&handles[5]).unwrap()), Some(&secret(5)));
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        #[test]
+        fn test_dense_arena_zeroes_vacated_tail()
+        {
+            use crate::dense_arena::DenseArena;
+
+            let mut dense = DenseArena::default();
+            let first = dense.insert([0xA1u8; 32]);
+            let last = dense.insert([0xA2u8; 32]);
+            let tail = dense.get(&last).unwrap() as *const [u8; 32];
+
+            // The last value moves into the hole, leaving its old position behind
+            dense.free(&first);
+            assert_eq!(unsafe { *tail }, [0; 32], "Vacated tail still holds the moved value");
+            assert_eq!(dense.get(&last), Some(&[0xA2; 32]));
+
+            let head = dense.get(&last).unwrap() as *const [u8; 32];
+            assert_eq!(dense.remove(&last), Some([0xA2; 32]));
+            assert_eq!(unsafe { *head }, [0; 32], "Removed value is still in the arena");
+        }
+
+        #[test]
+        fn test_entity_ptr_in_sets_and_sorted()
+        {
+            use std::collections::HashSet;
+
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let mut pointers: Vec<_> = (0..5).map(|_| allocator.insert(Entity::default())).collect();
+
+            // The slot header is atomic, but Hash and Eq only look at the address and generation
+            #[allow(clippy::mutable_key_type)]
+            let set: HashSet<_> = pointers.iter().collect();
+            assert_eq!(set.len(), 5);
+            assert!(set.contains(&pointers[3]));
+
+            pointers.reverse();
+            pointers.sort();
+            assert!(pointers.windows(2).all(|pair| pair[0] < pair[1]));
+
+            // Same entry, new generation: not the same entity
+            let freed = pointers.remove(0);
+            allocator.free(&freed);
+            let reused = allocator.insert(Entity::default());
+            assert!(freed != reused);
+        }
+
+        #[test]
+        fn test_entity_ptr_downgrade_upgrade()
+        {
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let first = allocator.insert(Entity::default());
+            let mut second = allocator.insert(Entity::default());
+            second.name = "second".to_string();
+
+            let handle = second.downgrade();
+            assert_eq!(handle.get_index(), 1);
+            let upgraded = unsafe { allocator.upgrade(&handle) }.unwrap();
+            assert!(upgraded == second);
+            assert_eq!(upgraded.name, "second");
+
+            allocator.free(&first);
+            assert!(unsafe { allocator.upgrade(&first.downgrade()) }.is_none(), "Freed entities can't be upgraded");
+            let reused = allocator.insert(Entity::default());
+            assert!(unsafe { allocator.upgrade(&first.downgrade()) }.is_none());
+            assert!(unsafe { allocator.upgrade(&reused.downgrade()) }.unwrap() == reused);
+            assert!(unsafe { allocator.upgrade(&GenerationalIndex::default()) }.is_none(), "Slot 0 moved to a new generation");
+        }
+
+        #[test]
+        fn test_box_alloc_iter()
+        {
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let pointers: Vec<_> = (0..4).map(|_| allocator.insert(Entity::default())).collect();
+            allocator.free(&pointers[1]);
+
+            for (_, entity) in allocator.iter_mut()
+            {
+                entity.is_active = true;
+            }
+
+            let live: Vec<_> = allocator.iter().collect();
+            assert_eq!(live.len(), 3);
+            for ((handle, entity), expected) in live.iter().zip([&pointers[0], &pointers[2], &pointers[3]])
+            {
+                assert_eq!(*handle, expected.downgrade());
+                assert!(entity.is_active && expected.is_active);
+            }
+        }
+
+        #[test]
+        fn test_box_alloc_free_all_and_trim()
+        {
+            use std::rc::Rc;
+
+            let counter = Rc::new(());
+            let mut allocator = BoxAllocator::<Rc<()>>::default();
+            let pointers: Vec<_> = (0..6).map(|_| allocator.insert(counter.clone())).collect();
+            let handles: Vec<_> = pointers.iter().map(|ptr| ptr.downgrade()).collect();
+
+            allocator.free_all();
+            assert_eq!(Rc::strong_count(&counter), 1, "free_all must drop every value");
+            assert!(pointers.iter().all(|ptr| !ptr.is_live()));
+            assert_eq!(allocator.stats().free, 6);
+
+            // Keep two entries around for reuse, the other four are released
+            assert_eq!(allocator.trim(2), 4);
+            assert_eq!(allocator.stats().capacity, 2);
+            assert_eq!(allocator.trim(2), 0);
+
+            // Growing back into trimmed positions doesn't resurrect old index handles
+            let fresh: Vec<_> = (0..6).map(|_| allocator.insert(counter.clone())).collect();
+            for handle in handles.iter()
+            {
+                assert!(unsafe { allocator.upgrade(handle) }.is_none());
+            }
+            assert!(fresh.iter().all(|ptr| unsafe { allocator.upgrade(&ptr.downgrade()) }.is_some()));
+            allocator.check_invariants();
+        }
+
+        #[test]
+        fn test_entity_ptr_raw_round_trip()
+        {
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let entity = allocator.insert(Entity::default());
+
+            let (raw, generation) = entity.as_raw();
+            let rebuilt = unsafe { EntityPtr::from_raw(raw, generation) }.unwrap();
+            assert!(rebuilt == entity);
+            assert_eq!(rebuilt.downgrade(), entity.downgrade());
+            assert!(unsafe { EntityPtr::<Entity>::from_raw(std::ptr::null_mut(), 0) }.is_none());
+
+            allocator.free(&entity);
+            assert!(!unsafe { EntityPtr::from_raw(raw, generation) }.unwrap().is_live());
+        }
+
+        #[test]
+        fn test_entity_ptr_try_get()
+        {
+            use crate::errors::{AccessError, SlotState};
+
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let mut entity = allocator.insert(Entity::default());
+            let mut other = allocator.insert(Entity::default());
+
+            entity.try_get_mut().unwrap().name = "changed".to_string();
+            assert_eq!(entity.try_get().map(|entity| entity.name.as_str()), Ok("changed"));
+
+            allocator.free(&entity);
+            assert!(matches!(entity.try_get(), Err(AccessError::NotLive(_))));
+            assert_eq!(entity.validate().unwrap_err().slot_state, SlotState::Unknown);
+            assert!(entity.try_get_mut().is_err());
+
+            drop(allocator);
+            assert!(other.try_get().is_err(), "Pointers die with their allocator");
+            assert!(other.try_get_mut().is_err());
+        }
+
+        #[test]
+        fn test_tagged_entity_ptr()
+        {
+            #[cfg(not(debug_assertions))]
+            assert_eq!(std::mem::size_of::<Option<TaggedEntityPtr<Entity>>>(), 8);
+
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let mut tagged = allocator.insert(Entity::default()).into_tagged().ok().unwrap();
+            unsafe
+            {
+                tagged.get_mut().unwrap().id = 7;
+                assert_eq!(tagged.get().map(|entity| entity.id), Some(7));
+            }
+
+            let ptr = unsafe { tagged.untag() };
+            assert_eq!(ptr.id, 7);
+            allocator.free(&ptr);
+            let tagged = ptr.into_tagged().ok().unwrap();
+            assert!(unsafe { !tagged.is_live() && tagged.get().is_none() });
+
+            // 2^16 frees later the tag matches again, which only debug builds notice
+            for _ in 0..u16::MAX
+            {
+                let ptr = allocator.insert(Entity::default());
+                allocator.free(&ptr);
+            }
+            let _reused = allocator.insert(Entity::default());
+            let collision = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe { tagged.is_live() }));
+            assert_eq!(collision.is_err(), cfg!(debug_assertions));
+        }
+
+        #[test]
+        #[cfg(not(feature = "strict"))]
+        fn test_try_variants_report_dead_handles()
+        {
+            use crate::errors::{AccessError, FreeError, HandleMismatch, SlotState};
+            use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut box_gia = GIABoxUninit::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut box_alloc = BoxAllocator::<u32>::default();
+            let handles = [array.insert(1), box_gia.insert(2), inplace_alloc.insert(3)];
+            let entity = box_alloc.insert(4);
+
+            *array.try_get_mut(&handles[0]).unwrap() += 10;
+            *inplace_alloc.try_get_mut(&handles[2]).unwrap() += 10;
+            assert_eq!(array.try_get(&handles[0]), Ok(&11));
+            assert_eq!(*inplace_alloc.try_get(&handles[2]).unwrap(), 13);
+            {
+                let _guard = inplace_alloc.try_get(&handles[2]).unwrap();
+                assert_eq!(inplace_alloc.try_get_mut(&handles[2]).err(), Some(AccessError::AlreadyBorrowed));
+            }
+
+            assert_eq!(array.try_free(&handles[0]), Ok(()));
+            assert_eq!(box_gia.try_free(&handles[1]), Ok(()));
+            assert_eq!(inplace_alloc.try_free(&handles[2]), Ok(()));
+            assert_eq!(box_alloc.try_free(&entity), Ok(()));
+
+            assert_eq!(array.try_free(&handles[0]), Err(FreeError::DeadHandle));
+            assert_eq!(box_gia.try_free(&handles[1]), Err(FreeError::DeadHandle));
+            assert_eq!(inplace_alloc.try_free(&handles[2]), Err(FreeError::DeadHandle));
+            assert_eq!(box_alloc.try_free(&entity), Err(FreeError::DeadHandle));
+            let mismatch = HandleMismatch { index: 0, expected_gen: 0, found_gen: Some(1), slot_state: SlotState::Free };
+            assert_eq!(array.try_get(&handles[0]), Err(AccessError::NotLive(mismatch)));
+            assert!(matches!(inplace_alloc.try_get(&handles[2]), Err(AccessError::NotLive(_))));
+
+            // Handles past the end fail instead of panicking on the bounds check
+            let foreign = GenerationalIndex{index: 10, generation: 0};
+            assert_eq!(array.try_free(&foreign), Err(FreeError::DeadHandle));
+            assert_eq!(box_gia.try_free(&foreign), Err(FreeError::DeadHandle));
+            assert_eq!(inplace_alloc.try_free(&foreign), Err(FreeError::DeadHandle));
+            assert!(array.get(&foreign).is_none());
+            assert!(matches!(array.try_get(&foreign), Err(AccessError::OutOfBounds(_))));
+            assert!(matches!(inplace_alloc.try_get_mut(&foreign), Err(AccessError::OutOfBounds(_))));
+            assert_eq!(box_gia.validate(&foreign).unwrap_err().slot_state, SlotState::OutOfRange);
+            assert_eq!(box_alloc.validate(&foreign).unwrap_err().found_gen, None);
+
+            // A newer value in the slot is reported as such
+            let _reused = array.insert(5);
+            assert_eq!(array.validate(&handles[0]).unwrap_err().slot_state, SlotState::Live);
+        }
+
+        #[test]
+        fn test_handle_mismatch_details()
+        {
+            use crate::errors::{HandleMismatch, SlotState};
+            use crate::kyren_generational_indices::{Generation, GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell};
+
+            let mut array = GenerationalIndexArray::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let mut uninit_gia = GIAUninitCell::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let mut box_gia = GIABoxUninit::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let mut inplace_alloc = InPlaceAllocator::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let last = array.insert(1);
+            assert_eq!(last, cell_gia.insert(1));
+            assert_eq!(last, uninit_gia.insert(1));
+            assert_eq!(last, box_gia.insert(1));
+            assert_eq!(last, inplace_alloc.insert(1));
+            array.free(&last);
+            cell_gia.free(&last);
+            uninit_gia.free(&last);
+            box_gia.free(&last);
+            inplace_alloc.free(&last);
+
+            // Every allocator reports the same details for the same history
+            let retired = HandleMismatch { index: 0, expected_gen: Generation::MAX - 1, found_gen: Some(Generation::MAX), slot_state: SlotState::Retired };
+            assert_eq!(array.validate(&last), Err(retired));
+            assert_eq!(cell_gia.validate(&last), Err(retired));
+            assert_eq!(uninit_gia.validate(&last), Err(retired));
+            assert_eq!(box_gia.validate(&last), Err(retired));
+            assert_eq!(inplace_alloc.validate(&last), Err(retired));
+
+            let next = GenerationalIndex { index: 1, generation: 0 };
+            assert_eq!(next, inplace_alloc.insert(2));
+            let stale = GenerationalIndex { index: 1, generation: 3 };
+            let newer = HandleMismatch { index: 1, expected_gen: 3, found_gen: Some(0), slot_state: SlotState::Live };
+            assert_eq!(inplace_alloc.validate(&stale), Err(newer));
+            assert_eq!(inplace_alloc.validate(&next), Ok(()));
+            assert_eq!(
+                newer.to_string(),
+                "handle to slot 1 at generation 3, slot is live at generation 0"
+            );
+            let past_end = HandleMismatch { index: 7, expected_gen: 0, found_gen: None, slot_state: SlotState::OutOfRange };
+            assert_eq!(inplace_alloc.validate(&GenerationalIndex { index: 7, generation: 0 }), Err(past_end));
+            assert_eq!(past_end.to_string(), "handle to slot 7 at generation 0, slot is out of range");
+        }
+
+        #[test]
+        #[should_panic(expected = "handle to slot 0 at generation 0, slot is free at generation 1")]
+        fn test_dead_handle_panic_carries_mismatch()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let handle = inplace_alloc.insert(1);
+            inplace_alloc.free(&handle);
+            inplace_alloc.get(&handle);
+        }
+
+        #[test]
+        #[cfg(not(feature = "strict"))]
+        fn test_try_get_tells_not_live_from_out_of_bounds()
+        {
+            use crate::errors::{AccessError, SlotState};
+            use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell};
+
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let mut uninit_gia = GIAUninitCell::<u32>::default();
+            let mut box_gia = GIABoxUninit::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let handle = array.insert(1);
+            assert_eq!(handle, cell_gia.insert(1));
+            assert_eq!(handle, uninit_gia.insert(1));
+            assert_eq!(handle, box_gia.insert(1));
+            assert_eq!(handle, inplace_alloc.insert(1));
+            array.free(&handle);
+            cell_gia.free(&handle);
+            uninit_gia.free(&handle);
+            box_gia.free(&handle);
+            inplace_alloc.free(&handle);
+
+            // A freed slot is NotLive, an index at or past the end is OutOfBounds
+            let at_end = GenerationalIndex { index: 1, generation: 0 };
+            let is_not_live = |result: Result<(), AccessError>| matches!(result, Err(AccessError::NotLive(mismatch)) if mismatch.slot_state == SlotState::Free);
+            let is_out_of_bounds = |result: Result<(), AccessError>| matches!(result, Err(AccessError::OutOfBounds(mismatch)) if mismatch.found_gen.is_none());
+            assert!(is_not_live(array.try_get(&handle).map(drop)));
+            assert!(is_not_live(cell_gia.try_get(&handle).map(drop)));
+            assert!(is_not_live(uninit_gia.try_get(&handle).map(drop)));
+            assert!(is_not_live(box_gia.try_get(&handle).map(drop)));
+            assert!(is_not_live(inplace_alloc.try_get(&handle).map(drop)));
+            assert!(is_out_of_bounds(array.try_get(&at_end).map(drop)));
+            assert!(is_out_of_bounds(cell_gia.try_get(&at_end).map(drop)));
+            assert!(is_out_of_bounds(uninit_gia.try_get(&at_end).map(drop)));
+            assert!(is_out_of_bounds(box_gia.try_get(&at_end).map(drop)));
+            assert!(is_out_of_bounds(inplace_alloc.try_get(&at_end).map(drop)));
+
+            // Once the slot is reused, the old handle is still NotLive, not a different value
+            let reused = inplace_alloc.insert(2);
+            assert_eq!(reused.index, handle.index);
+            assert!(matches!(inplace_alloc.try_get(&handle), Err(AccessError::NotLive(mismatch)) if mismatch.slot_state == SlotState::Live));
+            assert_eq!(*inplace_alloc.try_get(&reused).unwrap(), 2);
+            assert_eq!(
+                AccessError::OutOfBounds(inplace_alloc.validate(&at_end).unwrap_err()).to_string(),
+                "handle points past the end of the allocator: handle to slot 1 at generation 0, slot is out of range"
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to access a dead index")]
+        fn test_strict_get_panics_on_dead_handle()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArray;
+
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handle = array.insert(1);
+            array.free(&handle);
+            array.get(&handle);
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to access a dead index")]
+        fn test_strict_cell_get_panics_on_dead_handle()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let handle = cell_gia.insert(1);
+            cell_gia.free(&handle);
+            cell_gia.get(&handle);
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to remove an already dead index")]
+        fn test_strict_cell_remove_panics_on_dead_handle()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let handle = cell_gia.insert(1);
+            cell_gia.free(&handle);
+            cell_gia.remove(&handle);
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to free an already dead index: ")]
+        fn test_strict_cell_free_panics_on_double_free()
+        {
+            use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let handle = cell_gia.insert(1);
+            cell_gia.free(&handle);
+            cell_gia.free(&handle);
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to access a dead index")]
+        fn test_strict_uninit_cell_borrow_panics_on_dead_handle()
+        {
+            use crate::kyren_generational_indices::GIAUninitCell;
+
+            let mut uninit_gia = GIAUninitCell::<u32>::default();
+            let handle = uninit_gia.insert(1);
+            uninit_gia.free(&handle);
+            uninit_gia.borrow(&handle);
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to remove an already dead index")]
+        fn test_strict_uninit_cell_remove_panics_on_dead_handle()
+        {
+            use crate::kyren_generational_indices::GIAUninitCell;
+
+            let mut uninit_gia = GIAUninitCell::<u32>::default();
+            let handle = uninit_gia.insert(1);
+            uninit_gia.free(&handle);
+            uninit_gia.remove(&handle);
+        }
+
+        #[test]
+        #[cfg(feature = "strict")]
+        #[should_panic(expected = "Trying to free already unused index")]
+        fn test_strict_try_free_panics_on_double_free()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let handle = inplace_alloc.insert(1);
+            assert_eq!(inplace_alloc.try_free(&handle), Ok(()));
+            let _ = inplace_alloc.try_free(&handle);
+        }
+
+        #[test]
+        #[allow(deprecated)]
+        fn test_old_names_forward_to_insert()
+        {
+            use crate::generational_allocator::GenerationalAllocator;
+            use crate::kyren_generational_indices::GenerationalIndexArray;
+
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let old = array.new(1);
+            let tried = array.try_new(2).unwrap();
+            array.free(&old);
+            let reused = array.try_new_no_grow(3).unwrap();
+            assert_eq!(reused.get_index(), old.get_index());
+            assert_eq!(array.get(&tried), Some(&2));
+
+            // Generic code gets the same name through the trait
+            fn store<A: GenerationalAllocator<u32>>(allocator: &mut A) -> GenerationalIndex
+            {
+                allocator.insert(4)
+            }
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let handle = store(&mut inplace_alloc);
+            assert_eq!(*inplace_alloc.get(&handle), 4);
+            let handle = GenerationalAllocator::allocate(&mut inplace_alloc, 5);
+            assert_eq!(*inplace_alloc.get(&handle), 5);
+        }
+
+        // Checked in release builds too, dereferencing a dead pointer is never UB
+        #[test]
+        #[should_panic(expected = "Trying to deref free pointer")]
+        fn test_box_alloc_deref_freed()
+        {
+            let mut allocator = BoxAllocator::<Entity>::default();
+            let entity = allocator.insert(Entity::default());
+
+            allocator.free(&entity);
+            let _id = entity.id;
+        }
+
+        #[test]
+        fn test_remove_hands_back_values()
+        {
+            let mut gpa = GIABoxUninit::<String>::default();
+            let handle = gpa.insert("boxed".to_string());
+            assert_eq!(gpa.remove(&handle).as_deref(), Some("boxed"));
+            assert!(!gpa.is_live(&handle));
+
+            let mut inplace_alloc = InPlaceAllocator::<String>::default();
+            let handle = inplace_alloc.insert("in place".to_string());
+            assert_eq!(inplace_alloc.remove(&handle).as_deref(), Some("in place"));
+            let reused = inplace_alloc.insert("reused".to_string());
+            assert_eq!(*inplace_alloc.get(&reused), "reused");
+
+            let mut box_alloc = BoxAllocator::<String>::default();
+            let entity = box_alloc.insert("entity".to_string());
+            assert_eq!(box_alloc.remove(&entity).as_deref(), Some("entity"));
+            assert!(!entity.is_live());
+
+            let mut uninit_gia = GIAUninitCell::<String>::default();
+            let handle = uninit_gia.insert("cell".to_string());
+            assert_eq!(uninit_gia.remove(&handle).as_deref(), Some("cell"));
+
+            // Dead handles get None back, unless `strict` turns that into a panic
+            #[cfg(not(feature = "strict"))]
+            {
+                assert_eq!(gpa.remove(&handle), None);
+                assert_eq!(uninit_gia.remove(&handle), None);
+            }
+        }
+
+        #[test]
+        fn test_in_place_raw_parts()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<u64>::default();
+            let handles: Vec<_> = (0..70).map(|i| inplace_alloc.insert(i)).collect();
+            for handle in handles.iter().step_by(3)
+            {
+                inplace_alloc.free(handle);
+            }
+
+            let (generations, values, stride) = inplace_alloc.as_raw_parts();
+            let occupancy = inplace_alloc.occupancy();
+            assert_eq!(generations.len(), 70);
+            let mut sum = 0;
+            for (index, generation) in generations.iter().enumerate()
+            {
+                if occupancy.get(index / 64).is_some_and(|word| word & (1 << (index % 64)) != 0)
+                {
+                    assert_eq!(*generation, 0);
+                    sum += unsafe { *values.byte_add(index * stride) };
+                }
+            }
+            assert_eq!(sum, (0..70).filter(|i| i % 3 != 0).sum::<u64>());
+
+            let empty = InPlaceAllocator::<u64>::default();
+            assert!(empty.as_raw_parts().0.is_empty());
+        }
+
+        #[test]
+        fn test_in_place_validate_many()
+        {
+            use crate::bit_vec::BitVec;
+
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut handles: Vec<_> = (0..150).map(|i| inplace_alloc.insert(i)).collect();
+            for handle in handles.iter().step_by(3)
+            {
+                inplace_alloc.free(handle);
+            }
+            // Reused slot, and handles past the end of the allocator
+            handles.push(inplace_alloc.insert(150));
+            handles.extend((150..160).map(|index| GenerationalIndex { index, generation: 0 }));
+
+            let mut live = BitVec::default();
+            inplace_alloc.validate_many(&handles[..3], &mut live);
+            inplace_alloc.validate_many(&handles, &mut live);
+            assert_eq!(live.len(), handles.len());
+            assert_eq!(live.words().len(), 3);
+            for (i, handle) in handles.iter().enumerate()
+            {
+                assert_eq!(live.get(i), Some(inplace_alloc.is_live(handle)), "Handle {} ({:?})", i, handle);
+            }
+            assert_eq!(live.get(handles.len()), None);
+            assert_eq!(live.count_ones(), 101);
+            assert_eq!(live.iter_ones().count(), 101);
+
+            inplace_alloc.validate_many(&[], &mut live);
+            assert!(live.is_empty());
+
+            // Nothing was ever inserted, so there is no occupancy word to read
+            let empty = InPlaceAllocator::<u32>::default();
+            empty.validate_many(&handles[..70], &mut live);
+            assert_eq!(live.len(), 70);
+            assert_eq!(live.count_ones(), 0);
+        }
+
+        #[test]
+        fn test_in_place_exact_capacity_never_moves()
+        {
+            use crate::errors::AllocError;
+
+            let mut inplace_alloc = InPlaceAllocator::<u64>::with_exact_capacity(4);
+            assert!(inplace_alloc.is_fixed_capacity());
+            assert_eq!(inplace_alloc.stats().free, 4);
+
+            let first = inplace_alloc.insert(1);
+            assert_eq!(first.get_index(), 0, "Lowest slot first");
+            let ptr = inplace_alloc.value_ptr(&first).unwrap();
+            let handles: Vec<_> = (2..5).map(|i| inplace_alloc.insert(i)).collect();
+            assert!(matches!(inplace_alloc.try_insert(5), Err(AllocError::NoFreeSlot(5))));
+            assert_eq!(unsafe { *ptr.as_ptr() }, 1, "Filling up didn't move the first slot");
+
+            inplace_alloc.free(&handles[0]);
+            let reused = inplace_alloc.insert(6);
+            assert_eq!(reused.get_index(), handles[0].get_index());
+            assert!(inplace_alloc.value_ptr(&handles[0]).is_none());
+            assert_eq!(inplace_alloc.stats().capacity, 4);
+            inplace_alloc.check_invariants();
+            assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| inplace_alloc.insert(7))).is_err());
+            assert!(!InPlaceAllocator::<u64>::default().is_fixed_capacity());
+        }
+
+        #[test]
+        fn test_dense_export_of_live_values()
+        {
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut gia = GenerationalIndexArray::<u32>::default();
+            for i in 0..10
+            {
+                let in_place = inplace_alloc.insert(i);
+                let in_array = gia.insert(i);
+                if i % 2 == 0
+                {
+                    inplace_alloc.free(&in_place);
+                    gia.free(&in_array);
+                }
+            }
+
+            let mut out = vec![99; 20];
+            inplace_alloc.copy_live_to(&mut out);
+            assert_eq!(out, [1, 3, 5, 7, 9]);
+            gia.copy_live_to(&mut out);
+            assert_eq!(out, [1, 3, 5, 7, 9]);
+
+            #[cfg(feature = "bytemuck")]
+            {
+                let mut bytes = [0xff; 24];
+                assert_eq!(inplace_alloc.write_live_bytes(&mut bytes), 20);
+                assert_eq!(bytemuck::cast_slice::<u8, u32>(&bytes[..20]), [1, 3, 5, 7, 9]);
+                assert_eq!(bytes[20..], [0xff; 4], "Bytes past the live values are left alone");
+                assert_eq!(gia.write_live_bytes(&mut bytes), 20);
+                assert_eq!(bytemuck::cast_slice::<u8, u32>(&bytes[..20]), [1, 3, 5, 7, 9]);
+                assert!(std::panic::catch_unwind(|| gia.write_live_bytes(&mut [0; 19])).is_err());
+            }
+        }
+
+        #[test]
+        fn test_is_live_is_total()
+        {
+            use crate::kyren_generational_indices::{GenerationalIndexArray, GenerationalIndexArrayCell, GenerationalIndices};
+
+            // Default, past the end, the next generation of a freed slot, and whatever sits
+            // right after the last slot with the generation nothing is ever given
+            let handles = [
+                GenerationalIndex::default(),
+                GenerationalIndex { index: 1000, generation: 0 },
+                GenerationalIndex { index: 0, generation: 1 },
+                GenerationalIndex { index: 1, generation: crate::kyren_generational_indices::Generation::MAX },
+            ];
+
+            let mut gia = GenerationalIndexArray::<u32>::default();
+            let mut cell_gia = GenerationalIndexArrayCell::<u32>::default();
+            let mut uninit_gia = GIAUninitCell::<u32>::default();
+            let mut gpa = GIABoxUninit::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut box_alloc = BoxAllocator::<u32>::default();
+            let mut indices = GenerationalIndices::default();
+            assert!(!indices.is_live(&handles[1]));
+
+            let handle = gia.insert(1);
+            gia.free(&handle);
+            let handle = cell_gia.insert(1);
+            cell_gia.free(&handle);
+            let handle = uninit_gia.insert(1);
+            uninit_gia.free(&handle);
+            let handle = gpa.insert(1);
+            gpa.free(&handle);
+            let handle = inplace_alloc.insert(1);
+            inplace_alloc.free(&handle);
+            let entity = box_alloc.insert(1);
+            box_alloc.free(&entity);
+            let handle = indices.new();
+            indices.free(&handle);
+
+            for handle in handles.iter()
+            {
+                assert!(!gia.is_live(handle));
+                assert!(!cell_gia.is_live(handle));
+                assert!(!uninit_gia.is_live(handle));
+                assert!(!gpa.is_live(handle));
+                assert!(!inplace_alloc.is_live(handle));
+                assert!(!box_alloc.is_live(handle));
+                assert!(unsafe { box_alloc.upgrade(handle) }.is_none());
+            }
+            assert!(!indices.is_live(&handles[1]));
+            gpa.check_invariants();
+            uninit_gia.check_invariants();
+        }
+    }
+    mod validated_arena_tests
+    {
+        use crate::generational_allocator::{AllocatorStats, GenerationalAllocator};
+        use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray, GenerationalIndexArrayCell, GIAUninitCell};
+        use crate::dense_arena::DenseArena;
+        use crate::memory_allocators::{GIABoxUninit, InPlaceAllocator};
+        use crate::option_vec_adapter::OptionVecAdapter;
+        use crate::change_tracking::ChangeTrackedArena;
+        use crate::op_log::RecordingArena;
+        use crate::recycling_arena::RecyclingArena;
+        use crate::validated_arena::ValidatedArena;
+
+        fn exercise<A: GenerationalAllocator<String> + Default>()
+        {
+            let mut arena = ValidatedArena::<String, A>::default();
+
+            let first = arena.insert("first".to_string());
+            let second = arena.insert("second".to_string());
+            assert!(arena.is_live(&first));
+
+            arena.with_mut(&second, |name| name.push_str("-edited"));
+            assert_eq!(arena.with(&second, |name| name.clone()).unwrap(), "second-edited");
+
+            arena.free(&first);
+            assert!(!arena.is_live(&first));
+            #[cfg(not(feature = "strict"))]
+            assert!(arena.with(&first, |name| name.clone()).is_none());
+
+            let third = arena.insert("third".to_string());
+            assert!(!arena.is_live(&first));
+            assert!(arena.is_live(&third));
+            arena.check_invariants();
+
+            let stats = arena.stats();
+            assert_eq!(stats.capacity, 2, "The freed slot should have been reused");
+            assert_eq!(stats.live, 2);
+            assert_eq!(stats.free, 0);
+
+            // The accessors every allocator shares
+            arena.get_mut(&third).unwrap().push_str("-edited");
+            assert_eq!(*arena.get(&third).unwrap(), "third-edited");
+            assert_eq!(arena.remove(&second).as_deref(), Some("second-edited"));
+            assert!(!arena.is_live(&second));
+            #[cfg(not(feature = "strict"))]
+            assert!(arena.remove(&second).is_none());
+            arena.check_invariants();
+        }
+
+        #[test]
+        fn test_validated_every_allocator()
+        {
+            exercise::<GenerationalIndexArray<String>>();
+            exercise::<GenerationalIndexArrayCell<String>>();
+            exercise::<GIAUninitCell<String>>();
+            exercise::<GIABoxUninit<String>>();
+            exercise::<InPlaceAllocator<String>>();
+            exercise::<DenseArena<String>>();
+            exercise::<OptionVecAdapter<String>>();
+            exercise::<RecyclingArena<String>>();
+            exercise::<ChangeTrackedArena<String>>();
+            exercise::<RecordingArena<String>>();
+        }
+
+        /// Broken allocator that forgets to bump generations when reusing a slot
+        #[derive(Default)]
+        struct NoGenerationBump
+        {
+            inner: GenerationalIndexArray<String>,
+            free: Vec<usize>
+        }
+
+        impl GenerationalAllocator<String> for NoGenerationBump
+        {
+            type Ref<'a> = &'a String;
+            type RefMut<'a> = &'a mut String;
+
+            fn insert(&mut self, element: String) -> GenerationalIndex
+            {
+                match self.free.pop()
+                {
+                    Some(index) => GenerationalIndex { index, generation: 0 },
+                    None => self.inner.insert(element)
+                }
+            }
+
+            fn free(&mut self, index: &GenerationalIndex)
+            {
+                self.free.push(index.get_index());
+            }
+
+            fn remove(&mut self, index: &GenerationalIndex) -> Option<String>
+            {
+                self.free.push(index.get_index());
+                self.inner.get(index).cloned()
+            }
+
+            fn is_live(&self, index: &GenerationalIndex) -> bool
+            {
+                self.inner.is_live(index)
+            }
+
+            fn get(&self, index: &GenerationalIndex) -> Option<&String>
+            {
+                self.inner.get(index)
+            }
+
+            fn get_mut(&mut self, index: &GenerationalIndex) -> Option<&mut String>
+            {
+                self.inner.get_mut(index)
+            }
+
+            fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&String) -> R) -> Option<R>
+            {
+                self.inner.get(index).map(f)
+            }
+
+            fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut String) -> R) -> Option<R>
+            {
+                self.inner.get_mut(index).map(f)
+            }
+
+            fn stats(&self) -> AllocatorStats
+            {
+                self.inner.stats()
+            }
+
+            fn check_invariants(&self)
+            {
+                self.inner.check_invariants();
+            }
+        }
+
+        #[test]
+        #[should_panic(expected = "still live after free")]
+        fn test_validated_catches_broken_allocator()
+        {
+            let mut arena = ValidatedArena::<String, NoGenerationBump>::default();
+            let index = arena.insert("entity".to_string());
+            arena.free(&index);
+        }
+    }
+    mod fault_injection_tests
+    {
+        use crate::copy_arena::CopyArena;
+        use crate::errors::AllocError;
+        use crate::fault_injection;
+        use crate::kyren_generational_indices::{GenerationalArrayEntry, GenerationalIndexArray};
+        use crate::memory_allocators::{BoxAllocator, InPlaceAllocator};
+
+        #[test]
+        fn test_fail_nth_allocation()
+        {
+            let mut array = GenerationalIndexArray::<String>::default();
+            let first = array.try_insert("first".to_string()).unwrap();
+
+            fault_injection::fail_nth_allocation(2);
+            let second = array.try_insert("second".to_string()).unwrap();
+            let failed = array.try_insert("third".to_string());
+            assert_eq!(failed.unwrap_err(), AllocError::OutOfMemory("third".to_string()), "The element should be handed back");
+
+            // A failed growth leaves the array untouched and usable
+            let third = array.try_insert("third".to_string()).unwrap();
+            assert_eq!(third.get_index(), 2);
+            assert_eq!(array.get(&first).unwrap(), "first");
+            assert_eq!(array.get(&second).unwrap(), "second");
+            assert_eq!(array.get(&third).unwrap(), "third");
+        }
+
+        #[test]
+        fn test_alloc_error_hands_back_through_wrappers()
+        {
+            let mut copy = CopyArena::<u32>::default();
+            fault_injection::fail_nth_allocation(1);
+            let error = copy.try_insert(7).unwrap_err();
+            assert_eq!(error, AllocError::OutOfMemory(7));
+            assert_eq!(error.to_string(), "out of memory while allocating a new entry");
+
+            let boxed: Box<dyn std::error::Error> = Box::new(error);
+            assert!(boxed.to_string().starts_with("out of memory"));
+            assert_eq!(error.into_inner(), 7);
+            assert_eq!(error.map(|value| value * 2), AllocError::OutOfMemory(14));
+        }
+
+        #[test]
+        fn test_fail_after_bytes()
+        {
+            let mut array = GenerationalIndexArray::<u64>::default();
+            fault_injection::fail_after_bytes(2 * std::mem::size_of::<GenerationalArrayEntry<u64>>());
+
+            let first = array.try_insert(1).unwrap();
+            let _ = array.try_insert(2).unwrap();
+            assert!(array.try_insert(3).is_err());
+            assert!(array.try_insert(4).is_err());
+
+            // Reusing a free slot doesn't allocate, so it can't fail
+            array.free(&first);
+            assert!(array.try_insert(5).is_ok());
+            fault_injection::reset();
+            assert!(array.try_insert(6).is_ok());
+        }
+
+        #[test]
+        fn test_fail_box_and_inplace_allocators()
+        {
+            let mut box_alloc = BoxAllocator::<String>::default();
+            let mut inplace_alloc = InPlaceAllocator::<String>::default();
+
+            fault_injection::fail_nth_allocation(1);
+            assert!(box_alloc.try_insert("entity".to_string()).is_err());
+            fault_injection::fail_nth_allocation(1);
+            assert!(inplace_alloc.try_insert("entity".to_string()).is_err());
+
+            let entity = box_alloc.try_insert("entity".to_string()).unwrap();
+            let handle = inplace_alloc.try_insert("entity".to_string()).unwrap();
+            assert_eq!(entity.as_str(), "entity");
+            assert_eq!(inplace_alloc.get(&handle).as_str(), "entity");
+        }
+
+        #[test]
+        #[should_panic(expected = "Out of memory")]
+        fn test_new_panics_on_failed_growth()
+        {
+            let mut array = GenerationalIndexArray::<u64>::default();
+            fault_injection::fail_nth_allocation(1);
+            let _ = array.insert(42);
+        }
+    }
+    mod generation_exhaustion_tests
+    {
+        use crate::dense_arena::DenseArena;
+        use crate::generational_allocator::GenerationalAllocator;
+        use crate::kyren_generational_indices::{Generation, GenerationalIndices, GenerationalIndexArray, GenerationalIndexArrayCell, GIAUninitCell};
+        use crate::memory_allocators::{BoxAllocator, GIABoxUninit, InPlaceAllocator};
+        use crate::option_vec_adapter::OptionVecAdapter;
+        use crate::repr_c_arena::ReprCArena;
+
+        /// Expects an allocator with a single free slot at `Generation::MAX - 1`
+        fn check_retirement<A: GenerationalAllocator<u32>>(mut allocator: A)
+        {
+            let last = allocator.insert(1);
+            assert_eq!(last.get_index(), 0);
+            assert_eq!(last.get_generation(), Generation::MAX - 1);
+
+            // The slot reaches MAX, which is never handed out, so it gets retired
+            allocator.free(&last);
+            assert!(!allocator.is_live(&last));
+
+            let next = allocator.insert(2);
+            assert_eq!(next.get_index(), 1, "Retired slots must not be reused");
+            assert_eq!(next.get_generation(), 0);
+            assert!(!allocator.is_live(&last));
+            assert_eq!(allocator.with(&next, |value| *value), Some(2));
+        }
+
+        #[test]
+        fn test_retirement_every_allocator()
+        {
+            check_retirement(GenerationalIndexArray::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(GenerationalIndexArrayCell::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(GIAUninitCell::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(GIABoxUninit::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(InPlaceAllocator::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(DenseArena::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(ReprCArena::with_free_slots_at(1, Generation::MAX - 1));
+            check_retirement(OptionVecAdapter::with_free_slots_at(1, Generation::MAX - 1));
+        }
+
+        #[test]
+        fn test_retirement_generational_indices()
+        {
+            let mut indices = GenerationalIndices::with_free_slots_at(1, Generation::MAX - 1);
+            let last = indices.new();
+            indices.free(&last);
+
+            let next = indices.new();
+            assert_eq!(next.get_index(), 1);
+            assert!(!indices.is_live(&last));
+        }
+
+        #[test]
+        fn test_retirement_recycling_arena()
+        {
+            use crate::recycling_arena::RecyclingArena;
+
+            let mut arena = RecyclingArena::<String>::with_free_slots_at(1, Generation::MAX - 1);
+            let last = arena.insert_with(|name| name.push_str("last"));
+            arena.free(&last);
+
+            // The retired slot can't hand its value out again
+            let next = arena.insert_with(|name| assert_eq!(name.capacity(), 0));
+            assert_eq!(next.get_index(), 1);
+            assert!(!arena.is_live(&last));
+            assert_eq!(arena.stats().retired, 1);
+            arena.check_invariants();
+        }
+
+        #[test]
+        fn test_retirement_box_allocator()
+        {
+            let mut allocator = BoxAllocator::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let last = allocator.insert(1);
+            allocator.free(&last);
+            assert!(!last.is_live());
+
+            let next = allocator.insert(2);
+            assert!(next.is_live());
+            assert!(!last.is_live());
+            assert_eq!(*next, 2);
+
+            allocator.check_invariants();
+            assert_eq!(allocator.stats().retired, 1);
+            assert_eq!(allocator.stats().live, 1);
+        }
+    }
+    mod iteration_tests
+    {
+        use crate::dense_arena::DenseArena;
+        use crate::errors::AllocError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::memory_allocators::InPlaceAllocator;
+
+        #[test]
+        fn test_cursor_frees_entries_while_iterating()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..10).map(|i| array.insert(i)).collect();
+            array.free(&handles[3]);
+
+            let mut removed = Vec::new();
+            let mut cursor = array.cursor_mut();
+            assert!(cursor.current().is_none());
+            while let Some((handle, value)) = cursor.next()
+            {
+                *value += 100;
+                if handle.get_index() % 2 == 0
+                {
+                    removed.push(cursor.remove_current());
+                    assert!(cursor.current().is_none());
+                }
+            }
+
+            assert_eq!(removed, vec![100, 102, 104, 106, 108]);
+            assert_eq!(array.values().copied().collect::<Vec<_>>(), vec![101, 105, 107, 109]);
+            assert!(!array.is_live(&handles[0]));
+            array.check_invariants();
+        }
+
+        #[test]
+        fn test_retain_mut_updates_survivors()
+        {
+            let mut timers = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = [3, 1, 2, 1].into_iter().map(|ticks| timers.insert(ticks)).collect();
+
+            timers.retain_mut(|_, ticks| {
+                *ticks -= 1;
+                *ticks > 0
+            });
+
+            assert_eq!(timers.iter().map(|(handle, ticks)| (handle.get_index(), *ticks)).collect::<Vec<_>>(), vec![(0, 2), (2, 1)]);
+            assert!(!timers.is_live(&handles[1]) && !timers.is_live(&handles[3]));
+            timers.retain_mut(|handle, _| handle != handles[2]);
+            assert_eq!(timers.handles(), vec![handles[0].clone()]);
+            timers.check_invariants();
+        }
+
+        #[test]
+        fn test_extract_if_moves_matching_entries()
+        {
+            let mut level = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..10).map(|i| level.insert(i)).collect();
+
+            let mut other = GenerationalIndexArray::<u32>::default();
+            let moved: Vec<_> = level.extract_if(|_, value| *value >= 7).map(|(old, value)| (old, other.insert(value))).collect();
+
+            assert_eq!(moved.iter().map(|(old, _)| old.clone()).collect::<Vec<_>>(), handles[7..].to_vec());
+            assert_eq!(other.values().copied().collect::<Vec<_>>(), vec![7, 8, 9]);
+            assert_eq!(level.values().copied().collect::<Vec<_>>(), (0..7).collect::<Vec<_>>());
+
+            // Dropping the iterator early leaves the entries it didn't reach alone
+            assert_eq!(level.extract_if(|_, value| *value % 2 == 0).next().map(|(_, value)| value), Some(0));
+            assert_eq!(level.values().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+            level.check_invariants();
+        }
+
+        #[test]
+        #[cfg(feature = "insertion-order")]
+        fn test_insertion_order_survives_slot_reuse()
+        {
+            let mut array = GenerationalIndexArray::<&str>::default();
+            let handles: Vec<_> = ["a", "b", "c", "d", "e"].into_iter().map(|name| array.insert(name)).collect();
+            array.free(&handles[1]);
+            array.free(&handles[3]);
+            let _ = array.insert("f");
+            let _ = array.insert("g");
+            array.free(&handles[0]);
+
+            // Reuse put the newer entries in front of the older ones
+            assert_eq!(array.values().copied().collect::<Vec<_>>(), vec!["f", "c", "g", "e"]);
+            assert_eq!(array.iter_in_insertion_order().map(|(_, name)| *name).collect::<Vec<_>>(), vec!["c", "e", "f", "g"]);
+            array.check_invariants();
+
+            // Compaction moves entries without changing their place in the order
+            array.compact();
+            assert_eq!(array.iter_in_insertion_order().map(|(_, name)| *name).collect::<Vec<_>>(), vec!["c", "e", "f", "g"]);
+            array.check_invariants();
+
+            let (_, rest, _, _) = array.partition(|name| *name == "f");
+            assert_eq!(rest.values().copied().collect::<Vec<_>>(), vec!["c", "e", "g"]);
+            rest.check_invariants();
+        }
+
+        #[test]
+        fn test_find_skips_dead_entries()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+
+            let array_handles: Vec<_> = (0..10).map(|i| array.insert(i)).collect();
+            let inplace_handles: Vec<_> = (0..10).map(|i| inplace_alloc.insert(i)).collect();
+            let dense_handles: Vec<_> = (0..10).map(|i| dense.insert(i)).collect();
+            array.free(&array_handles[4]);
+            inplace_alloc.free(&inplace_handles[4]);
+            dense.free(&dense_handles[4]);
+
+            assert_eq!(array.find(|value| *value >= 4), Some((array_handles[5].clone(), &5)));
+            assert_eq!(inplace_alloc.find(|value| *value >= 4), Some((inplace_handles[5].clone(), &5)));
+            assert_eq!(dense.find(|value| *value == 5).map(|(handle, _)| handle), Some(dense_handles[5].clone()));
+            assert_eq!(array.find(|value| *value > 9), None);
+
+            let half = |value: &u32| (value % 2 == 1 && *value > 2).then(|| value / 2);
+            assert_eq!(array.find_map(half), Some((array_handles[3].clone(), 1)));
+            assert_eq!(inplace_alloc.find_map(half), Some((inplace_handles[3].clone(), 1)));
+            assert_eq!(dense.find_map(|value| (*value == 7).then_some("seven")), Some((dense_handles[7].clone(), "seven")));
+        }
+
+        #[test]
+        fn test_sample_picks_distinct_live_entries()
+        {
+            use std::collections::HashSet;
+
+            // xorshift64, enough to drive the sampling
+            let mut state = 0x2545_f491_4f6c_dd1d_u64;
+            let mut rng = move || {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            };
+
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+            let inplace_handles: Vec<_> = (0..1000).map(|i| inplace_alloc.insert(i)).collect();
+            let dense_handles: Vec<_> = (0..1000).map(|i| dense.insert(i)).collect();
+            for i in (0..1000).filter(|i| i % 10 != 0)
+            {
+                inplace_alloc.free(&inplace_handles[i]);
+                dense.free(&dense_handles[i]);
+            }
+
+            let mut drawn = HashSet::new();
+            for _ in 0..50
+            {
+                let picked = inplace_alloc.sample(&mut rng, 10);
+                assert_eq!(picked.len(), 10);
+                assert!(picked.windows(2).all(|pair| pair[0].get_index() < pair[1].get_index()), "Samples come in slot order, without repeats");
+                assert!(picked.iter().all(|handle| inplace_alloc.is_live(handle)));
+                drawn.extend(picked.iter().map(|handle| handle.get_index()));
+
+                let picked = dense.sample(&mut rng, 10);
+                assert_eq!(picked.iter().collect::<HashSet<_>>().len(), 10);
+                assert!(picked.iter().all(|handle| dense.is_live(handle)));
+            }
+            assert!(drawn.len() > 90, "500 draws out of 100 live entries should reach almost all of them");
+
+            assert_eq!(inplace_alloc.sample(&mut rng, 500), inplace_alloc.handles(), "Asking for more than there is returns everything");
+            assert!(DenseArena::<u32>::default().sample(&mut rng, 3).is_empty());
+        }
+
+        #[test]
+        fn test_iter_free_lists_slots_in_reuse_order()
+        {
+            use crate::kyren_generational_indices::GenerationalIndices;
+
+            let mut indices = GenerationalIndices::default();
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+
+            let index_handles: Vec<_> = (0..5).map(|_| indices.new()).collect();
+            let array_handles: Vec<_> = (0..5).map(|i| array.insert(i)).collect();
+            let inplace_handles: Vec<_> = (0..5).map(|i| inplace_alloc.insert(i)).collect();
+            let dense_handles: Vec<_> = (0..5).map(|i| dense.insert(i)).collect();
+            for i in [3, 1]
+            {
+                indices.free(&index_handles[i]);
+                array.free(&array_handles[i]);
+                inplace_alloc.free(&inplace_handles[i]);
+                dense.free(&dense_handles[i]);
+            }
+
+            // The kyren arrays reuse the oldest free slot, the others the newest one
+            assert_eq!(indices.iter_free().collect::<Vec<_>>(), vec![(3, 1), (1, 1)]);
+            assert_eq!(array.iter_free().collect::<Vec<_>>(), vec![(3, 1), (1, 1)]);
+            assert_eq!(inplace_alloc.iter_free().collect::<Vec<_>>(), vec![(1, 1), (3, 1)]);
+            assert_eq!(dense.iter_free().collect::<Vec<_>>(), vec![(1, 1), (3, 1)]);
+
+            assert_eq!(indices.new().get_index(), 3);
+            assert_eq!(array.insert(0).get_index(), 3);
+            assert_eq!(inplace_alloc.insert(0).get_index(), 1);
+            assert_eq!(dense.insert(0).get_index(), 1);
+            assert_eq!(array.iter_free().collect::<Vec<_>>(), vec![(1, 1)]);
+        }
+
+        #[test]
+        fn test_iterators_know_their_length_and_run_backwards()
+        {
+            use crate::memory_allocators::BoxAllocator;
+
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut box_alloc = BoxAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+
+            // Spread over several bitset words, with whole dead words in between
+            let array_handles: Vec<_> = (0..300).map(|i| array.insert(i)).collect();
+            let inplace_handles: Vec<_> = (0..300).map(|i| inplace_alloc.insert(i)).collect();
+            let box_ptrs: Vec<_> = (0..300).map(|i| box_alloc.insert(i)).collect();
+            let dense_handles: Vec<_> = (0..300).map(|i| dense.insert(i)).collect();
+            let kept = |i: &u32| i.is_multiple_of(7) && !(64..192).contains(i);
+            for i in (0..300).filter(|i| !kept(i))
+            {
+                array.free(&array_handles[i as usize]);
+                inplace_alloc.free(&inplace_handles[i as usize]);
+                box_alloc.free(&box_ptrs[i as usize]);
+                dense.free(&dense_handles[i as usize]);
+            }
+
+            let expected: Vec<u32> = (0..300).filter(kept).collect();
+            let reversed: Vec<u32> = expected.iter().rev().copied().collect();
+            assert_eq!(array.iter().len(), expected.len());
+            assert_eq!(array.values().rev().copied().collect::<Vec<_>>(), reversed);
+            assert_eq!(array.values_mut().len(), expected.len());
+            assert_eq!(inplace_alloc.values().len(), expected.len());
+            assert_eq!(inplace_alloc.values().rev().copied().collect::<Vec<_>>(), reversed);
+            assert_eq!(inplace_alloc.values_mut().rev().map(|value| *value).collect::<Vec<_>>(), reversed);
+            assert_eq!(box_alloc.iter().rev().map(|(_, value)| *value).collect::<Vec<_>>(), reversed);
+            assert_eq!(box_alloc.iter_mut().len(), expected.len());
+            assert_eq!(dense.iter().len(), expected.len());
+
+            // Both ends meet in the middle without yielding an entry twice
+            let mut iter = inplace_alloc.values();
+            let mut from_both_ends = Vec::new();
+            while let (Some(front), back) = (iter.next(), iter.next_back())
+            {
+                from_both_ends.push(*front);
+                from_both_ends.extend(back.copied());
+                assert_eq!(iter.len(), expected.len() - from_both_ends.len());
+            }
+            from_both_ends.sort();
+            assert_eq!(from_both_ends, expected);
+        }
+
+        #[test]
+        fn test_chunks_mut_split_live_slots_between_threads()
+        {
+            let mut allocator = InPlaceAllocator::<u32>::default();
+            let handles: Vec<_> = (0..100).map(|i| allocator.insert(i)).collect();
+            for handle in handles.iter().filter(|handle| handle.get_index() % 4 == 0)
+            {
+                allocator.free(handle);
+            }
+
+            let mut chunks = allocator.chunks_mut(3);
+            assert_eq!(chunks.len(), 3);
+            assert_eq!(chunks.iter().map(|chunk| chunk.slots()).collect::<Vec<_>>(), vec![0..34, 34..68, 68..100]);
+            std::thread::scope(|scope| {
+                for chunk in chunks.iter_mut()
+                {
+                    scope.spawn(move || {
+                        for (handle, value) in chunk.iter_mut()
+                        {
+                            assert_eq!(*value as usize, handle.get_index());
+                            *value *= 10;
+                        }
+                    });
+                }
+            });
+
+            let expected: Vec<u32> = (0..100).filter(|i| i % 4 != 0).map(|i| i * 10).collect();
+            assert_eq!(allocator.values().copied().collect::<Vec<_>>(), expected);
+            assert_eq!(allocator.chunks_mut(500).len(), 100, "Chunks never come back empty");
+        }
+
+        #[test]
+        fn test_iter_skips_dead_entries()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+
+            let array_handles: Vec<_> = (0..100).map(|i| array.insert(i)).collect();
+            let inplace_handles: Vec<_> = (0..100).map(|i| inplace_alloc.insert(i)).collect();
+            let dense_handles: Vec<_> = (0..100).map(|i| dense.insert(i)).collect();
+            for i in (0..100).filter(|i| i % 3 != 0)
+            {
+                array.free(&array_handles[i]);
+                inplace_alloc.free(&inplace_handles[i]);
+                dense.free(&dense_handles[i]);
+            }
+
+            let expected: Vec<u32> = (0..100).filter(|i| i % 3 == 0).collect();
+            assert_eq!(array.iter().map(|(_, value)| *value).collect::<Vec<_>>(), expected);
+            assert_eq!(inplace_alloc.iter().map(|(_, value)| *value).collect::<Vec<_>>(), expected);
+
+            let mut dense_values: Vec<u32> = dense.iter().map(|(_, value)| *value).collect();
+            dense_values.sort();
+            assert_eq!(dense_values, expected);
+
+            // Handles yielded by iteration are the live ones
+            for (handle, value) in inplace_alloc.iter()
+            {
+                assert_eq!(handle, inplace_handles[*value as usize]);
+            }
+            for (handle, value) in dense.iter()
+            {
+                assert_eq!(dense.get(&handle), Some(value));
+            }
+        }
+
+        #[test]
+        fn test_clone_keeps_handles_valid()
+        {
+            let mut array = GenerationalIndexArray::<String>::default();
+            let mut inplace_alloc = InPlaceAllocator::<String>::default();
+            let mut handles = Vec::new();
+            for i in 0..6
+            {
+                handles.push((array.insert(i.to_string()), inplace_alloc.insert(i.to_string())));
+            }
+            array.free(&handles[2].0);
+            inplace_alloc.free(&handles[2].1);
+
+            let mut array_copy = array.clone();
+            let mut inplace_copy = inplace_alloc.clone();
+            for (i, (array_handle, inplace_handle)) in handles.iter().enumerate().filter(|(i, _)| *i != 2)
+            {
+                assert_eq!(array_copy.get(array_handle), Some(&i.to_string()));
+                assert_eq!(*inplace_copy.get(inplace_handle), i.to_string());
+            }
+            assert!(!array_copy.is_live(&handles[2].0));
+            assert!(!inplace_copy.is_live(&handles[2].1));
+
+            // Both copies reuse the same free slot the original would
+            assert_eq!(array_copy.insert("new".to_string()), array.insert("new".to_string()));
+            assert_eq!(inplace_copy.insert("new".to_string()), inplace_alloc.insert("new".to_string()));
+            inplace_copy.check_invariants();
+        }
+
+        #[test]
+        fn test_arena_equality_and_content_hash()
+        {
+            use std::collections::hash_map::DefaultHasher;
+
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let array_handles: Vec<_> = (0..5).map(|i| array.insert(i)).collect();
+            let inplace_handles: Vec<_> = (0..5).map(|i| inplace_alloc.insert(i)).collect();
+            array.free(&array_handles[1]);
+            inplace_alloc.free(&inplace_handles[1]);
+
+            let mut array_copy = array.clone();
+            let inplace_copy = inplace_alloc.clone();
+            assert!(array_copy == array && inplace_copy == inplace_alloc);
+            assert_eq!(array_copy.content_hash(DefaultHasher::new()), array.content_hash(DefaultHasher::new()));
+            assert_eq!(inplace_copy.content_hash(DefaultHasher::new()), inplace_alloc.content_hash(DefaultHasher::new()));
+
+            // Same values, but the slot went through one more generation
+            let handle = array_copy.insert(1);
+            array_copy.free(&handle);
+            let _ = array.insert(1);
+            assert!(array_copy != array);
+
+            *inplace_copy.get(&inplace_handles[3]) = 30;
+            assert!(inplace_copy != inplace_alloc);
+            assert_ne!(inplace_copy.content_hash(DefaultHasher::new()), inplace_alloc.content_hash(DefaultHasher::new()));
+        }
+
+        #[test]
+        fn test_try_new_no_grow_only_reuses_free_slots()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+            assert_eq!(array.try_insert_no_grow(1), Err(AllocError::NoFreeSlot(1)));
+            assert_eq!(inplace_alloc.try_insert_no_grow(1), Err(AllocError::NoFreeSlot(1)));
+            assert_eq!(dense.try_insert_no_grow(1), Err(AllocError::NoFreeSlot(1)));
+
+            let array_handle = array.insert(1);
+            let inplace_handle = inplace_alloc.insert(1);
+            let dense_handle = dense.insert(1);
+            array.free(&array_handle);
+            inplace_alloc.free(&inplace_handle);
+            dense.free(&dense_handle);
+
+            assert_eq!(array.try_insert_no_grow(2).unwrap().get_index(), array_handle.get_index());
+            assert_eq!(inplace_alloc.try_insert_no_grow(2).unwrap().get_index(), inplace_handle.get_index());
+            assert_eq!(dense.try_insert_no_grow(2).unwrap().get_index(), dense_handle.get_index());
+            assert_eq!(array.try_insert_no_grow(3), Err(AllocError::NoFreeSlot(3)));
+            assert_eq!(array.stats().capacity, 1);
+        }
+
+        #[test]
+        fn test_values_skip_dead_slots()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            for i in 0..10
+            {
+                let array_handle = array.insert(i);
+                let inplace_handle = inplace_alloc.insert(i);
+                if i % 2 == 1
+                {
+                    array.free(&array_handle);
+                    inplace_alloc.free(&inplace_handle);
+                }
+            }
+
+            for value in array.values_mut()
+            {
+                *value *= 10;
+            }
+            for value in inplace_alloc.values_mut()
+            {
+                *value *= 10;
+            }
+
+            let expected = vec![0, 20, 40, 60, 80];
+            assert_eq!(array.values().copied().collect::<Vec<_>>(), expected);
+            assert_eq!(inplace_alloc.values().copied().collect::<Vec<_>>(), expected);
+        }
+
+        #[test]
+        fn test_handles_snapshot_survives_mutation()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut inplace_alloc = InPlaceAllocator::<u32>::default();
+            let mut dense = DenseArena::<u32>::default();
+            for i in 0..10
+            {
+                let handle = array.insert(i);
+                let _ = inplace_alloc.insert(i);
+                let _ = dense.insert(i);
+                if i % 2 == 0
+                {
+                    array.free(&handle);
+                }
+            }
+
+            // Free everything through the snapshot while allocating new entries
+            for handle in array.handles()
+            {
+                array.free(&handle);
+                let _ = array.insert(100);
+            }
+            for handle in inplace_alloc.handles()
+            {
+                inplace_alloc.free(&handle);
+            }
+            for handle in dense.handles()
+            {
+                dense.free(&handle);
+            }
+
+            assert_eq!(array.iter().map(|(_, value)| *value).collect::<Vec<_>>(), vec![100; 5]);
+            assert_eq!(inplace_alloc.stats().live, 0);
+            assert!(dense.is_empty());
+        }
+
+        #[test]
+        fn test_dense_arena_free_keeps_values_packed()
+        {
+            let mut dense = DenseArena::<&str>::default();
+            let first = dense.insert("first");
+            let second = dense.insert("second");
+            let third = dense.insert("third");
+
+            dense.free(&first);
+            assert_eq!(dense.values(), &["third", "second"]);
+            assert_eq!(dense.get(&third), Some(&"third"));
+            assert_eq!(dense.get(&second), Some(&"second"));
+            assert_eq!(dense.get(&first), None);
+            dense.check_invariants();
+        }
+    }
+    mod keyed_arena_tests
+    {
+        use crate::keyed_arena::KeyedArena;
+
+        #[test]
+        fn test_keyed_arena_insert_get_remove()
+        {
+            let mut sessions = KeyedArena::<String, u32>::new();
+            let (handle, previous) = sessions.insert("alice".to_string(), 1);
+            assert_eq!(previous, None);
+            let _ = sessions.insert("bob".to_string(), 2);
+
+            assert_eq!(sessions.get_by_key(&"alice".to_string()), Some(&1));
+            assert_eq!(sessions.get(&handle), Some(&1));
+            assert_eq!(sessions.handle_of(&"alice".to_string()), Some(handle.clone()));
+
+            assert_eq!(sessions.remove_by_key(&"alice".to_string()), Some(1));
+            assert_eq!(sessions.remove_by_key(&"alice".to_string()), None);
+            assert!(!sessions.is_live(&handle));
+            assert_eq!(sessions.len(), 1);
+            sessions.check_invariants();
+        }
+
+        #[test]
+        fn test_keyed_arena_overwrite_invalidates_old_handle()
+        {
+            let mut assets = KeyedArena::<&str, &str>::new();
+            let (old, _) = assets.insert("texture", "v1");
+            let (new, previous) = assets.insert("texture", "v2");
+
+            assert_eq!(previous, Some("v1"));
+            assert!(!assets.is_live(&old), "Stale handle must not see the new value");
+            assert_eq!(assets.get(&new), Some(&"v2"));
+            assert_eq!(assets.get_by_key(&"texture"), Some(&"v2"));
+            assert_eq!(assets.len(), 1);
+            assets.check_invariants();
+        }
+    }
+    mod handle_map_tests
+    {
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+
+        #[test]
+        fn test_compact_step_without_moves_keeps_free_order()
+        {
+            // Slots 0 and 1 live, 3 and 2 freed in that order: nothing can move forward
+            let build = || {
+                let mut array = GenerationalIndexArray::<u32>::default();
+                let handles: Vec<_> = (0..4).map(|i| array.insert(i)).collect();
+                array.free(&handles[3]);
+                array.free(&handles[2]);
+                array
+            };
+            let mut untouched = build();
+            let mut stepped = build();
+            assert!(stepped.compact_step(0).is_empty());
+            assert!(stepped.compact_step(8).is_empty());
+
+            for value in 0..2
+            {
+                assert_eq!(stepped.insert(value).get_index(), untouched.insert(value).get_index());
+            }
+
+            // A zero budget moves nothing even when there is something to move
+            let mut array = build();
+            let first = array.handles()[0].clone();
+            array.free(&first);
+            assert!(array.compact_step(0).is_empty());
+            assert!(!array.compact_step(1).is_empty());
+        }
+
+        #[test]
+        fn test_compact_step_moves_entries_to_the_front()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut handles: Vec<_> = (0..10).map(|i| array.insert(i)).collect();
+            for handle in handles.drain(..3)
+            {
+                array.free(&handle);
+            }
+            let stale = handles.clone();
+
+            let mut steps = 0;
+            loop
+            {
+                let moved = array.compact_step(2);
+                if moved.is_empty()
+                {
+                    break;
+                }
+                assert!(moved.len() <= 2, "Moved more entries than the budget allows");
+                for handle in handles.iter_mut()
+                {
+                    moved.remap(handle);
+                }
+                steps += 1;
+            }
+
+            assert_eq!(steps, 2);
+            for (value, handle) in (3..10).zip(handles.iter())
+            {
+                assert_eq!(array.get(handle), Some(&value));
+                assert!(handle.get_index() < 7, "Entry {} wasn't moved to the front", value);
+            }
+            assert!(!array.contains(&stale[6]), "Handles to moved entries must go stale");
+            array.check_invariants();
+        }
+
+        #[test]
+        fn test_compact_skips_pinned_slots()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..6).map(|i| array.insert(i)).collect();
+            array.free(&handles[0]);
+            array.free(&handles[1]);
+            assert!(array.pin_slot(&handles[5]));
+            assert!(!array.pin_slot(&handles[0]), "Dead handles can't be pinned");
+
+            let compaction = array.compact();
+            assert_eq!(compaction.pinned, vec![handles[5].clone()]);
+            assert_eq!(compaction.moved.len(), 2, "Entries 3 and 4 fill the holes, 5 stays");
+            assert_eq!(array.get(&handles[5]), Some(&5));
+            assert_eq!(array.get(compaction.moved.get(&handles[4]).unwrap()), Some(&4));
+
+            assert!(array.unpin_slot(&handles[5]));
+            let compaction = array.compact();
+            assert!(compaction.pinned.is_empty());
+            assert_eq!(array.get(compaction.moved.get(&handles[5]).unwrap()), Some(&5));
+            array.check_invariants();
+        }
+
+        #[test]
+        fn test_absorb_remaps_merged_entries()
+        {
+            let mut world = GenerationalIndexArray::<&str>::default();
+            let tree = world.insert("tree");
+            let rock = world.insert("rock");
+            world.free(&rock);
+
+            let mut chunk = GenerationalIndexArray::<&str>::default();
+            let mut chunk_handles = [chunk.insert("house"), chunk.insert("well")];
+
+            let moves = world.absorb(chunk);
+            assert_eq!(moves.len(), 2);
+            for handle in chunk_handles.iter_mut()
+            {
+                assert!(moves.remap(handle));
+            }
+            assert_eq!(world.get(&chunk_handles[0]), Some(&"house"));
+            assert_eq!(world.get(&chunk_handles[1]), Some(&"well"));
+            assert_eq!(world.get(&tree), Some(&"tree"));
+            world.check_invariants();
+        }
+
+        #[test]
+        fn test_partition_splits_live_entries()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..10).map(|i| array.insert(i)).collect();
+            array.free(&handles[4]);
+
+            let (even, odd, even_moves, odd_moves) = array.partition(|value| value % 2 == 0);
+            assert_eq!(even.values().copied().collect::<Vec<_>>(), vec![0, 2, 6, 8]);
+            assert_eq!(odd.values().copied().collect::<Vec<_>>(), vec![1, 3, 5, 7, 9]);
+            assert_eq!(even_moves.len() + odd_moves.len(), 9);
+            assert!(even_moves.get(&handles[4]).is_none() && odd_moves.get(&handles[4]).is_none());
+
+            for (value, handle) in handles.iter().enumerate().filter(|(value, _)| *value != 4)
+            {
+                let (arena, moves) = if value % 2 == 0 { (&even, &even_moves) } else { (&odd, &odd_moves) };
+                assert_eq!(arena.get(moves.get(handle).unwrap()), Some(&(value as u32)));
+            }
+            even.check_invariants();
+            odd.check_invariants();
+        }
+
+        #[test]
+        fn test_deferred_free_waits_for_ticks()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..5).map(|i| array.insert(i)).collect();
+            array.free_deferred(&handles[1], 2);
+            array.free_deferred(&handles[2], 1);
+            array.free_deferred(&handles[3], 0);
+            assert!(!array.contains(&handles[3]));
+
+            assert!(array.contains(&handles[1]) && array.is_free_pending(&handles[1]));
+            assert_eq!(array.tick(), vec![handles[2].clone()]);
+            assert_eq!(array.get(&handles[1]), Some(&1), "Still readable until its delay elapses");
+            assert_eq!(array.tick(), vec![handles[1].clone()]);
+            assert!(!array.contains(&handles[1]) && !array.is_free_pending(&handles[1]));
+            assert!(array.tick().is_empty());
+
+            // Freed early, then the pending free is dropped quietly
+            array.free_deferred(&handles[4], 1);
+            array.free(&handles[4]);
+            assert!(array.tick().is_empty());
+            array.check_invariants();
+        }
+
+        #[test]
+        fn test_deferred_free_follows_compaction()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..4).map(|i| array.insert(i)).collect();
+            array.free(&handles[0]);
+            array.free_deferred(&handles[3], 1);
+
+            let moved = array.compact();
+            let new = moved.moved.lookup(&handles[3]).unwrap();
+            assert!(array.is_free_pending(&new));
+            assert_eq!(array.tick(), vec![new]);
+            assert_eq!(array.stats().live, 2);
+        }
+
+        #[test]
+        fn test_handle_maps_compose()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let mut handles: Vec<_> = (0..8).map(|i| array.insert(i)).collect();
+            array.free(&handles[1]);
+            array.free(&handles[2]);
+            let first = array.compact_step(1);
+            array.free(&handles[0]);
+            let second = array.compact_step(usize::MAX);
+            assert!(!first.is_empty() && !second.is_empty());
+
+            // Same result as remapping through each map in turn
+            let both = first.then(&second);
+            for handle in handles.iter()
+            {
+                let mut stepwise = handle.clone();
+                first.remap(&mut stepwise);
+                second.remap(&mut stepwise);
+                assert_eq!(both.lookup(handle).unwrap_or_else(|| handle.clone()), stepwise);
+            }
+
+            handles.drain(..3);
+            assert_eq!(both.apply_to(&mut handles), 3);
+            for (handle, value) in handles.iter().zip([3, 4, 5, 6, 7])
+            {
+                assert_eq!(array.get(handle), Some(&value));
+            }
+            array.check_invariants();
+        }
+    }
+    mod atomic_entity_ptr_tests
+    {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        use crate::atomic_entity_ptr::AtomicEntityPtr;
+        use crate::memory_allocators::BoxAllocator;
+
+        #[test]
+        fn test_atomic_entity_ptr_store_and_compare_exchange()
+        {
+            let mut allocator = BoxAllocator::<u32>::default();
+            let first = allocator.insert(1);
+            let second = allocator.insert(2);
+
+            let slot = AtomicEntityPtr::empty();
+            assert!(slot.load_handle().is_none() && !slot.is_live());
+            assert!(slot.compare_exchange(None, Some(&first)).is_ok());
+            // Only this thread loads, and `allocator` outlives the pointer
+            assert_eq!(unsafe { slot.load() }.unwrap().try_get(), Ok(&1));
+
+            // Fails while holding `first`, and reports it
+            let actual = slot.compare_exchange(Some(&second), None).unwrap_err();
+            assert_eq!(actual, Some(first.downgrade()));
+
+            allocator.free(&first);
+            assert!(!slot.is_live(), "The slot still knows when its entity dies");
+            assert_eq!(slot.load_handle(), Some(first.downgrade()));
+            assert!(slot.compare_exchange(Some(&first), Some(&second)).is_ok());
+            slot.store(None);
+            assert!(slot.load_handle().is_none());
+        }
+
+        #[test]
+        fn test_atomic_entity_ptr_loads_are_never_torn()
+        {
+            let mut allocator = BoxAllocator::<u32>::default();
+            let old = allocator.insert(0);
+            let first = allocator.insert(1);
+            allocator.free(&old);
+            // Same entry as `old` with a newer generation, so a torn read would mix them up
+            let second = allocator.insert(2);
+            let expected = [first.downgrade(), second.downgrade()];
+
+            let slot = Arc::new(AtomicEntityPtr::new(&first));
+            let done = Arc::new(AtomicBool::new(false));
+            let readers: Vec<_> = (0..4).map(|_| {
+                let (slot, done, expected) = (slot.clone(), done.clone(), expected.clone());
+                std::thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed)
+                    {
+                        let handle = slot.load_handle().unwrap();
+                        assert!(expected.contains(&handle), "Torn read: {:?}", handle);
+                    }
+                })
+            }).collect();
+
+            for i in 0..20_000
+            {
+                slot.store(Some(if i % 2 == 0 { &second } else { &first }));
+            }
+            done.store(true, Ordering::Relaxed);
+            for reader in readers
+            {
+                reader.join().unwrap();
+            }
+        }
+
+        #[test]
+        fn test_atomic_entity_ptr_read_optimistic()
+        {
+            use crate::errors::AccessError;
+
+            let mut allocator = BoxAllocator::<[u64; 4]>::default();
+            let first = allocator.insert([1; 4]);
+            let slot = Arc::new(AtomicEntityPtr::new(&first));
+            assert_eq!(unsafe { slot.read_optimistic(|value| value[0]) }, Some(Ok(1)));
+            allocator.free(&first);
+            assert!(matches!(unsafe { slot.read_optimistic(|value| value[0]) }, Some(Err(AccessError::NotLive(_)))));
+            assert_eq!(unsafe { AtomicEntityPtr::<[u64; 4]>::empty().read_optimistic(|value| value[0]) }, None);
+
+            // The same entry is freed and reused with a new value over and over, reads that
+            // succeed must never see half of one value and half of another
+            let done = Arc::new(AtomicBool::new(false));
+            let readers: Vec<_> = (0..4).map(|_| {
+                let (slot, done) = (slot.clone(), done.clone());
+                std::thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed)
+                    {
+                        // The allocator outlives the readers and nothing writes to live entries
+                        if let Some(Ok(value)) = unsafe { slot.read_optimistic(|value| *value) }
+                        {
+                            assert!(value.iter().all(|part| *part == value[0]), "Torn read: {:?}", value);
+                        }
+                    }
+                })
+            }).collect();
+
+            for i in 0..20_000
+            {
+                let entity = allocator.insert([i; 4]);
+                slot.store(Some(&entity));
+                allocator.free(&entity);
+            }
+            done.store(true, Ordering::Relaxed);
+            for reader in readers
+            {
+                reader.join().unwrap();
+            }
+        }
+
+        #[test]
+        fn test_atomic_entity_ptr_read_optimistic_during_reuse_on_other_thread()
+        {
+            // Byte sized parts, so the copy goes a byte at a time
+            let slot = Arc::new(AtomicEntityPtr::<[u8; 7]>::empty());
+            let done = Arc::new(AtomicBool::new(false));
+            let reader_done = Arc::new(AtomicBool::new(false));
+
+            // The allocator lives on the writer, which frees and reuses the entry while this
+            // thread reads it, and is only dropped once the reads are over
+            let writer = {
+                let (slot, done, reader_done) = (slot.clone(), done.clone(), reader_done.clone());
+                std::thread::spawn(move || {
+                    let mut allocator = BoxAllocator::<[u8; 7]>::default();
+                    let mut entity = allocator.insert([0; 7]);
+                    for i in 0..20_000u32
+                    {
+                        slot.store(Some(&entity));
+                        allocator.free(&entity);
+                        entity = allocator.insert([i as u8; 7]);
+                    }
+                    done.store(true, Ordering::Release);
+                    while !reader_done.load(Ordering::Acquire)
+                    {
+                        std::thread::yield_now();
+                    }
+                })
+            };
+
+            while !done.load(Ordering::Acquire)
+            {
+                if let Some(Ok(value)) = unsafe { slot.read_optimistic(|value| *value) }
+                {
+                    assert!(value.iter().all(|part| *part == value[0]), "Torn read: {:?}", value);
+                }
+            }
+            reader_done.store(true, Ordering::Release);
+            writer.join().unwrap();
+        }
+    }
+    #[cfg(feature = "rwlock-slots")]
+    mod rwlock_arena_tests
+    {
+        use crate::errors::AccessError;
+        use crate::rwlock_arena::RwLockArena;
+
+        #[test]
+        fn test_rwlock_arena_readers_share_writers_exclude()
+        {
+            let mut arena = RwLockArena::default();
+            let a = arena.insert(1);
+            let b = arena.insert(2);
+
+            {
+                let _first = arena.read(&a).unwrap();
+                let _second = arena.try_read(&a).unwrap();
+                assert_eq!(arena.try_write(&a).err(), Some(AccessError::AlreadyBorrowed));
+                // Other slots have their own lock
+                *arena.try_write(&b).unwrap() += 10;
+            }
+
+            assert_eq!(*arena.read(&b).unwrap(), 12);
+            assert_eq!(arena.remove(&a), Some(1));
+            assert!(!arena.is_live(&a));
+            #[cfg(not(feature = "strict"))]
+            assert!(arena.read(&a).is_none());
+            #[cfg(not(feature = "strict"))]
+            assert!(matches!(arena.try_write(&a), Err(AccessError::NotLive(_))));
+            arena.check_invariants();
+        }
+
+        #[test]
+        fn test_padded_rwlock_arena_slots_take_a_cache_line()
+        {
+            use crate::cache_padded::{CachePadded, CACHE_LINE};
+            use crate::rwlock_arena::PaddedRwLockArena;
+
+            assert_eq!(std::mem::size_of::<std::sync::RwLock<CachePadded<u64>>>() % CACHE_LINE, 0);
+
+            let mut arena = PaddedRwLockArena::default();
+            let a = arena.insert(CachePadded::new(1u64));
+            let b = arena.insert(CachePadded::new(2u64));
+            **arena.write(&a).unwrap() += 10;
+
+            let (first, second) = (arena.read(&a).unwrap(), arena.read(&b).unwrap());
+            let distance = (&**second as *const u64 as usize).abs_diff(&**first as *const u64 as usize);
+            assert_eq!((**first, **second), (11, 2));
+            assert!(distance >= CACHE_LINE, "Neighbouring slots share a cache line");
+        }
+
+        #[test]
+        fn test_rwlock_arena_shared_between_threads()
+        {
+            let mut arena = RwLockArena::default();
+            let handles: Vec<_> = (0..8).map(|_| arena.insert(0u64)).collect();
+
+            std::thread::scope(|scope| {
+                for handle in &handles
+                {
+                    let (arena, shared) = (&arena, &handles[0]);
+                    scope.spawn(move || {
+                        for _ in 0..1000
+                        {
+                            *arena.write(handle).unwrap() += 1;
+                            let _ = *arena.read(shared).unwrap();
+                        }
+                    });
+                }
+            });
+
+            for handle in &handles
+            {
+                assert_eq!(*arena.read(handle).unwrap(), 1000);
+            }
+        }
+    }
+    mod ghost_arena_tests
+    {
+        use crate::ghost_arena::{GhostArena, GhostToken};
+
+        #[test]
+        fn test_ghost_arena_mutates_through_shared_arena()
+        {
+            GhostToken::new(|mut token| {
+                let mut arena = GhostArena::default();
+                let a = arena.insert(1);
+                let b = arena.insert(2);
+
+                let shared = &arena;
+                let also_shared = &arena;
+                *shared.get_mut(&a, &mut token).unwrap() += 10;
+                *also_shared.get_mut(&b, &mut token).unwrap() += 20;
+                // Any number of reads can overlap while the token is shared
+                let (first, second) = (shared.get(&a, &token).unwrap(), also_shared.get(&b, &token).unwrap());
+                assert_eq!((*first, *second), (11, 22));
+
+                assert_eq!(arena.remove(&a), Some(11));
+                #[cfg(not(feature = "strict"))]
+                assert!(arena.get(&a, &token).is_none());
+                #[cfg(not(feature = "strict"))]
+                assert!(arena.get_mut(&a, &mut token).is_none());
+                let c = arena.insert(3);
+                assert_eq!(c.get_index(), a.get_index(), "Slot should be reused");
+                assert!(!arena.is_live(&a));
+                arena.check_invariants();
+            });
+        }
+    }
+    mod copy_arena_tests
+    {
+        use crate::copy_arena::CopyArena;
+        use crate::generational_allocator::GenerationalAllocator;
+
+        #[test]
+        fn test_copy_arena_get_set_update()
+        {
+            let mut arena = CopyArena::default();
+            let a = arena.insert((1.0f32, 2.0f32));
+            let b = arena.insert((0.0, 0.0));
+
+            let shared = &arena;
+            assert!(shared.set(&b, (5.0, 5.0)));
+            assert_eq!(shared.update(&a, |(x, y)| (x + 1.0, y * 2.0)), Some((2.0, 4.0)));
+            assert_eq!(arena.get(&a), Some((2.0, 4.0)));
+            assert_eq!(arena.get(&b), Some((5.0, 5.0)));
+
+            arena.free(&a);
+            assert!(!arena.is_live(&a));
+            #[cfg(not(feature = "strict"))]
+            assert!(!arena.set(&a, (9.0, 9.0)), "Setting through a dead handle must fail");
+            #[cfg(not(feature = "strict"))]
+            assert_eq!(arena.update(&a, |value| value), None);
+
+            let c = arena.insert((7.0, 7.0));
+            assert_eq!(c.get_index(), a.get_index());
+            assert!(!arena.is_live(&a), "Reused slot must not answer to the old handle");
+            assert_eq!(arena.with_mut(&c, |value| { value.0 = 8.0; value.0 }), Some(8.0));
+            assert_eq!(arena.get(&c), Some((8.0, 7.0)));
+            arena.check_invariants();
+        }
+    }
+    mod hot_cold_arena_tests
+    {
+        use crate::hot_cold_arena::{HotCold, HotColdArena};
+
+        #[derive(Debug, PartialEq)]
+        struct Unit
+        {
+            position: (i32, i32),
+            name: String,
+        }
+
+        impl HotCold for Unit
+        {
+            type Hot = (i32, i32);
+            type Cold = String;
+
+            fn split(self) -> ((i32, i32), String)
+            {
+                (self.position, self.name)
+            }
+
+            fn join(position: (i32, i32), name: String) -> Self
+            {
+                Unit { position, name }
+            }
+        }
+
+        #[test]
+        fn test_hot_cold_arena_keeps_parts_together()
+        {
+            let mut arena = HotColdArena::default();
+            let a = arena.insert(Unit { position: (0, 0), name: "a".to_string() });
+            let b = arena.insert(Unit { position: (1, 1), name: "b".to_string() });
+            let c = arena.insert(Unit { position: (2, 2), name: "c".to_string() });
+
+            for position in arena.hot_values_mut()
+            {
+                position.0 += 10;
+            }
+            arena.cold_mut(&b).unwrap().push('!');
+
+            // Freeing `a` moves another hot value into its place, its cold part must follow
+            arena.free(&a);
+            assert_eq!(arena.hot(&a), None);
+            assert_eq!(arena.cold(&a), None);
+            assert_eq!(arena.hot(&c), Some(&(12, 2)));
+            assert_eq!(arena.cold(&c).map(String::as_str), Some("c"));
+            assert_eq!(arena.cold(&b).map(String::as_str), Some("b!"));
+            assert_eq!(arena.len(), 2);
+
+            let d = arena.insert(Unit { position: (3, 3), name: "d".to_string() });
+            assert_eq!(d.get_index(), a.get_index());
+            assert_eq!(arena.cold(&a), None, "Reused slot must not answer to the old handle");
+            assert_eq!(arena.cold(&d).map(String::as_str), Some("d"));
+            arena.check_invariants();
+        }
+    }
+    mod size_class_allocator_tests
+    {
+        use crate::size_class_allocator::{SizeClassAllocator, SIZE_CLASSES};
+
+        #[test]
+        fn test_size_classes_share_one_handle_namespace()
+        {
+            let mut allocator = SizeClassAllocator::default();
+            let small = allocator.allocate_from(b"tiny");
+            let medium = allocator.allocate_from(&[7; 40]);
+            let large = allocator.allocate(128);
+
+            assert_eq!(SizeClassAllocator::class_size(&small), 16);
+            assert_eq!(SizeClassAllocator::class_size(&medium), 64);
+            assert_eq!(SizeClassAllocator::class_size(&large), 128);
+            assert_eq!(allocator.get(&small), Some(&b"tiny"[..]));
+            assert_eq!(allocator.get(&medium).unwrap(), &[7; 40][..]);
+            assert!(allocator.get(&large).unwrap().iter().all(|byte| *byte == 0));
+            assert!(allocator.try_allocate(SIZE_CLASSES[3] + 1).is_none());
+
+            // First slot of every pool, yet every handle is distinct
+            assert_ne!(small.get_index(), medium.get_index());
+            assert_ne!(medium.get_index(), large.get_index());
+
+            allocator.free(&small);
+            assert!(allocator.get(&small).is_none());
+            let reused = allocator.allocate(3);
+            assert_eq!(reused.get_index(), small.get_index());
+            assert!(!allocator.is_live(&small), "Reused block must not answer to the old handle");
+            assert_eq!(allocator.get(&reused), Some(&[0, 0, 0][..]), "Reused blocks come back zeroed");
+            assert!(allocator.is_live(&medium));
+
+            assert_eq!(allocator.stats().live, 3);
+            assert_eq!(allocator.class_stats(0).capacity, 1);
+            allocator.check_invariants();
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        #[test]
+        fn test_free_zeroes_whole_block()
+        {
+            let mut allocator = SizeClassAllocator::default();
+            let secret = allocator.allocate_from(&[0xAB; 32]);
+            let block = allocator.get(&secret).unwrap().as_ptr();
+            allocator.free(&secret);
+            assert_eq!(unsafe { std::slice::from_raw_parts(block, SIZE_CLASSES[1]) }, &[0; 32][..]);
+
+            // A short block reused from a long one is cleared past its length as well
+            let short = allocator.allocate(20);
+            allocator.get_mut(&short).unwrap().fill(0xCD);
+            allocator.free(&short);
+            assert_eq!(unsafe { std::slice::from_raw_parts(block, SIZE_CLASSES[1]) }, &[0; 32][..]);
+        }
+    }
+    mod repr_c_arena_tests
+    {
+        use crate::repr_c_arena::{value_offset, ReprCArena, ReprCEntry};
+
+        #[test]
+        fn test_repr_c_entries_follow_documented_layout()
+        {
+            let mut arena = ReprCArena::default();
+            let a = arena.insert([1.0f32, 2.0, 3.0, 4.0]);
+            let b = arena.insert([5.0f32; 4]);
+            arena.free(&a);
+
+            // Read the raw bytes the way C code would
+            let (ptr, len) = arena.as_raw_parts();
+            assert_eq!(len, 2);
+            let stride = std::mem::size_of::<ReprCEntry<[f32; 4]>>();
+            let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len * stride) };
+            let word = |offset: usize| u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+            assert_eq!((word(0), word(4)), (1, 0), "First slot was freed, so its generation moved on");
+            assert_eq!((word(stride), word(stride + 4)), (b.get_generation(), 1));
+            let value = value_offset::<[f32; 4]>();
+            assert_eq!(f32::from_ne_bytes(bytes[stride + value..stride + value + 4].try_into().unwrap()), 5.0);
+
+            assert!(arena.entries()[0].value().is_none());
+            assert_eq!(arena.entries()[1].value(), Some(&[5.0; 4]));
+            assert_eq!(arena.get(&a), None);
+            arena.check_invariants();
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        #[test]
+        fn test_free_and_remove_zero_the_value()
+        {
+            let mut arena = ReprCArena::default();
+            let a = arena.insert([0xABu8; 16]);
+            let b = arena.insert([0xCDu8; 16]);
+            arena.free(&a);
+            assert_eq!(arena.remove(&b), Some([0xCD; 16]));
+
+            let (ptr, len) = arena.as_raw_parts();
+            let stride = std::mem::size_of::<ReprCEntry<[u8; 16]>>();
+            let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len * stride) };
+            let value = value_offset::<[u8; 16]>();
+            for slot in 0..len
+            {
+                assert_eq!(&bytes[slot * stride + value..slot * stride + value + 16], &[0; 16][..], "Slot {} kept its value", slot);
+            }
+        }
+    }
+    mod recycling_arena_tests
+    {
+        use crate::recycling_arena::RecyclingArena;
+
+        #[test]
+        fn test_recycling_arena_reuses_capacity()
+        {
+            let mut arena = RecyclingArena::<String>::default();
+            let first = arena.insert_with(|name| name.push_str("a rather long entity name"));
+            let capacity = arena.get(&first).unwrap().capacity();
+            arena.free(&first);
+            assert!(arena.get(&first).is_none());
+
+            let second = arena.insert_with(|name| {
+                assert!(name.is_empty(), "Recycled values must be reset");
+                assert_eq!(name.capacity(), capacity, "Recycled values keep their capacity");
+                name.push_str("short");
+            });
+            assert_eq!(second.get_index(), first.get_index());
+            assert_eq!(arena.get(&second).map(String::as_str), Some("short"));
+            assert!(!arena.is_live(&first));
+
+            // Nothing to recycle, so the value starts as `Default`
+            let third = arena.insert_with(|name| assert_eq!(name.capacity(), 0));
+            arena.free(&third);
+            arena.release_recycled();
+            let fourth = arena.insert_with(|name| assert_eq!(name.capacity(), 0, "Released values are gone"));
+
+            *arena.get_mut(&fourth).unwrap() = "replaced".to_string();
+            assert_eq!(arena.get(&fourth).map(String::as_str), Some("replaced"));
+            arena.check_invariants();
+        }
+    }
+    mod sorted_index_tests
+    {
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::sorted_index::SortedIndex;
+
+        #[test]
+        fn test_sorted_index_orders_live_handles_by_key()
+        {
+            let mut sprites = GenerationalIndexArray::<&str>::default();
+            let mut by_depth = SortedIndex::<i32>::default();
+            let handles: Vec<_> = [("sky", -10), ("tree", 3), ("player", 5), ("hud", 100)]
+                .into_iter()
+                .map(|(name, depth)| {
+                    let handle = sprites.insert(name);
+                    by_depth.insert(handle.clone(), depth);
+                    handle
+                })
+                .collect();
+
+            fn names(index: &SortedIndex<i32>, sprites: &GenerationalIndexArray<&'static str>) -> Vec<&'static str>
+            {
+                index.iter(sprites).map(|(handle, _)| *sprites.get(&handle).unwrap()).collect()
+            }
+            assert_eq!(names(&by_depth, &sprites), vec!["sky", "tree", "player", "hud"]);
+
+            // The player walks behind the tree
+            assert_eq!(by_depth.update_key(&handles[2], 1), Some(5));
+            assert_eq!(names(&by_depth, &sprites), vec!["sky", "player", "tree", "hud"]);
+            assert_eq!(by_depth.range(0..=3, &sprites).map(|(_, depth)| *depth).collect::<Vec<_>>(), vec![1, 3]);
+
+            // Freed entries are skipped without touching the index, until it's pruned
+            sprites.free(&handles[3]);
+            assert_eq!(by_depth.last(&sprites).map(|(handle, _)| handle), Some(handles[1].clone()));
+            assert_eq!(by_depth.len(), 4);
+            assert_eq!(by_depth.prune(&sprites), 1);
+            assert_eq!(by_depth.len(), 3);
+
+            assert_eq!(by_depth.remove(&handles[0]), Some(-10));
+            assert_eq!(by_depth.update_key(&handles[0], 0), None, "Only indexed handles can be updated");
+            assert_eq!(by_depth.first(&sprites).map(|(handle, _)| handle), Some(handles[2].clone()));
+            by_depth.check_invariants();
+        }
+    }
+    mod compact_encoding_tests
+    {
+        use crate::compact_encoding::{read_signed_varint, read_varint, write_signed_varint, write_varint, CompactDecode, CompactEncode};
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::{Generation, GenerationalIndexArray};
+
+        #[test]
+        fn test_varints_round_trip()
+        {
+            for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX]
+            {
+                let mut out = Vec::new();
+                write_varint(&mut out, value);
+                assert_eq!(read_varint(&mut out.as_slice()), Ok(value));
+            }
+            for value in [0, -1, 1, -64, 64, i64::MIN, i64::MAX]
+            {
+                let mut out = Vec::new();
+                write_signed_varint(&mut out, value);
+                assert_eq!(read_signed_varint(&mut out.as_slice()), Ok(value));
+            }
+
+            let mut small = Vec::new();
+            write_signed_varint(&mut small, -3);
+            assert_eq!(small.len(), 1, "Small negative numbers take a single byte");
+            assert_eq!(read_varint(&mut [0xff; 11].as_slice()), Err(DecodeError::VarintOverflow));
+            assert_eq!(Vec::<String>::decode(&mut [5, 1, b'a'].as_slice()), Err(DecodeError::UnexpectedEnd));
+        }
+
+        #[derive(Debug, PartialEq)]
+        struct Enemy
+        {
+            name: String,
+            health: i32
+        }
+
+        impl CompactEncode for Enemy
+        {
+            fn encode(&self, out: &mut Vec<u8>)
+            {
+                self.name.encode(out);
+                self.health.encode(out);
+            }
+        }
+
+        impl CompactDecode for Enemy
+        {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>
+            {
+                Ok(Enemy { name: String::decode(input)?, health: i32::decode(input)? })
+            }
+        }
+
+        #[test]
+        fn test_array_round_trips_through_bytes()
+        {
+            let mut array = GenerationalIndexArray::<Enemy>::with_free_slots_at(1, Generation::MAX - 1);
+            let handles: Vec<_> = (0..200).map(|i| array.insert(Enemy { name: format!("enemy {}", i), health: 100 - i })).collect();
+            array.free(&handles[0]);
+            for handle in handles[10..150].iter()
+            {
+                array.free(handle);
+            }
+
+            let bytes = array.to_bytes();
+            let decoded = GenerationalIndexArray::<Enemy>::from_bytes(&bytes).unwrap();
+            assert!(decoded == array);
+            decoded.check_invariants();
+            assert_eq!(decoded.stats(), array.stats());
+            assert_eq!(decoded.get(&handles[5]).map(|enemy| enemy.health), Some(95));
+            assert!(!decoded.is_live(&handles[0]), "The retired slot stays retired");
+
+            // Both hand out the same slot next
+            let (mut array, mut decoded) = (array, decoded);
+            let spawn = || Enemy { name: "boss".to_string(), health: 1000 };
+            assert_eq!(array.insert(spawn()), decoded.insert(spawn()));
+        }
+
+        #[test]
+        fn test_from_bytes_rejects_corrupt_input()
+        {
+            let mut array = GenerationalIndexArray::<u32>::default();
+            let handles: Vec<_> = (0..20).map(|i| array.insert(i * 1000)).collect();
+            array.free(&handles[3]);
+            array.free(&handles[7]);
+            let bytes = array.to_bytes();
+
+            for len in 0..bytes.len()
+            {
+                assert!(GenerationalIndexArray::<u32>::from_bytes(&bytes[..len]).is_err(), "Truncated to {} bytes", len);
+            }
+
+            let mut wrong_version = bytes.clone();
+            wrong_version[0] = 99;
+            assert_eq!(GenerationalIndexArray::<u32>::from_bytes(&wrong_version), Err(DecodeError::UnsupportedVersion(99)));
+
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            assert_eq!(GenerationalIndexArray::<u32>::from_bytes(&trailing), Err(DecodeError::TrailingBytes));
+
+            // Point the last free list entry (a delta of +4, from slot 3 to 7) at live slot 4
+            let mut live_in_free_list = bytes.clone();
+            *live_in_free_list.last_mut().unwrap() = 2;
+            assert!(matches!(GenerationalIndexArray::<u32>::from_bytes(&live_in_free_list), Err(DecodeError::Invalid(_))));
+
+            // Drop slot 7 from the free list
+            let mut missing_free_slot = bytes[..bytes.len() - 1].to_vec();
+            let count = missing_free_slot.len() - 2;
+            missing_free_slot[count] = 1;
+            assert!(matches!(GenerationalIndexArray::<u32>::from_bytes(&missing_free_slot), Err(DecodeError::Invalid(_))));
+        }
+    }
+    mod change_tracking_tests
+    {
+        use crate::change_tracking::ChangeTrackedArena;
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+
+        fn replica(server : &ChangeTrackedArena<u32>) -> ChangeTrackedArena<u32>
+        {
+            ChangeTrackedArena::from_array(GenerationalIndexArray::from_bytes(&server.array().to_bytes()).unwrap())
+        }
+
+        #[test]
+        fn test_deltas_keep_replica_in_sync()
+        {
+            let mut server = ChangeTrackedArena::default();
+            let handles: Vec<_> = (0..50).map(|i| server.insert(i)).collect();
+            let mut client = replica(&server);
+            let mut last_sent = server.current_tick();
+
+            for tick in 0..10
+            {
+                server.advance_tick();
+                *server.get_mut(&handles[tick]).unwrap() += 100;
+                server.free(&handles[20 + tick]);
+                let spawned = server.insert(1000 + tick as u32);
+                if tick % 3 == 0
+                {
+                    server.remove(&spawned);
+                }
+
+                let delta = server.serialize_delta(last_sent);
+                last_sent = server.current_tick();
+                assert_eq!(client.apply_delta(&delta), Ok(last_sent));
+                assert_eq!(client.array(), server.array());
+                client.check_invariants();
+            }
+
+            // Both hand out the same handles from here on
+            assert_eq!(server.insert(7), client.insert(7));
+        }
+
+        #[test]
+        fn test_delta_only_holds_changed_slots()
+        {
+            let mut server = ChangeTrackedArena::default();
+            let handles: Vec<_> = (0..1000).map(|i| server.insert(i)).collect();
+            let since = server.current_tick();
+            server.advance_tick();
+            *server.get_mut(&handles[10]).unwrap() = 1;
+            server.free(&handles[500]);
+
+            assert_eq!(server.changed_since(since), vec![handles[10].clone()]);
+            assert_eq!(server.changed_at(&handles[500]), Some(server.current_tick()));
+            assert!(server.serialize_delta(since).len() < 20);
+            assert!(server.serialize_delta(since).len() * 100 < server.array().to_bytes().len());
+            server.check_invariants();
+        }
+
+        #[test]
+        fn test_apply_delta_rejects_corrupt_input()
+        {
+            let mut server = ChangeTrackedArena::default();
+            let handles: Vec<_> = (0..20).map(|i| server.insert(i)).collect();
+            let mut client = replica(&server);
+            let since = server.current_tick();
+            server.advance_tick();
+            server.free(&handles[3]);
+            let _ = server.insert(99);
+            let _ = server.insert(100);
+            let delta = server.serialize_delta(since);
+            let before = client.array().to_bytes();
+
+            for len in 0..delta.len()
+            {
+                assert!(client.apply_delta(&delta[..len]).is_err(), "Truncated to {} bytes", len);
+            }
+            let mut wrong_version = delta.clone();
+            wrong_version[0] = 99;
+            assert_eq!(client.apply_delta(&wrong_version), Err(DecodeError::UnsupportedVersion(99)));
+            let mut trailing = delta.clone();
+            trailing.push(0);
+            assert_eq!(client.apply_delta(&trailing), Err(DecodeError::TrailingBytes));
+            // Slot 3 was reused by the first new entry, so the free list is empty. Claim slot 4 is free
+            let mut live_in_free_list = delta.clone();
+            *live_in_free_list.last_mut().unwrap() = 1;
+            live_in_free_list.push(8);
+            assert!(matches!(client.apply_delta(&live_in_free_list), Err(DecodeError::Invalid(_))));
+
+            assert_eq!(client.array().to_bytes(), before);
+            assert_eq!(client.apply_delta(&delta), Ok(server.current_tick()));
+            assert_eq!(client.array(), server.array());
+        }
+    }
+    mod stable_ids_tests
+    {
+        use std::collections::HashMap;
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::stable_ids::StableIdMap;
+
+        #[test]
+        fn test_ids_survive_rebuilding_the_arena()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let mut ids = StableIdMap::default();
+            let handles: Vec<_> = (0..10).map(|i| array.insert(i * 10)).collect();
+            let saved_ids: Vec<_> = handles.iter().map(|handle| ids.assign(handle)).collect();
+            assert_eq!(ids.assign(&handles[0]), saved_ids[0]);
+            array.free(&handles[4]);
+            assert_eq!(ids.prune(&array), 1);
+            let bytes = ids.to_bytes();
+
+            // Load the entries back in reverse order, so every one of them changes slot
+            let mut rebuilt = GenerationalIndexArray::default();
+            let mut moved = HashMap::new();
+            for (handle, value) in array.iter().rev()
+            {
+                moved.insert(handle, rebuilt.insert(*value));
+            }
+            let mut loaded = StableIdMap::from_bytes(&bytes, |handle| moved.get(handle).cloned()).unwrap();
+            loaded.check_invariants();
+
+            for (i, id) in saved_ids.iter().enumerate()
+            {
+                let value = loaded.resolve(*id, &rebuilt).map(|handle| *rebuilt.get(&handle).unwrap());
+                assert_eq!(value, if i == 4 { None } else { Some(i * 10) });
+            }
+
+            // Removed ids aren't handed out again
+            let fresh = rebuilt.insert(100);
+            assert!(loaded.assign(&fresh) > *saved_ids.last().unwrap());
+        }
+
+        #[test]
+        fn test_from_bytes_rejects_corrupt_map()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let mut ids = StableIdMap::default();
+            for i in 0..5
+            {
+                ids.assign(&array.insert(i));
+            }
+            let bytes = ids.to_bytes();
+            let same = |handle : &_| Some(Clone::clone(handle));
+
+            for len in 0..bytes.len()
+            {
+                assert!(StableIdMap::from_bytes(&bytes[..len], same).is_err(), "Truncated to {} bytes", len);
+            }
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            assert_eq!(StableIdMap::from_bytes(&trailing, same).err(), Some(DecodeError::TrailingBytes));
+
+            let handle = array.handles()[0].clone();
+            let collapsed = StableIdMap::from_bytes(&bytes, |_| Some(handle.clone()));
+            assert!(matches!(collapsed, Err(DecodeError::Invalid(_))));
+            assert_eq!(StableIdMap::from_bytes(&bytes, same).unwrap().len(), 5);
+        }
+    }
+    mod migrations_tests
+    {
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::migrations::VersionedFormat;
+
+        // Health was a u16, then a u64, then became a label
+        fn latest() -> VersionedFormat<String>
+        {
+            VersionedFormat::<u16>::new(1)
+                .migrate(2, |health : &mut u16| *health as u64 * 10)
+                .migrate(3, |health : &mut u64| format!("{} hp", health))
+        }
+
+        #[test]
+        fn test_old_saves_are_migrated_to_the_current_schema()
+        {
+            let mut array = GenerationalIndexArray::<u16>::default();
+            let handles: Vec<_> = (0..10).map(|i| array.insert(i)).collect();
+            array.free(&handles[2]);
+            let v1_save = VersionedFormat::<u16>::new(1).save(&array);
+
+            let v2_array = GenerationalIndexArray::<u16>::from_bytes(&array.to_bytes()).unwrap().map(|health| *health as u64 * 10);
+            let v2_save = VersionedFormat::<u64>::new(2).save(&v2_array);
+
+            let format = latest();
+            assert_eq!(format.version(), 3);
+            assert_eq!(format.supported_versions(), vec![1, 2, 3]);
+            let from_v1 = format.load(&v1_save).unwrap();
+            assert_eq!(from_v1, format.load(&v2_save).unwrap());
+            from_v1.check_invariants();
+
+            // Handles saved with the old arena still point at the same entities
+            assert_eq!(from_v1.get(&handles[7]).map(String::as_str), Some("70 hp"));
+            assert!(!from_v1.is_live(&handles[2]));
+            assert_eq!(format.load(&format.save(&from_v1)).unwrap(), from_v1);
+        }
+
+        #[test]
+        fn test_load_rejects_unknown_versions()
+        {
+            let save = VersionedFormat::<u16>::new(4).save(&GenerationalIndexArray::default());
+            assert!(matches!(latest().load(&save), Err(DecodeError::Invalid(_))));
+            assert_eq!(latest().load(&[]).err(), Some(DecodeError::UnexpectedEnd));
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_migrations_must_move_forward()
+        {
+            let _ = VersionedFormat::<u16>::new(2).migrate(2, |health : &mut u16| *health);
+        }
+    }
+    mod relocatable_tests
+    {
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+
+        #[test]
+        fn test_relocatable_round_trip_keeps_handles()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let handles: Vec<_> = ["goblin", "orc", "troll", "ogre"].iter().map(|name| array.insert(name.to_string())).collect();
+            array.free(&handles[1]);
+            array.free(&handles[3]);
+            let recycled = array.insert("wyvern".to_string());
+
+            // Moving the buffer around doesn't matter, only offsets are stored
+            let buffer = array.export_relocatable();
+            let moved = buffer.clone();
+            drop(buffer);
+            let mut imported = GenerationalIndexArray::<String>::import_relocatable(&moved).unwrap();
+            imported.check_invariants();
+            assert!(imported == array);
+            assert_eq!(imported.get(&recycled).map(String::as_str), Some("wyvern"));
+            #[cfg(not(feature = "strict"))]
+            assert!(imported.get(&handles[1]).is_none());
+            assert_eq!(imported.insert("imp".to_string()), array.insert("imp".to_string()));
+        }
+
+        #[test]
+        fn test_import_relocatable_rejects_corrupt_buffers()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let _ = array.insert(7u32);
+            let _ = array.insert(300u32);
+            let buffer = array.export_relocatable();
+
+            assert_eq!(GenerationalIndexArray::<u32>::import_relocatable(&[]).err(), Some(DecodeError::UnexpectedEnd));
+            assert_eq!(GenerationalIndexArray::<u32>::import_relocatable(&buffer[..20]).err(), Some(DecodeError::UnexpectedEnd));
+
+            let mut wrong_version = buffer.clone();
+            wrong_version[0] = 9;
+            assert_eq!(GenerationalIndexArray::<u32>::import_relocatable(&wrong_version).err(), Some(DecodeError::UnsupportedVersion(9)));
+
+            // Second slot's value offset pointing back into the slot table
+            let mut into_tables = buffer.clone();
+            into_tables[16 + 16 + 8] = 0;
+            assert!(matches!(GenerationalIndexArray::<u32>::import_relocatable(&into_tables), Err(DecodeError::Invalid(_))));
+
+            // First slot's value length covering the second value too
+            let mut too_long = buffer.clone();
+            too_long[16 + 12] = 3;
+            assert!(matches!(GenerationalIndexArray::<u32>::import_relocatable(&too_long), Err(DecodeError::Invalid(_))));
+        }
+    }
+    mod checksum_tests
+    {
+        use crate::checksum::{crc32, seal, unseal};
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+
+        #[test]
+        fn test_crc32_matches_the_reference_value()
+        {
+            assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+            assert_eq!(crc32(b""), 0);
+            assert_eq!(unseal(&seal(b"payload".to_vec())), Ok(&b"payload"[..]));
+        }
+
+        #[test]
+        fn test_from_bytes_checked_rejects_flipped_bits()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let handles: Vec<_> = (0..4u32).map(|i| array.insert(i * 100)).collect();
+            array.free(&handles[2]);
+            let saved = array.to_bytes_checked();
+            assert!(GenerationalIndexArray::<u32>::from_bytes_checked(&saved).unwrap() == array);
+
+            // Every single bit flip is caught, even those that would still decode
+            for bit in 0..saved.len() * 8
+            {
+                let mut corrupted = saved.clone();
+                corrupted[bit / 8] ^= 1 << (bit % 8);
+                assert!(matches!(
+                    GenerationalIndexArray::<u32>::from_bytes_checked(&corrupted),
+                    Err(DecodeError::ChecksumMismatch { .. })
+                ));
+            }
+            assert!(GenerationalIndexArray::<u32>::from_bytes_checked(&saved[..3]).is_err());
+            assert_eq!(GenerationalIndexArray::<u32>::from_bytes_checked(&[1, 2]).err(), Some(DecodeError::UnexpectedEnd));
+        }
+    }
+    mod lazy_arena_tests
+    {
+        use crate::errors::DecodeError;
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::lazy_arena::LazyArena;
+
+        #[test]
+        fn test_lazy_arena_hydrates_on_first_get()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let handles: Vec<_> = (0..100).map(|i| array.insert(format!("chunk {}", i))).collect();
+            array.free(&handles[10]);
+
+            let lazy = LazyArena::<String>::load(array.export_relocatable()).unwrap();
+            assert_eq!(lazy.live(), 99);
+            assert_eq!(lazy.hydrated(), 0);
+            assert!(lazy.is_live(&handles[42]) && !lazy.is_live(&handles[10]));
+
+            assert_eq!(lazy.get(&handles[42]).map(String::as_str), Some("chunk 42"));
+            assert_eq!(lazy.get(&handles[42]).map(String::as_str), Some("chunk 42"));
+            assert!(lazy.get(&handles[10]).is_none());
+            assert_eq!(lazy.hydrated(), 1);
+
+            let mut rebuilt = lazy.into_array().unwrap();
+            rebuilt.check_invariants();
+            assert!(rebuilt == array);
+            assert_eq!(rebuilt.insert("new".to_string()), array.insert("new".to_string()));
+        }
+
+        #[test]
+        fn test_lazy_arena_reports_corrupt_values_when_hydrated()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let first = array.insert(vec![1u8, 2, 3]);
+            let second = array.insert(vec![4u8]);
+            let mut buffer = array.export_relocatable();
+
+            // Stretch the first value's length prefix past its recorded length
+            let offset = u32::from_le_bytes(buffer[16 + 8..16 + 12].try_into().unwrap()) as usize;
+            buffer[offset] = 9;
+
+            let lazy = LazyArena::<Vec<u8>>::load(buffer).unwrap();
+            assert_eq!(lazy.try_get(&second), Ok(Some(&vec![4u8])));
+            assert!(lazy.try_get(&first).is_err());
+            assert_eq!(lazy.hydrated(), 1);
+            assert!(lazy.into_array().is_err());
+            assert_eq!(LazyArena::<u32>::load(vec![1, 0, 0]).err(), Some(DecodeError::UnexpectedEnd));
+        }
+    }
+    mod op_log_tests
+    {
+        use crate::errors::DecodeError;
+        use crate::generational_allocator::GenerationalAllocator;
+        use crate::op_log::{replay, replay_with, Op, OpLog, RecordingArena};
+
+        #[test]
+        fn test_replay_rebuilds_an_identical_arena()
+        {
+            let mut arena = RecordingArena::default();
+            let handles: Vec<_> = (0..5u32).map(|i| arena.insert(i)).collect();
+            arena.free(&handles[1]);
+            assert_eq!(arena.remove(&handles[3]), Some(3));
+            assert_eq!(arena.replace(&handles[0], 100), Some(0));
+            assert!(arena.update(&handles[4], |value| *value += 40));
+            assert_eq!(arena.with_mut(&handles[2], |value| { *value *= 7; *value }), Some(14));
+            let _ = arena.insert(9);
+            #[cfg(not(feature = "strict"))]
+            assert!(!arena.update(&handles[1], |value| *value = 0));
+            assert_eq!(arena.log().len(), 11);
+
+            // Through bytes, as a log attached to a bug report would be
+            let log = OpLog::from_bytes(&arena.log().to_bytes()).unwrap();
+            assert_eq!(&log, arena.log());
+            let mut replayed = replay::<u32>(&log).unwrap();
+            replayed.check_invariants();
+            assert!(&replayed == arena.array());
+            assert_eq!(replayed.insert(1), arena.insert(1));
+        }
+
+        #[test]
+        fn test_replay_with_custom_encoder()
+        {
+            #[derive(Debug, PartialEq)]
+            struct Tag(char);
+
+            let mut arena = RecordingArena::with_encoder(|tag : &Tag, out : &mut Vec<u8>| out.push(tag.0 as u8));
+            let a = arena.insert(Tag('a'));
+            let _ = arena.insert(Tag('b'));
+            arena.replace(&a, Tag('z'));
+
+            let replayed = replay_with(arena.log(), |payload| Ok(Tag(payload[0] as char))).unwrap();
+            assert_eq!(replayed.get(&a), Some(&Tag('z')));
+            assert!(matches!(arena.log().ops()[2], Op::Replace { .. }));
+        }
+
+        #[test]
+        fn test_replay_rejects_logs_that_dont_match()
+        {
+            let mut arena = RecordingArena::default();
+            let first = arena.insert(1u8);
+            arena.free(&first);
+
+            let mut bytes = arena.log().to_bytes();
+            bytes.push(0);
+            assert_eq!(OpLog::from_bytes(&bytes).err(), Some(DecodeError::TrailingBytes));
+
+            // Same log with a second free of the first handle: tag 1, index 0, generation 0
+            let mut bytes = arena.log().to_bytes();
+            bytes[1] = 3;
+            bytes.extend_from_slice(&[1, 0, 0]);
+            let doubled = OpLog::from_bytes(&bytes).unwrap();
+            assert_eq!(doubled.ops()[2], Op::Free { handle: first });
+            assert!(matches!(replay::<u8>(&doubled), Err(DecodeError::Invalid(_))));
+        }
+    }
+    mod gc_tests
+    {
+        use crate::gc::Trace;
+        use crate::kyren_generational_indices::{GenerationalIndex, GenerationalIndexArray};
+
+        struct Node
+        {
+            name : &'static str,
+            edges : Vec<GenerationalIndex>
+        }
+
+        impl Trace for Node
+        {
+            fn trace(&self, visitor : &mut impl FnMut(&GenerationalIndex))
+            {
+                self.edges.trace(visitor);
+            }
+        }
+
+        fn node(name : &'static str) -> Node
+        {
+            Node { name, edges: Vec::new() }
+        }
+
+        #[test]
+        fn test_collect_garbage_frees_unreachable_islands()
+        {
+            let mut graph = GenerationalIndexArray::default();
+            let [root, child, grandchild, island_a, island_b, pinned] = ["root", "child", "grandchild", "a", "b", "pinned"].map(|name| graph.insert(node(name)));
+            graph.get_mut(&root).unwrap().edges = vec![child.clone()];
+            graph.get_mut(&child).unwrap().edges = vec![grandchild.clone(), root.clone()];
+            // A cycle nothing points into, the case manual freeing leaks
+            graph.get_mut(&island_a).unwrap().edges = vec![island_b.clone()];
+            graph.get_mut(&island_b).unwrap().edges = vec![island_a.clone()];
+            graph.pin_slot(&pinned);
+
+            // Stale edges are ignored
+            let stale = graph.insert(node("stale"));
+            graph.free(&stale);
+            graph.get_mut(&grandchild).unwrap().edges = vec![stale];
+
+            let mut freed = graph.collect_garbage(&[root]);
+            freed.sort_by_key(|handle| handle.get_index());
+            assert_eq!(freed, vec![island_a, island_b]);
+            let names: Vec<_> = graph.values().map(|node| node.name).collect();
+            assert_eq!(names, vec!["root", "child", "grandchild", "pinned"]);
+
+            assert_eq!(graph.collect_garbage(&[]).len(), 3);
+            assert!(graph.contains(&pinned));
+            graph.check_invariants();
+        }
+
+        #[test]
+        fn test_find_cycles_reports_strongly_connected_components()
+        {
+            let mut graph = GenerationalIndexArray::default();
+            let nodes: Vec<_> = (0..7).map(|_| graph.insert(node("n"))).collect();
+            let link = |graph : &mut GenerationalIndexArray<Node>, from : usize, to : &[usize]|
+            {
+                graph.get_mut(&nodes[from]).unwrap().edges = to.iter().map(|to| nodes[*to].clone()).collect();
+            };
+            // 0 -> 1 -> 2 -> 0 is a cycle, 3 hangs off it, 4 references itself, 5 <-> 6
+            link(&mut graph, 0, &[1]);
+            link(&mut graph, 1, &[2, 3]);
+            link(&mut graph, 2, &[0]);
+            link(&mut graph, 4, &[4]);
+            link(&mut graph, 5, &[6]);
+            link(&mut graph, 6, &[5]);
+
+            let mut cycles = graph.find_cycles();
+            cycles.sort_by_key(|cycle| cycle[0].get_index());
+            assert_eq!(cycles, vec![nodes[0..3].to_vec(), vec![nodes[4].clone()], nodes[5..7].to_vec()]);
+
+            // Breaking an edge breaks the cycle, freeing an entry drops its references
+            link(&mut graph, 2, &[]);
+            graph.free(&nodes[6]);
+            assert_eq!(graph.find_cycles(), vec![vec![nodes[4].clone()]]);
+        }
+    }
+    mod interner_tests
+    {
+        use crate::interner::Interner;
+
+        #[test]
+        fn test_interner_stores_each_string_once()
+        {
+            let mut interner = Interner::default();
+            let words = ["goblin", "orc", "goblin", "", "troll", "orc"];
+            let symbols: Vec<_> = words.iter().map(|word| interner.intern(word)).collect();
+
+            assert_eq!(interner.len(), 4);
+            assert_eq!(symbols[0], symbols[2]);
+            assert_eq!(symbols[1], symbols[5]);
+            assert_ne!(symbols[0], symbols[1]);
+            for (word, symbol) in words.iter().zip(symbols.iter())
+            {
+                assert_eq!(interner.resolve(*symbol), *word);
+            }
+            assert_eq!(interner.get("troll"), Some(symbols[4]));
+            assert_eq!(interner.get("dragon"), None);
+            assert_eq!(interner.len(), 4);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_resolving_foreign_symbols_panics()
+        {
+            let mut first = Interner::default();
+            first.intern("a");
+            let foreign = first.intern("b");
+            Interner::default().resolve(foreign);
+        }
+    }
+    mod graph_tests
+    {
+        use crate::graph::Graph;
+
+        #[test]
+        fn test_removing_a_node_removes_its_edges()
+        {
+            let mut graph = Graph::default();
+            let [a, b, c] = ["a", "b", "c"].map(|name| graph.add_node(name));
+            let ab = graph.add_edge(&a, &b, 1).unwrap();
+            let bc = graph.add_edge(&b, &c, 2).unwrap();
+            let ca = graph.add_edge(&c, &a, 3).unwrap();
+            let bb = graph.add_edge(&b, &b, 4).unwrap();
+            graph.check_invariants();
+
+            let targets: Vec<_> = graph.outgoing(&b).map(|(_, to)| *graph.node(to).unwrap()).collect();
+            assert_eq!(targets, vec!["c", "b"]);
+            assert_eq!(graph.endpoints(&ca), Some((&c, &a)));
+
+            assert_eq!(graph.remove_node(&b), Some("b"));
+            assert_eq!(graph.edge_count(), 1);
+            for edge in [&ab, &bc, &bb]
+            {
+                assert!(!graph.contains_edge(edge));
+            }
+            assert_eq!(graph.edge(&ca), Some(&3));
+            #[cfg(not(feature = "strict"))]
+            assert_eq!(graph.outgoing(&b).count(), 0);
+            assert_eq!(graph.incoming(&c).count(), 0);
+            assert!(graph.add_edge(&a, &b, 5).is_none(), "Dead nodes can't get new edges");
+            graph.check_invariants();
+
+            // The slot is reused, the old id stays dead
+            let d = graph.add_node("d");
+            assert!(!graph.contains_node(&b));
+            assert_eq!(graph.node(&d), Some(&"d"));
+            assert_eq!(graph.remove_edge(&ca), Some(3));
+            #[cfg(not(feature = "strict"))]
+            assert_eq!(graph.remove_edge(&ca), None);
+            graph.check_invariants();
+        }
+    }
+    mod mailbox_tests
+    {
+        use crate::mailbox::Mailbox;
+
+        #[test]
+        fn test_mailbox_delivers_in_order_and_drops_dead_sends()
+        {
+            let mut mailbox = Mailbox::default();
+            let orc = mailbox.insert("orc");
+            let elf = mailbox.insert("elf");
+            assert!(mailbox.send(&orc, "hit 3"));
+            assert!(mailbox.send(&elf, "heal 1"));
+            assert!(mailbox.send(&orc, "hit 5"));
+            assert_eq!(mailbox.pending(&orc), 2);
+
+            assert_eq!(mailbox.drain(&orc).collect::<Vec<_>>(), vec!["hit 3", "hit 5"]);
+            assert_eq!(mailbox.pending(&orc), 0);
+            mailbox.check_invariants();
+
+            // Undelivered messages go with the entity, nothing leaks into the reused slot
+            mailbox.send(&elf, "heal 2");
+            assert_eq!(mailbox.remove(&elf), Some("elf"));
+            assert!(!mailbox.send(&elf, "heal 3"));
+            assert_eq!(mailbox.dropped(), 1);
+            let dwarf = mailbox.insert("dwarf");
+            assert_eq!(dwarf.get_index(), elf.get_index());
+            assert_eq!(mailbox.pending(&dwarf), 0);
+            assert_eq!(mailbox.drain(&elf).count(), 0);
+            mailbox.check_invariants();
+        }
+
+        #[test]
+        fn test_mailbox_reuses_pool_nodes()
+        {
+            let mut mailbox = Mailbox::default();
+            let entity = mailbox.insert(());
+            for round in 0..10
+            {
+                for message in 0..4
+                {
+                    mailbox.send(&entity, round * 4 + message);
+                }
+                // Stopping early still returns every node
+                assert_eq!(mailbox.drain(&entity).next(), Some(round * 4));
+                mailbox.check_invariants();
+            }
+            assert_eq!(mailbox.pending(&entity), 0);
+        }
+    }
+    mod spatial_grid_tests
+    {
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::spatial_grid::{Aabb, SpatialGrid};
+
+        #[test]
+        fn test_spatial_grid_queries_skip_and_forget_dead_handles()
+        {
+            let mut entities = GenerationalIndexArray::default();
+            let mut grid = SpatialGrid::new(10.0);
+            let small = entities.insert("small");
+            let wide = entities.insert("wide");
+            let far = entities.insert("far");
+            grid.update(&small, Aabb::new([1.0, 1.0], [2.0, 2.0]));
+            grid.update(&wide, Aabb::new([-15.0, 0.0], [25.0, 5.0]));
+            grid.update(&far, Aabb::new([100.0, 100.0], [101.0, 101.0]));
+
+            // `wide` spans several cells but is reported once
+            let mut hits = grid.query(&Aabb::new([-20.0, -20.0], [20.0, 20.0]), &entities);
+            hits.sort_by_key(|handle| handle.get_index());
+            assert_eq!(hits, vec![small.clone(), wide.clone()]);
+            // Same cells, but the boxes themselves don't overlap the query
+            assert!(grid.query(&Aabb::new([3.0, 6.0], [4.0, 7.0]), &entities).is_empty());
+
+            grid.update(&small, Aabb::new([99.0, 99.0], [100.5, 100.5]));
+            assert_eq!(grid.query(&Aabb::new([0.0, 0.0], [3.0, 3.0]), &entities), vec![wide.clone()]);
+
+            // No call to the grid when freeing
+            entities.free(&wide);
+            let reused = entities.insert("reused");
+            assert!(grid.query(&Aabb::new([0.0, 0.0], [3.0, 3.0]), &entities).is_empty());
+            assert_eq!(grid.len(), 2);
+            assert!(!grid.remove(&reused));
+
+            entities.free(&far);
+            grid.prune(&entities);
+            assert_eq!(grid.len(), 1);
+            assert_eq!(grid.query(&Aabb::new([90.0, 90.0], [110.0, 110.0]), &entities), vec![small]);
+        }
+    }
+    mod option_vec_adapter_tests
+    {
+        use crate::option_vec_adapter::OptionVecAdapter;
+
+        #[test]
+        fn test_adapter_layers_handles_over_a_legacy_vec()
+        {
+            let legacy = vec![Some("knight"), None, Some("archer"), None];
+            let mut adapter = OptionVecAdapter::from_vec(legacy);
+            adapter.check_invariants();
+
+            // Old call sites pass raw indices, new ones get handles for them
+            let archer = adapter.handle_at(2).unwrap();
+            assert!(adapter.handle_at(1).is_none());
+            assert_eq!(adapter.get(&archer), Some(&"archer"));
+
+            let mage = adapter.insert("mage");
+            assert_eq!(mage.get_index(), 1, "Empty slots are reused lowest first");
+            assert_eq!(adapter.slots()[1], Some("mage"));
+
+            adapter.free(&archer);
+            let priest = adapter.insert("priest");
+            assert_eq!(priest.get_index(), 2);
+            assert!(adapter.get(&archer).is_none(), "The old handle must not see the new value");
+
+            assert_eq!(adapter.into_vec(), vec![Some("knight"), Some("mage"), Some("priest"), None]);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_adapter_rejects_free_lists_naming_full_slots()
+        {
+            OptionVecAdapter::from_parts(vec![Some(1), None], vec![0, 1]);
+        }
+    }
+    mod leak_tracker_tests
+    {
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+        use crate::leak_tracker::LeakTracker;
+
+        #[test]
+        fn test_leak_tracker_reports_entries_nobody_can_reach()
+        {
+            let mut arena = GenerationalIndexArray::<&str>::default();
+            let mut tracker = LeakTracker::default();
+            let kept = tracker.track(arena.insert("kept"));
+            let lost = tracker.track(arena.insert("lost"));
+            let freed = tracker.track(arena.insert("freed"));
+            let copy = kept.clone();
+            assert!(tracker.leaked(&arena).is_empty());
+
+            let lost_handle = (*lost).clone();
+            drop(lost);
+            arena.free(&freed);
+            drop(freed);
+            drop(kept);
+            assert_eq!(tracker.leaked(&arena), vec![lost_handle.clone()], "A surviving clone keeps the entry reachable");
+            assert_eq!(tracker.len(), 2, "Freed entries are forgotten");
+
+            // Tracking the handle again means someone holds it after all
+            let found = tracker.track(lost_handle);
+            assert!(tracker.leaked(&arena).is_empty());
+            assert_eq!(arena.get(&found), Some(&"lost"));
+            assert_eq!(arena.get(&copy), Some(&"kept"));
+        }
+    }
+    mod frozen_arena_tests
+    {
+        use crate::errors::SlotState;
+        use crate::kyren_generational_indices::GenerationalIndexArrayCell;
+
+        #[test]
+        fn test_frozen_arena_shared_reads_and_thaw()
+        {
+            let mut arena = GenerationalIndexArrayCell::<u64>::default();
+            let handles: Vec<_> = (0..100).map(|i| arena.insert(i)).collect();
+            let freed = handles[10].clone();
+            arena.free(&freed);
+
+            let frozen = arena.freeze();
+            assert_eq!(frozen.resolve(&freed).unwrap_err().slot_state, SlotState::Free);
+            let live: Vec<_> = handles.iter().filter_map(|handle| frozen.resolve(handle).ok()).collect();
+            assert_eq!(live.len(), 99);
+
+            let sums: Vec<u64> = std::thread::scope(|scope| {
+                let readers: Vec<_> = (0..4).map(|_| scope.spawn(|| live.iter().map(|index| *frozen.get(*index)).sum())).collect();
+                readers.into_iter().map(|reader| reader.join().unwrap()).collect()
+            });
+            assert!(sums.iter().all(|sum| *sum == (0..100).sum::<u64>() - 10));
+
+            let mut arena = frozen.thaw();
+            *arena.get(&handles[0]).unwrap().borrow_mut() += 1;
+            assert_eq!(*arena.get(&handles[0]).unwrap().borrow(), 1);
+            arena.free(&handles[0]);
+            arena.check_invariants();
+        }
+    }
+    mod dense_map_tests
+    {
+        use crate::dense_map::DenseMap;
+        use crate::memory_allocators::InPlaceAllocator;
+
+        #[test]
+        fn test_dense_map_follows_generations()
+        {
+            let mut arena = InPlaceAllocator::<u32>::default();
+            let mut names = DenseMap::default();
+            let handles: Vec<_> = (0..200).map(|i| arena.insert(i)).collect();
+            for handle in handles.iter().step_by(2)
+            {
+                assert_eq!(names.insert(handle, format!("entity {}", handle.get_index())), None);
+            }
+            assert_eq!(names.insert(&handles[0], "renamed".to_string()).as_deref(), Some("entity 0"));
+            assert_eq!(names.len(), 100);
+            assert!(names.get(&handles[1]).is_none());
+
+            // The slot of handles[4] is reused, its old value must not show up for the new handle
+            arena.free(&handles[2]);
+            arena.free(&handles[4]);
+            let reused = arena.insert(2);
+            assert_eq!(reused.get_index(), handles[4].get_index());
+            assert!(names.get(&reused).is_none());
+            assert_eq!(names.get(&handles[4]).map(String::as_str), Some("entity 4"), "Stale until pruned");
+
+            let live: Vec<_> = names.iter_live(&arena).map(|(handle, _)| handle).collect();
+            assert_eq!(live.len(), 98);
+            assert!(live.iter().all(|handle| arena.is_live(handle)));
+            assert!(live.windows(2).all(|pair| pair[0].get_index() < pair[1].get_index()));
+
+            assert_eq!(names.prune(&arena), 2);
+            assert_eq!(names.iter().count(), 98);
+            *names.get_mut(&handles[198]).unwrap() = "last".to_string();
+            assert_eq!(names.remove(&handles[198]).as_deref(), Some("last"));
+            assert_eq!(names.remove(&handles[198]), None);
+            assert_eq!(names.len(), 97);
+        }
+    }
+    #[cfg(feature = "json-debug")]
+    mod json_debug_tests
+    {
+        use crate::kyren_generational_indices::GenerationalIndexArray;
+
+        #[test]
+        fn test_json_debug_lists_live_slots_and_metadata()
+        {
+            let mut array = GenerationalIndexArray::default();
+            let handles: Vec<_> = ["goblin", "orc", "troll"].iter().map(|name| array.insert(name.to_string())).collect();
+            array.free(&handles[1]);
+            array.pin_slot(&handles[2]);
+
+            let json : serde_json::Value = serde_json::from_str(&array.to_json_debug().unwrap()).unwrap();
+            assert_eq!(json["slots"], 3);
+            assert_eq!(json["live"], 2);
+            assert_eq!(json["free_list"], serde_json::json!([1]));
+            assert_eq!(json["pinned"], serde_json::json!([2]));
+            assert_eq!(json["live_slots"], serde_json::json!([
+                { "index": 0, "generation": 0, "value": "goblin" },
+                { "index": 2, "generation": 0, "value": "troll" }
+            ]));
+        }
+    }
+    #[cfg(feature = "mmap-arena")]
+    mod mmap_arena_tests
+    {
+        use std::path::PathBuf;
+
+        use crate::kyren_generational_indices::GenerationalIndex;
+        use crate::mmap_arena::MmapArena;
+
+        // File in the temp dir that's removed when the test ends
+        struct TempPath(PathBuf);
+
+        impl TempPath {
+            fn new(name : &str) -> Self {
+                let path = std::env::temp_dir().join(format!("example_allocators_{}_{}", std::process::id(), name));
+                let _ = std::fs::remove_file(&path);
+                return TempPath(path);
+            }
+        }
+
+        impl Drop for TempPath {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+
+        #[test]
+        fn test_mmap_arena_survives_reopening()
+        {
+            let path = TempPath::new("reopen");
+            let (kept, freed, moved) = {
+                let mut arena = MmapArena::<[f32; 3]>::open(&path.0).unwrap();
+                let handles: Vec<_> = (0..40).map(|i| arena.insert([i as f32; 3])).collect();
+                arena.free(&handles[5]);
+                arena.get_mut(&handles[7]).unwrap()[1] = -1.0;
+                arena.flush().unwrap();
+                arena.check_invariants();
+                (handles[39].clone(), handles[5].clone(), handles[7].clone())
+            };
+
+            let mut arena = MmapArena::<[f32; 3]>::open(&path.0).unwrap();
+            arena.check_invariants();
+            assert_eq!(arena.get(&kept), Some(&[39.0; 3]));
+            assert_eq!(arena.get(&moved), Some(&[7.0, -1.0, 7.0]));
+            assert!(arena.get(&freed).is_none());
+            assert_eq!(arena.stats().live, 39);
+
+            // The rebuilt free list hands the freed slot out again, with a new generation
+            let reused = arena.insert([0.5; 3]);
+            assert_eq!(reused.get_index(), freed.get_index());
+            assert_ne!(reused.get_generation(), freed.get_generation());
+        }
+
+        #[test]
+        fn test_mmap_arena_rejects_other_files()
+        {
+            let path = TempPath::new("reject");
+            std::fs::write(&path.0, b"definitely not an arena, just some bytes that fill a header").unwrap();
+            assert_eq!(MmapArena::<u32>::open(&path.0).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+
+            std::fs::remove_file(&path.0).unwrap();
+            let mut arena = MmapArena::<u32>::open(&path.0).unwrap();
+            let _ = arena.insert(1);
+            drop(arena);
+            assert_eq!(MmapArena::<u64>::open(&path.0).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+            assert!(MmapArena::<u32>::open(&path.0).unwrap().get(&GenerationalIndex { index: 0, generation: 0 }).is_some());
+        }
+
+        #[test]
+        fn test_mmap_arena_rejects_corrupt_slots()
+        {
+            let path = TempPath::new("corrupt");
+            let mut arena = MmapArena::<u32>::open(&path.0).unwrap();
+            let _ = arena.insert(1);
+            drop(arena);
+
+            // The first entry starts right after the 64 byte header: generation, then occupied
+            let write_entry = |generation: u32, occupied: u32| {
+                let mut bytes = std::fs::read(&path.0).unwrap();
+                bytes[64..68].copy_from_slice(&generation.to_ne_bytes());
+                bytes[68..72].copy_from_slice(&occupied.to_ne_bytes());
+                std::fs::write(&path.0, bytes).unwrap();
+            };
+            write_entry(u32::MAX, 1);
+            assert_eq!(MmapArena::<u32>::open(&path.0).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+            write_entry(3, 2);
+            assert_eq!(MmapArena::<u32>::open(&path.0).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+
+            // A retired slot that's free is fine, and stays out of the free list
+            write_entry(u32::MAX, 0);
+            let mut arena = MmapArena::<u32>::open(&path.0).unwrap();
+            arena.check_invariants();
+            assert_eq!(arena.insert(2).get_index(), 1);
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        #[test]
+        fn test_mmap_arena_remove_zeroes_file()
+        {
+            const SECRET: [u8; 32] = [0xAB; 32];
+            let path = TempPath::new("zero");
+            let mut arena = MmapArena::<[u8; 32]>::open(&path.0).unwrap();
+            let key = arena.insert(SECRET);
+            arena.flush().unwrap();
+            let holds_secret = || std::fs::read(&path.0).unwrap().windows(SECRET.len()).any(|window| window == SECRET);
+            assert!(holds_secret());
+
+            assert_eq!(arena.remove(&key), Some(SECRET));
+            arena.flush().unwrap();
+            assert!(!holds_secret(), "Removed value is still in the file");
+        }
+    }
+
+    mod generation_table_tests
+    {
+        use crate::bit_vec::BitVec;
+        use crate::generation_table::GenerationTable;
+        use crate::kyren_generational_indices::{Generation, GenerationalIndex};
+        use crate::memory_allocators::InPlaceAllocator;
+
+        #[test]
+        fn test_sentinel_is_hidden()
+        {
+            let mut table = GenerationTable::default();
+            assert!(table.is_empty());
+            // Clamped to the sentinel, which holds MAX
+            assert!(!table.matches(0, 0));
+            assert!(table.matches(0, Generation::MAX));
+
+            table.push(3);
+            table.push(0);
+            assert_eq!(&table[..], &[3, 0]);
+            assert!(table.matches(0, 3));
+            assert!(table.matches(1, 0));
+            assert!(!table.matches(2, 0));
+            assert!(!table.matches(usize::MAX, 3));
+
+            let table = GenerationTable::from_vec(vec![1, 2]);
+            assert_eq!(table.len(), 2);
+            assert!(!table.matches(2, 2));
+        }
+
+        #[test]
+        fn test_sentinel_is_never_live()
+        {
+            let mut allocator = InPlaceAllocator::<u32>::default();
+            let at_sentinel = GenerationalIndex { index: 0, generation: Generation::MAX };
+            assert!(!allocator.is_live(&GenerationalIndex::default()));
+            assert!(!allocator.is_live(&at_sentinel));
+
+            let first = allocator.insert(1);
+            assert!(allocator.is_live(&first));
+            for handle in [
+                GenerationalIndex { index: 1, generation: 0 },
+                GenerationalIndex { index: 1, generation: Generation::MAX },
+                GenerationalIndex { index: usize::MAX, generation: 0 },
+                GenerationalIndex { index: usize::MAX, generation: Generation::MAX },
+            ] {
+                assert!(!allocator.is_live(&handle));
+                assert!(allocator.validate(&handle).is_err());
+            }
+
+            let handles = [first, GenerationalIndex { index: 1, generation: Generation::MAX }, GenerationalIndex { index: usize::MAX, generation: 0 }];
+            let mut out = BitVec::default();
+            allocator.validate_many(&handles, &mut out);
+            assert_eq!(out.iter_ones().collect::<Vec<_>>(), vec![0]);
+        }
+
+        #[test]
+        fn test_wraparound_never_reaches_sentinel()
+        {
+            let mut allocator = InPlaceAllocator::<u32>::with_free_slots_at(1, Generation::MAX - 1);
+            let last = allocator.insert(1);
+            assert_eq!(last.generation, Generation::MAX - 1);
+            allocator.free(&last);
+
+            // The slot is retired at MAX instead of being handed out again
+            let retired = GenerationalIndex { index: 0, generation: Generation::MAX };
+            assert!(!allocator.is_live(&retired));
+            let next = allocator.insert(2);
+            assert_eq!(next, GenerationalIndex { index: 1, generation: 0 });
+            assert!(!allocator.is_live(&retired));
+            assert!(!allocator.is_live(&last));
+        }
     }
-}
\ No newline at end of file
+}
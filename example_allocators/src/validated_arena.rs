@@ -0,0 +1,246 @@
+/// Shadow-model validation for allocators.
+///
+/// `ValidatedArena` wraps any `GenerationalAllocator` and mirrors every operation into a
+/// plain `BTreeMap` that is obviously correct. After each call the wrapped allocator and
+/// the model must agree on which handles are live and what they point to, otherwise it
+/// panics right at the operation that diverged. It's slow, so use it in tests.
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+
+use crate::generational_allocator::{AllocatorStats, GenerationalAllocator};
+use crate::kyren_generational_indices::{Generation, GenerationalIndex, GenerationalIndexArray};
+
+pub struct ValidatedArena<T, A = GenerationalIndexArray<T>> {
+    inner: A,
+    // Live slots: index -> (generation, expected value)
+    model: BTreeMap<usize, (Generation, T)>,
+    // Last generation that was live in each slot, new handles must be newer than this
+    retired: BTreeMap<usize, Generation>,
+}
+
+impl<T, A: Default> Default for ValidatedArena<T, A> {
+    fn default() -> Self {
+        return Self::wrap(A::default());
+    }
+}
+
+impl<T, A> ValidatedArena<T, A> {
+    /// Start validating `inner`. It should be empty, the model doesn't know about
+    /// anything allocated before this call
+    pub fn wrap(inner: A) -> Self {
+        return Self {
+            inner,
+            model: BTreeMap::new(),
+            retired: BTreeMap::new(),
+        };
+    }
+
+    pub fn into_inner(self) -> A {
+        return self.inner;
+    }
+
+    /// Number of live entries according to the model
+    pub fn len(&self) -> usize {
+        return self.model.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.model.is_empty();
+    }
+
+    fn model_is_live(&self, index: &GenerationalIndex) -> bool {
+        return matches!(self.model.get(&index.index), Some((generation, _)) if *generation == index.generation);
+    }
+}
+
+impl<T, A> ValidatedArena<T, A>
+where
+    T: Clone + PartialEq + Debug,
+    A: GenerationalAllocator<T>,
+{
+    /// Compare every live entry of the model against the wrapped allocator
+    pub fn check_all(&self) {
+        for (&index, (generation, expected)) in self.model.iter() {
+            let handle = GenerationalIndex { index, generation: *generation };
+            assert!(self.inner.is_live(&handle), "Slot {} should be live at generation {}", index, generation);
+            self.inner.with(&handle, |value| {
+                assert_eq!(value, expected, "Slot {} holds a different value than the model", index);
+            });
+        }
+    }
+}
+
+/// Mutable access through `GenerationalAllocator::get_mut`. The model picks up the new
+/// value when the guard is dropped
+pub struct ValidatedMut<'a, T: Clone, A: GenerationalAllocator<T> + 'a> {
+    value: A::RefMut<'a>,
+    model: &'a mut T,
+}
+
+impl<'a, T: Clone, A: GenerationalAllocator<T> + 'a> Deref for ValidatedMut<'a, T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.value;
+    }
+}
+
+impl<'a, T: Clone, A: GenerationalAllocator<T> + 'a> DerefMut for ValidatedMut<'a, T, A> {
+    fn deref_mut(&mut self) -> &mut T {
+        return &mut self.value;
+    }
+}
+
+impl<'a, T: Clone, A: GenerationalAllocator<T> + 'a> Drop for ValidatedMut<'a, T, A> {
+    fn drop(&mut self) {
+        *self.model = (*self.value).clone();
+    }
+}
+
+impl<T, A> GenerationalAllocator<T> for ValidatedArena<T, A>
+where
+    T: Clone + PartialEq + Debug,
+    A: GenerationalAllocator<T>,
+{
+    type Ref<'a> = A::Ref<'a> where Self: 'a;
+    type RefMut<'a> = ValidatedMut<'a, T, A> where Self: 'a;
+
+    fn insert(&mut self, element: T) -> GenerationalIndex {
+        let index = self.inner.insert(element.clone());
+
+        assert!(
+            !self.model.contains_key(&index.index),
+            "Allocator handed out slot {} which is still live",
+            index.index
+        );
+        if let Some(retired) = self.retired.get(&index.index) {
+            assert!(
+                index.generation > *retired,
+                "Slot {} was reused with generation {}, but generation {} was already used",
+                index.index,
+                index.generation,
+                retired
+            );
+        }
+        assert!(self.inner.is_live(&index), "Freshly allocated index {:?} is not live", index);
+        assert_eq!(
+            self.inner.with(&index, T::clone).as_ref(),
+            Some(&element),
+            "Freshly allocated index {:?} doesn't hold the stored value",
+            index
+        );
+
+        self.model.insert(index.index, (index.generation, element));
+        return index;
+    }
+
+    fn free(&mut self, index: &GenerationalIndex) {
+        assert_eq!(
+            self.inner.is_live(index),
+            self.model_is_live(index),
+            "Allocator and model disagree on liveness of {:?} before free",
+            index
+        );
+        assert!(self.model_is_live(index), "Trying to free an already dead index");
+
+        self.inner.free(index);
+
+        assert!(!self.inner.is_live(index), "Index {:?} is still live after free", index);
+        self.model.remove(&index.index);
+        self.retired.insert(index.index, index.generation);
+    }
+
+    fn remove(&mut self, index: &GenerationalIndex) -> Option<T> {
+        let expected = self.model_is_live(index);
+        assert_eq!(self.inner.is_live(index), expected, "Allocator and model disagree on liveness of {:?} before remove", index);
+
+        let value = self.inner.remove(index);
+        assert_eq!(value.is_some(), expected, "Allocator and model disagree on liveness of {:?}", index);
+        assert!(!self.inner.is_live(index), "Index {:?} is still live after remove", index);
+        if let Some(value) = value.as_ref() {
+            let (_, model) = self.model.remove(&index.index).unwrap();
+            assert_eq!(*value, model, "Slot {} held a different value than the model", index.index);
+            self.retired.insert(index.index, index.generation);
+        }
+
+        return value;
+    }
+
+    fn is_live(&self, index: &GenerationalIndex) -> bool {
+        let expected = self.model_is_live(index);
+        assert_eq!(
+            self.inner.is_live(index),
+            expected,
+            "Allocator and model disagree on liveness of {:?}",
+            index
+        );
+
+        return expected;
+    }
+
+    fn get(&self, index: &GenerationalIndex) -> Option<A::Ref<'_>> {
+        let expected = self.model.get(&index.index).filter(|(generation, _)| *generation == index.generation);
+
+        let value = self.inner.get(index);
+        assert_eq!(value.as_deref(), expected.map(|(_, value)| value), "Allocator and model disagree on {:?}", index);
+
+        return value;
+    }
+
+    fn get_mut(&mut self, index: &GenerationalIndex) -> Option<ValidatedMut<'_, T, A>> {
+        let expected = self.model.get_mut(&index.index).filter(|(generation, _)| *generation == index.generation);
+
+        let value = self.inner.get_mut(index);
+        assert_eq!(value.as_deref(), expected.as_ref().map(|(_, value)| value), "Allocator and model disagree on {:?}", index);
+
+        return Some(ValidatedMut { value: value?, model: &mut expected?.1 });
+    }
+
+    fn with<R>(&self, index: &GenerationalIndex, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let expected = self.model.get(&index.index).filter(|(generation, _)| *generation == index.generation);
+
+        let result = self.inner.with(index, |value| {
+            assert_eq!(Some(value), expected.map(|(_, value)| value), "Slot {} holds a different value than the model", index.index);
+            f(value)
+        });
+        assert_eq!(result.is_some(), expected.is_some(), "Allocator and model disagree on liveness of {:?}", index);
+
+        return result;
+    }
+
+    fn with_mut<R>(&mut self, index: &GenerationalIndex, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let expected = self
+            .model
+            .get(&index.index)
+            .filter(|(generation, _)| *generation == index.generation)
+            .map(|(_, value)| value.clone());
+
+        let mut updated = None;
+        let result = self.inner.with_mut(index, |value| {
+            assert_eq!(Some(&*value), expected.as_ref(), "Slot {} holds a different value than the model", index.index);
+            let result = f(value);
+            updated = Some(value.clone());
+            result
+        });
+        assert_eq!(result.is_some(), expected.is_some(), "Allocator and model disagree on liveness of {:?}", index);
+
+        if let Some(value) = updated {
+            self.model.insert(index.index, (index.generation, value));
+        }
+
+        return result;
+    }
+
+    fn stats(&self) -> AllocatorStats {
+        let stats = self.inner.stats();
+        assert_eq!(stats.live, self.model.len(), "Allocator and model disagree on the number of live entries");
+
+        return stats;
+    }
+
+    fn check_invariants(&self) {
+        self.inner.check_invariants();
+        self.check_all();
+    }
+}
@@ -0,0 +1,50 @@
+/// Helpers for the `zero-on-free` feature.
+///
+/// Arenas holding keys, tokens or other secrets shouldn't leave them lying around in
+/// freed slots, where a later bug or a memory dump could find them. With the feature on,
+/// `free` overwrites the slot's inline bytes with zeros after dropping the value. The
+/// writes are volatile, so the compiler can't drop them as dead stores to memory that
+/// is never read again.
+///
+/// Only the bytes stored in the slot itself are cleared. Heap memory owned by the value,
+/// like the buffer of a `String`, is released by the value's own `Drop` and is not
+/// zeroed; payloads that own secrets on the heap need to clear them in their `Drop`.
+///
+/// Slots are `MaybeUninit<T>` once their value is dropped, so overwriting them with any
+/// byte pattern is fine. `Option<T>` slots are different: zeroed bytes aren't
+/// necessarily a valid `None`, so `None` is written back right after zeroing, before
+/// anything can read the slot.
+use std::mem::{size_of, MaybeUninit};
+use std::sync::atomic::{compiler_fence, Ordering};
+
+#[inline]
+unsafe fn zero_bytes(ptr: *mut u8, len: usize) {
+    for offset in 0..len {
+        ptr.add(offset).write_volatile(0);
+    }
+    // Keep later writes to the slot from being reordered before the zeroing
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrite every byte of `slot` with zero. The slot must not hold a live value.
+#[inline]
+pub(crate) fn zeroize<T>(slot: &mut MaybeUninit<T>) {
+    unsafe { zero_bytes(slot.as_mut_ptr() as *mut u8, size_of::<T>()) };
+}
+
+/// Overwrite every byte of `bytes` with zero
+#[inline]
+pub(crate) fn zeroize_bytes(bytes: &mut [u8]) {
+    unsafe { zero_bytes(bytes.as_mut_ptr(), bytes.len()) };
+}
+
+/// Zero the leftover bytes of an emptied `Option` slot and leave it as `None`
+#[inline]
+pub(crate) fn zeroize_option<T>(slot: &mut Option<T>) {
+    debug_assert!(slot.is_none(), "Only empty slots can be zeroed");
+    let ptr = slot as *mut Option<T>;
+    unsafe {
+        zero_bytes(ptr as *mut u8, size_of::<Option<T>>());
+        ptr.write(None);
+    }
+}